@@ -0,0 +1,22 @@
+-- Migration 005: Local message cache
+-- Headers (and bodies, once fetched) for `sync` and `--offline`.
+-- Rows are keyed by UIDVALIDITY so a reset folder never mixes old and new UIDs.
+
+CREATE TABLE IF NOT EXISTS message_cache (
+    account TEXT NOT NULL,
+    folder TEXT NOT NULL,
+    uid_validity INTEGER NOT NULL,
+    uid INTEGER NOT NULL,
+    message_id TEXT,
+    flags_json TEXT NOT NULL,        -- {"seen": true, ...}
+    message_json TEXT NOT NULL,      -- parsed Message (headers, attachments, bodies if fetched)
+    raw BLOB,                        -- full RFC822 source, NULL for header-only entries
+    cached_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account, folder, uid_validity, uid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_message_cache_folder ON message_cache(account, folder);
+CREATE INDEX IF NOT EXISTS idx_message_cache_message_id ON message_cache(account, message_id);
+
+-- Mark migration as applied
+INSERT OR IGNORE INTO schema_migrations (version) VALUES (5);
@@ -0,0 +1,13 @@
+-- Migration 006: Full-text search
+-- FTS5 index over subject, addresses and decoded text bodies.
+-- The rowid is the shadow UID (messages.id), so results join straight back to messages.
+
+CREATE VIRTUAL TABLE IF NOT EXISTS message_fts USING fts5(
+    subject,
+    addresses,
+    body,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- Mark migration as applied
+INSERT OR IGNORE INTO schema_migrations (version) VALUES (6);
@@ -0,0 +1,12 @@
+-- Migration 007: Per-folder sync state
+-- Tracks what the server reported at the last SELECT/sync so stale UIDs can be
+-- detected (UIDVALIDITY) and flag changes fetched incrementally (CONDSTORE)
+
+ALTER TABLE folders ADD COLUMN uid_validity INTEGER;
+ALTER TABLE folders ADD COLUMN uid_next INTEGER;
+
+-- Only set by a completed sync; NULL forces a full flag comparison
+ALTER TABLE folders ADD COLUMN highest_modseq INTEGER;
+
+-- Mark migration as applied
+INSERT OR IGNORE INTO schema_migrations (version) VALUES (7);
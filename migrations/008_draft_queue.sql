@@ -0,0 +1,32 @@
+-- Migration 008: Draft queue
+-- drafts held a single pending operation per account (UNIQUE(account)).
+-- Rebuild the table without that constraint so staged operations queue up
+-- and are applied in staging order (by id).
+-- Runs in a single transaction; the staging table is dropped first in case an
+-- earlier, non-transactional run left it behind.
+
+DROP TABLE IF EXISTS drafts_queue;
+
+CREATE TABLE drafts_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account TEXT NOT NULL,
+    action_type TEXT NOT NULL,       -- 'flag', 'move', 'copy', 'delete', 'archive'
+    folder TEXT NOT NULL,
+    uids_json TEXT NOT NULL,         -- JSON array of shadow UIDs
+    flag_params_json TEXT,           -- {"read": true, "starred": false, ...}
+    dest_folder TEXT,
+    permanent BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO drafts_queue (id, account, action_type, folder, uids_json, flag_params_json, dest_folder, permanent, created_at)
+SELECT id, account, action_type, folder, uids_json, flag_params_json, dest_folder, permanent, created_at
+FROM drafts;
+
+DROP TABLE drafts;
+ALTER TABLE drafts_queue RENAME TO drafts;
+
+CREATE INDEX IF NOT EXISTS idx_drafts_account ON drafts(account, id);
+
+-- Mark migration as applied
+INSERT OR IGNORE INTO schema_migrations (version) VALUES (8);
@@ -0,0 +1,20 @@
+-- Migration 009: Action log
+-- Every executed move/copy/archive/delete/flag operation, with enough detail
+-- (source folders, Message-IDs, previous flags) for `undo` to reverse it
+
+CREATE TABLE IF NOT EXISTS action_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account TEXT NOT NULL,
+    action_type TEXT NOT NULL,       -- 'flag', 'move', 'copy', 'delete', 'archive'
+    messages_json TEXT NOT NULL,     -- [{"shadow_uid", "message_id", "source_folder", "previous_flags"}]
+    flag_params_json TEXT,
+    dest_folder TEXT,
+    permanent BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    undone_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_action_log_account ON action_log(account, id);
+
+-- Mark migration as applied
+INSERT OR IGNORE INTO schema_migrations (version) VALUES (9);
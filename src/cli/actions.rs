@@ -1,11 +1,18 @@
-use crate::core::imap::ImapClient;
-use crate::core::state::{ActionType, Draft, FlagParams, ResolvedMessage, StateManager};
+use crate::cli::{draft, query};
+use crate::core::cache;
+use crate::core::imap::{bare_message_id, ImapClient};
+use crate::core::state::{
+    ActionType, Draft, FlagParams, LoggedAction, LoggedMessage, Operation, ResolvedMessage,
+    StateManager,
+};
+use crate::models::account::Account;
 use crate::models::config::Config;
+use crate::models::filter::MessageFilter;
 use crate::output::json;
 use crate::utils::batch::{chunk_uids, DEFAULT_BATCH_SIZE};
 use anyhow::{anyhow, Result};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{self, Write};
 
 #[derive(Serialize)]
@@ -22,6 +29,10 @@ pub struct ActionOutput {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub draft_staged: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub draft_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub selection_cleared: Option<bool>,
 }
 
@@ -71,22 +82,218 @@ async fn resolve_shadow_uids_by_folder(
         provided_ids
     } else {
         return Err(anyhow!(
-            "No message IDs provided. Either provide IDs directly or use --selection flag."
+            "No message IDs provided. Provide IDs directly, or use --selection or --query."
         ));
     };
 
-    // Resolve all shadow UIDs to their current locations
+    let by_folder = group_by_folder(state, account_email, &shadow_uids).await?;
+    Ok((shadow_uids, by_folder))
+}
+
+/// Resolve shadow UIDs to their current locations, grouped by folder
+pub async fn group_by_folder(
+    state: &StateManager,
+    account_email: &str,
+    shadow_uids: &[i64],
+) -> Result<HashMap<String, Vec<ResolvedMessage>>> {
     let resolved = state
-        .resolve_shadow_uids(account_email, &shadow_uids)
+        .resolve_shadow_uids(account_email, shadow_uids)
         .await?;
 
-    // Group by folder
     let mut by_folder: HashMap<String, Vec<ResolvedMessage>> = HashMap::new();
     for msg in resolved {
         by_folder.entry(msg.folder.clone()).or_default().push(msg);
     }
+    Ok(by_folder)
+}
 
-    Ok((shadow_uids, by_folder))
+/// Act on the messages a query matches (`--query`) instead of IDs or the selection
+pub struct QueryTarget {
+    pub query: String,
+    pub folders: Vec<String>,
+    pub limit: Option<usize>,
+    /// Skip the confirmation asked above `bulk_confirm_threshold` matches
+    pub yes: bool,
+    /// Only list what would be affected
+    pub dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct QueryMatch {
+    id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    folder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+}
+
+#[derive(Serialize)]
+struct QueryMatchOutput {
+    action: String,
+    account: String,
+    query: String,
+    folders: Vec<String>,
+    description: String,
+    count: usize,
+    dry_run: bool,
+    messages: Vec<QueryMatch>,
+}
+
+/// Messages an action applies to. For `--query`, `client` is the connection the query ran on.
+struct Targets {
+    shadow_uids: Vec<i64>,
+    by_folder: HashMap<String, Vec<ResolvedMessage>>,
+    client: Option<ImapClient>,
+}
+
+/// Resolve the messages an action applies to: explicit IDs, the selection, or `--query`
+/// matches. Query matches are listed for `--dry-run` and confirmed above the configured
+/// threshold unless `--yes`; `None` means there is nothing to execute.
+#[allow(clippy::too_many_arguments)]
+async fn resolve_targets(
+    config: &Config,
+    account: &Account,
+    state: &StateManager,
+    ids: Vec<i64>,
+    use_selection: bool,
+    query: Option<&QueryTarget>,
+    op: &Draft,
+    output_format: Option<&str>,
+) -> Result<Option<Targets>> {
+    let Some(query) = query else {
+        let (shadow_uids, by_folder) =
+            resolve_shadow_uids_by_folder(ids, use_selection, &account.email, state).await?;
+        return Ok(Some(Targets {
+            shadow_uids,
+            by_folder,
+            client: None,
+        }));
+    };
+
+    let folders = query::effective_folders(&query.query, &query.folders);
+    let mut filter = MessageFilter::new().with_query(query.query.clone());
+    let mut client = ImapClient::connect(account).await?;
+    let messages = query::find_messages(
+        config,
+        &account.email,
+        Some(&mut client),
+        state,
+        &folders,
+        &mut filter,
+        query.limit,
+    )
+    .await?;
+
+    // Messages without a Message-ID get no shadow UID and can't be tracked
+    let shadow_uids: Vec<i64> = messages.iter().filter_map(|m| m.shadow_uid).collect();
+    let description = draft::describe_operation(&op.operation(), shadow_uids.len(), &op.folder);
+
+    if query.dry_run || shadow_uids.is_empty() {
+        let output = QueryMatchOutput {
+            action: op.action_type.as_str().to_string(),
+            account: account.email.clone(),
+            query: query.query.clone(),
+            folders,
+            description,
+            count: shadow_uids.len(),
+            dry_run: query.dry_run,
+            messages: messages
+                .iter()
+                .filter_map(|m| {
+                    Some(QueryMatch {
+                        id: m.shadow_uid?,
+                        folder: m.folder.clone(),
+                        subject: m.subject.clone(),
+                        from: m.from.as_ref().map(|f| f.address.clone()),
+                        date: m.date.map(|d| d.to_rfc3339()),
+                    })
+                })
+                .collect(),
+        };
+        match output_format.unwrap_or("text") {
+            "json" => json::print_json(&output)?,
+            _ if output.count == 0 => println!("No messages match '{}'", output.query),
+            _ => {
+                println!("Dry run: {} (query '{}')", output.description, output.query);
+                for m in &output.messages {
+                    println!(
+                        "  [{}] {} — {} ({})",
+                        m.id,
+                        m.subject.as_deref().unwrap_or("(no subject)"),
+                        m.from.as_deref().unwrap_or("unknown sender"),
+                        m.folder.as_deref().unwrap_or("?")
+                    );
+                }
+            }
+        }
+        return Ok(None);
+    }
+
+    // Permanent deletes always ask (unless --yes), so don't ask twice
+    let permanent_delete = op.action_type == ActionType::Delete && op.permanent;
+    if shadow_uids.len() > config.preferences.bulk_confirm_threshold
+        && !query.yes
+        && !permanent_delete
+    {
+        print!("{} matching '{}'? [y/N] ", description, query.query);
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            println!("Aborted.");
+            return Ok(None);
+        }
+    }
+
+    let by_folder = group_by_folder(state, &account.email, &shadow_uids).await?;
+    Ok(Some(Targets {
+        shadow_uids,
+        by_folder,
+        client: Some(client),
+    }))
+}
+
+/// Reuse the connection a `--query` ran on, or open a new one
+async fn connect_or_reuse(client: Option<ImapClient>, account: &Account) -> Result<ImapClient> {
+    match client {
+        Some(client) => Ok(client),
+        None => ImapClient::connect(account).await,
+    }
+}
+
+/// Select a source folder and return its messages' current UIDs. If the folder's
+/// UIDVALIDITY changed since the IDs were resolved, the locations are re-read after the
+/// re-map; messages that are no longer there are an error rather than a wrong UID.
+async fn select_source_folder(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    folder: &str,
+    messages: &[ResolvedMessage],
+) -> Result<Vec<ResolvedMessage>> {
+    let (_, remap) = cache::select_tracked(client, state, account, folder).await?;
+    if remap.is_none() {
+        return Ok(messages.to_vec());
+    }
+
+    let shadow_uids: Vec<i64> = messages.iter().map(|m| m.shadow_uid).collect();
+    let current = state.resolve_shadow_uids(account, &shadow_uids).await?;
+    if let Some(lost) = current
+        .iter()
+        .find(|m| m.imap_uid == 0 || m.folder != folder)
+    {
+        return Err(anyhow!(
+            "Message {} is no longer in '{}' (UIDVALIDITY changed). Re-run 'inbox' or 'query' to refresh IDs.",
+            lost.shadow_uid,
+            folder
+        ));
+    }
+    Ok(current)
 }
 
 /// After action completion, optionally clear selection
@@ -103,40 +310,621 @@ async fn post_action_cleanup(
     }
 }
 
+/// Record the new location of moved messages.
+/// Uses the COPYUID mapping when the server sent one, otherwise searches the destination
+/// by Message-ID. UID 0 is only stored if the message cannot be found there.
+async fn record_moved_locations(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    dest_folder: &str,
+    messages: &[ResolvedMessage],
+    uid_map: &HashMap<u32, u32>,
+) -> Result<()> {
+    // Without COPYUID, look the moved messages up in the destination in one go
+    let unmapped: Vec<String> = messages
+        .iter()
+        .filter(|m| !uid_map.contains_key(&m.imap_uid))
+        .filter_map(|m| m.message_id.clone())
+        .collect();
+    let found = if unmapped.is_empty() {
+        HashMap::new()
+    } else {
+        client.select_folder(dest_folder).await?;
+        client.find_uids_by_message_ids(&unmapped).await?
+    };
+
+    for msg in messages {
+        let Some(ref msg_id) = msg.message_id else {
+            continue;
+        };
+        let new_uid = uid_map
+            .get(&msg.imap_uid)
+            .or_else(|| found.get(bare_message_id(msg_id)))
+            .copied();
+        state
+            .update_message_location_by_message_id(
+                account,
+                msg_id,
+                dest_folder,
+                new_uid.unwrap_or(0), // 0 = resolved on next access
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Move messages into `dest_folder` and record where they landed. The shadow UIDs of the
+/// messages moved are added to `moved`, also when a later batch fails.
+async fn move_and_record(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    dest_folder: &str,
+    messages: &[ResolvedMessage],
+    moved: &mut HashSet<i64>,
+) -> Result<()> {
+    let mut uid_map = HashMap::new();
+    let mut done = 0;
+    let mut outcome = Ok(());
+    for chunk in messages.chunks(DEFAULT_BATCH_SIZE) {
+        match client.move_messages(&imap_uids(chunk), dest_folder).await {
+            Ok(map) => {
+                uid_map.extend(map);
+                done += chunk.len();
+            }
+            Err(e) => {
+                outcome = Err(e);
+                break;
+            }
+        }
+    }
+    let done = &messages[..done];
+    moved.extend(done.iter().map(|m| m.shadow_uid));
+
+    // Update message locations in database
+    if !done.is_empty() {
+        record_moved_locations(client, state, account, dest_folder, done, &uid_map).await?;
+    }
+    outcome
+}
+
+fn imap_uids(messages: &[ResolvedMessage]) -> Vec<u32> {
+    messages.iter().map(|m| m.imap_uid).collect()
+}
+
+/// Result of executing an operation against the server
+pub struct OperationResult {
+    pub count: usize,
+    /// Past-tense descriptions of what was done, e.g. "marked read", "moved"
+    pub actions: Vec<&'static str>,
+    /// Action log entry for `undo`, if anything was changed
+    pub history_id: Option<i64>,
+}
+
+/// Folder an operation moves messages into, if any
+fn operation_dest(op: &Operation) -> Option<String> {
+    match op.action_type {
+        ActionType::Delete if !op.permanent => Some(resolve_folder_path("trash")),
+        ActionType::Delete => None,
+        ActionType::Flag => op
+            .flag_params
+            .as_ref()
+            .and_then(|p| p.move_to.as_deref())
+            .map(resolve_folder_path),
+        _ => op.dest_folder.clone(),
+    }
+}
+
+/// Execute a staged or direct operation on messages already grouped by their current folder.
+/// Shared by the action commands and `draft apply`. Whatever was changed is written to the
+/// action log, even if a later folder fails, so it can be undone.
+pub async fn execute_operation(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    op: &Draft,
+    by_folder: &HashMap<String, Vec<ResolvedMessage>>,
+) -> Result<OperationResult> {
+    let dest_folder = operation_dest(&op.operation());
+
+    // Validate the destination before touching any message
+    if let Some(ref dest) = dest_folder {
+        if !client.folder_exists(dest).await? {
+            return Err(match op.action_type {
+                ActionType::Archive => anyhow!("Archive folder does not exist"),
+                ActionType::Delete => anyhow!("Trash folder not found"),
+                _ => anyhow!("Destination folder '{}' does not exist", dest),
+            });
+        }
+    } else if matches!(
+        op.action_type,
+        ActionType::Move | ActionType::Copy | ActionType::Archive
+    ) {
+        return Err(anyhow!(
+            "No destination folder for {}",
+            op.action_type.as_str()
+        ));
+    }
+
+    let mut actions = Vec::new();
+    let mut logged = Vec::new();
+    let mut outcome = Ok(());
+
+    for (folder, messages) in by_folder {
+        let done = execute_in_folder(
+            client,
+            state,
+            account,
+            op,
+            dest_folder.as_deref(),
+            folder,
+            messages,
+            &mut actions,
+            &mut logged,
+        )
+        .await;
+        if let Err(e) = done {
+            outcome = Err(e);
+            break;
+        }
+    }
+
+    let history_id = if logged.is_empty() {
+        None
+    } else {
+        Some(state.log_action(op, &logged).await?)
+    };
+    outcome?;
+
+    Ok(OperationResult {
+        count: logged.len(),
+        actions,
+        history_id,
+    })
+}
+
+/// Run an operation on the messages of one source folder and add log entries for the
+/// messages it changed to `logged`. If it fails part way, the messages already changed
+/// are still logged (and listed on stderr) so they can be undone.
+#[allow(clippy::too_many_arguments)]
+async fn execute_in_folder(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    op: &Draft,
+    dest_folder: Option<&str>,
+    folder: &str,
+    messages: &[ResolvedMessage],
+    actions: &mut Vec<&'static str>,
+    logged: &mut Vec<LoggedMessage>,
+) -> Result<()> {
+    let messages = &select_source_folder(client, state, account, folder, messages).await?;
+
+    // Flag changes are undone by restoring what each message had before
+    let mut previous_flags = if op.action_type == ActionType::Flag {
+        client.fetch_raw_flags(&imap_uids(messages)).await?
+    } else {
+        HashMap::new()
+    };
+
+    let mut done = HashSet::new();
+    let outcome = apply_in_folder(
+        client,
+        state,
+        account,
+        op,
+        dest_folder,
+        messages,
+        actions,
+        &mut done,
+    )
+    .await;
+
+    let changed: Vec<&ResolvedMessage> = messages
+        .iter()
+        .filter(|m| done.contains(&m.shadow_uid))
+        .collect();
+    if outcome.is_err() && !changed.is_empty() {
+        let uids: Vec<String> = changed.iter().map(|m| m.imap_uid.to_string()).collect();
+        eprintln!(
+            "Note: {} message(s) in '{}' were changed before the error (UIDs {}); they are recorded for undo",
+            changed.len(),
+            folder,
+            uids.join(",")
+        );
+    }
+    logged.extend(changed.into_iter().map(|m| LoggedMessage {
+        shadow_uid: m.shadow_uid,
+        message_id: m.message_id.clone(),
+        source_folder: folder.to_string(),
+        previous_flags: previous_flags.remove(&m.imap_uid).unwrap_or_default(),
+    }));
+
+    outcome
+}
+
+/// The server side of `execute_in_folder`; adds the shadow UIDs of the messages it has
+/// changed to `done` as it goes
+#[allow(clippy::too_many_arguments)]
+async fn apply_in_folder(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    op: &Draft,
+    dest_folder: Option<&str>,
+    messages: &[ResolvedMessage],
+    actions: &mut Vec<&'static str>,
+    done: &mut HashSet<i64>,
+) -> Result<()> {
+    match op.action_type {
+        ActionType::Move | ActionType::Archive => {
+            let dest = dest_folder.unwrap_or_default();
+            move_and_record(client, state, account, dest, messages, done).await?;
+            push_action(actions, "moved");
+        }
+        ActionType::Copy => {
+            let dest = dest_folder.unwrap_or_default();
+            for chunk in messages.chunks(DEFAULT_BATCH_SIZE) {
+                client.copy_messages(&imap_uids(chunk), dest).await?;
+                done.extend(chunk.iter().map(|m| m.shadow_uid));
+            }
+            push_action(actions, "copied");
+        }
+        ActionType::Delete => {
+            if op.permanent {
+                // Permanent delete: mark as deleted and expunge
+                for chunk in messages.chunks(DEFAULT_BATCH_SIZE) {
+                    client.mark_messages_deleted(&imap_uids(chunk)).await?;
+                    done.extend(chunk.iter().map(|m| m.shadow_uid));
+                }
+                client.expunge_uids(&imap_uids(messages)).await?;
+                push_action(actions, "deleted");
+            } else {
+                // Soft delete: move to Trash
+                let trash = dest_folder.unwrap_or_default();
+                move_and_record(client, state, account, trash, messages, done).await?;
+                push_action(actions, "moved to Trash");
+            }
+        }
+        ActionType::Flag => {
+            let flag_params = op.flag_params.clone().unwrap_or_default();
+
+            // Restoring the previous flags is harmless for messages not reached yet,
+            // so every message is undoable from the first change on
+            done.extend(messages.iter().map(|m| m.shadow_uid));
+            let imap_uids = imap_uids(messages);
+
+            // Apply read/unread flags
+            if flag_params.read == Some(true) {
+                for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
+                    client.mark_messages_read(&chunk).await?;
+                }
+                push_action(actions, "marked read");
+            }
+            if flag_params.read == Some(false) {
+                for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
+                    client.mark_messages_unread(&chunk).await?;
+                }
+                push_action(actions, "marked unread");
+            }
+
+            // Apply starred/unstarred flags
+            if flag_params.starred == Some(true) {
+                for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
+                    client.star_messages(&chunk).await?;
+                }
+                push_action(actions, "starred");
+            }
+            if flag_params.starred == Some(false) {
+                for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
+                    client.unstar_messages(&chunk).await?;
+                }
+                push_action(actions, "unstarred");
+            }
+
+            // Add labels
+            for label in &flag_params.labels {
+                for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
+                    client.modify_flags(&chunk, label, true).await?;
+                }
+            }
+            if !flag_params.labels.is_empty() {
+                push_action(actions, "labeled");
+            }
+
+            // Remove labels
+            for unlabel in &flag_params.unlabels {
+                for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
+                    client.modify_flags(&chunk, unlabel, false).await?;
+                }
+            }
+            if !flag_params.unlabels.is_empty() {
+                push_action(actions, "unlabeled");
+            }
+
+            // Move to destination folder if specified
+            if let Some(dest) = dest_folder {
+                move_and_record(client, state, account, dest, messages, done).await?;
+                push_action(actions, "moved");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Why a logged action can't be undone, if it can't
+pub fn undo_blocker(action: &LoggedAction) -> Option<String> {
+    if let Some(ref at) = action.undone_at {
+        return Some(format!("it was already undone at {}", at));
+    }
+    match action.operation.action_type {
+        ActionType::Copy => Some("delete the copied messages instead".to_string()),
+        ActionType::Delete if action.operation.permanent => {
+            Some("permanently deleted messages can't be recovered".to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Result of undoing a logged action
+pub struct UndoResult {
+    pub count: usize,
+    pub actions: Vec<&'static str>,
+    /// Messages that couldn't be put back (no Message-ID, or no longer where the action
+    /// left them). The action stays undoable with just these, so undo can be retried.
+    pub remaining: usize,
+}
+
+/// Reverse a logged action: messages are found in the destination by Message-ID and moved
+/// back to their source folder, and flags a flag action changed are restored. The action
+/// is only marked undone once every message is back; otherwise it keeps the rest.
+pub async fn undo_operation(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    action: &LoggedAction,
+) -> Result<UndoResult> {
+    if let Some(reason) = undo_blocker(action) {
+        return Err(anyhow!("Action #{} can't be undone: {}", action.id, reason));
+    }
+    let op = &action.operation;
+    let dest = operation_dest(op);
+
+    let mut actions = Vec::new();
+    let mut moved = HashSet::new();
+    let mut flags_restored = HashSet::new();
+    let mut outcome = Ok(());
+
+    if let Some(ref dest) = dest {
+        outcome = move_back(client, state, account, action, dest, &mut moved).await;
+        if !moved.is_empty() {
+            push_action(&mut actions, "moved back");
+        }
+    }
+    if outcome.is_ok() && op.action_type == ActionType::Flag {
+        outcome = restore_flags(client, state, account, action, &mut flags_restored).await;
+        if !flags_restored.is_empty() {
+            push_action(&mut actions, "restored flags");
+        }
+    }
+
+    let remaining: Vec<LoggedMessage> = action
+        .messages
+        .iter()
+        .filter(|m| {
+            (dest.is_some() && !moved.contains(&m.shadow_uid))
+                || (op.action_type == ActionType::Flag && !flags_restored.contains(&m.shadow_uid))
+        })
+        .cloned()
+        .collect();
+    let count = action.messages.len() - remaining.len();
+
+    if remaining.is_empty() {
+        state.mark_action_undone(account, action.id).await?;
+    } else if count > 0 {
+        state
+            .set_action_messages(account, action.id, &remaining)
+            .await?;
+    }
+    outcome?;
+
+    Ok(UndoResult {
+        count,
+        actions,
+        remaining: remaining.len(),
+    })
+}
+
+/// Move the messages of a logged action from `dest` back to their source folders,
+/// adding the shadow UIDs of those moved to `moved`
+async fn move_back(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    action: &LoggedAction,
+    dest: &str,
+    moved: &mut HashSet<i64>,
+) -> Result<()> {
+    let message_ids: Vec<String> = action
+        .messages
+        .iter()
+        .filter_map(|m| m.message_id.clone())
+        .collect();
+    cache::select_tracked(client, state, account, dest).await?;
+    let found = if message_ids.is_empty() {
+        HashMap::new()
+    } else {
+        client.find_uids_by_message_ids(&message_ids).await?
+    };
+
+    let mut by_source: HashMap<&str, Vec<ResolvedMessage>> = HashMap::new();
+    for message in &action.messages {
+        let Some(ref msg_id) = message.message_id else {
+            continue;
+        };
+        if let Some(&uid) = found.get(bare_message_id(msg_id)) {
+            by_source
+                .entry(message.source_folder.as_str())
+                .or_default()
+                .push(ResolvedMessage {
+                    shadow_uid: message.shadow_uid,
+                    folder: dest.to_string(),
+                    imap_uid: uid,
+                    message_id: Some(msg_id.clone()),
+                });
+        }
+    }
+    if by_source.is_empty() {
+        return Err(anyhow!(
+            "None of the messages are still in '{}'; nothing to undo",
+            dest
+        ));
+    }
+
+    for (source, messages) in &by_source {
+        // Recording locations may select other folders, so re-select every time
+        cache::select_tracked(client, state, account, dest).await?;
+        move_and_record(client, state, account, source, messages, moved).await?;
+    }
+    Ok(())
+}
+
+/// Put back the flags a flag action changed, adding the shadow UIDs of the messages
+/// restored to `restored`
+async fn restore_flags(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    action: &LoggedAction,
+    restored: &mut HashSet<i64>,
+) -> Result<()> {
+    let flag_params = action.operation.flag_params.clone().unwrap_or_default();
+    let previous: HashMap<i64, &LoggedMessage> =
+        action.messages.iter().map(|m| (m.shadow_uid, m)).collect();
+    let shadow_uids: Vec<i64> = previous.keys().copied().collect();
+
+    for (folder, messages) in &group_by_folder(state, account, &shadow_uids).await? {
+        let messages = &select_source_folder(client, state, account, folder, messages).await?;
+
+        // Group the per-message restorations into one STORE per flag change
+        let mut changes: BTreeMap<(String, bool), Vec<u32>> = BTreeMap::new();
+        let mut in_folder = Vec::new();
+        for message in messages.iter().filter(|m| m.imap_uid != 0) {
+            let Some(logged) = previous.get(&message.shadow_uid) else {
+                continue;
+            };
+            for change in flag_restorations(&flag_params, &logged.previous_flags) {
+                changes.entry(change).or_default().push(message.imap_uid);
+            }
+            in_folder.push(message.shadow_uid);
+        }
+        for ((flag, add), uids) in &changes {
+            for chunk in chunk_uids(uids, DEFAULT_BATCH_SIZE) {
+                client.modify_flags(&chunk, flag, *add).await?;
+            }
+        }
+        restored.extend(in_folder);
+    }
+    Ok(())
+}
+
+/// Flag changes (flag, add) that put a message back the way it was before a flag action
+fn flag_restorations(params: &FlagParams, previous: &[String]) -> Vec<(String, bool)> {
+    let had = |flag: &str| previous.iter().any(|f| f.eq_ignore_ascii_case(flag));
+    let mut changes = Vec::new();
+
+    if params.read.is_some() {
+        changes.push(("\\Seen".to_string(), had("\\Seen")));
+    }
+    if params.starred.is_some() {
+        changes.push(("\\Flagged".to_string(), had("\\Flagged")));
+    }
+    // Labels the message already had were left unchanged
+    for label in params.labels.iter().filter(|l| !had(l)) {
+        changes.push((label.clone(), false));
+    }
+    for label in params.unlabels.iter().filter(|l| had(l)) {
+        changes.push((label.clone(), true));
+    }
+
+    changes
+}
+
+fn push_action(actions: &mut Vec<&'static str>, action: &'static str) {
+    if !actions.contains(&action) {
+        actions.push(action);
+    }
+}
+
+/// Ask before a permanent delete; returns false if the user declined
+pub fn confirm_permanent_delete(count: usize) -> Result<bool> {
+    print!(
+        "Permanently delete {} message(s)? This cannot be undone. [y/N] ",
+        count
+    );
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().eq_ignore_ascii_case("y"))
+}
+
 /// Move messages to another folder
+#[allow(clippy::too_many_arguments)]
 pub async fn move_messages(
+    account_email: Option<&str>,
     ids: Vec<i64>,
     to: &str,
     use_selection: bool,
+    query: Option<QueryTarget>,
     create_draft: bool,
     keep_selection: bool,
     output_format: Option<&str>,
 ) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
     let dest_folder = resolve_folder_path(to);
 
-    // Resolve shadow UIDs to current locations
-    let (shadow_uids, by_folder) =
-        resolve_shadow_uids_by_folder(ids, use_selection, &account.email, &state).await?;
+    // For drafts, we store shadow UIDs (they persist across moves)
+    let mut op = Draft {
+        account: account.email.clone(),
+        action_type: ActionType::Move,
+        folder: "".to_string(),
+        uids: Vec::new(),
+        flag_params: None,
+        dest_folder: Some(dest_folder.clone()),
+        permanent: false,
+    };
 
-    // If draft mode, save draft and return
+    // Resolve IDs, the selection or --query matches to current locations
+    let Some(targets) = resolve_targets(
+        &config,
+        account,
+        &state,
+        ids,
+        use_selection,
+        query.as_ref(),
+        &op,
+        output_format,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+    let Targets {
+        shadow_uids,
+        by_folder,
+        client,
+    } = targets;
+    op.uids = shadow_uids.iter().map(|&id| id as u32).collect();
+
+    // If draft mode, queue the operation and return
     if create_draft {
-        // For drafts, we store shadow UIDs (they persist across moves)
-        let draft = Draft {
-            account: account.email.clone(),
-            action_type: ActionType::Move,
-            folder: "".to_string(), // Not used with shadow UIDs
-            uids: shadow_uids.iter().map(|&id| id as u32).collect(), // Store as u32 for backwards compat
-            flag_params: None,
-            dest_folder: Some(dest_folder.clone()),
-            permanent: false,
-        };
-        state.save_draft(&draft).await?;
+        let draft_id = state.save_draft(&op).await?;
 
         let output = ActionOutput {
             action: "move".to_string(),
@@ -147,61 +935,26 @@ pub async fn move_messages(
             success_count: 0,
             failed_ids: vec![],
             draft_staged: Some(true),
+            draft_id: Some(draft_id),
+            history_id: None,
             selection_cleared: None,
         };
 
         match output_format.unwrap_or("text") {
             "json" => json::print_json(&output)?,
             _ => println!(
-                "Draft staged: Move {} message(s) → {}. Run 'protoncli move --to {}' to execute.",
+                "Draft #{} staged: Move {} message(s) → {}. Run 'protoncli draft apply {}' to execute.",
+                draft_id,
                 shadow_uids.len(),
                 dest_folder,
-                to
+                draft_id
             ),
         }
         return Ok(());
     }
 
-    let mut client = ImapClient::connect(account).await?;
-
-    // Validate destination folder exists
-    if !client.folder_exists(&dest_folder).await? {
-        return Err(anyhow!(
-            "Destination folder '{}' does not exist",
-            dest_folder
-        ));
-    }
-
-    // Process each source folder
-    let mut moved_count = 0;
-    for (folder, messages) in &by_folder {
-        client.select_folder(folder).await?;
-
-        let imap_uids: Vec<u32> = messages.iter().map(|m| m.imap_uid).collect();
-        for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-            client.move_messages(&chunk, &dest_folder).await?;
-        }
-
-        // Update message locations in database
-        // After move, we need to find the new UIDs. For now, we mark location as dest_folder
-        // with UID 0 (will be resolved on next fetch)
-        for msg in messages {
-            if let Some(ref msg_id) = msg.message_id {
-                // Try to find the new UID by searching in dest folder
-                // For simplicity, just update the folder - UID will be resolved on next access
-                state
-                    .update_message_location_by_message_id(
-                        &account.email,
-                        msg_id,
-                        &dest_folder,
-                        0, // UID will be resolved on next access
-                    )
-                    .await?;
-            }
-        }
-
-        moved_count += messages.len();
-    }
+    let mut client = connect_or_reuse(client, account).await?;
+    let result = execute_operation(&mut client, &state, &account.email, &op, &by_folder).await?;
 
     // Clear selection unless --keep
     let selection_cleared = if use_selection {
@@ -216,53 +969,75 @@ pub async fn move_messages(
         source_folder: by_folder.keys().next().cloned(),
         dest_folder: Some(dest_folder.clone()),
         ids: shadow_uids.clone(),
-        success_count: moved_count,
+        success_count: result.count,
         failed_ids: vec![],
         draft_staged: None,
+        draft_id: None,
+        history_id: result.history_id,
         selection_cleared,
     };
 
     match output_format.unwrap_or("text") {
         "json" => json::print_json(&output)?,
-        _ => println!("✓ Moved {} message(s) → {}", moved_count, dest_folder),
+        _ => println!("✓ Moved {} message(s) → {}", result.count, dest_folder),
     }
 
     Ok(())
 }
 
 /// Copy messages to another folder
+#[allow(clippy::too_many_arguments)]
 pub async fn copy_messages(
+    account_email: Option<&str>,
     ids: Vec<i64>,
     to: &str,
     use_selection: bool,
+    query: Option<QueryTarget>,
     create_draft: bool,
     keep_selection: bool,
     output_format: Option<&str>,
 ) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
     let dest_folder = resolve_folder_path(to);
 
-    // Resolve shadow UIDs to current locations
-    let (shadow_uids, by_folder) =
-        resolve_shadow_uids_by_folder(ids, use_selection, &account.email, &state).await?;
+    let mut op = Draft {
+        account: account.email.clone(),
+        action_type: ActionType::Copy,
+        folder: "".to_string(),
+        uids: Vec::new(),
+        flag_params: None,
+        dest_folder: Some(dest_folder.clone()),
+        permanent: false,
+    };
 
-    // If draft mode, save draft and return
+    // Resolve IDs, the selection or --query matches to current locations
+    let Some(targets) = resolve_targets(
+        &config,
+        account,
+        &state,
+        ids,
+        use_selection,
+        query.as_ref(),
+        &op,
+        output_format,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+    let Targets {
+        shadow_uids,
+        by_folder,
+        client,
+    } = targets;
+    op.uids = shadow_uids.iter().map(|&id| id as u32).collect();
+
+    // If draft mode, queue the operation and return
     if create_draft {
-        let draft = Draft {
-            account: account.email.clone(),
-            action_type: ActionType::Copy,
-            folder: "".to_string(),
-            uids: shadow_uids.iter().map(|&id| id as u32).collect(),
-            flag_params: None,
-            dest_folder: Some(dest_folder.clone()),
-            permanent: false,
-        };
-        state.save_draft(&draft).await?;
+        let draft_id = state.save_draft(&op).await?;
 
         let output = ActionOutput {
             action: "copy".to_string(),
@@ -273,43 +1048,26 @@ pub async fn copy_messages(
             success_count: 0,
             failed_ids: vec![],
             draft_staged: Some(true),
+            draft_id: Some(draft_id),
+            history_id: None,
             selection_cleared: None,
         };
 
         match output_format.unwrap_or("text") {
             "json" => json::print_json(&output)?,
             _ => println!(
-                "Draft staged: Copy {} message(s) → {}. Run 'protoncli copy --to {}' to execute.",
+                "Draft #{} staged: Copy {} message(s) → {}. Run 'protoncli draft apply {}' to execute.",
+                draft_id,
                 shadow_uids.len(),
                 dest_folder,
-                to
+                draft_id
             ),
         }
         return Ok(());
     }
 
-    let mut client = ImapClient::connect(account).await?;
-
-    // Validate destination folder exists
-    if !client.folder_exists(&dest_folder).await? {
-        return Err(anyhow!(
-            "Destination folder '{}' does not exist",
-            dest_folder
-        ));
-    }
-
-    // Process each source folder
-    let mut copied_count = 0;
-    for (folder, messages) in &by_folder {
-        client.select_folder(folder).await?;
-
-        let imap_uids: Vec<u32> = messages.iter().map(|m| m.imap_uid).collect();
-        for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-            client.copy_messages(&chunk, &dest_folder).await?;
-        }
-
-        copied_count += messages.len();
-    }
+    let mut client = connect_or_reuse(client, account).await?;
+    let result = execute_operation(&mut client, &state, &account.email, &op, &by_folder).await?;
 
     // Clear selection unless --keep
     let selection_cleared = if use_selection {
@@ -324,15 +1082,17 @@ pub async fn copy_messages(
         source_folder: by_folder.keys().next().cloned(),
         dest_folder: Some(dest_folder.clone()),
         ids: shadow_uids.clone(),
-        success_count: copied_count,
+        success_count: result.count,
         failed_ids: vec![],
         draft_staged: None,
+        draft_id: None,
+        history_id: result.history_id,
         selection_cleared,
     };
 
     match output_format.unwrap_or("text") {
         "json" => json::print_json(&output)?,
-        _ => println!("✓ Copied {} message(s) → {}", copied_count, dest_folder),
+        _ => println!("✓ Copied {} message(s) → {}", result.count, dest_folder),
     }
 
     Ok(())
@@ -341,37 +1101,56 @@ pub async fn copy_messages(
 /// Delete messages (move to Trash or permanent delete)
 #[allow(clippy::too_many_arguments)]
 pub async fn delete_messages(
+    account_email: Option<&str>,
     ids: Vec<i64>,
     permanent: bool,
     yes: bool,
     use_selection: bool,
+    query: Option<QueryTarget>,
     create_draft: bool,
     keep_selection: bool,
     output_format: Option<&str>,
 ) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
 
-    // Resolve shadow UIDs to current locations
-    let (shadow_uids, by_folder) =
-        resolve_shadow_uids_by_folder(ids, use_selection, &account.email, &state).await?;
+    let mut op = Draft {
+        account: account.email.clone(),
+        action_type: ActionType::Delete,
+        folder: "".to_string(),
+        uids: Vec::new(),
+        flag_params: None,
+        dest_folder: None,
+        permanent,
+    };
 
-    // If draft mode, save draft and return
+    // Resolve IDs, the selection or --query matches to current locations
+    let Some(targets) = resolve_targets(
+        &config,
+        account,
+        &state,
+        ids,
+        use_selection,
+        query.as_ref(),
+        &op,
+        output_format,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+    let Targets {
+        shadow_uids,
+        by_folder,
+        client,
+    } = targets;
+    op.uids = shadow_uids.iter().map(|&id| id as u32).collect();
+
+    // If draft mode, queue the operation and return
     if create_draft {
-        let draft = Draft {
-            account: account.email.clone(),
-            action_type: ActionType::Delete,
-            folder: "".to_string(),
-            uids: shadow_uids.iter().map(|&id| id as u32).collect(),
-            flag_params: None,
-            dest_folder: None,
-            permanent,
-        };
-        state.save_draft(&draft).await?;
+        let draft_id = state.save_draft(&op).await?;
 
         let action_desc = if permanent {
             "Permanently delete"
@@ -391,76 +1170,32 @@ pub async fn delete_messages(
             success_count: 0,
             failed_ids: vec![],
             draft_staged: Some(true),
+            draft_id: Some(draft_id),
+            history_id: None,
             selection_cleared: None,
         };
 
         match output_format.unwrap_or("text") {
             "json" => json::print_json(&output)?,
             _ => println!(
-                "Draft staged: {} {} message(s). Run 'protoncli delete' to execute.",
+                "Draft #{} staged: {} {} message(s). Run 'protoncli draft apply {}' to execute.",
+                draft_id,
                 action_desc,
-                shadow_uids.len()
+                shadow_uids.len(),
+                draft_id
             ),
         }
         return Ok(());
     }
 
     // Confirm permanent delete if not already confirmed
-    if permanent && !yes {
-        print!(
-            "Permanently delete {} message(s)? This cannot be undone. [y/N] ",
-            shadow_uids.len()
-        );
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        if !input.trim().eq_ignore_ascii_case("y") {
-            println!("Aborted.");
-            return Ok(());
-        }
+    if permanent && !yes && !confirm_permanent_delete(shadow_uids.len())? {
+        println!("Aborted.");
+        return Ok(());
     }
 
-    let mut client = ImapClient::connect(account).await?;
-    let trash_folder = resolve_folder_path("trash");
-
-    let mut deleted_count = 0;
-    for (folder, messages) in &by_folder {
-        client.select_folder(folder).await?;
-        let imap_uids: Vec<u32> = messages.iter().map(|m| m.imap_uid).collect();
-
-        if permanent {
-            // Permanent delete: mark as deleted and expunge
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.mark_messages_deleted(&chunk).await?;
-            }
-            client.expunge().await?;
-        } else {
-            // Soft delete: move to Trash
-            if !client.folder_exists(&trash_folder).await? {
-                return Err(anyhow!("Trash folder not found"));
-            }
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.move_messages(&chunk, &trash_folder).await?;
-            }
-
-            // Update message locations
-            for msg in messages {
-                if let Some(ref msg_id) = msg.message_id {
-                    state
-                        .update_message_location_by_message_id(
-                            &account.email,
-                            msg_id,
-                            &trash_folder,
-                            0,
-                        )
-                        .await?;
-                }
-            }
-        }
-
-        deleted_count += messages.len();
-    }
+    let mut client = connect_or_reuse(client, account).await?;
+    let result = execute_operation(&mut client, &state, &account.email, &op, &by_folder).await?;
 
     // Clear selection unless --keep
     let selection_cleared = if use_selection {
@@ -479,9 +1214,11 @@ pub async fn delete_messages(
             Some("Trash".to_string())
         },
         ids: shadow_uids.clone(),
-        success_count: deleted_count,
+        success_count: result.count,
         failed_ids: vec![],
         draft_staged: None,
+        draft_id: None,
+        history_id: result.history_id,
         selection_cleared,
     };
 
@@ -489,9 +1226,9 @@ pub async fn delete_messages(
         "json" => json::print_json(&output)?,
         _ => {
             if permanent {
-                println!("✓ Permanently deleted {} message(s)", deleted_count);
+                println!("✓ Permanently deleted {} message(s)", result.count);
             } else {
-                println!("✓ Moved {} message(s) to Trash", deleted_count);
+                println!("✓ Moved {} message(s) to Trash", result.count);
             }
         }
     }
@@ -500,37 +1237,57 @@ pub async fn delete_messages(
 }
 
 /// Archive messages (shortcut for move to Archive)
+#[allow(clippy::too_many_arguments)]
 pub async fn archive_messages(
+    account_email: Option<&str>,
     ids: Vec<i64>,
     use_selection: bool,
+    query: Option<QueryTarget>,
     create_draft: bool,
     keep_selection: bool,
     output_format: Option<&str>,
 ) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
     let dest_folder = "Archive".to_string();
 
-    // Resolve shadow UIDs to current locations
-    let (shadow_uids, by_folder) =
-        resolve_shadow_uids_by_folder(ids, use_selection, &account.email, &state).await?;
+    let mut op = Draft {
+        account: account.email.clone(),
+        action_type: ActionType::Archive,
+        folder: "".to_string(),
+        uids: Vec::new(),
+        flag_params: None,
+        dest_folder: Some(dest_folder.clone()),
+        permanent: false,
+    };
 
-    // If draft mode, save draft and return
+    // Resolve IDs, the selection or --query matches to current locations
+    let Some(targets) = resolve_targets(
+        &config,
+        account,
+        &state,
+        ids,
+        use_selection,
+        query.as_ref(),
+        &op,
+        output_format,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+    let Targets {
+        shadow_uids,
+        by_folder,
+        client,
+    } = targets;
+    op.uids = shadow_uids.iter().map(|&id| id as u32).collect();
+
+    // If draft mode, queue the operation and return
     if create_draft {
-        let draft = Draft {
-            account: account.email.clone(),
-            action_type: ActionType::Archive,
-            folder: "".to_string(),
-            uids: shadow_uids.iter().map(|&id| id as u32).collect(),
-            flag_params: None,
-            dest_folder: Some(dest_folder.clone()),
-            permanent: false,
-        };
-        state.save_draft(&draft).await?;
+        let draft_id = state.save_draft(&op).await?;
 
         let output = ActionOutput {
             action: "archive".to_string(),
@@ -541,47 +1298,25 @@ pub async fn archive_messages(
             success_count: 0,
             failed_ids: vec![],
             draft_staged: Some(true),
+            draft_id: Some(draft_id),
+            history_id: None,
             selection_cleared: None,
         };
 
         match output_format.unwrap_or("text") {
             "json" => json::print_json(&output)?,
             _ => println!(
-                "Draft staged: Archive {} message(s). Run 'protoncli archive' to execute.",
-                shadow_uids.len()
+                "Draft #{} staged: Archive {} message(s). Run 'protoncli draft apply {}' to execute.",
+                draft_id,
+                shadow_uids.len(),
+                draft_id
             ),
         }
         return Ok(());
     }
 
-    let mut client = ImapClient::connect(account).await?;
-
-    // Validate Archive folder exists
-    if !client.folder_exists(&dest_folder).await? {
-        return Err(anyhow!("Archive folder does not exist"));
-    }
-
-    // Process each source folder
-    let mut archived_count = 0;
-    for (folder, messages) in &by_folder {
-        client.select_folder(folder).await?;
-
-        let imap_uids: Vec<u32> = messages.iter().map(|m| m.imap_uid).collect();
-        for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-            client.move_messages(&chunk, &dest_folder).await?;
-        }
-
-        // Update message locations
-        for msg in messages {
-            if let Some(ref msg_id) = msg.message_id {
-                state
-                    .update_message_location_by_message_id(&account.email, msg_id, &dest_folder, 0)
-                    .await?;
-            }
-        }
-
-        archived_count += messages.len();
-    }
+    let mut client = connect_or_reuse(client, account).await?;
+    let result = execute_operation(&mut client, &state, &account.email, &op, &by_folder).await?;
 
     // Clear selection unless --keep
     let selection_cleared = if use_selection {
@@ -596,15 +1331,17 @@ pub async fn archive_messages(
         source_folder: by_folder.keys().next().cloned(),
         dest_folder: Some(dest_folder.clone()),
         ids: shadow_uids.clone(),
-        success_count: archived_count,
+        success_count: result.count,
         failed_ids: vec![],
         draft_staged: None,
+        draft_id: None,
+        history_id: result.history_id,
         selection_cleared,
     };
 
     match output_format.unwrap_or("text") {
         "json" => json::print_json(&output)?,
-        _ => println!("✓ Archived {} message(s) → Archive", archived_count),
+        _ => println!("✓ Archived {} message(s) → Archive", result.count),
     }
 
     Ok(())
@@ -613,6 +1350,7 @@ pub async fn archive_messages(
 /// Modify message flags (read/unread, starred, labels) and optionally move
 #[allow(clippy::too_many_arguments)]
 pub async fn modify_flags(
+    account_email: Option<&str>,
     ids: Vec<i64>,
     read: bool,
     unread: bool,
@@ -622,6 +1360,7 @@ pub async fn modify_flags(
     unlabels: Vec<String>,
     move_to: Option<String>,
     use_selection: bool,
+    query: Option<QueryTarget>,
     create_draft: bool,
     keep_selection: bool,
     output_format: Option<&str>,
@@ -635,9 +1374,7 @@ pub async fn modify_flags(
     }
 
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
 
@@ -662,10 +1399,6 @@ pub async fn modify_flags(
         move_to: move_to.clone(),
     };
 
-    // Resolve shadow UIDs to current locations
-    let (shadow_uids, by_folder) =
-        resolve_shadow_uids_by_folder(ids, use_selection, &account.email, &state).await?;
-
     // Require at least one action
     if !flag_params.has_any_action() {
         return Err(anyhow!(
@@ -673,18 +1406,41 @@ pub async fn modify_flags(
         ));
     }
 
-    // If draft mode, save draft and return
+    let mut op = Draft {
+        account: account.email.clone(),
+        action_type: ActionType::Flag,
+        folder: "".to_string(),
+        uids: Vec::new(),
+        flag_params: Some(flag_params.clone()),
+        dest_folder: flag_params.move_to.clone().map(|d| resolve_folder_path(&d)),
+        permanent: false,
+    };
+
+    // Resolve IDs, the selection or --query matches to current locations
+    let Some(targets) = resolve_targets(
+        &config,
+        account,
+        &state,
+        ids,
+        use_selection,
+        query.as_ref(),
+        &op,
+        output_format,
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+    let Targets {
+        shadow_uids,
+        by_folder,
+        client,
+    } = targets;
+    op.uids = shadow_uids.iter().map(|&id| id as u32).collect();
+
+    // If draft mode, queue the operation and return
     if create_draft {
-        let draft = Draft {
-            account: account.email.clone(),
-            action_type: ActionType::Flag,
-            folder: "".to_string(),
-            uids: shadow_uids.iter().map(|&id| id as u32).collect(),
-            flag_params: Some(flag_params.clone()),
-            dest_folder: flag_params.move_to.clone().map(|d| resolve_folder_path(&d)),
-            permanent: false,
-        };
-        state.save_draft(&draft).await?;
+        let draft_id = state.save_draft(&op).await?;
 
         let mut actions = Vec::new();
         if flag_params.read == Some(true) {
@@ -718,119 +1474,26 @@ pub async fn modify_flags(
             success_count: 0,
             failed_ids: vec![],
             draft_staged: Some(true),
+            draft_id: Some(draft_id),
+            history_id: None,
             selection_cleared: None,
         };
 
         match output_format.unwrap_or("text") {
             "json" => json::print_json(&output)?,
             _ => println!(
-                "Draft staged: {} on {} message(s). Run 'protoncli flag' to execute.",
+                "Draft #{} staged: {} on {} message(s). Run 'protoncli draft apply {}' to execute.",
+                draft_id,
                 actions.join(", "),
-                shadow_uids.len()
+                shadow_uids.len(),
+                draft_id
             ),
         }
         return Ok(());
     }
 
-    let mut client = ImapClient::connect(account).await?;
-    let mut actions_performed = Vec::new();
-    let mut updated_count = 0;
-
-    // Process each folder
-    for (folder, messages) in &by_folder {
-        client.select_folder(folder).await?;
-        let imap_uids: Vec<u32> = messages.iter().map(|m| m.imap_uid).collect();
-
-        // Apply read/unread flags
-        if flag_params.read == Some(true) {
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.mark_messages_read(&chunk).await?;
-            }
-            if !actions_performed.contains(&"marked read") {
-                actions_performed.push("marked read");
-            }
-        }
-        if flag_params.read == Some(false) {
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.mark_messages_unread(&chunk).await?;
-            }
-            if !actions_performed.contains(&"marked unread") {
-                actions_performed.push("marked unread");
-            }
-        }
-
-        // Apply starred/unstarred flags
-        if flag_params.starred == Some(true) {
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.star_messages(&chunk).await?;
-            }
-            if !actions_performed.contains(&"starred") {
-                actions_performed.push("starred");
-            }
-        }
-        if flag_params.starred == Some(false) {
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.unstar_messages(&chunk).await?;
-            }
-            if !actions_performed.contains(&"unstarred") {
-                actions_performed.push("unstarred");
-            }
-        }
-
-        // Add labels
-        for label in &flag_params.labels {
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.modify_flags(&chunk, label, true).await?;
-            }
-        }
-        if !flag_params.labels.is_empty() && !actions_performed.contains(&"labeled") {
-            actions_performed.push("labeled");
-        }
-
-        // Remove labels
-        for unlabel in &flag_params.unlabels {
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.modify_flags(&chunk, unlabel, false).await?;
-            }
-        }
-        if !flag_params.unlabels.is_empty() && !actions_performed.contains(&"unlabeled") {
-            actions_performed.push("unlabeled");
-        }
-
-        // Move to destination folder if specified
-        if let Some(ref dest) = flag_params.move_to {
-            let dest_resolved = resolve_folder_path(dest);
-            if !client.folder_exists(&dest_resolved).await? {
-                return Err(anyhow!(
-                    "Destination folder '{}' does not exist",
-                    dest_resolved
-                ));
-            }
-            for chunk in chunk_uids(&imap_uids, DEFAULT_BATCH_SIZE) {
-                client.move_messages(&chunk, &dest_resolved).await?;
-            }
-
-            // Update message locations
-            for msg in messages {
-                if let Some(ref msg_id) = msg.message_id {
-                    state
-                        .update_message_location_by_message_id(
-                            &account.email,
-                            msg_id,
-                            &dest_resolved,
-                            0,
-                        )
-                        .await?;
-                }
-            }
-
-            if !actions_performed.contains(&"moved") {
-                actions_performed.push("moved");
-            }
-        }
-
-        updated_count += messages.len();
-    }
+    let mut client = connect_or_reuse(client, account).await?;
+    let result = execute_operation(&mut client, &state, &account.email, &op, &by_folder).await?;
 
     // Clear selection unless --keep
     let selection_cleared = if use_selection {
@@ -845,17 +1508,19 @@ pub async fn modify_flags(
         source_folder: by_folder.keys().next().cloned(),
         dest_folder: flag_params.move_to.map(|d| resolve_folder_path(&d)),
         ids: shadow_uids.clone(),
-        success_count: updated_count,
+        success_count: result.count,
         failed_ids: vec![],
         draft_staged: None,
+        draft_id: None,
+        history_id: result.history_id,
         selection_cleared,
     };
 
     match output_format.unwrap_or("text") {
         "json" => json::print_json(&output)?,
         _ => {
-            let actions_str = actions_performed.join(", ");
-            println!("✓ Updated {} message(s): {}", updated_count, actions_str);
+            let actions_str = result.actions.join(", ");
+            println!("✓ Updated {} message(s): {}", result.count, actions_str);
         }
     }
 
@@ -915,4 +1580,39 @@ mod tests {
         assert_eq!(resolve_folder_path("myfolder"), "myfolder");
         assert_eq!(resolve_folder_path("MYFOLDER"), "MYFOLDER");
     }
+
+    #[test]
+    fn test_flag_restorations_restore_previous_state() {
+        let params = FlagParams {
+            read: Some(true),
+            starred: Some(false),
+            labels: vec!["Work".to_string(), "Todo".to_string()],
+            unlabels: vec!["Later".to_string(), "Old".to_string()],
+            move_to: None,
+        };
+        let previous = vec![
+            "\\Flagged".to_string(),
+            "todo".to_string(),
+            "Later".to_string(),
+        ];
+
+        assert_eq!(
+            flag_restorations(&params, &previous),
+            vec![
+                ("\\Seen".to_string(), false),
+                ("\\Flagged".to_string(), true),
+                ("Work".to_string(), false),
+                ("Later".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flag_restorations_ignore_untouched_flags() {
+        let params = FlagParams {
+            move_to: Some("Archive".to_string()),
+            ..Default::default()
+        };
+        assert!(flag_restorations(&params, &["\\Seen".to_string()]).is_empty());
+    }
 }
@@ -0,0 +1,321 @@
+use crate::core::imap::ImapClient;
+use crate::core::state::{validate_shadow_uids, StateManager};
+use crate::models::config::Config;
+use crate::models::message::{Attachment, Message};
+use crate::output::json;
+use crate::output::markdown::format_size;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Maximum length (in bytes) of a saved filename
+const MAX_FILENAME_LEN: usize = 200;
+
+#[derive(Serialize)]
+struct AttachmentListOutput {
+    account: String,
+    id: i64,
+    count: usize,
+    attachments: Vec<Attachment>,
+}
+
+#[derive(Serialize)]
+struct SavedAttachment {
+    part: usize,
+    filename: String,
+    content_type: String,
+    size: usize,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct AttachmentSaveOutput {
+    account: String,
+    id: i64,
+    count: usize,
+    saved: Vec<SavedAttachment>,
+}
+
+/// Resolve a shadow UID and fetch the full message (including raw bytes)
+async fn fetch_message(account_email: Option<&str>, shadow_uid: i64) -> Result<(String, Message)> {
+    validate_shadow_uids(&[shadow_uid])?;
+
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?;
+
+    let state = StateManager::new().await?;
+    let resolved = state
+        .resolve_shadow_uids(&account.email, &[shadow_uid])
+        .await?;
+    let msg_info = resolved
+        .first()
+        .ok_or_else(|| anyhow!("Message {} not found", shadow_uid))?;
+
+    let mut client = ImapClient::connect(account).await?;
+    let message = client
+        .fetch_message_by_uid(msg_info.imap_uid, &msg_info.folder, true)
+        .await?;
+
+    Ok((account.email.clone(), message))
+}
+
+/// List the attachments of a message
+pub async fn list_attachments(
+    account_email: Option<&str>,
+    shadow_uid: i64,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let (account, message) = fetch_message(account_email, shadow_uid).await?;
+
+    let output = AttachmentListOutput {
+        account,
+        id: shadow_uid,
+        count: message.attachments.len(),
+        attachments: message.attachments,
+    };
+
+    match output_format.unwrap_or("json") {
+        "json" => json::print_json(&output)?,
+        "text" => {
+            if output.attachments.is_empty() {
+                println!("Message {} has no attachments", shadow_uid);
+            }
+            for attachment in &output.attachments {
+                println!(
+                    "[{}] {} ({}, {})",
+                    attachment.part,
+                    attachment.display_name(),
+                    attachment.content_type,
+                    format_size(attachment.size)
+                );
+            }
+        }
+        _ => return Err(anyhow!("Unsupported output format")),
+    }
+
+    Ok(())
+}
+
+/// Save one (--part N) or all attachments of a message to a directory
+pub async fn save_attachments(
+    account_email: Option<&str>,
+    shadow_uid: i64,
+    part: Option<usize>,
+    dir: Option<&str>,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let (account, message) = fetch_message(account_email, shadow_uid).await?;
+
+    if message.attachments.is_empty() {
+        return Err(anyhow!("Message {} has no attachments", shadow_uid));
+    }
+    if let Some(n) = part {
+        if n == 0 || n > message.attachments.len() {
+            return Err(anyhow!(
+                "Attachment part {} not found. Message {} has {} attachment(s)",
+                n,
+                shadow_uid,
+                message.attachments.len()
+            ));
+        }
+    }
+
+    let raw = message
+        .raw_message
+        .as_deref()
+        .ok_or_else(|| anyhow!("Raw message not available"))?;
+    let parsed = mail_parser::MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| anyhow!("Failed to parse message {}", shadow_uid))?;
+
+    let dir = PathBuf::from(dir.unwrap_or("."));
+    fs::create_dir_all(&dir).context(format!("Failed to create directory: {}", dir.display()))?;
+
+    let mut saved = Vec::new();
+    for (meta, mime_part) in message.attachments.iter().zip(parsed.attachments()) {
+        if part.is_some_and(|n| n != meta.part) {
+            continue;
+        }
+
+        let filename = sanitize_filename(&meta.display_name(), meta.part);
+        let path = write_unique(&dir, &filename, mime_part.contents())?;
+
+        saved.push(SavedAttachment {
+            part: meta.part,
+            filename,
+            content_type: meta.content_type.clone(),
+            size: meta.size,
+            path: path.display().to_string(),
+        });
+    }
+
+    let output = AttachmentSaveOutput {
+        account,
+        id: shadow_uid,
+        count: saved.len(),
+        saved,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            println!("✓ Saved {} attachment(s)", output.count);
+            for item in &output.saved {
+                println!(
+                    "  [{}] {} ({})",
+                    item.part,
+                    item.path,
+                    format_size(item.size)
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Make an attachment filename safe to write to disk.
+/// Strips any directory components, control characters and characters that are
+/// reserved on common filesystems, and never returns an empty or hidden name.
+pub fn sanitize_filename(name: &str, part: usize) -> String {
+    // Keep only the final path component (both separators, regardless of platform)
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+
+    let mut cleaned: String = base
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // No leading dots (hidden files, "..") or surrounding whitespace
+    cleaned = cleaned
+        .trim()
+        .trim_start_matches('.')
+        .trim_end_matches(['.', ' '])
+        .to_string();
+
+    if cleaned.is_empty() {
+        return format!("attachment-{}", part);
+    }
+
+    if cleaned.len() > MAX_FILENAME_LEN {
+        let (stem, ext) = split_extension(&cleaned);
+        let ext_len = ext.map(|e| e.len() + 1).unwrap_or(0);
+        let mut cut = MAX_FILENAME_LEN.saturating_sub(ext_len);
+        while !stem.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        cleaned = match ext {
+            Some(ext) => format!("{}.{}", &stem[..cut], ext),
+            None => stem[..cut].to_string(),
+        };
+    }
+
+    cleaned
+}
+
+/// Split "name.ext" into ("name", Some("ext"))
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    }
+}
+
+/// Name for the n-th collision candidate: "report.pdf" -> "report (2).pdf"
+pub fn collision_name(name: &str, n: usize) -> String {
+    if n <= 1 {
+        return name.to_string();
+    }
+    match split_extension(name) {
+        (stem, Some(ext)) => format!("{} ({}).{}", stem, n, ext),
+        (stem, None) => format!("{} ({})", stem, n),
+    }
+}
+
+/// Write data to dir/name without overwriting existing files
+fn write_unique(dir: &Path, name: &str, data: &[u8]) -> Result<PathBuf> {
+    for n in 1..10_000 {
+        let path = dir.join(collision_name(name, n));
+        // create_new fails if the file exists, so there is no check-then-write race
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(data)
+                    .context(format!("Failed to write attachment: {}", path.display()))?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(e).context(format!("Failed to create file: {}", path.display()));
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "Could not find a free filename for '{}' in {}",
+        name,
+        dir.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_strips_paths() {
+        assert_eq!(sanitize_filename("../../etc/passwd", 1), "passwd");
+        assert_eq!(
+            sanitize_filename("C:\\Users\\x\\invoice.pdf", 1),
+            "invoice.pdf"
+        );
+        assert_eq!(sanitize_filename("/tmp/", 3), "attachment-3");
+    }
+
+    #[test]
+    fn test_sanitize_reserved_and_hidden() {
+        assert_eq!(sanitize_filename("a:b*c?.txt", 1), "a_b_c_.txt");
+        assert_eq!(sanitize_filename("..", 2), "attachment-2");
+        assert_eq!(sanitize_filename(".bashrc", 1), "bashrc");
+        assert_eq!(sanitize_filename("bad\u{0}name\n.pdf", 1), "bad_name_.pdf");
+    }
+
+    #[test]
+    fn test_sanitize_truncates_long_names() {
+        let long = format!("{}.pdf", "a".repeat(500));
+        let cleaned = sanitize_filename(&long, 1);
+        assert!(cleaned.len() <= MAX_FILENAME_LEN);
+        assert!(cleaned.ends_with(".pdf"));
+    }
+
+    #[test]
+    fn test_collision_name() {
+        assert_eq!(collision_name("report.pdf", 1), "report.pdf");
+        assert_eq!(collision_name("report.pdf", 2), "report (2).pdf");
+        assert_eq!(collision_name("README", 3), "README (3)");
+        assert_eq!(collision_name("archive.tar.gz", 2), "archive.tar (2).gz");
+    }
+
+    #[test]
+    fn test_write_unique_does_not_overwrite() {
+        let dir = std::env::temp_dir().join(format!("protoncli-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = write_unique(&dir, "a.txt", b"one").unwrap();
+        let second = write_unique(&dir, "a.txt", b"two").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(fs::read(&first).unwrap(), b"one");
+        assert_eq!(fs::read(&second).unwrap(), b"two");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
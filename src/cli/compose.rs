@@ -0,0 +1,722 @@
+use crate::cli::query;
+use crate::cli::reply::ComposeStart;
+use crate::cli::send::{
+    append_message, compose_builder, resolve_sender, save_to_sent, split_attachment_spec,
+    validate_attachment_path, warn_attachment_size, EmailBuilder, MessageContent,
+};
+use crate::core::imap::ImapClient;
+use crate::core::smtp::SmtpClient;
+use crate::models::account::Account;
+use crate::models::config::Config;
+use crate::output::json;
+use anyhow::{anyhow, Context, Result};
+use lettre::message::Mailbox;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::NamedTempFile;
+
+/// Subject and recipients of a composed message
+#[derive(Serialize)]
+struct Outline {
+    subject: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    to: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cc: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bcc: Vec<String>,
+}
+
+impl Outline {
+    fn of(content: &MessageContent) -> Self {
+        Self {
+            subject: content
+                .subject
+                .clone()
+                .unwrap_or_else(|| "(No subject)".to_string()),
+            to: content.to.clone(),
+            cc: content.cc.clone(),
+            bcc: content.bcc.clone(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DraftSaved {
+    account: String,
+    folder: String,
+    /// Shadow UID of the draft, for `send --draft-id`
+    draft_id: i64,
+    #[serde(flatten)]
+    outline: Outline,
+}
+
+#[derive(Serialize)]
+struct MessageSent {
+    account: String,
+    #[serde(flatten)]
+    outline: Outline,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_reply_to: Option<String>,
+    /// Shadow UID of the copy saved to the Sent folder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sent_id: Option<i64>,
+}
+
+/// What an interactive compose starts from
+pub enum ComposeSource {
+    New,
+    Reply { id: i64, all: bool },
+    Forward { id: i64 },
+}
+
+/// Build a message and APPEND it to the Drafts folder with `\Draft` instead of sending it
+pub async fn save_draft(
+    account_email: Option<&str>,
+    content: MessageContent,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let (account, from_email) = resolve_sender(&config, account_email, content.from.as_deref())?;
+
+    let outline = Outline::of(&content);
+    let builder = compose_builder(&from_email, content)?;
+    warn_attachment_size(&config, &builder);
+
+    let mut client = ImapClient::connect(account).await?;
+    store_draft(
+        &mut client,
+        &config,
+        &account.email,
+        builder,
+        outline,
+        output_format,
+    )
+    .await
+}
+
+async fn store_draft(
+    client: &mut ImapClient,
+    config: &Config,
+    account: &str,
+    builder: EmailBuilder,
+    outline: Outline,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let folder = config.preferences.drafts_folder.clone();
+    let raw = builder.draft().build()?.formatted();
+    let draft_id = append_message(client, config, account, &folder, &raw, "\\Seen \\Draft").await?;
+
+    let output = DraftSaved {
+        account: account.to_string(),
+        folder,
+        draft_id,
+        outline,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            println!("✓ Draft saved to {} (id {})", output.folder, draft_id);
+            println!("  Subject: {}", output.outline.subject);
+            if !output.outline.to.is_empty() {
+                println!("  To: {}", output.outline.to.join(", "));
+            }
+            println!("  Send it with: protoncli send --draft-id {}", draft_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compose in $EDITOR: a To/Cc/Bcc/Subject/Attach header block above the body, prefilled
+/// from the options or from the message being replied to or forwarded. The result is
+/// validated, then sent, saved as a draft, edited again or dropped.
+pub async fn compose_in_editor(
+    account_email: Option<&str>,
+    content: MessageContent,
+    source: ComposeSource,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let (account, from_email) = resolve_sender(&config, account_email, content.from.as_deref())?;
+
+    let text = match &content.body_file {
+        Some(file_path) => fs::read_to_string(file_path)
+            .context(format!("Failed to read body file: {}", file_path))?,
+        None => content.body.clone().unwrap_or_default(),
+    };
+    let start = match source {
+        ComposeSource::New => None,
+        ComposeSource::Reply { id, all } => {
+            Some(ComposeStart::reply(account, id, all, &text).await?)
+        }
+        ComposeSource::Forward { id } => Some(ComposeStart::forward(account, id, &text).await?),
+    };
+
+    let mut editable = EditableMessage {
+        to: content.to.clone(),
+        cc: content.cc.clone(),
+        bcc: content.bcc.clone(),
+        subject: content.subject.clone().unwrap_or_default(),
+        attach: content.attach.clone(),
+        body: text,
+    };
+    let mut notes = Vec::new();
+    if let Some(start) = &start {
+        if editable.to.is_empty() {
+            editable.to = start.to.clone();
+        }
+        if editable.cc.is_empty() {
+            editable.cc = start.cc.clone();
+        }
+        if editable.subject.is_empty() {
+            editable.subject = start.subject.clone();
+        }
+        // Leave room above the quoted or forwarded original to type
+        editable.body = if editable.body.trim().is_empty() {
+            format!("\n\n{}", start.body)
+        } else {
+            start.body.clone()
+        };
+        if !start.attachments.is_empty() {
+            let names: Vec<&str> = start
+                .attachments
+                .iter()
+                .map(|a| a.filename.as_str())
+                .collect();
+            notes.push(format!(
+                "Forwarding the original's attachments: {}",
+                names.join(", ")
+            ));
+        }
+    }
+    if content.html.is_some() || content.html_file.is_some() {
+        notes.push(
+            "This text is the plain-text part; the HTML body comes from --html/--html-file"
+                .to_string(),
+        );
+    } else if content.markdown {
+        notes.push("The body is markdown and is also sent rendered as HTML".to_string());
+    }
+
+    let Some((edited, send, file)) = edit_until_done(&editable.render(&notes))? else {
+        eprintln!("Aborted, nothing was sent or saved.");
+        return Ok(());
+    };
+
+    // Until the message is out (or saved), the edited text stays on disk
+    let result = deliver(
+        &config,
+        account,
+        &from_email,
+        content,
+        edited,
+        send,
+        start,
+        output_format,
+    )
+    .await;
+    if result.is_err() {
+        match file.keep() {
+            Ok(path) => eprintln!("The message you wrote is kept in {}", path.display()),
+            Err(e) => eprintln!("Warning: could not keep the message you wrote: {:#}", e),
+        }
+    }
+    result
+}
+
+/// Send the edited message, or save it as a draft when `send` is false
+#[allow(clippy::too_many_arguments)]
+async fn deliver(
+    config: &Config,
+    account: &Account,
+    from_email: &str,
+    content: MessageContent,
+    edited: EditableMessage,
+    send: bool,
+    mut start: Option<ComposeStart>,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let content = MessageContent {
+        from: content.from,
+        to: edited.to,
+        cc: edited.cc,
+        bcc: edited.bcc,
+        subject: Some(edited.subject).filter(|s| !s.trim().is_empty()),
+        body: Some(edited.body),
+        body_file: None,
+        html: content.html,
+        html_file: content.html_file,
+        markdown: content.markdown,
+        inline: content.inline,
+        attach: edited.attach,
+    };
+    let outline = Outline::of(&content);
+    let mut builder = compose_builder(from_email, content)?;
+    if let Some(start) = &start {
+        builder = start.apply(builder);
+    }
+    warn_attachment_size(config, &builder);
+
+    // The editor may have been open long enough for the earlier IMAP connection to time
+    // out, so everything after it connects again
+    if !send {
+        let mut client = ImapClient::connect(account).await?;
+        return store_draft(
+            &mut client,
+            config,
+            &account.email,
+            builder,
+            outline,
+            output_format,
+        )
+        .await;
+    }
+
+    let email = builder.build()?;
+    let mut smtp_client = SmtpClient::connect(account)?;
+    let (_, raw) = smtp_client.send_message(email)?;
+
+    // The message is out; from here on problems only warn
+    let sent_id = match start.as_mut() {
+        Some(start) => match start.reconnect(account).await {
+            Ok(()) => {
+                if let Err(e) = start.mark_answered().await {
+                    eprintln!("Warning: could not flag the original as answered: {:#}", e);
+                }
+                save_to_sent(start.client(), config, &account.email, &raw).await
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: message sent, but reconnecting to update the original and Sent failed: {:#}",
+                    e
+                );
+                None
+            }
+        },
+        None if config.preferences.save_sent => match ImapClient::connect(account).await {
+            Ok(mut client) => save_to_sent(&mut client, config, &account.email, &raw).await,
+            Err(e) => {
+                eprintln!("Warning: message sent but not saved to Sent: {:#}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let output = MessageSent {
+        account: account.email.clone(),
+        outline,
+        in_reply_to: start.and_then(|s| s.in_reply_to),
+        sent_id,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            let recipients: Vec<&str> = output
+                .outline
+                .to
+                .iter()
+                .chain(&output.outline.cc)
+                .chain(&output.outline.bcc)
+                .map(String::as_str)
+                .collect();
+            println!("✓ Email sent to {}", recipients.join(", "));
+            println!("  Subject: {}", output.outline.subject);
+            if let Some(id) = sent_id {
+                println!("  Saved to {} (id {})", config.preferences.sent_folder, id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the editor until the message is valid and the user picks send (true) or save
+/// draft (false), returning the file it was edited in; None if they abort or empty the file
+fn edit_until_done(initial: &str) -> Result<Option<(EditableMessage, bool, ComposeFile)>> {
+    let file = ComposeFile::create(initial)?;
+
+    loop {
+        run_editor(file.path())?;
+        let text = fs::read_to_string(file.path()).context("Failed to read the edited message")?;
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let problems = match EditableMessage::parse(&text) {
+            Ok(edited) => {
+                let problems = edited.problems();
+                if problems.is_empty() {
+                    edited.print_summary();
+                    match ask(
+                        "[s]end, save [d]raft, [e]dit again or [a]bort?",
+                        &['s', 'd', 'e', 'a'],
+                    )? {
+                        's' if edited.to.is_empty() => {
+                            vec!["Add a To: recipient before sending".to_string()]
+                        }
+                        's' => return Ok(Some((edited, true, file))),
+                        'd' => return Ok(Some((edited, false, file))),
+                        'e' => continue,
+                        _ => return Ok(None),
+                    }
+                } else {
+                    problems
+                }
+            }
+            Err(e) => vec![format!("{:#}", e)],
+        };
+
+        for problem in problems {
+            eprintln!("✗ {}", problem);
+        }
+        if ask("[e]dit again or [a]bort?", &['e', 'a'])? != 'e' {
+            return Ok(None);
+        }
+    }
+}
+
+/// Ask until one of `choices` (by first letter) is answered; end of input counts as abort
+fn ask(prompt: &str, choices: &[char]) -> Result<char> {
+    loop {
+        eprint!("{} ", prompt);
+        io::stderr().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok('a');
+        }
+        let answer = input.trim().chars().next().map(|c| c.to_ascii_lowercase());
+        if let Some(answer) = answer.filter(|c| choices.contains(c)) {
+            return Ok(answer);
+        }
+    }
+}
+
+/// Open `path` in $VISUAL or $EDITOR (vi if neither is set); the command may carry arguments
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .context(format!("Failed to start editor '{}'", editor))?;
+    if !status.success() {
+        return Err(anyhow!("Editor '{}' exited with {}", editor, status));
+    }
+    Ok(())
+}
+
+/// Temporary file holding the message while it's edited, removed when dropped unless kept.
+/// It gets a new random name and is readable by the owner only (0600 on Unix), so nothing
+/// already in the temp directory (a symlink, another user's file) can be written through.
+struct ComposeFile(NamedTempFile);
+
+impl ComposeFile {
+    fn create(text: &str) -> Result<Self> {
+        let mut file = tempfile::Builder::new()
+            .prefix("protoncli-compose-")
+            .suffix(".eml")
+            .tempfile()
+            .context("Failed to create the compose file")?;
+        file.write_all(text.as_bytes())
+            .context("Failed to write the compose file")?;
+        Ok(Self(file))
+    }
+
+    fn path(&self) -> &Path {
+        self.0.path()
+    }
+
+    /// Leave the file in place (e.g. when sending failed) and return its path
+    fn keep(self) -> Result<PathBuf> {
+        Ok(self.0.into_temp_path().keep()?)
+    }
+}
+
+const HEADER_HELP: &str = "\
+# Addresses are comma-separated; one file per Attach: line (PATH or PATH:TYPE).
+# Lines starting with # are ignored. The body starts after the first blank line.
+";
+
+/// The header block and body edited in $EDITOR
+#[derive(Debug, Default, PartialEq)]
+struct EditableMessage {
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+    subject: String,
+    attach: Vec<String>,
+    body: String,
+}
+
+impl EditableMessage {
+    fn render(&self, notes: &[String]) -> String {
+        let mut output = String::new();
+        output.push_str(&format!("To: {}\n", self.to.join(", ")));
+        output.push_str(&format!("Cc: {}\n", self.cc.join(", ")));
+        output.push_str(&format!("Bcc: {}\n", self.bcc.join(", ")));
+        output.push_str(&format!("Subject: {}\n", self.subject));
+        if self.attach.is_empty() {
+            output.push_str("Attach: \n");
+        }
+        for spec in &self.attach {
+            output.push_str(&format!("Attach: {}\n", spec));
+        }
+        output.push_str(HEADER_HELP);
+        for note in notes {
+            output.push_str(&format!("# {}\n", note));
+        }
+        output.push('\n');
+        output.push_str(&self.body);
+        output
+    }
+
+    /// Parse the edited file: headers (folded lines continue the previous one) up to the
+    /// first blank line, then the body
+    fn parse(text: &str) -> Result<Self> {
+        let mut headers: Vec<(usize, String)> = Vec::new();
+        let mut body = "";
+        let mut offset = 0;
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            offset += line.len();
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.trim().is_empty() {
+                body = &text[offset..];
+                break;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with([' ', '\t']) {
+                let (_, header) = headers
+                    .last_mut()
+                    .ok_or_else(|| anyhow!("Line {}: continuation without a header", index + 1))?;
+                header.push(' ');
+                header.push_str(line.trim());
+                continue;
+            }
+            headers.push((index + 1, line.to_string()));
+        }
+
+        let mut message = Self {
+            body: body.to_string(),
+            ..Self::default()
+        };
+        for (line_number, header) in headers {
+            let (name, value) = header.split_once(':').ok_or_else(|| {
+                anyhow!(
+                    "Line {}: expected 'Header: value' (leave a blank line before the body), got '{}'",
+                    line_number,
+                    header
+                )
+            })?;
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "to" => message.to.extend(split_addresses(value)),
+                "cc" => message.cc.extend(split_addresses(value)),
+                "bcc" => message.bcc.extend(split_addresses(value)),
+                "subject" => message.subject = value.to_string(),
+                "attach" if value.is_empty() => {}
+                "attach" => message.attach.push(value.to_string()),
+                _ => {
+                    return Err(anyhow!(
+                        "Line {}: unknown header '{}' (use To, Cc, Bcc, Subject or Attach)",
+                        line_number,
+                        name.trim()
+                    ))
+                }
+            }
+        }
+        Ok(message)
+    }
+
+    /// Invalid addresses and unreadable attachments
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (field, addresses) in [("To", &self.to), ("Cc", &self.cc), ("Bcc", &self.bcc)] {
+            for address in addresses {
+                if address.parse::<Mailbox>().is_err() {
+                    problems.push(format!("{}: invalid address '{}'", field, address));
+                }
+            }
+        }
+        for spec in &self.attach {
+            if let Err(e) = validate_attachment_path(split_attachment_spec(spec).0) {
+                problems.push(format!("Attach: {:#}", e));
+            }
+        }
+        problems
+    }
+
+    fn print_summary(&self) {
+        for (field, addresses) in [("To", &self.to), ("Cc", &self.cc), ("Bcc", &self.bcc)] {
+            if !addresses.is_empty() {
+                eprintln!("{}: {}", field, addresses.join(", "));
+            }
+        }
+        let subject = self.subject.trim();
+        eprintln!(
+            "Subject: {}",
+            if subject.is_empty() {
+                "(No subject)"
+            } else {
+                subject
+            }
+        );
+        if !self.attach.is_empty() {
+            eprintln!("Attachments: {}", self.attach.len());
+        }
+        eprintln!("Body: {} line(s)", self.body.trim_end().lines().count());
+    }
+}
+
+/// Split a comma-separated address list, keeping commas inside quotes or <...>
+fn split_addresses(value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let mut current = String::new();
+    let (mut quoted, mut escaped, mut angle) = (false, false, false);
+
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '<' if !quoted => angle = true,
+            '>' if !quoted => angle = false,
+            ',' if !quoted && !angle => {
+                addresses.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    addresses.push(current.trim().to_string());
+
+    addresses.retain(|address| !address.is_empty());
+    addresses
+}
+
+/// List the messages in the Drafts folder, newest first, like `query` does
+pub async fn list_draft_mail(
+    account_email: Option<&str>,
+    limit: Option<usize>,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let folders = [config.preferences.drafts_folder.clone()];
+
+    query::execute_query(
+        account_email,
+        "",
+        &folders,
+        None,
+        limit,
+        false,
+        false,
+        output_format,
+        false,
+        false,
+        None,
+        false,
+        None,
+        None,
+        None,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_file_is_private_and_kept_on_request() {
+        let first = ComposeFile::create("Subject: hi\n").unwrap();
+        let second = ComposeFile::create("").unwrap();
+        assert_ne!(first.path(), second.path());
+        assert_eq!(fs::read_to_string(first.path()).unwrap(), "Subject: hi\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(first.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let dropped = second.path().to_path_buf();
+        drop(second);
+        assert!(!dropped.exists());
+        let kept = first.keep().unwrap();
+        assert!(kept.exists());
+        fs::remove_file(kept).unwrap();
+    }
+
+    #[test]
+    fn test_split_addresses() {
+        assert_eq!(
+            split_addresses(r#""Doe, Jane" <jane@example.com>, bob@example.com,, "#),
+            vec![r#""Doe, Jane" <jane@example.com>"#, "bob@example.com"]
+        );
+        assert!(split_addresses("  ").is_empty());
+    }
+
+    #[test]
+    fn test_editable_message_round_trip() {
+        let message = EditableMessage {
+            to: vec!["alice@example.com".to_string()],
+            cc: vec![],
+            bcc: vec!["Me <me@example.com>".to_string()],
+            subject: "Re: Lunch".to_string(),
+            attach: vec!["menu.pdf".to_string(), "a.bin:application/pdf".to_string()],
+            body: "Sounds good\n\n> Are you free?\n".to_string(),
+        };
+        let text = message.render(&["Forwarding the original's attachments: x.png".to_string()]);
+        assert_eq!(EditableMessage::parse(&text).unwrap(), message);
+    }
+
+    #[test]
+    fn test_parse_edited_headers() {
+        let text = "to: a@example.com,\n  b@example.com\nSubject:   Hi  \n# note\n\nBody\n\nTo: not a header\n";
+        let message = EditableMessage::parse(text).unwrap();
+        assert_eq!(message.to, vec!["a@example.com", "b@example.com"]);
+        assert_eq!(message.subject, "Hi");
+        assert_eq!(message.body, "Body\n\nTo: not a header\n");
+
+        let err = EditableMessage::parse("To: a@example.com\nFrom: me@example.com\n\nx")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Line 2") && err.contains("From"));
+        assert!(EditableMessage::parse("To: a@example.com\nHello there\n").is_err());
+    }
+
+    #[test]
+    fn test_problems_report_bad_addresses_and_files() {
+        let message = EditableMessage {
+            to: vec![
+                "alice@example.com".to_string(),
+                "not an address".to_string(),
+            ],
+            attach: vec!["/nonexistent/protoncli/file.pdf".to_string()],
+            ..EditableMessage::default()
+        };
+        let problems = message.problems();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("'not an address'"));
+        assert!(problems[1].contains("not found"));
+    }
+}
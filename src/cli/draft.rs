@@ -1,116 +1,313 @@
-use crate::core::state::{ActionType, StateManager};
+use crate::cli::actions;
+use crate::core::imap::ImapClient;
+use crate::core::state::{ActionType, Draft, Operation, StagedDraft, StateManager};
 use crate::models::config::Config;
 use crate::output::json;
 use anyhow::{anyhow, Result};
 use serde::Serialize;
 
+/// A message a staged operation will touch, as last seen locally
 #[derive(Serialize)]
-pub struct DraftOutput {
-    pub account: String,
-    pub has_draft: bool,
+struct DraftMessagePreview {
+    id: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub action_type: Option<String>,
+    folder: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub folder: Option<String>,
+    subject: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub message_count: Option<usize>,
+    from: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DraftEntry {
+    #[serde(flatten)]
+    staged: StagedDraft,
+    description: String,
+    messages: Vec<DraftMessagePreview>,
+}
+
+#[derive(Serialize)]
+struct DraftListOutput {
+    account: String,
+    count: usize,
+    drafts: Vec<DraftEntry>,
+}
+
+#[derive(Serialize)]
+struct AppliedDraft {
+    id: i64,
+    action: String,
+    description: String,
+    success_count: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub uids: Option<Vec<u32>>,
+    history_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct DraftApplyOutput {
+    account: String,
+    applied: Vec<AppliedDraft>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub dest_folder: Option<String>,
+    failed_id: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub description: Option<String>,
+    error: Option<String>,
+    remaining: usize,
 }
 
-/// Show the current draft
-pub async fn show_draft(output_format: Option<&str>) -> Result<()> {
+/// List the queued operations, resolving each message to its subject
+pub async fn list_drafts(account_email: Option<&str>, output_format: Option<&str>) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
-    let draft = state.get_draft(&account.email).await?;
-
-    let output = match draft {
-        Some(d) => {
-            let description = format_draft_description(&d);
-            DraftOutput {
-                account: account.email.clone(),
-                has_draft: true,
-                action_type: Some(d.action_type.as_str().to_string()),
-                folder: Some(d.folder),
-                message_count: Some(d.uids.len()),
-                uids: Some(d.uids),
-                dest_folder: d.dest_folder,
-                description: Some(description),
-            }
+    let staged = state.list_drafts(&account.email).await?;
+
+    let mut drafts = Vec::new();
+    for staged in staged {
+        let mut messages = Vec::new();
+        for &uid in &staged.draft.uids {
+            let record = state
+                .get_message_by_shadow_uid(&account.email, uid as i64)
+                .await?;
+            messages.push(match record {
+                Some(r) => DraftMessagePreview {
+                    id: r.id,
+                    folder: Some(r.folder),
+                    subject: r.subject,
+                    from: r.from_address,
+                },
+                None => DraftMessagePreview {
+                    id: uid as i64,
+                    folder: None,
+                    subject: None,
+                    from: None,
+                },
+            });
         }
-        None => DraftOutput {
-            account: account.email.clone(),
-            has_draft: false,
-            action_type: None,
-            folder: None,
-            message_count: None,
-            uids: None,
-            dest_folder: None,
-            description: None,
-        },
+        drafts.push(DraftEntry {
+            description: format_draft_description(&staged.draft),
+            staged,
+            messages,
+        });
+    }
+
+    let output = DraftListOutput {
+        account: account.email.clone(),
+        count: drafts.len(),
+        drafts,
     };
 
     match output_format.unwrap_or("text") {
         "json" => json::print_json(&output)?,
         _ => {
-            if output.has_draft {
-                println!("Draft staged for {}:", output.account);
+            if output.drafts.is_empty() {
+                println!("No drafts staged for {}", output.account);
+                return Ok(());
+            }
+
+            println!(
+                "{} draft(s) staged for {} (applied in this order):",
+                output.count, output.account
+            );
+            for entry in &output.drafts {
                 println!();
-                println!("  Action: {}", output.action_type.as_ref().unwrap());
-                println!("  Folder: {}", output.folder.as_ref().unwrap());
-                println!("  Messages: {}", output.message_count.unwrap());
-                if let Some(dest) = &output.dest_folder {
-                    println!("  Destination: {}", dest);
+                println!(
+                    "  #{}  {}  (staged {})",
+                    entry.staged.id, entry.description, entry.staged.created_at
+                );
+                for message in &entry.messages {
+                    println!("      {}", format_message_preview(message));
                 }
-                println!();
-                println!("{}", output.description.as_ref().unwrap());
-                println!();
-                println!("Run the action command without --draft to execute.");
-            } else {
-                println!("No draft staged for {}", output.account);
             }
+            println!();
+            println!(
+                "Run 'protoncli draft apply [N|--all]' to execute, or 'protoncli draft drop N' to discard."
+            );
         }
     }
 
     Ok(())
 }
 
-/// Clear the current draft
-pub async fn clear_draft(output_format: Option<&str>) -> Result<()> {
+/// Apply the oldest draft, a specific draft, or the whole queue in order.
+/// Each draft is removed once it succeeds; the first failure stops the run and
+/// leaves it (and everything after it) queued.
+pub async fn apply_drafts(
+    account_email: Option<&str>,
+    id: Option<i64>,
+    all: bool,
+    yes: bool,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
-    let had_draft = state.clear_draft(&account.email).await?;
+    let queue = state.list_drafts(&account.email).await?;
+
+    let to_apply: Vec<StagedDraft> = match (id, all) {
+        (_, true) => queue,
+        (Some(id), false) => vec![queue
+            .into_iter()
+            .find(|d| d.id == id)
+            .ok_or_else(|| anyhow!("Draft #{} not found. Run 'protoncli draft list'.", id))?],
+        (None, false) => queue.into_iter().take(1).collect(),
+    };
+
+    if to_apply.is_empty() {
+        println!("No drafts staged for {}", account.email);
+        return Ok(());
+    }
+
+    // Permanent deletes are confirmed up front, like `delete --permanent`
+    let permanent_count: usize = to_apply
+        .iter()
+        .filter(|d| d.draft.action_type == ActionType::Delete && d.draft.permanent)
+        .map(|d| d.draft.uids.len())
+        .sum();
+    if permanent_count > 0 && !yes && !actions::confirm_permanent_delete(permanent_count)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    let mut client = ImapClient::connect(account).await?;
+    let mut applied = Vec::new();
+    let mut failure = None;
+
+    for staged in &to_apply {
+        let shadow_uids: Vec<i64> = staged.draft.uids.iter().map(|&uid| uid as i64).collect();
+        let result = match actions::group_by_folder(&state, &account.email, &shadow_uids).await {
+            Ok(by_folder) => {
+                actions::execute_operation(
+                    &mut client,
+                    &state,
+                    &account.email,
+                    &staged.draft,
+                    &by_folder,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(result) => {
+                state.drop_draft(&account.email, staged.id).await?;
+                let description = format_draft_description(&staged.draft);
+                if output_format != Some("json") {
+                    println!("✓ #{} {}", staged.id, description);
+                }
+                applied.push(AppliedDraft {
+                    id: staged.id,
+                    action: staged.draft.action_type.as_str().to_string(),
+                    description,
+                    success_count: result.count,
+                    history_id: result.history_id,
+                });
+            }
+            Err(e) => {
+                failure = Some((staged.id, e));
+                break;
+            }
+        }
+    }
+
+    let remaining = state.list_drafts(&account.email).await?.len();
+    let output = DraftApplyOutput {
+        account: account.email.clone(),
+        applied,
+        failed_id: failure.as_ref().map(|(id, _)| *id),
+        error: failure.as_ref().map(|(_, e)| format!("{:#}", e)),
+        remaining,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            if failure.is_none() {
+                println!(
+                    "Applied {} draft(s); {} still queued",
+                    output.applied.len(),
+                    output.remaining
+                );
+            }
+        }
+    }
+
+    match failure {
+        Some((id, e)) => Err(e.context(format!(
+            "Draft #{} failed; it and {} other draft(s) remain queued",
+            id,
+            remaining.saturating_sub(1)
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Discard one queued draft
+pub async fn drop_draft(
+    account_email: Option<&str>,
+    id: i64,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?;
+
+    let state = StateManager::new().await?;
+    if !state.drop_draft(&account.email, id).await? {
+        return Err(anyhow!(
+            "Draft #{} not found. Run 'protoncli draft list'.",
+            id
+        ));
+    }
+
+    #[derive(Serialize)]
+    struct DropOutput {
+        account: String,
+        dropped: i64,
+    }
+
+    let output = DropOutput {
+        account: account.email.clone(),
+        dropped: id,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => println!("✓ Draft #{} dropped", id),
+    }
+
+    Ok(())
+}
+
+/// Clear every queued draft
+pub async fn clear_draft(account_email: Option<&str>, output_format: Option<&str>) -> Result<()> {
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?;
+
+    let state = StateManager::new().await?;
+    let cleared_count = state.clear_drafts(&account.email).await?;
 
     #[derive(Serialize)]
     struct ClearOutput {
         account: String,
         cleared: bool,
+        cleared_count: u64,
     }
 
     let output = ClearOutput {
         account: account.email.clone(),
-        cleared: had_draft,
+        cleared: cleared_count > 0,
+        cleared_count,
     };
 
     match output_format.unwrap_or("text") {
         "json" => json::print_json(&output)?,
         _ => {
-            if had_draft {
-                println!("✓ Draft cleared");
+            if cleared_count > 0 {
+                println!("✓ Cleared {} draft(s)", cleared_count);
             } else {
-                println!("No draft to clear");
+                println!("No drafts to clear");
             }
         }
     }
@@ -118,16 +315,40 @@ pub async fn clear_draft(output_format: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+/// One preview line: "[id] subject — sender (folder)"
+fn format_message_preview(message: &DraftMessagePreview) -> String {
+    match message.folder {
+        Some(ref folder) => format!(
+            "[{}] {} — {} ({})",
+            message.id,
+            message.subject.as_deref().unwrap_or("(no subject)"),
+            message.from.as_deref().unwrap_or("unknown sender"),
+            folder
+        ),
+        None => format!("[{}] (unknown message)", message.id),
+    }
+}
+
 /// Format a human-readable description of what the draft will do
-fn format_draft_description(draft: &crate::core::state::Draft) -> String {
-    let count = draft.uids.len();
+pub fn format_draft_description(draft: &Draft) -> String {
+    describe_operation(&draft.operation(), draft.uids.len(), &draft.folder)
+}
+
+/// Describe `op` applied to `count` messages from `folder` (empty when they span folders)
+pub fn describe_operation(op: &Operation, count: usize, folder: &str) -> String {
     let msgs = if count == 1 { "message" } else { "messages" };
+    // Drafts staged by ID span folders and leave this empty
+    let from = if folder.is_empty() {
+        String::new()
+    } else {
+        format!(" from '{}'", folder)
+    };
 
-    match &draft.action_type {
+    match &op.action_type {
         ActionType::Flag => {
             let mut actions = Vec::new();
 
-            if let Some(params) = &draft.flag_params {
+            if let Some(params) = &op.flag_params {
                 if params.read == Some(true) {
                     actions.push("mark as read".to_string());
                 } else if params.read == Some(false) {
@@ -160,34 +381,88 @@ fn format_draft_description(draft: &crate::core::state::Draft) -> String {
             }
         }
         ActionType::Move => {
-            let dest = draft.dest_folder.as_deref().unwrap_or("?");
-            format!(
-                "Move {} {} from '{}' to '{}'",
-                count, msgs, draft.folder, dest
-            )
+            let dest = op.dest_folder.as_deref().unwrap_or("?");
+            format!("Move {} {}{} to '{}'", count, msgs, from, dest)
         }
         ActionType::Copy => {
-            let dest = draft.dest_folder.as_deref().unwrap_or("?");
-            format!(
-                "Copy {} {} from '{}' to '{}'",
-                count, msgs, draft.folder, dest
-            )
+            let dest = op.dest_folder.as_deref().unwrap_or("?");
+            format!("Copy {} {}{} to '{}'", count, msgs, from, dest)
         }
         ActionType::Delete => {
-            if draft.permanent {
-                format!(
-                    "Permanently delete {} {} from '{}'",
-                    count, msgs, draft.folder
-                )
+            if op.permanent {
+                format!("Permanently delete {} {}{}", count, msgs, from)
             } else {
-                format!("Move {} {} from '{}' to Trash", count, msgs, draft.folder)
+                format!("Move {} {}{} to Trash", count, msgs, from)
             }
         }
         ActionType::Archive => {
-            format!(
-                "Archive {} {} from '{}' to 'Archive'",
-                count, msgs, draft.folder
-            )
+            format!("Archive {} {}{} to 'Archive'", count, msgs, from)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::state::FlagParams;
+
+    fn draft(action_type: ActionType, folder: &str) -> Draft {
+        Draft {
+            account: "me@example.com".to_string(),
+            action_type,
+            folder: folder.to_string(),
+            uids: vec![3, 7],
+            flag_params: None,
+            dest_folder: Some("Receipts".to_string()),
+            permanent: false,
+        }
+    }
+
+    #[test]
+    fn test_description_omits_empty_source_folder() {
+        assert_eq!(
+            format_draft_description(&draft(ActionType::Move, "")),
+            "Move 2 messages to 'Receipts'"
+        );
+        assert_eq!(
+            format_draft_description(&draft(ActionType::Copy, "INBOX")),
+            "Copy 2 messages from 'INBOX' to 'Receipts'"
+        );
+    }
+
+    #[test]
+    fn test_description_lists_flag_changes() {
+        let mut d = draft(ActionType::Flag, "");
+        d.flag_params = Some(FlagParams {
+            read: Some(true),
+            labels: vec!["Work".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(
+            format_draft_description(&d),
+            "mark as read, add label 'Work' on 2 messages"
+        );
+    }
+
+    #[test]
+    fn test_message_preview_line() {
+        let known = DraftMessagePreview {
+            id: 12,
+            folder: Some("INBOX".to_string()),
+            subject: Some("Invoice".to_string()),
+            from: None,
+        };
+        assert_eq!(
+            format_message_preview(&known),
+            "[12] Invoice — unknown sender (INBOX)"
+        );
+
+        let unknown = DraftMessagePreview {
+            id: 40,
+            folder: None,
+            subject: None,
+            from: None,
+        };
+        assert_eq!(format_message_preview(&unknown), "[40] (unknown message)");
+    }
+}
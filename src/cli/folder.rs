@@ -27,12 +27,10 @@ struct FolderRenameOutput {
     message: String,
 }
 
-pub async fn list_folders(output_format: Option<&str>) -> Result<()> {
+pub async fn list_folders(account_email: Option<&str>, output_format: Option<&str>) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     let mut client = ImapClient::connect(account).await?;
     let folders = client.list_folders().await?;
@@ -52,12 +50,14 @@ pub async fn list_folders(output_format: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-pub async fn create_folder(name: &str, output_format: Option<&str>) -> Result<()> {
+pub async fn create_folder(
+    account_email: Option<&str>,
+    name: &str,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     // Prepend "Folders/" prefix for ProtonMail custom folders
     let folder_path = format!("Folders/{}", name);
@@ -88,12 +88,14 @@ pub async fn create_folder(name: &str, output_format: Option<&str>) -> Result<()
     Ok(())
 }
 
-pub async fn delete_folder(name: &str, output_format: Option<&str>) -> Result<()> {
+pub async fn delete_folder(
+    account_email: Option<&str>,
+    name: &str,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     // Prepend "Folders/" prefix for ProtonMail custom folders
     let folder_path = format!("Folders/{}", name);
@@ -124,12 +126,15 @@ pub async fn delete_folder(name: &str, output_format: Option<&str>) -> Result<()
     Ok(())
 }
 
-pub async fn rename_folder(from: &str, to: &str, output_format: Option<&str>) -> Result<()> {
+pub async fn rename_folder(
+    account_email: Option<&str>,
+    from: &str,
+    to: &str,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     // Prepend "Folders/" prefix for ProtonMail custom folders
     let from_path = format!("Folders/{}", from);
@@ -0,0 +1,190 @@
+use crate::cli::{actions, draft};
+use crate::core::imap::ImapClient;
+use crate::core::state::{LoggedAction, StateManager};
+use crate::models::config::Config;
+use crate::output::json;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct HistoryEntry {
+    #[serde(flatten)]
+    action: LoggedAction,
+    description: String,
+    undoable: bool,
+}
+
+#[derive(Serialize)]
+struct HistoryOutput {
+    account: String,
+    count: usize,
+    actions: Vec<HistoryEntry>,
+}
+
+#[derive(Serialize)]
+struct UndoOutput {
+    account: String,
+    id: i64,
+    action: String,
+    description: String,
+    success_count: usize,
+    /// Messages left to undo; the action can be undone again to retry them
+    remaining: usize,
+    actions: Vec<&'static str>,
+}
+
+/// List recently executed actions, newest first
+pub async fn show_history(
+    account_email: Option<&str>,
+    limit: usize,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?;
+
+    let state = StateManager::new().await?;
+    let actions: Vec<HistoryEntry> = state
+        .list_actions(&account.email, limit)
+        .await?
+        .into_iter()
+        .map(|action| HistoryEntry {
+            description: describe(&action),
+            undoable: actions::undo_blocker(&action).is_none(),
+            action,
+        })
+        .collect();
+
+    let output = HistoryOutput {
+        account: account.email.clone(),
+        count: actions.len(),
+        actions,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            if output.actions.is_empty() {
+                println!("No actions recorded for {}", output.account);
+                return Ok(());
+            }
+            for entry in &output.actions {
+                let status = match entry.action.undone_at {
+                    Some(ref at) => format!("  (undone {})", at),
+                    None if !entry.undoable => "  (can't be undone)".to_string(),
+                    None => String::new(),
+                };
+                println!(
+                    "#{}  {}  {}{}",
+                    entry.action.id, entry.action.created_at, entry.description, status
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reverse a logged action (default: the most recent one not yet undone)
+pub async fn undo(
+    account_email: Option<&str>,
+    id: Option<i64>,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?;
+
+    let state = StateManager::new().await?;
+    let action = match (state.get_action(&account.email, id).await?, id) {
+        (Some(action), _) => action,
+        (None, Some(id)) => {
+            return Err(anyhow!(
+                "Action #{} not found. Run 'protoncli history'.",
+                id
+            ))
+        }
+        (None, None) => return Err(anyhow!("Nothing to undo")),
+    };
+    if let Some(reason) = actions::undo_blocker(&action) {
+        return Err(anyhow!("Action #{} can't be undone: {}", action.id, reason));
+    }
+
+    let mut client = ImapClient::connect(account).await?;
+    let result = actions::undo_operation(&mut client, &state, &account.email, &action).await?;
+
+    let output = UndoOutput {
+        account: account.email.clone(),
+        id: action.id,
+        action: action.operation.action_type.as_str().to_string(),
+        description: describe(&action),
+        success_count: result.count,
+        remaining: result.remaining,
+        actions: result.actions,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            if output.remaining == 0 {
+                println!("✓ Undid #{}: {}", output.id, output.description);
+            } else {
+                println!("⚠ Partly undid #{}: {}", output.id, output.description);
+            }
+            println!(
+                "  {} message(s) {}",
+                output.success_count,
+                output.actions.join(", ")
+            );
+            if output.remaining > 0 {
+                println!(
+                    "  {} message(s) could not be found (no Message-ID, or moved since); \
+                     run 'protoncli undo {}' to retry them",
+                    output.remaining, output.id
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Description of a logged action; its messages may come from several folders
+fn describe(action: &LoggedAction) -> String {
+    draft::describe_operation(&action.operation, action.messages.len(), "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::state::{ActionType, Operation};
+
+    fn logged(action_type: ActionType, permanent: bool) -> LoggedAction {
+        LoggedAction {
+            id: 1,
+            created_at: "2024-01-01 00:00:00".to_string(),
+            undone_at: None,
+            operation: Operation {
+                account: "me@example.com".to_string(),
+                action_type,
+                flag_params: None,
+                dest_folder: None,
+                permanent,
+            },
+            messages: vec![],
+        }
+    }
+
+    #[test]
+    fn test_undo_blockers() {
+        assert!(actions::undo_blocker(&logged(ActionType::Archive, false)).is_none());
+        assert!(actions::undo_blocker(&logged(ActionType::Delete, false)).is_none());
+        assert!(actions::undo_blocker(&logged(ActionType::Delete, true)).is_some());
+        assert!(actions::undo_blocker(&logged(ActionType::Copy, false)).is_some());
+
+        let mut undone = logged(ActionType::Move, false);
+        undone.undone_at = Some("2024-01-02 00:00:00".to_string());
+        assert_eq!(
+            actions::undo_blocker(&undone).as_deref(),
+            Some("it was already undone at 2024-01-02 00:00:00")
+        );
+    }
+}
@@ -47,12 +47,10 @@ fn folder_to_label(folder: &Folder) -> Option<LabelInfo> {
     }
 }
 
-pub async fn list_labels(output_format: Option<&str>) -> Result<()> {
+pub async fn list_labels(account_email: Option<&str>, output_format: Option<&str>) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     let mut client = ImapClient::connect(account).await?;
     let folders = client.list_folders().await?;
@@ -75,12 +73,14 @@ pub async fn list_labels(output_format: Option<&str>) -> Result<()> {
     Ok(())
 }
 
-pub async fn create_label(name: &str, output_format: Option<&str>) -> Result<()> {
+pub async fn create_label(
+    account_email: Option<&str>,
+    name: &str,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     // Prepend "Labels/" prefix for ProtonMail labels
     let label_path = format!("Labels/{}", name);
@@ -111,12 +111,14 @@ pub async fn create_label(name: &str, output_format: Option<&str>) -> Result<()>
     Ok(())
 }
 
-pub async fn delete_label(name: &str, output_format: Option<&str>) -> Result<()> {
+pub async fn delete_label(
+    account_email: Option<&str>,
+    name: &str,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     // Prepend "Labels/" prefix for ProtonMail labels
     let label_path = format!("Labels/{}", name);
@@ -147,12 +149,15 @@ pub async fn delete_label(name: &str, output_format: Option<&str>) -> Result<()>
     Ok(())
 }
 
-pub async fn rename_label(from: &str, to: &str, output_format: Option<&str>) -> Result<()> {
+pub async fn rename_label(
+    account_email: Option<&str>,
+    from: &str,
+    to: &str,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
 
     // Prepend "Labels/" prefix for ProtonMail labels
     let from_path = format!("Labels/{}", from);
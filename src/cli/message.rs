@@ -1,15 +1,22 @@
+use crate::cli::query::{self, QueryField};
+use crate::core::cache;
 use crate::core::imap::{FetchStats, ImapClient};
 use crate::core::state::{validate_shadow_uids, StateManager};
+use crate::core::thread::{self, ConversationSummary};
 use crate::models::config::Config;
+use crate::models::cursor::PageCursor;
 use crate::models::filter::MessageFilter;
 use crate::models::message::Message;
+use crate::models::sort::SortOrder;
+use crate::output::csv::Delimited;
+use crate::output::template::Template;
 use crate::output::{json, markdown, table};
 use anyhow::{anyhow, Result};
 use serde::Serialize;
 
 /// Serializable stats for diagnostic output
 #[derive(Serialize)]
-struct DiagnosticStats {
+pub struct DiagnosticStats {
     /// Number of UIDs returned by IMAP search
     search_count: usize,
     /// Number of UIDs requested for fetch (after limit applied)
@@ -39,9 +46,41 @@ struct InboxOutput {
     messages: Vec<Message>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stats: Option<DiagnosticStats>,
+    /// Pass to `--cursor` for the next page (with `--page-size`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+/// Final record of `inbox -o ndjson`
+#[derive(Serialize)]
+struct InboxSummary {
+    account: String,
+    folder: String,
+    /// Number of messages written
+    count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<DiagnosticStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+struct InboxThreadsOutput {
+    account: String,
+    folder: String,
+    /// Number of conversations
+    count: usize,
+    message_count: usize,
+    threads: Vec<ConversationSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn list_inbox(
+    account_email: Option<&str>,
     days: Option<u32>,
     unread_only: bool,
     agent_unread: bool,
@@ -49,15 +88,41 @@ pub async fn list_inbox(
     output_format: Option<&str>,
     query: Option<String>,
     preview: bool,
+    offline: bool,
+    threads: bool,
+    sort: Option<&str>,
+    reverse: bool,
+    page_size: Option<usize>,
+    cursor: Option<&str>,
+    fields: Option<&str>,
+    format_template: Option<&str>,
 ) -> Result<()> {
     let config = Config::load()?;
 
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured. Please add an account first."))?;
+    let account = config.resolve_account(account_email)?;
+    let sort = sort.map(|key| SortOrder::parse(key, reverse)).transpose()?;
+    let cursor = cursor.map(PageCursor::decode).transpose()?;
+    let delimited = output_format.and_then(Delimited::from_format);
+    if threads && delimited.is_some() {
+        return Err(anyhow!(
+            "--threads can't be combined with csv or tsv output"
+        ));
+    }
+    let requested_fields = fields.map(query::parse_fields);
+    let template = format_template
+        .map(|spec| Template::resolve(spec, &config.templates))
+        .transpose()?;
+    if let Some(template) = &template {
+        template.check_listing_fields()?;
+    }
+    let preview = preview
+        || template.as_ref().is_some_and(Template::uses_preview)
+        || requested_fields
+            .as_ref()
+            .is_some_and(|f| f.contains(&QueryField::Preview));
 
     // Build filter
-    let mut filter = MessageFilter::new();
+    let mut filter = MessageFilter::new().with_sort(sort);
     if let Some(d) = days {
         filter = filter.with_days(d);
     }
@@ -77,53 +142,137 @@ pub async fn list_inbox(
         filter = filter.with_preview(true);
     }
 
-    // Connect and fetch messages
-    let mut client = ImapClient::connect(account).await?;
-    client.select_folder("INBOX").await?;
-    let (mut messages, fetch_stats) = client.fetch_messages(&filter).await?;
-
     // Initialize state manager for shadow UID assignment
     let state = StateManager::new().await?;
 
-    // If agent_unread filter is set, check the database
-    if agent_unread {
-        let mut filtered_messages = Vec::new();
+    cache::resolve_text_terms(&config, &state, &account.email, "INBOX", &mut filter).await?;
 
-        for message in messages {
-            // Use message_id for agent_read lookup
-            if let Some(ref msg_id) = message.message_id {
-                let is_read = state.is_agent_read(&account.email, msg_id).await?;
-                if !is_read {
-                    filtered_messages.push(message);
-                }
-            } else {
-                // No message_id means we haven't seen it before
-                filtered_messages.push(message);
+    // Fetch messages from the server, or from the local cache when offline.
+    // NDJSON writes each batch as soon as it is fetched instead of collecting them.
+    let streaming = output_format == Some("ndjson") && !threads;
+    let mut messages = Vec::new();
+    let mut streamed = 0;
+    let mut emit = |batch: Vec<Message>| -> Result<()> {
+        if streaming {
+            for message in &batch {
+                json::print_ndjson_record("message", message)?;
+            }
+            streamed += batch.len();
+        } else {
+            messages.extend(batch);
+        }
+        Ok(())
+    };
+
+    let mut client = if offline {
+        cache::ensure_enabled(&config)?;
+        None
+    } else {
+        Some(ImapClient::connect(account).await?)
+    };
+    let (fetch_stats, next_cursor) = match (page_size, client.as_mut()) {
+        (Some(page_size), client) => {
+            let page = query::find_page(
+                &config,
+                &account.email,
+                client,
+                &state,
+                &["INBOX".to_string()],
+                &mut filter,
+                page_size,
+                cursor.as_ref(),
+            )
+            .await?;
+            emit(
+                register_inbox_messages(
+                    &config,
+                    &state,
+                    &account.email,
+                    page.messages,
+                    agent_unread,
+                )
+                .await?,
+            )?;
+            (None, page.next_cursor.map(|c| c.encode()))
+        }
+        (None, None) => {
+            let cached = cache::query_cached(&state, &account.email, "INBOX", &filter).await?;
+            emit(
+                register_inbox_messages(&config, &state, &account.email, cached, agent_unread)
+                    .await?,
+            )?;
+            (None, None)
+        }
+        (None, Some(client)) => {
+            cache::select_tracked(client, &state, &account.email, "INBOX").await?;
+            let mut batches = client.plan_fetch(&filter).await?;
+            while let Some(batch) = client.next_batch(&mut batches).await? {
+                emit(
+                    register_inbox_messages(&config, &state, &account.email, batch, agent_unread)
+                        .await?,
+                )?;
             }
+            (Some(batches.finish()), None)
         }
+    };
 
-        messages = filtered_messages;
+    if streaming {
+        return json::print_ndjson_record(
+            "summary",
+            &InboxSummary {
+                account: account.email.clone(),
+                folder: "INBOX".to_string(),
+                count: streamed,
+                thread_count: None,
+                stats: fetch_stats.map(Into::into),
+                next_cursor,
+            },
+        );
     }
 
-    // Assign shadow UIDs to all messages and update state database
-    for message in &mut messages {
-        message.folder = Some("INBOX".to_string());
+    if let Some(template) = template {
+        template.print_messages(&messages);
+        query::print_next_cursor_hint(output_format, next_cursor.as_deref());
+        return Ok(());
+    }
 
-        // Get or create shadow UID for this message
-        if let Some(ref msg_id) = message.message_id {
-            let shadow_uid = state
-                .get_or_create_shadow_uid(
-                    &account.email,
-                    "INBOX",
-                    message.uid,
-                    Some(msg_id),
-                    message.subject.as_deref(),
-                    message.from.as_ref().map(|f| f.address.as_str()),
-                    message.date,
-                )
-                .await?;
-            message.shadow_uid = Some(shadow_uid);
+    if threads {
+        let summaries: Vec<ConversationSummary> = thread::build_threads(&messages)
+            .iter()
+            .map(|c| thread::summarize(c, &messages))
+            .collect();
+
+        match output_format.unwrap_or("json") {
+            "json" => json::print_json(&InboxThreadsOutput {
+                account: account.email.clone(),
+                folder: "INBOX".to_string(),
+                count: summaries.len(),
+                message_count: messages.len(),
+                threads: summaries,
+                next_cursor: next_cursor.clone(),
+            })?,
+            "ndjson" => {
+                for summary in &summaries {
+                    json::print_ndjson_record("thread", summary)?;
+                }
+                json::print_ndjson_record(
+                    "summary",
+                    &InboxSummary {
+                        account: account.email.clone(),
+                        folder: "INBOX".to_string(),
+                        count: messages.len(),
+                        thread_count: Some(summaries.len()),
+                        stats: fetch_stats.map(Into::into),
+                        next_cursor: next_cursor.clone(),
+                    },
+                )?;
+            }
+            "markdown" => markdown::print_thread_list(&account.email, "INBOX", &summaries),
+            "table" => table::print_thread_table(&account.email, "INBOX", &summaries),
+            _ => return Err(anyhow!("Unsupported output format")),
         }
+        query::print_next_cursor_hint(output_format, next_cursor.as_deref());
+        return Ok(());
     }
 
     match output_format.unwrap_or("json") {
@@ -134,7 +283,8 @@ pub async fn list_inbox(
                 folder: "INBOX".to_string(),
                 count: messages.len(),
                 messages,
-                stats: Some(fetch_stats.into()),
+                stats: fetch_stats.map(Into::into),
+                next_cursor: next_cursor.clone(),
             };
             json::print_json(&output)?;
         }
@@ -144,28 +294,91 @@ pub async fn list_inbox(
         "table" => {
             table::print_message_table(&account.email, "INBOX", &messages);
         }
+        "csv" | "tsv" => {
+            let columns = query::delimited_columns(requested_fields.as_deref(), preview);
+            query::print_delimited(delimited.unwrap_or(Delimited::Csv), &messages, &columns)?;
+        }
         _ => {
             return Err(anyhow!("Unsupported output format"));
         }
     }
+    query::print_next_cursor_hint(output_format, next_cursor.as_deref());
 
     Ok(())
 }
 
+/// Drop messages the agent already read (with `--agent-unread`) and assign shadow UIDs
+async fn register_inbox_messages(
+    config: &Config,
+    state: &StateManager,
+    account: &str,
+    messages: Vec<Message>,
+    agent_unread: bool,
+) -> Result<Vec<Message>> {
+    let mut registered = Vec::with_capacity(messages.len());
+
+    for mut message in messages {
+        // If agent_unread filter is set, check the database (by message_id;
+        // no message_id means we haven't seen it before)
+        if agent_unread {
+            if let Some(ref msg_id) = message.message_id {
+                if state.is_agent_read(account, msg_id).await? {
+                    continue;
+                }
+            }
+        }
+
+        message.folder = Some("INBOX".to_string());
+
+        // Get or create shadow UID for this message
+        if let Some(ref msg_id) = message.message_id {
+            let shadow_uid = state
+                .get_or_create_shadow_uid(
+                    account,
+                    "INBOX",
+                    message.uid,
+                    Some(msg_id),
+                    message.subject.as_deref(),
+                    message.from.as_ref().map(|f| f.address.as_str()),
+                    message.date,
+                )
+                .await?;
+            message.shadow_uid = Some(shadow_uid);
+        }
+
+        registered.push(message);
+    }
+
+    if config.preferences.cache_enabled {
+        let indexed: Vec<(i64, &Message)> = registered
+            .iter()
+            .filter_map(|m| m.shadow_uid.map(|id| (id, m)))
+            .collect();
+        cache::index_messages(state, &indexed).await?;
+    }
+
+    Ok(registered)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn read_message(
+    account_email: Option<&str>,
     shadow_uid: i64,
     folder_override: Option<&str>,
     output_format: Option<&str>,
     mark_read: bool,
     show_raw: bool,
+    offline: bool,
+    format_template: Option<&str>,
 ) -> Result<()> {
     // Validate shadow UID
     validate_shadow_uids(&[shadow_uid])?;
 
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
+    let template = format_template
+        .map(|spec| Template::resolve(spec, &config.templates))
+        .transpose()?;
 
     let state = StateManager::new().await?;
 
@@ -173,28 +386,87 @@ pub async fn read_message(
     let resolved = state
         .resolve_shadow_uids(&account.email, &[shadow_uid])
         .await?;
-    let msg_info = resolved
-        .first()
+    let mut msg_info = resolved
+        .into_iter()
+        .next()
         .ok_or_else(|| anyhow!("Message {} not found", shadow_uid))?;
 
     // Use folder override if provided, otherwise use resolved folder
-    let folder_name = folder_override.unwrap_or(&msg_info.folder);
+    let folder_name = folder_override.unwrap_or(&msg_info.folder).to_string();
+    let folder_name = folder_name.as_str();
 
-    // Connect and fetch message
-    let mut client = ImapClient::connect(account).await?;
-    let mut message = client
-        .fetch_message_by_uid(msg_info.imap_uid, folder_name, show_raw)
-        .await?;
+    let mut message = if offline {
+        cache::ensure_enabled(&config)?;
+        if mark_read {
+            return Err(anyhow!(
+                "--mark-read needs a server connection and cannot be used with --offline"
+            ));
+        }
+        cache::read_cached(&state, &account.email, folder_name, msg_info.imap_uid).await?
+    } else {
+        let cache_enabled = config.preferences.cache_enabled;
+
+        // Connect and fetch message (with raw source when it goes into the cache)
+        let mut client = ImapClient::connect(account).await?;
+        let (status, remap) =
+            cache::select_tracked(&mut client, &state, &account.email, folder_name).await?;
+        let uid_validity = status.uid_validity.filter(|_| cache_enabled);
+
+        // The stored UID is stale if UIDVALIDITY changed; use the re-mapped one
+        if remap.is_some() {
+            if let Some(current) = state
+                .resolve_shadow_uids(&account.email, &[shadow_uid])
+                .await?
+                .into_iter()
+                .next()
+            {
+                msg_info = current;
+            }
+            if msg_info.imap_uid == 0 {
+                return Err(anyhow!(
+                    "Message {} is no longer in '{}' (UIDVALIDITY changed)",
+                    shadow_uid,
+                    folder_name
+                ));
+            }
+        }
+        let message = client
+            .fetch_message_by_uid(msg_info.imap_uid, folder_name, show_raw || cache_enabled)
+            .await?;
+
+        // Mark as read in IMAP if requested
+        if mark_read {
+            client
+                .mark_message_read(msg_info.imap_uid, folder_name)
+                .await?;
+        }
+
+        // Keep a copy for `read --offline`
+        if let Some(uid_validity) = uid_validity {
+            let mut cached = message.clone();
+            cached.flags.seen |= mark_read;
+            state
+                .store_cached_message(
+                    &account.email,
+                    folder_name,
+                    uid_validity,
+                    &cached,
+                    message.raw_message.as_deref(),
+                )
+                .await?;
+        }
+
+        message
+    };
 
     // Set shadow_uid on the message
     message.shadow_uid = Some(shadow_uid);
     message.folder = Some(folder_name.to_string());
-
-    // Mark as read in IMAP if requested
-    if mark_read {
-        client
-            .mark_message_read(msg_info.imap_uid, folder_name)
-            .await?;
+    if config.preferences.cache_enabled {
+        cache::index_message(&state, shadow_uid, &message).await?;
+    }
+    if !show_raw {
+        message.raw_message = None;
     }
 
     // Always mark as agent-read in local state (using message_id as stable identifier)
@@ -202,6 +474,11 @@ pub async fn read_message(
         state.mark_agent_read(&account.email, msg_id).await?;
     }
 
+    if let Some(template) = template {
+        println!("{}", template.render(&message));
+        return Ok(());
+    }
+
     // Output based on format
     match output_format.unwrap_or("markdown") {
         "json" => {
@@ -210,6 +487,9 @@ pub async fn read_message(
         "markdown" => {
             markdown::print_message(&message);
         }
+        "text" => {
+            markdown::print_message_text(&message);
+        }
         "raw" => {
             if let Some(raw) = message.raw_message {
                 println!("{}", String::from_utf8_lossy(&raw));
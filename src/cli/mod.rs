@@ -1,9 +1,17 @@
 pub mod account;
 pub mod actions;
+pub mod attachments;
+pub mod compose;
 pub mod draft;
 pub mod folder;
+pub mod history;
 pub mod label;
 pub mod message;
 pub mod query;
+pub mod reply;
+pub mod search;
 pub mod select;
 pub mod send;
+pub mod sync;
+pub mod thread;
+pub mod watch;
@@ -1,8 +1,16 @@
-use crate::core::imap::ImapClient;
+use crate::cli::message::DiagnosticStats;
+use crate::core::cache;
+use crate::core::imap::{FetchStats, ImapClient};
 use crate::core::state::StateManager;
+use crate::core::thread::{self, ConversationSummary};
 use crate::models::config::Config;
+use crate::models::cursor::PageCursor;
 use crate::models::filter::MessageFilter;
-use crate::output::{json, table};
+use crate::models::message::{EmailAddress, Message};
+use crate::models::sort::SortOrder;
+use crate::output::csv::{self, Delimited};
+use crate::output::template::Template;
+use crate::output::{json, markdown, table};
 use anyhow::{anyhow, Result};
 use serde::Serialize;
 use std::collections::HashSet;
@@ -16,6 +24,25 @@ pub struct QueryOutput {
     pub messages: Vec<QueryMessage>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub added_to_selection: Option<usize>,
+    /// Pass to `--cursor` for the next page (with `--page-size`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct QueryThreadsOutput {
+    pub account: String,
+    pub folders: Vec<String>,
+    pub query: String,
+    /// Number of conversations
+    pub count: usize,
+    pub message_count: usize,
+    pub threads: Vec<ConversationSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added_to_selection: Option<usize>,
+    /// Pass to `--cursor` for the next page (with `--page-size`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -32,8 +59,16 @@ pub struct QueryMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub date: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cc: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub flags: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub preview: Option<String>,
 }
 
@@ -45,10 +80,26 @@ pub enum QueryField {
     MessageId,
     Subject,
     From,
+    To,
+    Cc,
     Date,
+    Size,
     Flags,
+    Labels,
+    Preview,
 }
 
+/// Fields shown when `--fields` is not given
+pub const DEFAULT_FIELDS: [QueryField; 7] = [
+    QueryField::Uid,
+    QueryField::Folder,
+    QueryField::MessageId,
+    QueryField::Subject,
+    QueryField::From,
+    QueryField::Date,
+    QueryField::Flags,
+];
+
 impl QueryField {
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
@@ -57,11 +108,87 @@ impl QueryField {
             "message_id" | "messageid" | "id" => Some(QueryField::MessageId),
             "subject" => Some(QueryField::Subject),
             "from" => Some(QueryField::From),
+            "to" => Some(QueryField::To),
+            "cc" => Some(QueryField::Cc),
             "date" => Some(QueryField::Date),
+            "size" => Some(QueryField::Size),
             "flags" => Some(QueryField::Flags),
+            "labels" => Some(QueryField::Labels),
+            "preview" => Some(QueryField::Preview),
             _ => None,
         }
     }
+
+    /// Column name in csv/tsv headers
+    pub fn name(self) -> &'static str {
+        match self {
+            QueryField::Uid => "uid",
+            QueryField::Folder => "folder",
+            QueryField::MessageId => "message_id",
+            QueryField::Subject => "subject",
+            QueryField::From => "from",
+            QueryField::To => "to",
+            QueryField::Cc => "cc",
+            QueryField::Date => "date",
+            QueryField::Size => "size",
+            QueryField::Flags => "flags",
+            QueryField::Labels => "labels",
+            QueryField::Preview => "preview",
+        }
+    }
+
+    /// Cell value for csv/tsv; lists are joined (addresses with ", ", flags and labels with " ")
+    pub fn value(self, msg: &Message) -> String {
+        match self {
+            QueryField::Uid => msg.uid.to_string(),
+            QueryField::Folder => msg.folder.clone().unwrap_or_default(),
+            QueryField::MessageId => msg.message_id.clone().unwrap_or_default(),
+            QueryField::Subject => msg.subject.clone().unwrap_or_default(),
+            QueryField::From => msg.from.as_ref().map(|f| f.format()).unwrap_or_default(),
+            QueryField::To => format_addresses(&msg.to).join(", "),
+            QueryField::Cc => format_addresses(&msg.cc).join(", "),
+            QueryField::Date => msg.date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            QueryField::Size => msg.size.map(|s| s.to_string()).unwrap_or_default(),
+            QueryField::Flags => msg.flags.names().join(" "),
+            QueryField::Labels => msg.labels.join(" "),
+            QueryField::Preview => msg.preview.clone().unwrap_or_default(),
+        }
+    }
+}
+
+fn format_addresses(addresses: &[EmailAddress]) -> Vec<String> {
+    addresses.iter().map(|a| a.format()).collect()
+}
+
+/// Columns for csv/tsv: the `--fields` selection in the given order, otherwise the
+/// default fields (plus the preview with `--preview`)
+pub fn delimited_columns(requested: Option<&[QueryField]>, preview: bool) -> Vec<QueryField> {
+    match requested {
+        Some(fields) if !fields.is_empty() => fields.to_vec(),
+        _ => {
+            let mut columns = DEFAULT_FIELDS.to_vec();
+            if preview {
+                columns.push(QueryField::Preview);
+            }
+            columns
+        }
+    }
+}
+
+/// `-o csv` / `-o tsv`: a header row, then one record per message
+pub fn print_delimited(
+    format: Delimited,
+    messages: &[Message],
+    columns: &[QueryField],
+) -> Result<()> {
+    let header: Vec<&str> = columns.iter().map(|c| c.name()).collect();
+    csv::print_rows(
+        format,
+        &header,
+        messages
+            .iter()
+            .map(|msg| columns.iter().map(|c| c.value(msg)).collect::<Vec<_>>()),
+    )
 }
 
 /// Parse fields string into a set of QueryFields
@@ -72,8 +199,231 @@ pub fn parse_fields(fields_str: &str) -> Vec<QueryField> {
         .collect()
 }
 
+/// Folders a query searches:
+/// 1. Folders from query (in:folder syntax) take precedence
+/// 2. CLI folders (--folder flags) if no in-query folders
+/// 3. Default to INBOX if neither specified
+pub fn effective_folders(query_str: &str, cli_folders: &[String]) -> Vec<String> {
+    let query_folders = MessageFilter::extract_folders_from_query(query_str);
+    if !query_folders.is_empty() {
+        query_folders
+    } else if !cli_folders.is_empty() {
+        cli_folders.to_vec()
+    } else {
+        vec!["INBOX".to_string()]
+    }
+}
+
+/// Run a query over `folders` (from the server, or the local cache when `client` is None):
+/// deduplicate by Message-ID, assign shadow UIDs, sort newest first and apply `limit`
+pub async fn find_messages(
+    config: &Config,
+    account: &str,
+    mut client: Option<&mut ImapClient>,
+    state: &StateManager,
+    folders: &[String],
+    filter: &mut MessageFilter,
+    limit: Option<usize>,
+) -> Result<Vec<Message>> {
+    let mut all_messages: Vec<Message> = Vec::new();
+
+    for folder in folders {
+        cache::resolve_text_terms(config, state, account, folder, filter).await?;
+
+        let folder_messages = match client.as_deref_mut() {
+            Some(client) => {
+                cache::select_tracked(client, state, account, folder).await?;
+                client.fetch_messages(filter).await?.0
+            }
+            None => cache::query_cached(state, account, folder, filter).await?,
+        };
+
+        for mut message in folder_messages {
+            message.folder = Some(folder.clone());
+            all_messages.push(message);
+        }
+    }
+
+    let mut all_messages =
+        register_messages(config, state, account, all_messages, &mut HashSet::new()).await?;
+
+    // Merge folders in the requested order (default: date, newest first)
+    #[allow(clippy::unnecessary_sort_by)]
+    match filter.sort {
+        Some(order) => order.sort(&mut all_messages),
+        None => all_messages.sort_by(|a, b| b.date.cmp(&a.date)),
+    }
+
+    // Apply limit after merging
+    if let Some(l) = limit {
+        all_messages.truncate(l);
+    }
+
+    Ok(all_messages)
+}
+
+/// Drop duplicates (same Message-ID, first occurrence wins, also across calls sharing
+/// `seen_message_ids`) and assign shadow UIDs. Each message must carry its folder.
+pub async fn register_messages(
+    config: &Config,
+    state: &StateManager,
+    account: &str,
+    messages: Vec<Message>,
+    seen_message_ids: &mut HashSet<String>,
+) -> Result<Vec<Message>> {
+    let mut registered = Vec::with_capacity(messages.len());
+
+    for mut message in messages {
+        // Deduplicate by message_id - keep first occurrence
+        if let Some(ref msg_id) = message.message_id {
+            if !seen_message_ids.insert(msg_id.clone()) {
+                continue;
+            }
+
+            // Assign shadow UID
+            let shadow_uid = state
+                .get_or_create_shadow_uid(
+                    account,
+                    message.folder.as_deref().unwrap_or("INBOX"),
+                    message.uid,
+                    Some(msg_id),
+                    message.subject.as_deref(),
+                    message.from.as_ref().map(|f| f.address.as_str()),
+                    message.date,
+                )
+                .await?;
+            message.shadow_uid = Some(shadow_uid);
+        }
+
+        registered.push(message);
+    }
+
+    // One transaction for the whole batch rather than three statements per message
+    if config.preferences.cache_enabled {
+        let indexed: Vec<(i64, &Message)> = registered
+            .iter()
+            .filter_map(|m| m.shadow_uid.map(|id| (id, m)))
+            .collect();
+        cache::index_messages(state, &indexed).await?;
+    }
+
+    Ok(registered)
+}
+
+/// One `--page-size` page of a listing
+pub struct Page {
+    /// Messages with their folder set, not yet registered (see `register_messages`)
+    pub messages: Vec<Message>,
+    /// Where the next page starts; `None` on the last page
+    pub next_cursor: Option<PageCursor>,
+}
+
+/// Fetch up to `page_size` messages after `cursor`, walking `folders` in order and each
+/// folder newest UID first. Only as many messages as the page needs are fetched.
+#[allow(clippy::too_many_arguments)]
+pub async fn find_page(
+    config: &Config,
+    account: &str,
+    mut client: Option<&mut ImapClient>,
+    state: &StateManager,
+    folders: &[String],
+    filter: &mut MessageFilter,
+    page_size: usize,
+    cursor: Option<&PageCursor>,
+) -> Result<Page> {
+    if page_size == 0 {
+        return Err(anyhow!("--page-size must be at least 1"));
+    }
+    let start = match cursor {
+        Some(cursor) => folders
+            .iter()
+            .position(|f| *f == cursor.folder)
+            .ok_or_else(|| {
+                anyhow!(
+                    "This cursor continues folder '{}', which is not being listed",
+                    cursor.folder
+                )
+            })?,
+        None => 0,
+    };
+
+    let mut messages: Vec<(Message, u32)> = Vec::new();
+    let mut has_more = false;
+
+    for folder in &folders[start..] {
+        cache::resolve_text_terms(config, state, account, folder, filter).await?;
+
+        let uid_validity = match client.as_deref_mut() {
+            Some(client) => {
+                cache::select_tracked(client, state, account, folder)
+                    .await?
+                    .0
+                    .uid_validity
+            }
+            None => state
+                .folder_state(account, folder)
+                .await?
+                .and_then(|f| f.uid_validity),
+        };
+
+        // One extra message tells whether another page follows
+        let mut folder_filter = filter.clone().with_limit(page_size + 1 - messages.len());
+        folder_filter.before_uid = None;
+        folder_filter.before_date = None;
+        if let Some(cursor) = cursor.filter(|c| c.folder == *folder) {
+            if uid_validity == Some(cursor.uid_validity) {
+                folder_filter = folder_filter.with_before_uid(cursor.uid);
+            } else {
+                let date = cursor.date.ok_or_else(|| {
+                    anyhow!(
+                        "UIDVALIDITY of '{}' changed since this cursor was issued; start over without --cursor",
+                        folder
+                    )
+                })?;
+                eprintln!(
+                    "Note: UIDVALIDITY of '{}' changed since this cursor was issued; resuming by date",
+                    folder
+                );
+                folder_filter = folder_filter.with_before_date(date);
+            }
+        }
+
+        let folder_messages = match client.as_deref_mut() {
+            Some(client) => client.fetch_messages(&folder_filter).await?.0,
+            None => cache::query_cached(state, account, folder, &folder_filter).await?,
+        };
+
+        for mut message in folder_messages {
+            if messages.len() == page_size {
+                has_more = true;
+                break;
+            }
+            message.folder = Some(folder.clone());
+            messages.push((message, uid_validity.unwrap_or(0)));
+        }
+        if has_more {
+            break;
+        }
+    }
+
+    let next_cursor = messages
+        .last()
+        .filter(|_| has_more)
+        .map(|(message, uid_validity)| {
+            let folder = message.folder.as_deref().unwrap_or_default();
+            PageCursor::after(message, folder, *uid_validity)
+        });
+
+    Ok(Page {
+        messages: messages.into_iter().map(|(message, _)| message).collect(),
+        next_cursor,
+    })
+}
+
 /// Execute a query and optionally add results to selection
+#[allow(clippy::too_many_arguments)]
 pub async fn execute_query(
+    account_email: Option<&str>,
     query_str: &str,
     cli_folders: &[String],
     fields: Option<&str>,
@@ -81,149 +431,132 @@ pub async fn execute_query(
     preview: bool,
     select: bool,
     output_format: Option<&str>,
+    offline: bool,
+    threads: bool,
+    sort: Option<&str>,
+    reverse: bool,
+    page_size: Option<usize>,
+    cursor: Option<&str>,
+    format_template: Option<&str>,
 ) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
+    let template = format_template
+        .map(|spec| Template::resolve(spec, &config.templates))
+        .transpose()?;
+    if let Some(template) = &template {
+        template.check_listing_fields()?;
+    }
+    let sort = sort.map(|key| SortOrder::parse(key, reverse)).transpose()?;
+    let cursor = cursor.map(PageCursor::decode).transpose()?;
+    let delimited = output_format.and_then(Delimited::from_format);
+    if threads && delimited.is_some() {
+        return Err(anyhow!(
+            "--threads can't be combined with csv or tsv output"
+        ));
+    }
+
+    // Parse which fields to include; a preview column needs previews fetched
+    let requested_fields = fields.map(parse_fields);
+    let preview = preview
+        || template.as_ref().is_some_and(Template::uses_preview)
+        || requested_fields
+            .as_ref()
+            .is_some_and(|f| f.contains(&QueryField::Preview));
 
     // Build the message filter with query (without limit - we'll apply after merging)
-    let mut filter = MessageFilter::new().with_query(query_str.to_string());
+    let mut filter = MessageFilter::new()
+        .with_query(query_str.to_string())
+        .with_sort(sort);
     if preview {
         filter = filter.with_preview(true);
     }
 
-    // Determine effective folders:
-    // 1. Folders from query (in:folder syntax) take precedence
-    // 2. CLI folders (--folder flags) if no in-query folders
-    // 3. Default to INBOX if neither specified
-    let query_folders = MessageFilter::extract_folders_from_query(query_str);
-    let effective_folders: Vec<String> = if !query_folders.is_empty() {
-        query_folders
-    } else if !cli_folders.is_empty() {
-        cli_folders.to_vec()
+    let effective_folders = effective_folders(query_str, cli_folders);
+
+    // Connect to IMAP, unless answering from the local cache
+    let mut client = if offline {
+        cache::ensure_enabled(&config)?;
+        None
     } else {
-        vec!["INBOX".to_string()]
+        Some(ImapClient::connect(account).await?)
     };
 
-    // Connect to IMAP
-    let mut client = ImapClient::connect(account).await?;
-
     // Initialize state manager for shadow UID assignment
     let state = StateManager::new().await?;
 
-    // Fetch messages from all folders
-    let mut all_messages: Vec<crate::models::message::Message> = Vec::new();
-    let mut seen_message_ids: HashSet<String> = HashSet::new();
-
-    for folder in &effective_folders {
-        client.select_folder(folder).await?;
-        let (folder_messages, _stats) = client.fetch_messages(&filter).await?;
-
-        for mut message in folder_messages {
-            message.folder = Some(folder.clone());
-
-            // Deduplicate by message_id - keep first occurrence
-            if let Some(ref msg_id) = message.message_id {
-                if seen_message_ids.contains(msg_id) {
-                    continue;
-                }
-                seen_message_ids.insert(msg_id.clone());
-
-                // Assign shadow UID
-                let shadow_uid = state
-                    .get_or_create_shadow_uid(
-                        &account.email,
-                        folder,
-                        message.uid,
-                        Some(msg_id),
-                        message.subject.as_deref(),
-                        message.from.as_ref().map(|f| f.address.as_str()),
-                        message.date,
-                    )
-                    .await?;
-                message.shadow_uid = Some(shadow_uid);
-            }
-
-            all_messages.push(message);
-        }
-    }
-
-    // Sort by date (newest first)
-    all_messages.sort_by(|a, b| b.date.cmp(&a.date));
+    let show_multiple_folders = effective_folders.len() > 1;
 
-    // Apply limit after merging
-    if let Some(l) = limit {
-        all_messages.truncate(l);
+    if output_format == Some("ndjson") && !threads {
+        let target = QueryStream {
+            query_str,
+            folders: &effective_folders,
+            requested_fields: requested_fields.as_deref(),
+            show_folder: show_multiple_folders,
+            preview,
+            select,
+        };
+        return stream_query(
+            &config,
+            &account.email,
+            client.as_mut(),
+            &state,
+            &target,
+            &mut filter,
+            limit,
+            page_size.map(|size| (size, cursor.as_ref())),
+        )
+        .await;
     }
 
-    // Parse which fields to include
-    let requested_fields = fields.map(parse_fields);
-    let show_all_fields = requested_fields.is_none();
-    let show_multiple_folders = effective_folders.len() > 1;
+    let (all_messages, next_cursor) = match page_size {
+        Some(page_size) => {
+            let page = find_page(
+                &config,
+                &account.email,
+                client.as_mut(),
+                &state,
+                &effective_folders,
+                &mut filter,
+                page_size,
+                cursor.as_ref(),
+            )
+            .await?;
+            let messages = register_messages(
+                &config,
+                &state,
+                &account.email,
+                page.messages,
+                &mut HashSet::new(),
+            )
+            .await?;
+            (messages, page.next_cursor.map(|c| c.encode()))
+        }
+        None => {
+            let messages = find_messages(
+                &config,
+                &account.email,
+                client.as_mut(),
+                &state,
+                &effective_folders,
+                &mut filter,
+                limit,
+            )
+            .await?;
+            (messages, None)
+        }
+    };
 
     // Convert to output format
     let query_messages: Vec<QueryMessage> = all_messages
         .iter()
         .map(|msg| {
-            let include = |field: QueryField| -> bool {
-                show_all_fields
-                    || requested_fields
-                        .as_ref()
-                        .is_some_and(|f| f.contains(&field))
-            };
-
-            QueryMessage {
-                uid: msg.uid,
-                // Always include folder when querying multiple folders, or if explicitly requested
-                folder: if show_multiple_folders || include(QueryField::Folder) {
-                    msg.folder.clone()
-                } else {
-                    None
-                },
-                message_id: if include(QueryField::MessageId) {
-                    msg.message_id.clone()
-                } else {
-                    None
-                },
-                subject: if include(QueryField::Subject) {
-                    msg.subject.clone()
-                } else {
-                    None
-                },
-                from: if include(QueryField::From) {
-                    msg.from.as_ref().map(|f| f.format())
-                } else {
-                    None
-                },
-                date: if include(QueryField::Date) {
-                    msg.date.map(|d| d.to_rfc3339())
-                } else {
-                    None
-                },
-                flags: if include(QueryField::Flags) {
-                    let mut flags = Vec::new();
-                    if msg.flags.seen {
-                        flags.push("seen".to_string());
-                    }
-                    if msg.flags.answered {
-                        flags.push("answered".to_string());
-                    }
-                    if msg.flags.flagged {
-                        flags.push("flagged".to_string());
-                    }
-                    if msg.flags.deleted {
-                        flags.push("deleted".to_string());
-                    }
-                    if msg.flags.draft {
-                        flags.push("draft".to_string());
-                    }
-                    Some(flags)
-                } else {
-                    None
-                },
-                preview: if preview { msg.preview.clone() } else { None },
-            }
+            to_query_message(
+                msg,
+                requested_fields.as_deref(),
+                show_multiple_folders,
+                preview,
+            )
         })
         .collect();
 
@@ -255,6 +588,48 @@ pub async fn execute_query(
         None
     };
 
+    if threads {
+        let summaries: Vec<ConversationSummary> = thread::build_threads(&all_messages)
+            .iter()
+            .map(|c| thread::summarize(c, &all_messages))
+            .collect();
+        let output = QueryThreadsOutput {
+            account: account.email.clone(),
+            folders: effective_folders.clone(),
+            query: query_str.to_string(),
+            count: summaries.len(),
+            message_count: all_messages.len(),
+            threads: summaries,
+            added_to_selection,
+            next_cursor,
+        };
+
+        let folders_str = output.folders.join(", ");
+        match output_format.unwrap_or("text") {
+            "json" => json::print_json(&output)?,
+            "markdown" => {
+                markdown::print_thread_list(&output.account, &folders_str, &output.threads)
+            }
+            "table" => table::print_thread_table(&output.account, &folders_str, &output.threads),
+            _ => print_threads_text(&output),
+        }
+        print_next_cursor_hint(output_format, output.next_cursor.as_deref());
+        return Ok(());
+    }
+
+    if let Some(template) = template {
+        template.print_messages(&all_messages);
+        print_next_cursor_hint(output_format, next_cursor.as_deref());
+        return Ok(());
+    }
+
+    if let Some(format) = delimited {
+        let columns = delimited_columns(requested_fields.as_deref(), preview);
+        print_delimited(format, &all_messages, &columns)?;
+        print_next_cursor_hint(output_format, next_cursor.as_deref());
+        return Ok(());
+    }
+
     let output = QueryOutput {
         account: account.email.clone(),
         folders: effective_folders.clone(),
@@ -262,6 +637,7 @@ pub async fn execute_query(
         count: query_messages.len(),
         messages: query_messages,
         added_to_selection,
+        next_cursor,
     };
 
     match output_format.unwrap_or("text") {
@@ -277,10 +653,267 @@ pub async fn execute_query(
         }
         _ => print_text(&output)?,
     }
+    print_next_cursor_hint(output_format, output.next_cursor.as_deref());
 
     Ok(())
 }
 
+/// Output record for one message, with only the requested fields
+fn to_query_message(
+    msg: &Message,
+    requested_fields: Option<&[QueryField]>,
+    show_folder: bool,
+    preview: bool,
+) -> QueryMessage {
+    let include = |field: QueryField| -> bool {
+        match requested_fields {
+            Some(fields) => fields.contains(&field),
+            None => DEFAULT_FIELDS.contains(&field),
+        }
+    };
+
+    QueryMessage {
+        uid: msg.uid,
+        // Always include folder when querying multiple folders, or if explicitly requested
+        folder: if show_folder || include(QueryField::Folder) {
+            msg.folder.clone()
+        } else {
+            None
+        },
+        message_id: if include(QueryField::MessageId) {
+            msg.message_id.clone()
+        } else {
+            None
+        },
+        subject: if include(QueryField::Subject) {
+            msg.subject.clone()
+        } else {
+            None
+        },
+        from: if include(QueryField::From) {
+            msg.from.as_ref().map(|f| f.format())
+        } else {
+            None
+        },
+        date: if include(QueryField::Date) {
+            msg.date.map(|d| d.to_rfc3339())
+        } else {
+            None
+        },
+        to: if include(QueryField::To) {
+            Some(format_addresses(&msg.to))
+        } else {
+            None
+        },
+        cc: if include(QueryField::Cc) {
+            Some(format_addresses(&msg.cc))
+        } else {
+            None
+        },
+        size: if include(QueryField::Size) {
+            msg.size
+        } else {
+            None
+        },
+        flags: if include(QueryField::Flags) {
+            Some(msg.flags.names().into_iter().map(String::from).collect())
+        } else {
+            None
+        },
+        labels: if include(QueryField::Labels) {
+            Some(msg.labels.clone())
+        } else {
+            None
+        },
+        preview: if preview || include(QueryField::Preview) {
+            msg.preview.clone()
+        } else {
+            None
+        },
+    }
+}
+
+/// What `stream_query` lists and how
+struct QueryStream<'a> {
+    query_str: &'a str,
+    folders: &'a [String],
+    requested_fields: Option<&'a [QueryField]>,
+    show_folder: bool,
+    preview: bool,
+    select: bool,
+}
+
+/// What is kept of a written message for the query history and `--select`
+struct StreamedEntry {
+    uid: u32,
+    folder: String,
+    message_id: Option<String>,
+    subject: Option<String>,
+    shadow_uid: Option<i64>,
+}
+
+/// Final record of `query -o ndjson`
+#[derive(Serialize)]
+struct QuerySummary {
+    account: String,
+    folders: Vec<String>,
+    query: String,
+    /// Number of messages written
+    count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<DiagnosticStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    added_to_selection: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+/// `query -o ndjson`: write each message of a single folder as soon as its fetch batch
+/// is parsed. Several folders are merged like the other formats, so they are written once
+/// all of them are fetched; with `page` the page is fetched first, then written.
+#[allow(clippy::too_many_arguments)]
+async fn stream_query(
+    config: &Config,
+    account: &str,
+    client: Option<&mut ImapClient>,
+    state: &StateManager,
+    target: &QueryStream<'_>,
+    filter: &mut MessageFilter,
+    limit: Option<usize>,
+    page: Option<(usize, Option<&PageCursor>)>,
+) -> Result<()> {
+    let mut seen_message_ids = HashSet::new();
+    let mut entries: Vec<StreamedEntry> = Vec::new();
+    let mut emit = |batch: Vec<Message>| -> Result<()> {
+        for msg in &batch {
+            let record = to_query_message(
+                msg,
+                target.requested_fields,
+                target.show_folder,
+                target.preview,
+            );
+            json::print_ndjson_record("message", &record)?;
+            entries.push(StreamedEntry {
+                uid: msg.uid,
+                folder: msg.folder.clone().unwrap_or_else(|| "INBOX".to_string()),
+                message_id: msg.message_id.clone(),
+                subject: msg.subject.clone(),
+                shadow_uid: msg.shadow_uid,
+            });
+        }
+        Ok(())
+    };
+
+    let mut stats: Option<FetchStats> = None;
+    let mut next_cursor = None;
+
+    if let Some((page_size, cursor)) = page {
+        let page = find_page(
+            config,
+            account,
+            client,
+            state,
+            target.folders,
+            filter,
+            page_size,
+            cursor,
+        )
+        .await?;
+        emit(
+            register_messages(config, state, account, page.messages, &mut seen_message_ids).await?,
+        )?;
+        next_cursor = page.next_cursor.map(|c| c.encode());
+    } else if let [folder] = target.folders {
+        cache::resolve_text_terms(config, state, account, folder, filter).await?;
+        let mut folder_filter = filter.clone();
+        folder_filter.limit = limit;
+
+        match client {
+            Some(client) => {
+                cache::select_tracked(client, state, account, folder).await?;
+                let mut batches = client.plan_fetch(&folder_filter).await?;
+                while let Some(mut batch) = client.next_batch(&mut batches).await? {
+                    for message in &mut batch {
+                        message.folder = Some(folder.clone());
+                    }
+                    emit(
+                        register_messages(config, state, account, batch, &mut seen_message_ids)
+                            .await?,
+                    )?;
+                }
+                stats = Some(batches.finish());
+            }
+            None => {
+                let mut cached =
+                    cache::query_cached(state, account, folder, &folder_filter).await?;
+                for message in &mut cached {
+                    message.folder = Some(folder.clone());
+                }
+                emit(
+                    register_messages(config, state, account, cached, &mut seen_message_ids)
+                        .await?,
+                )?;
+            }
+        }
+    } else {
+        // The newest (or --sort) first across folders, with the limit applied to the merge
+        emit(
+            find_messages(
+                config,
+                account,
+                client,
+                state,
+                target.folders,
+                filter,
+                limit,
+            )
+            .await?,
+        )?;
+    }
+
+    let result_entries: Vec<crate::core::state::SelectionEntryTuple<'_>> = entries
+        .iter()
+        .map(|e| {
+            (
+                e.uid,
+                e.folder.as_str(),
+                e.message_id.as_deref(),
+                e.subject.as_deref(),
+                e.shadow_uid,
+            )
+        })
+        .collect();
+    state
+        .save_query_results(account, target.query_str, &result_entries)
+        .await?;
+    let added_to_selection = if target.select {
+        Some(state.add_to_selection(account, &result_entries).await?)
+    } else {
+        None
+    };
+
+    json::print_ndjson_record(
+        "summary",
+        &QuerySummary {
+            account: account.to_string(),
+            folders: target.folders.to_vec(),
+            query: target.query_str.to_string(),
+            count: result_entries.len(),
+            stats: stats.map(Into::into),
+            added_to_selection,
+            next_cursor,
+        },
+    )
+}
+
+/// Outside JSON, mention the next page on stderr so stdout stays a plain listing
+pub fn print_next_cursor_hint(output_format: Option<&str>, next_cursor: Option<&str>) {
+    let structured = matches!(output_format, Some("json" | "ndjson"));
+    if let (Some(cursor), false) = (next_cursor, structured) {
+        eprintln!("\nMore results: --cursor {}", cursor);
+    }
+}
+
 fn print_text(output: &QueryOutput) -> Result<()> {
     let folders_str = output.folders.join(", ");
     println!(
@@ -307,6 +940,12 @@ fn print_text(output: &QueryOutput) -> Result<()> {
         if let Some(from) = &msg.from {
             parts.push(format!("from: {}", from));
         }
+        if let Some(to) = msg.to.as_ref().filter(|to| !to.is_empty()) {
+            parts.push(format!("to: {}", to.join(", ")));
+        }
+        if let Some(cc) = msg.cc.as_ref().filter(|cc| !cc.is_empty()) {
+            parts.push(format!("cc: {}", cc.join(", ")));
+        }
         if let Some(subject) = &msg.subject {
             parts.push(format!("\"{}\"", subject));
         }
@@ -318,6 +957,12 @@ fn print_text(output: &QueryOutput) -> Result<()> {
                 parts.push(format!("({})", flags.join(", ")));
             }
         }
+        if let Some(labels) = msg.labels.as_ref().filter(|l| !l.is_empty()) {
+            parts.push(format!("labels: {}", labels.join(", ")));
+        }
+        if let Some(size) = msg.size {
+            parts.push(format!("{} bytes", size));
+        }
 
         println!("  {}", parts.join(" | "));
 
@@ -335,6 +980,46 @@ fn print_text(output: &QueryOutput) -> Result<()> {
     Ok(())
 }
 
+fn print_threads_text(output: &QueryThreadsOutput) {
+    println!(
+        "Query '{}' in {}/{}: {} conversation(s), {} message(s)",
+        output.query,
+        output.account,
+        output.folders.join(", "),
+        output.count,
+        output.message_count
+    );
+
+    if !output.threads.is_empty() {
+        println!();
+    }
+    for thread in &output.threads {
+        let mut parts = vec![match thread.id {
+            Some(id) => format!("ID {}", id),
+            None => "ID -".to_string(),
+        }];
+        parts.push(format!(
+            "{} message(s), {} unread",
+            thread.count, thread.unread
+        ));
+        if let Some(subject) = &thread.subject {
+            parts.push(format!("\"{}\"", subject));
+        }
+        if !thread.participants.is_empty() {
+            parts.push(thread.participants.join(", "));
+        }
+        if let Some(date) = &thread.last_date {
+            parts.push(format!("[{}]", date.to_rfc3339()));
+        }
+        println!("  {}", parts.join(" | "));
+    }
+
+    if let Some(count) = output.added_to_selection {
+        println!();
+        println!("✓ Added {} message(s) to selection", count);
+    }
+}
+
 fn print_markdown(output: &QueryOutput) -> Result<()> {
     let folders_str = output.folders.join(", ");
     let show_folder = output.folders.len() > 1;
@@ -399,6 +1084,35 @@ mod tests {
         assert_eq!(QueryField::from_str("flags"), Some(QueryField::Flags));
     }
 
+    #[test]
+    fn test_query_field_new_columns() {
+        let fields = parse_fields("to, cc,size,labels,preview");
+        assert_eq!(
+            fields,
+            vec![
+                QueryField::To,
+                QueryField::Cc,
+                QueryField::Size,
+                QueryField::Labels,
+                QueryField::Preview
+            ]
+        );
+
+        let mut msg = Message::new(7);
+        msg.to = vec![
+            EmailAddress::new("a@x.com".to_string(), Some("Ann".to_string())),
+            EmailAddress::new("b@x.com".to_string(), None),
+        ];
+        msg.labels = vec!["Work".to_string(), "Urgent".to_string()];
+        msg.flags.seen = true;
+        msg.flags.flagged = true;
+        assert_eq!(QueryField::To.value(&msg), "Ann <a@x.com>, b@x.com");
+        assert_eq!(QueryField::Cc.value(&msg), "");
+        assert_eq!(QueryField::Size.value(&msg), "");
+        assert_eq!(QueryField::Labels.value(&msg), "Work Urgent");
+        assert_eq!(QueryField::Flags.value(&msg), "seen flagged");
+    }
+
     #[test]
     fn test_query_field_from_str_message_id_variants() {
         assert_eq!(
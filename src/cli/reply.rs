@@ -0,0 +1,564 @@
+use crate::cli::send::{
+    resolve_sender, save_to_sent, warn_attachment_size, AttachmentData, EmailBuilder,
+};
+use crate::core::imap::ImapClient;
+use crate::core::smtp::SmtpClient;
+use crate::core::state::{validate_shadow_uids, StateManager};
+use crate::models::account::Account;
+use crate::models::config::Config;
+use crate::models::message::{Attachment, EmailAddress, Message};
+use crate::output::{html, json};
+use anyhow::{anyhow, Context, Result};
+use lettre::message::Mailbox;
+use serde::Serialize;
+use std::fs;
+
+#[derive(Serialize)]
+struct ReplyOutput {
+    account: String,
+    action: String,
+    original_id: i64,
+    subject: String,
+    to: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cc: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bcc: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    in_reply_to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attachment_count: Option<usize>,
+    /// Shadow UID of the copy saved to the Sent folder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sent_id: Option<i64>,
+}
+
+/// Original message fetched for replying or forwarding
+struct Original {
+    client: ImapClient,
+    folder: String,
+    imap_uid: u32,
+    message: Message,
+}
+
+/// Resolve a shadow UID and fetch the full original message
+async fn fetch_original(account: &Account, shadow_uid: i64, include_raw: bool) -> Result<Original> {
+    validate_shadow_uids(&[shadow_uid])?;
+
+    let state = StateManager::new().await?;
+    let resolved = state
+        .resolve_shadow_uids(&account.email, &[shadow_uid])
+        .await?;
+    let msg_info = resolved
+        .first()
+        .ok_or_else(|| anyhow!("Message {} not found", shadow_uid))?;
+
+    let mut client = ImapClient::connect(account).await?;
+    let message = client
+        .fetch_message_by_uid(msg_info.imap_uid, &msg_info.folder, include_raw)
+        .await?;
+
+    Ok(Original {
+        client,
+        folder: msg_info.folder.clone(),
+        imap_uid: msg_info.imap_uid,
+        message,
+    })
+}
+
+/// Read the new message text from --body or --body-file
+fn read_body(body: Option<String>, body_file: Option<String>) -> Result<String> {
+    if let Some(file_path) = body_file {
+        fs::read_to_string(&file_path).context(format!("Failed to read body file: {}", file_path))
+    } else {
+        Ok(body.unwrap_or_default())
+    }
+}
+
+/// Prefix a subject with "Re" or "Fwd" unless it already carries that prefix
+pub fn prefix_subject(prefix: &str, subject: Option<&str>) -> String {
+    let subject = subject.unwrap_or("").trim();
+    let marker = format!("{}:", prefix.to_lowercase());
+    if subject.to_lowercase().starts_with(&marker) {
+        subject.to_string()
+    } else if subject.is_empty() {
+        format!("{}: (No subject)", prefix)
+    } else {
+        format!("{}: {}", prefix, subject)
+    }
+}
+
+/// Format an address for use as a recipient, quoting display names when needed
+fn mailbox_string(addr: &EmailAddress) -> String {
+    match addr.address.parse() {
+        Ok(email) => Mailbox::new(addr.name.clone(), email).to_string(),
+        Err(_) => addr.address.clone(),
+    }
+}
+
+/// Compute (to, cc) for a reply.
+/// Replies go to Reply-To if present, otherwise From. Reply-all also copies the
+/// original To and Cc recipients, excluding our own address and duplicates.
+pub fn reply_recipients(
+    original: &Message,
+    own_email: &str,
+    all: bool,
+) -> (Vec<EmailAddress>, Vec<EmailAddress>) {
+    let mut seen: Vec<String> = Vec::new();
+    let mut to = Vec::new();
+    let mut cc = Vec::new();
+
+    if let Some(sender) = original.reply_to.as_ref().or(original.from.as_ref()) {
+        seen.push(sender.address.to_lowercase());
+        to.push(sender.clone());
+    }
+
+    if all {
+        let own = own_email.to_lowercase();
+        for addr in original.to.iter().chain(original.cc.iter()) {
+            let key = addr.address.to_lowercase();
+            if key == own || seen.contains(&key) {
+                continue;
+            }
+            seen.push(key);
+            cc.push(addr.clone());
+        }
+    }
+
+    (to, cc)
+}
+
+/// Plain-text body of the original message, falling back to stripped HTML
+fn original_text(message: &Message) -> String {
+    message
+        .body_text
+        .clone()
+        .or_else(|| message.body_html.as_deref().map(html::to_text))
+        .unwrap_or_default()
+}
+
+/// Build a reply body: the new text followed by the quoted original
+pub fn quote_body(reply: &str, original: &Message) -> String {
+    let mut output = String::new();
+    if !reply.is_empty() {
+        output.push_str(reply.trim_end());
+        output.push_str("\n\n");
+    }
+
+    let sender = original
+        .from
+        .as_ref()
+        .map(|f| f.format())
+        .unwrap_or_else(|| "unknown sender".to_string());
+    match original.date {
+        Some(date) => output.push_str(&format!(
+            "On {}, {} wrote:\n",
+            date.format("%a, %d %b %Y at %H:%M UTC"),
+            sender
+        )),
+        None => output.push_str(&format!("{} wrote:\n", sender)),
+    }
+
+    for line in original_text(original).trim_end().lines() {
+        if line.is_empty() {
+            output.push_str(">\n");
+        } else {
+            output.push_str(&format!("> {}\n", line));
+        }
+    }
+
+    output
+}
+
+/// Build a forward body: the new text followed by the original headers and body
+pub fn forward_body(note: &str, original: &Message) -> String {
+    let mut output = String::new();
+    if !note.is_empty() {
+        output.push_str(note.trim_end());
+        output.push_str("\n\n");
+    }
+
+    output.push_str("---------- Forwarded message ---------\n");
+    if let Some(ref from) = original.from {
+        output.push_str(&format!("From: {}\n", from.format()));
+    }
+    if let Some(ref date) = original.date {
+        output.push_str(&format!(
+            "Date: {}\n",
+            date.format("%a, %d %b %Y %H:%M:%S UTC")
+        ));
+    }
+    output.push_str(&format!(
+        "Subject: {}\n",
+        original.subject.as_deref().unwrap_or("(No subject)")
+    ));
+    if !original.to.is_empty() {
+        let to: Vec<String> = original.to.iter().map(|a| a.format()).collect();
+        output.push_str(&format!("To: {}\n", to.join(", ")));
+    }
+    if !original.cc.is_empty() {
+        let cc: Vec<String> = original.cc.iter().map(|a| a.format()).collect();
+        output.push_str(&format!("Cc: {}\n", cc.join(", ")));
+    }
+    output.push('\n');
+    output.push_str(&original_text(original));
+
+    output
+}
+
+/// Extract the attachments of the original message from its raw bytes
+fn original_attachments(message: &Message) -> Vec<AttachmentData> {
+    let Some(raw) = message.raw_message.as_deref() else {
+        return vec![];
+    };
+    let Some(parsed) = mail_parser::MessageParser::default().parse(raw) else {
+        return vec![];
+    };
+
+    parsed
+        .attachments()
+        .enumerate()
+        .map(|(i, part)| {
+            let meta = Attachment::from_part(i + 1, part);
+            AttachmentData {
+                filename: meta.display_name(),
+                content_type: meta.content_type,
+                data: part.contents().to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// Threading headers for a reply: In-Reply-To and References
+fn thread_ids(original: &Message) -> (Option<String>, Vec<String>) {
+    let mut references = original.references.clone();
+    if let Some(ref id) = original.message_id {
+        if !references.contains(id) {
+            references.push(id.clone());
+        }
+    }
+    (original.message_id.clone(), references)
+}
+
+/// A reply or forward prefilled from the original message
+pub struct ComposeStart {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub subject: String,
+    /// New text followed by the quoted (reply) or forwarded original
+    pub body: String,
+    /// Attachments carried over from a forwarded original
+    pub attachments: Vec<AttachmentData>,
+    pub in_reply_to: Option<String>,
+    references: Vec<String>,
+    is_reply: bool,
+    original: Original,
+}
+
+impl ComposeStart {
+    /// Reply (optionally to all recipients) with `text` above the quoted original
+    pub async fn reply(account: &Account, shadow_uid: i64, all: bool, text: &str) -> Result<Self> {
+        let original = fetch_original(account, shadow_uid, false).await?;
+        let message = &original.message;
+
+        let (to, cc) = reply_recipients(message, &account.email, all);
+        if to.is_empty() {
+            return Err(anyhow!(
+                "Message {} has no From or Reply-To address to reply to",
+                shadow_uid
+            ));
+        }
+        let (in_reply_to, references) = thread_ids(message);
+
+        Ok(Self {
+            to: to.iter().map(mailbox_string).collect(),
+            cc: cc.iter().map(mailbox_string).collect(),
+            subject: prefix_subject("Re", message.subject.as_deref()),
+            body: quote_body(text, message),
+            attachments: vec![],
+            in_reply_to,
+            references,
+            is_reply: true,
+            original,
+        })
+    }
+
+    /// Forward with `note` above the original headers and body, keeping its attachments
+    pub async fn forward(account: &Account, shadow_uid: i64, note: &str) -> Result<Self> {
+        let original = fetch_original(account, shadow_uid, true).await?;
+        let message = &original.message;
+
+        Ok(Self {
+            to: vec![],
+            cc: vec![],
+            subject: prefix_subject("Fwd", message.subject.as_deref()),
+            body: forward_body(note, message),
+            attachments: original_attachments(message),
+            in_reply_to: None,
+            references: vec![],
+            is_reply: false,
+            original,
+        })
+    }
+
+    /// Add the threading headers and forwarded attachments to a message being built
+    pub fn apply(&self, mut builder: EmailBuilder) -> EmailBuilder {
+        if let Some(ref id) = self.in_reply_to {
+            builder = builder.in_reply_to(id);
+        }
+        if !self.references.is_empty() {
+            builder = builder.references(self.references.clone());
+        }
+        for attachment in &self.attachments {
+            builder = builder.attach_data(attachment.clone());
+        }
+        builder
+    }
+
+    /// The IMAP connection the original was fetched over
+    pub fn client(&mut self) -> &mut ImapClient {
+        &mut self.original.client
+    }
+
+    /// Replace the IMAP connection, e.g. after the message sat in an editor long enough
+    /// for the server to drop it
+    pub async fn reconnect(&mut self, account: &Account) -> Result<()> {
+        self.original.client = ImapClient::connect(account).await?;
+        Ok(())
+    }
+
+    /// Once a reply has been sent, flag the original as answered
+    pub async fn mark_answered(&mut self) -> Result<()> {
+        if !self.is_reply {
+            return Ok(());
+        }
+        let original = &mut self.original;
+        original.client.select_folder(&original.folder).await?;
+        original
+            .client
+            .modify_flags(&[original.imap_uid], "\\Answered", true)
+            .await
+    }
+}
+
+/// Reply to a message (optionally to all recipients), quoting the original
+#[allow(clippy::too_many_arguments)]
+pub async fn reply_message(
+    account_email: Option<&str>,
+    shadow_uid: i64,
+    all: bool,
+    body: Option<String>,
+    body_file: Option<String>,
+    attachments: Vec<String>,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    let (account, from_email) = resolve_sender(&config, account_email, None)?;
+
+    let reply_text = read_body(body, body_file)?;
+    let mut start = ComposeStart::reply(account, shadow_uid, all, &reply_text).await?;
+
+    let mut builder = start.apply(
+        EmailBuilder::new()
+            .from(&from_email)
+            .subject(start.subject.clone())
+            .body(start.body.clone()),
+    );
+    for addr in &start.to {
+        builder = builder.to(addr)?;
+    }
+    for addr in &start.cc {
+        builder = builder.cc(addr)?;
+    }
+    for attachment in attachments {
+        builder = builder.attach(attachment);
+    }
+    warn_attachment_size(&config, &builder);
+    let email = builder.build()?;
+
+    let mut smtp_client = SmtpClient::connect(account)?;
+    let (_, raw) = smtp_client.send_message(email)?;
+
+    // The reply is out; failing to flag the original only warns
+    if let Err(e) = start.mark_answered().await {
+        eprintln!("Warning: could not flag the original as answered: {:#}", e);
+    }
+    let sent_id = save_to_sent(start.client(), &config, &account.email, &raw).await;
+
+    let output = ReplyOutput {
+        account: account.email.clone(),
+        action: if all { "reply_all" } else { "reply" }.to_string(),
+        original_id: shadow_uid,
+        subject: start.subject,
+        to: start.to,
+        cc: start.cc,
+        bcc: vec![],
+        in_reply_to: start.in_reply_to,
+        attachment_count: None,
+        sent_id,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            println!("✓ Reply sent to {}", output.to.join(", "));
+            if !output.cc.is_empty() {
+                println!("  Cc: {}", output.cc.join(", "));
+            }
+            println!("  Subject: {}", output.subject);
+            if let Some(id) = output.sent_id {
+                println!("  Saved to {} (id {})", config.preferences.sent_folder, id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Forward a message, including the original attachments
+#[allow(clippy::too_many_arguments)]
+pub async fn forward_message(
+    account_email: Option<&str>,
+    shadow_uid: i64,
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+    body: Option<String>,
+    body_file: Option<String>,
+    attachments: Vec<String>,
+    output_format: Option<&str>,
+) -> Result<()> {
+    if to.is_empty() {
+        return Err(anyhow!("At least one --to recipient is required"));
+    }
+
+    let config = Config::load()?;
+    let (account, from_email) = resolve_sender(&config, account_email, None)?;
+
+    let note = read_body(body, body_file)?;
+    let mut start = ComposeStart::forward(account, shadow_uid, &note).await?;
+    let attachment_count = start.attachments.len() + attachments.len();
+
+    let mut builder = start.apply(
+        EmailBuilder::new()
+            .from(&from_email)
+            .subject(start.subject.clone())
+            .body(start.body.clone()),
+    );
+    for addr in &to {
+        builder = builder.to(addr)?;
+    }
+    for addr in &cc {
+        builder = builder.cc(addr)?;
+    }
+    for addr in &bcc {
+        builder = builder.bcc(addr)?;
+    }
+    for attachment in attachments {
+        builder = builder.attach(attachment);
+    }
+    warn_attachment_size(&config, &builder);
+    let email = builder.build()?;
+
+    let mut smtp_client = SmtpClient::connect(account)?;
+    let (_, raw) = smtp_client.send_message(email)?;
+    let sent_id = save_to_sent(start.client(), &config, &account.email, &raw).await;
+
+    let output = ReplyOutput {
+        account: account.email.clone(),
+        action: "forward".to_string(),
+        original_id: shadow_uid,
+        subject: start.subject,
+        to,
+        cc,
+        bcc,
+        in_reply_to: None,
+        attachment_count: Some(attachment_count),
+        sent_id,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            println!("✓ Message forwarded to {}", output.to.join(", "));
+            println!("  Subject: {}", output.subject);
+            if attachment_count > 0 {
+                println!("  Attachments: {}", attachment_count);
+            }
+            if let Some(id) = output.sent_id {
+                println!("  Saved to {} (id {})", config.preferences.sent_folder, id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(address: &str) -> EmailAddress {
+        EmailAddress::new(address.to_string(), None)
+    }
+
+    fn original() -> Message {
+        let mut message = Message::new(1);
+        message.from = Some(addr("alice@example.com"));
+        message.to = vec![addr("me@example.com"), addr("bob@example.com")];
+        message.cc = vec![addr("carol@example.com"), addr("Alice@Example.com")];
+        message.subject = Some("Lunch".to_string());
+        message.body_text = Some("Are you free?\n\nThanks".to_string());
+        message
+    }
+
+    #[test]
+    fn test_prefix_subject() {
+        assert_eq!(prefix_subject("Re", Some("Lunch")), "Re: Lunch");
+        assert_eq!(prefix_subject("Re", Some("RE: Lunch")), "RE: Lunch");
+        assert_eq!(prefix_subject("Fwd", Some("Re: Lunch")), "Fwd: Re: Lunch");
+        assert_eq!(prefix_subject("Re", None), "Re: (No subject)");
+    }
+
+    #[test]
+    fn test_reply_recipients_sender_only() {
+        let (to, cc) = reply_recipients(&original(), "me@example.com", false);
+        assert_eq!(to.len(), 1);
+        assert_eq!(to[0].address, "alice@example.com");
+        assert!(cc.is_empty());
+    }
+
+    #[test]
+    fn test_reply_recipients_prefers_reply_to() {
+        let mut message = original();
+        message.reply_to = Some(addr("list@example.com"));
+        let (to, _) = reply_recipients(&message, "me@example.com", false);
+        assert_eq!(to[0].address, "list@example.com");
+    }
+
+    #[test]
+    fn test_reply_all_excludes_self_and_duplicates() {
+        let (to, cc) = reply_recipients(&original(), "ME@example.com", true);
+        assert_eq!(to[0].address, "alice@example.com");
+        let cc: Vec<&str> = cc.iter().map(|a| a.address.as_str()).collect();
+        assert_eq!(cc, vec!["bob@example.com", "carol@example.com"]);
+    }
+
+    #[test]
+    fn test_quote_body() {
+        let body = quote_body("Sure!", &original());
+        assert!(body.starts_with("Sure!\n\n"));
+        assert!(body.contains("alice@example.com wrote:\n"));
+        assert!(body.contains("> Are you free?\n>\n> Thanks\n"));
+    }
+
+    #[test]
+    fn test_thread_ids_appends_message_id() {
+        let mut message = original();
+        message.message_id = Some("b@example.com".to_string());
+        message.references = vec!["a@example.com".to_string()];
+        let (in_reply_to, references) = thread_ids(&message);
+        assert_eq!(in_reply_to.as_deref(), Some("b@example.com"));
+        assert_eq!(references, vec!["a@example.com", "b@example.com"]);
+    }
+}
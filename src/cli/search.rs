@@ -0,0 +1,101 @@
+use crate::core::cache;
+use crate::core::state::{StateManager, TextSearchHit};
+use crate::models::config::Config;
+use crate::output::json;
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct SearchResult {
+    id: i64,
+    folder: String,
+    uid: i64,
+    message_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    score: f64,
+    snippet: String,
+}
+
+impl From<TextSearchHit> for SearchResult {
+    fn from(hit: TextSearchHit) -> Self {
+        Self {
+            id: hit.shadow_uid,
+            folder: hit.folder,
+            uid: hit.uid,
+            message_id: hit.message_id,
+            subject: hit.subject,
+            from: hit.from_address,
+            date: hit.date_sent,
+            score: hit.score,
+            snippet: hit.snippet,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SearchOutput {
+    account: String,
+    query: String,
+    count: usize,
+    results: Vec<SearchResult>,
+}
+
+/// Rank locally indexed messages against free-text terms
+pub async fn search(
+    account_email: Option<&str>,
+    terms: &str,
+    folder: Option<&str>,
+    limit: usize,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    cache::ensure_enabled(&config)?;
+    let account = config.resolve_account(account_email)?;
+
+    let match_expr = cache::fts_match_expression(terms)?;
+
+    let state = StateManager::new().await?;
+    let hits = state
+        .search_text(&account.email, &match_expr, folder, limit)
+        .await?;
+
+    if hits.is_empty() && state.indexed_message_count(&account.email).await? == 0 {
+        eprintln!(
+            "Note: the search index is empty. Run `protoncli sync` (or read/list messages) to populate it."
+        );
+    }
+
+    let results: Vec<SearchResult> = hits.into_iter().map(Into::into).collect();
+    let output = SearchOutput {
+        account: account.email.clone(),
+        query: terms.to_string(),
+        count: results.len(),
+        results,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            if output.results.is_empty() {
+                println!("No messages match \"{}\"", output.query);
+            }
+            for result in &output.results {
+                println!(
+                    "[{}] {} — {} ({})",
+                    result.id,
+                    result.subject.as_deref().unwrap_or("(no subject)"),
+                    result.from.as_deref().unwrap_or("unknown sender"),
+                    result.folder
+                );
+                println!("    {}", result.snippet.replace('\n', " "));
+            }
+        }
+    }
+
+    Ok(())
+}
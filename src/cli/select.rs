@@ -11,6 +11,13 @@ pub struct SelectionOutput {
     pub messages: Vec<SelectionMessage>,
 }
 
+/// Final record of `select show -o ndjson`
+#[derive(Serialize)]
+struct SelectionSummary<'a> {
+    account: &'a str,
+    total_count: usize,
+}
+
 #[derive(Serialize)]
 pub struct SelectionMessage {
     pub id: i64,
@@ -27,11 +34,13 @@ pub struct SelectActionOutput {
 }
 
 /// Add shadow UIDs to the selection
-pub async fn add_to_selection(ids: Vec<i64>, output_format: Option<&str>) -> Result<()> {
+pub async fn add_to_selection(
+    account_email: Option<&str>,
+    ids: Vec<i64>,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     if ids.is_empty() {
         return Err(anyhow!("No message IDs specified"));
@@ -71,11 +80,13 @@ pub async fn add_to_selection(ids: Vec<i64>, output_format: Option<&str>) -> Res
 }
 
 /// Add last query results to the selection
-pub async fn add_last_query_to_selection(folder: &str, output_format: Option<&str>) -> Result<()> {
+pub async fn add_last_query_to_selection(
+    account_email: Option<&str>,
+    folder: &str,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
 
@@ -120,11 +131,13 @@ pub async fn add_last_query_to_selection(folder: &str, output_format: Option<&st
 }
 
 /// Remove shadow UIDs from the selection
-pub async fn remove_from_selection(ids: Vec<i64>, output_format: Option<&str>) -> Result<()> {
+pub async fn remove_from_selection(
+    account_email: Option<&str>,
+    ids: Vec<i64>,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     if ids.is_empty() {
         return Err(anyhow!("No message IDs specified"));
@@ -159,22 +172,25 @@ pub async fn remove_from_selection(ids: Vec<i64>, output_format: Option<&str>) -
 }
 
 /// Show the current selection
-pub async fn show_selection(output_format: Option<&str>) -> Result<()> {
+pub async fn show_selection(
+    account_email: Option<&str>,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
     let entries = state.get_selection(&account.email).await?;
 
     if entries.is_empty() {
+        let output = SelectionOutput {
+            account: account.email.clone(),
+            total_count: 0,
+            messages: vec![],
+        };
         match output_format.unwrap_or("text") {
-            "json" => json::print_json(&SelectionOutput {
-                account: account.email.clone(),
-                total_count: 0,
-                messages: vec![],
-            })?,
+            "json" => json::print_json(&output)?,
+            "ndjson" => print_selection_ndjson(&output)?,
             _ => println!("Selection is empty"),
         }
         return Ok(());
@@ -200,6 +216,7 @@ pub async fn show_selection(output_format: Option<&str>) -> Result<()> {
 
     match output_format.unwrap_or("text") {
         "json" => json::print_json(&output)?,
+        "ndjson" => print_selection_ndjson(&output)?,
         "table" => {
             print_selection_table(&output);
         }
@@ -217,12 +234,27 @@ pub async fn show_selection(output_format: Option<&str>) -> Result<()> {
     Ok(())
 }
 
+/// One `message` record per selected message, then a `summary` record
+fn print_selection_ndjson(output: &SelectionOutput) -> Result<()> {
+    for msg in &output.messages {
+        json::print_ndjson_record("message", msg)?;
+    }
+    json::print_ndjson_record(
+        "summary",
+        &SelectionSummary {
+            account: &output.account,
+            total_count: output.total_count,
+        },
+    )
+}
+
 /// Clear the selection
-pub async fn clear_selection(output_format: Option<&str>) -> Result<()> {
+pub async fn clear_selection(
+    account_email: Option<&str>,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
     let count = state.clear_selection(&account.email).await?;
@@ -242,11 +274,12 @@ pub async fn clear_selection(output_format: Option<&str>) -> Result<()> {
 }
 
 /// Count messages in the selection
-pub async fn count_selection(output_format: Option<&str>) -> Result<()> {
+pub async fn count_selection(
+    account_email: Option<&str>,
+    output_format: Option<&str>,
+) -> Result<()> {
     let config = Config::load()?;
-    let account = config
-        .get_default_account()
-        .ok_or_else(|| anyhow!("No default account configured"))?;
+    let account = config.resolve_account(account_email)?;
 
     let state = StateManager::new().await?;
     let count = state.selection_count(&account.email).await?;
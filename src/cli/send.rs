@@ -1,9 +1,58 @@
+use crate::core::imap::{parse_message, ImapClient};
 use crate::core::smtp::SmtpClient;
+use crate::core::state::{validate_shadow_uids, StateManager};
+use crate::models::account::Account;
 use crate::models::config::Config;
+use crate::output::markdown::format_size;
+use crate::output::{html, json};
+use crate::utils::mime;
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use lettre::address::{Address, Envelope};
 use lettre::message::{header::ContentType, Mailbox, Message, MultiPart, SinglePart};
+use mail_parser::MessageParser;
+use serde::Serialize;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// An attachment held in memory (e.g. carried over from a forwarded message)
+#[derive(Clone)]
+pub struct AttachmentData {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// An image embedded next to the HTML body and referenced from it as `cid:<content_id>`
+pub struct InlineImage {
+    pub content_id: String,
+    pub path: String,
+}
+
+impl InlineImage {
+    /// Parse `--inline CID=PATH`, or `--inline PATH` to use the file name as Content-ID
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (content_id, path) = match spec.split_once('=') {
+            Some((cid, path)) if !cid.is_empty() && !cid.contains(['/', '\\']) => {
+                (cid.to_string(), path.to_string())
+            }
+            _ => {
+                let name = Path::new(spec)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .ok_or_else(|| anyhow!("Invalid inline image '{}', expected CID=PATH", spec))?;
+                (name.to_string(), spec.to_string())
+            }
+        };
+        let content_id = content_id
+            .trim_start_matches("cid:")
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string();
+        Ok(Self { content_id, path })
+    }
+}
 
 pub struct EmailBuilder {
     from: Option<Mailbox>,
@@ -12,7 +61,13 @@ pub struct EmailBuilder {
     bcc: Vec<Mailbox>,
     subject: Option<String>,
     body: Option<String>,
+    html: Option<String>,
+    inline_images: Vec<InlineImage>,
     attachments: Vec<String>,
+    attachment_data: Vec<AttachmentData>,
+    in_reply_to: Option<String>,
+    references: Vec<String>,
+    draft: bool,
 }
 
 impl EmailBuilder {
@@ -24,7 +79,13 @@ impl EmailBuilder {
             bcc: Vec::new(),
             subject: None,
             body: None,
+            html: None,
+            inline_images: Vec::new(),
             attachments: Vec::new(),
+            attachment_data: Vec::new(),
+            in_reply_to: None,
+            references: Vec::new(),
+            draft: false,
         }
     }
 
@@ -67,23 +128,127 @@ impl EmailBuilder {
         self
     }
 
+    /// HTML body; the message becomes multipart/alternative with the text body
+    /// (or the HTML rendered as text) as the plain part
+    pub fn html(mut self, html: String) -> Self {
+        self.html = Some(html);
+        self
+    }
+
+    pub fn inline_image(mut self, image: InlineImage) -> Self {
+        self.inline_images.push(image);
+        self
+    }
+
+    /// Attach a file; `PATH:TYPE` (e.g. `report.bin:application/pdf`) overrides the detected type
     pub fn attach(mut self, file_path: String) -> Self {
         self.attachments.push(file_path);
         self
     }
 
+    pub fn attach_data(mut self, attachment: AttachmentData) -> Self {
+        self.attachment_data.push(attachment);
+        self
+    }
+
+    /// Set the In-Reply-To header (Message-ID without angle brackets)
+    pub fn in_reply_to(mut self, message_id: &str) -> Self {
+        self.in_reply_to = Some(message_id.to_string());
+        self
+    }
+
+    /// Set the References header (Message-IDs without angle brackets, oldest first)
+    pub fn references(mut self, message_ids: Vec<String>) -> Self {
+        self.references = message_ids;
+        self
+    }
+
+    /// Build a draft: Bcc stays in the headers for whoever sends it later, and recipients
+    /// may still be missing
+    pub fn draft(mut self) -> Self {
+        self.draft = true;
+        self
+    }
+
+    /// Total size of attachments and inline images in bytes (files that can't be read count as 0)
+    pub fn attachment_bytes(&self) -> u64 {
+        let file_size = |path: &str| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let files: u64 = self
+            .attachments
+            .iter()
+            .map(|spec| file_size(split_attachment_spec(spec).0))
+            .sum();
+        let inline: u64 = self
+            .inline_images
+            .iter()
+            .map(|image| file_size(&image.path))
+            .sum();
+        let data: u64 = self
+            .attachment_data
+            .iter()
+            .map(|a| a.data.len() as u64)
+            .sum();
+        files + inline + data
+    }
+
     pub fn build(self) -> Result<Message> {
         let from = self.from.ok_or_else(|| anyhow!("From address required"))?;
 
-        if self.to.is_empty() {
+        if self.to.is_empty() && !self.draft {
             return Err(anyhow!("At least one recipient required"));
         }
 
         let subject = self.subject.unwrap_or_else(|| "(No subject)".to_string());
-        let body_text = self.body.unwrap_or_default();
+        let body_text = match (self.body, &self.html) {
+            (Some(body), _) => body,
+            (None, Some(html)) => html::to_text(html),
+            (None, None) => String::new(),
+        };
+
+        if self.html.is_none() && !self.inline_images.is_empty() {
+            return Err(anyhow!("Inline images need an HTML body"));
+        }
+        // text/plain and text/html alternatives; inline images go with the HTML in multipart/related
+        let alternative = match self.html {
+            Some(html) => {
+                let plain = SinglePart::plain(body_text.clone());
+                let alternative = if self.inline_images.is_empty() {
+                    MultiPart::alternative()
+                        .singlepart(plain)
+                        .singlepart(SinglePart::html(html))
+                } else {
+                    let mut related = MultiPart::related().singlepart(SinglePart::html(html));
+                    for image in &self.inline_images {
+                        let safe_path = validate_attachment_path(&image.path)?;
+                        let data = fs::read(&safe_path)
+                            .context(format!("Failed to read inline image: {}", image.path))?;
+                        let content_type = ContentType::parse(&mime::detect(&safe_path, &data))
+                            .unwrap_or_else(|_| octet_stream());
+                        related = related.singlepart(
+                            lettre::message::Attachment::new_inline(image.content_id.clone())
+                                .body(data, content_type),
+                        );
+                    }
+                    MultiPart::alternative()
+                        .singlepart(plain)
+                        .multipart(related)
+                };
+                Some(alternative)
+            }
+            None => None,
+        };
 
         // Start building message
-        let mut message_builder = Message::builder().from(from);
+        let mut message_builder = Message::builder().message_id(Some(new_message_id(&from)));
+        if self.draft {
+            // Drafts never reach SMTP themselves; a placeholder envelope lets lettre build
+            // one without recipients
+            message_builder = message_builder.keep_bcc().envelope(Envelope::new(
+                Some(from.email.clone()),
+                vec![from.email.clone()],
+            )?);
+        }
+        message_builder = message_builder.from(from);
 
         // Add recipients
         for to_addr in &self.to {
@@ -100,22 +265,45 @@ impl EmailBuilder {
 
         message_builder = message_builder.subject(subject);
 
+        // Threading headers for replies
+        if let Some(in_reply_to) = &self.in_reply_to {
+            message_builder = message_builder.in_reply_to(format_message_id(in_reply_to));
+        }
+        if !self.references.is_empty() {
+            let references: Vec<String> = self
+                .references
+                .iter()
+                .map(|id| format_message_id(id))
+                .collect();
+            message_builder = message_builder.references(references.join(" "));
+        }
+
         // Build message with or without attachments
-        if self.attachments.is_empty() {
-            // Simple text message
-            message_builder
-                .body(body_text)
-                .context("Failed to build message")
+        if self.attachments.is_empty() && self.attachment_data.is_empty() {
+            match alternative {
+                Some(alternative) => message_builder
+                    .multipart(alternative)
+                    .context("Failed to build message"),
+                // Simple text message
+                None => message_builder
+                    .body(body_text)
+                    .context("Failed to build message"),
+            }
         } else {
             // Multipart message with attachments
-            let mut multipart = MultiPart::mixed().singlepart(
-                SinglePart::builder()
-                    .header(ContentType::TEXT_PLAIN)
-                    .body(body_text),
-            );
+            let mut multipart = match alternative {
+                Some(alternative) => MultiPart::mixed().multipart(alternative),
+                None => MultiPart::mixed().singlepart(
+                    SinglePart::builder()
+                        .header(ContentType::TEXT_PLAIN)
+                        .body(body_text),
+                ),
+            };
 
-            // Add each attachment with path validation
-            for attachment_path in &self.attachments {
+            // Add each attachment with path validation; the content type comes from the
+            // `PATH:TYPE` override or is detected from the file
+            for spec in &self.attachments {
+                let (attachment_path, override_type) = split_attachment_spec(spec);
                 let safe_path = validate_attachment_path(attachment_path)?;
                 let file_content = fs::read(&safe_path)
                     .context(format!("Failed to read attachment: {}", attachment_path))?;
@@ -124,12 +312,28 @@ impl EmailBuilder {
                     .file_name()
                     .and_then(|n| n.to_str())
                     .unwrap_or("attachment");
+                let content_type = match override_type {
+                    Some(mime) => content_type_with_name(mime, filename)?,
+                    None => {
+                        content_type_with_name(&mime::detect(&safe_path, &file_content), filename)
+                            .unwrap_or_else(|_| octet_stream())
+                    }
+                };
 
                 multipart = multipart.singlepart(
-                    lettre::message::Attachment::new(filename.to_string()).body(
-                        file_content,
-                        ContentType::parse("application/octet-stream").unwrap(),
-                    ),
+                    lettre::message::Attachment::new(filename.to_string())
+                        .body(file_content, content_type),
+                );
+            }
+
+            // Add in-memory attachments, keeping their original content type when valid
+            for attachment in self.attachment_data {
+                let content_type =
+                    content_type_with_name(&attachment.content_type, &attachment.filename)
+                        .unwrap_or_else(|_| octet_stream());
+                multipart = multipart.singlepart(
+                    lettre::message::Attachment::new(attachment.filename)
+                        .body(attachment.data, content_type),
                 );
             }
 
@@ -140,11 +344,201 @@ impl EmailBuilder {
     }
 }
 
+/// Split `PATH:TYPE` into the path and the content type override.
+/// A spec that names an existing file, or whose suffix isn't a MIME type, is all path.
+pub fn split_attachment_spec(spec: &str) -> (&str, Option<&str>) {
+    match spec.rsplit_once(':') {
+        Some((path, mime))
+            if !path.is_empty() && mime::is_mime_type(mime) && !Path::new(spec).exists() =>
+        {
+            (path, Some(mime.trim()))
+        }
+        _ => (spec, None),
+    }
+}
+
+fn octet_stream() -> ContentType {
+    ContentType::parse("application/octet-stream").unwrap()
+}
+
+/// `mime` with a `name` parameter for clients that read the file name from Content-Type;
+/// non-ASCII names use RFC 2231 encoding, as lettre does for Content-Disposition
+fn content_type_with_name(mime: &str, filename: &str) -> Result<ContentType> {
+    let name = if filename.is_ascii() {
+        format!(
+            "name=\"{}\"",
+            filename.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    } else {
+        format!("name*=utf-8''{}", rfc2231_encode(filename))
+    };
+    ContentType::parse(&format!("{}; {}", mime.trim(), name))
+        .map_err(|_| anyhow!("Invalid content type '{}'", mime))
+}
+
+/// Percent-encode everything outside RFC 2231 `attribute-char`
+fn rfc2231_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+/// Warn when attachments exceed the `attachment_warning_mb` preference
+pub fn warn_attachment_size(config: &Config, builder: &EmailBuilder) {
+    let limit_mb = config.preferences.attachment_warning_mb;
+    let total = builder.attachment_bytes();
+    if limit_mb > 0 && total > limit_mb * 1024 * 1024 {
+        eprintln!(
+            "Warning: attachments total {} (over the {} MB warning limit); the server may reject the message",
+            format_size(total as usize),
+            limit_mb
+        );
+    }
+}
+
+/// Append a sent message to the Sent folder when `save_sent` is on and register it in the
+/// state DB. Returns its shadow UID; failures only warn since the message has gone out.
+pub async fn save_to_sent(
+    client: &mut ImapClient,
+    config: &Config,
+    account: &str,
+    raw: &[u8],
+) -> Option<i64> {
+    if !config.preferences.save_sent {
+        return None;
+    }
+    let folder = config.preferences.sent_folder.as_str();
+    match append_message(client, config, account, folder, raw, "\\Seen").await {
+        Ok(shadow_uid) => Some(shadow_uid),
+        Err(e) => {
+            eprintln!(
+                "Warning: message sent but not saved to '{}': {:#}",
+                config.preferences.sent_folder, e
+            );
+            None
+        }
+    }
+}
+
+/// APPEND a message to `folder` with `flags` and give it a shadow UID (and a cache entry
+/// when the cache is enabled and the server reports APPENDUID)
+pub async fn append_message(
+    client: &mut ImapClient,
+    config: &Config,
+    account: &str,
+    folder: &str,
+    raw: &[u8],
+    flags: &str,
+) -> Result<i64> {
+    let appended = client.append(folder, raw, flags).await?;
+
+    let mut message =
+        parse_message(0, raw).ok_or_else(|| anyhow!("Failed to parse the appended message"))?;
+    let message_id = message
+        .message_id
+        .clone()
+        .ok_or_else(|| anyhow!("Appended message has no Message-ID"))?;
+    // Without UIDPLUS the new UID has to be looked up
+    message.uid = match appended {
+        Some(appended) => appended.uid,
+        None => client
+            .find_uid_by_message_id(folder, &message_id)
+            .await?
+            .ok_or_else(|| anyhow!("Appended message not found in '{}'", folder))?,
+    };
+    message.flags.seen = flags.contains("\\Seen");
+    message.flags.draft = flags.contains("\\Draft");
+
+    let state = StateManager::new().await?;
+    let shadow_uid = state
+        .get_or_create_shadow_uid(
+            account,
+            folder,
+            message.uid,
+            Some(&message_id),
+            message.subject.as_deref(),
+            message.from.as_ref().map(|f| f.address.as_str()),
+            message.date,
+        )
+        .await?;
+
+    // Keep a copy for `read --offline`
+    if let Some(appended) = appended.filter(|_| config.preferences.cache_enabled) {
+        state
+            .store_cached_message(account, folder, appended.uid_validity, &message, Some(raw))
+            .await?;
+    }
+
+    Ok(shadow_uid)
+}
+
+/// A unique Message-ID on the sender's domain (lettre's default would use the local hostname).
+/// Set explicitly so the copy saved to Sent can be found and tracked by it.
+fn new_message_id(from: &Mailbox) -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    format!(
+        "<{:x}.{:x}.{}@{}>",
+        Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        from.email.domain()
+    )
+}
+
+/// Wrap a bare Message-ID in angle brackets for use in headers
+fn format_message_id(id: &str) -> String {
+    let id = id.trim().trim_start_matches('<').trim_end_matches('>');
+    format!("<{}>", id)
+}
+
+/// Determine which account to use for SMTP credentials and which email for the From header.
+/// An explicit --account always sends through that account; --from alone selects a configured
+/// account if it matches one, otherwise it is used as an alias on the default account.
+pub fn resolve_sender<'a>(
+    config: &'a Config,
+    account_email: Option<&str>,
+    from: Option<&str>,
+) -> Result<(&'a Account, String)> {
+    if let Some(email) = account_email {
+        // Explicit --account: always send through it, --from only overrides the header (alias support)
+        let account = config.resolve_account(Some(email))?;
+        let from_email = from
+            .map(String::from)
+            .unwrap_or_else(|| account.email.clone());
+        Ok((account, from_email))
+    } else if let Some(from_addr) = from {
+        // Check if --from matches a configured account
+        if let Some(account) = config.get_account(from_addr) {
+            // Use the matched account for SMTP and From
+            Ok((account, account.email.clone()))
+        } else {
+            // Use default account for SMTP, but specified email for From (alias support)
+            let default = config
+                .get_default_account()
+                .ok_or_else(|| anyhow!("No default account configured. Configure an account with 'protoncli account add' or specify a configured account with --from"))?;
+            Ok((default, from_addr.to_string()))
+        }
+    } else {
+        // No --from specified, use default account for both
+        let default = config
+            .get_default_account()
+            .ok_or_else(|| anyhow!("No default account configured. Use --from to specify an account or set a default with 'protoncli account set-default'"))?;
+        Ok((default, default.email.clone()))
+    }
+}
+
 /// Validate an attachment path for security.
 /// - Canonicalizes the path to resolve symlinks and ../ traversals
 /// - Verifies the file exists and is a regular file (not a directory or special file)
 /// - Returns the canonical path if valid
-fn validate_attachment_path(path: &str) -> Result<PathBuf> {
+pub fn validate_attachment_path(path: &str) -> Result<PathBuf> {
     let path = Path::new(path);
 
     // Check if path exists first (before canonicalization to give better errors)
@@ -178,83 +572,586 @@ fn validate_attachment_path(path: &str) -> Result<PathBuf> {
     Ok(canonical)
 }
 
-#[allow(clippy::too_many_arguments)]
-pub async fn send_email(
-    from: Option<String>,
+/// What to send, as given on the `send` / `compose` command line
+pub struct MessageContent {
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+    pub body_file: Option<String>,
+    pub html: Option<String>,
+    pub html_file: Option<String>,
+    /// Render the body from markdown into an HTML part
+    pub markdown: bool,
+    /// Inline images as CID=PATH
+    pub inline: Vec<String>,
+    /// Attachments as PATH or PATH:TYPE
+    pub attach: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SendOutput {
+    account: String,
+    from: String,
+    subject: String,
     to: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     cc: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     bcc: Vec<String>,
-    subject: Option<String>,
-    body: Option<String>,
-    body_file: Option<String>,
-    attachments: Vec<String>,
-) -> Result<()> {
-    let config = Config::load()?;
-
-    // Determine which account to use for SMTP credentials and which email for From header
-    let (smtp_account, from_email) = if let Some(from_addr) = &from {
-        // Check if --from matches a configured account
-        if let Some(account) = config.get_account(from_addr) {
-            // Use the matched account for SMTP and From
-            (account, account.email.clone())
-        } else {
-            // Use default account for SMTP, but specified email for From (alias support)
-            let default = config
-                .get_default_account()
-                .ok_or_else(|| anyhow!("No default account configured. Configure an account with 'protoncli account add' or specify a configured account with --from"))?;
-            (default, from_addr.clone())
-        }
-    } else {
-        // No --from specified, use default account for both
-        let default = config
-            .get_default_account()
-            .ok_or_else(|| anyhow!("No default account configured. Use --from to specify an account or set a default with 'protoncli account set-default'"))?;
-        (default, default.email.clone())
-    };
+    /// Shadow UID of the draft that was sent (`send --draft-id`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    draft_id: Option<i64>,
+    /// Shadow UID of the copy saved to the Sent folder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sent_id: Option<i64>,
+}
 
+/// Builder for the message described by `content`, sent from `from_email`
+pub fn compose_builder(from_email: &str, content: MessageContent) -> Result<EmailBuilder> {
     // Read body from file if specified
-    let body_text = if let Some(file_path) = body_file {
+    let body_text = if let Some(file_path) = content.body_file {
         fs::read_to_string(&file_path)
             .context(format!("Failed to read body file: {}", file_path))?
     } else {
-        body.unwrap_or_default()
+        content.body.unwrap_or_default()
     };
 
-    // Build email
-    let mut builder = EmailBuilder::new()
-        .from(&from_email)
-        .subject(subject.unwrap_or_else(|| "(No subject)".to_string()))
-        .body(body_text);
+    // HTML body: given directly, read from a file, or rendered from a markdown body
+    let html_body = match (content.html, content.html_file) {
+        (Some(html), _) => Some(html),
+        (None, Some(file_path)) => Some(
+            fs::read_to_string(&file_path)
+                .context(format!("Failed to read HTML file: {}", file_path))?,
+        ),
+        (None, None) if content.markdown => {
+            if body_text.trim().is_empty() {
+                return Err(anyhow!(
+                    "--markdown needs a --body or --body-file to render"
+                ));
+            }
+            Some(html::from_markdown(&body_text))
+        }
+        (None, None) => None,
+    };
+
+    let inline_images = content
+        .inline
+        .iter()
+        .map(|spec| InlineImage::parse(spec))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(html) = &html_body {
+        for image in &inline_images {
+            if !html.contains(&format!("cid:{}", image.content_id)) {
+                eprintln!(
+                    "Warning: inline image '{}' is not referenced as cid:{} in the HTML body",
+                    image.path, image.content_id
+                );
+            }
+        }
+    } else if !inline_images.is_empty() {
+        return Err(anyhow!(
+            "--inline needs an HTML body (--html, --html-file or --markdown)"
+        ));
+    }
+
+    // Build email; without a text body the HTML is rendered as the plain-text part
+    let mut builder = EmailBuilder::new().from(from_email).subject(
+        content
+            .subject
+            .unwrap_or_else(|| "(No subject)".to_string()),
+    );
+    if html_body.is_none() || !body_text.is_empty() {
+        builder = builder.body(body_text);
+    }
+    if let Some(html) = html_body {
+        builder = builder.html(html);
+    }
+    for image in inline_images {
+        builder = builder.inline_image(image);
+    }
 
     // Add recipients
-    for to_addr in to {
-        builder = builder.to(&to_addr)?;
+    for to_addr in &content.to {
+        builder = builder.to(to_addr)?;
     }
 
-    for cc_addr in cc {
-        builder = builder.cc(&cc_addr)?;
+    for cc_addr in &content.cc {
+        builder = builder.cc(cc_addr)?;
     }
 
-    for bcc_addr in bcc {
-        builder = builder.bcc(&bcc_addr)?;
+    for bcc_addr in &content.bcc {
+        builder = builder.bcc(bcc_addr)?;
     }
 
     // Add attachments
-    for attachment in attachments {
+    for attachment in content.attach {
         builder = builder.attach(attachment);
     }
 
+    Ok(builder)
+}
+
+pub async fn send_email(
+    account_email: Option<&str>,
+    content: MessageContent,
+    output_format: Option<&str>,
+) -> Result<()> {
+    if content.to.is_empty() {
+        return Err(anyhow!("At least one --to recipient is required"));
+    }
+    let config = Config::load()?;
+    let text_output = output_format.unwrap_or("text") != "json";
+
+    let (smtp_account, from_email) =
+        resolve_sender(&config, account_email, content.from.as_deref())?;
+    let subject = content
+        .subject
+        .clone()
+        .unwrap_or_else(|| "(No subject)".to_string());
+    let (to, cc, bcc) = (content.to.clone(), content.cc.clone(), content.bcc.clone());
+
+    let builder = compose_builder(&from_email, content)?;
+    warn_attachment_size(&config, &builder);
     let message = builder.build()?;
 
     // Connect to SMTP and send
-    println!("Connecting to SMTP server...");
+    if text_output {
+        println!("Connecting to SMTP server...");
+    }
     let mut smtp_client = SmtpClient::connect(smtp_account)?;
 
-    println!("Sending email...");
-    let response = smtp_client.send_message(message)?;
+    if text_output {
+        println!("Sending email...");
+    }
+    let (response, raw) = smtp_client.send_message(message)?;
+
+    if text_output {
+        println!("✓ Email sent successfully!");
+        println!("  Response: {:?}", response);
+    }
 
-    println!("✓ Email sent successfully!");
-    println!("  Response: {:?}", response);
+    // Keep a copy in the Sent folder
+    let sent_id = if config.preferences.save_sent {
+        match ImapClient::connect(smtp_account).await {
+            Ok(mut client) => save_to_sent(&mut client, &config, &smtp_account.email, &raw).await,
+            Err(e) => {
+                eprintln!("Warning: message sent but not saved to Sent: {:#}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if text_output {
+        if let Some(id) = sent_id {
+            println!("  Saved to {} (id {})", config.preferences.sent_folder, id);
+        }
+    } else {
+        json::print_json(&SendOutput {
+            account: smtp_account.email.clone(),
+            from: from_email,
+            subject,
+            to,
+            cc,
+            bcc,
+            draft_id: None,
+            sent_id,
+        })?;
+    }
 
     Ok(())
 }
+
+/// A draft from the Drafts folder, ready to go out over SMTP
+struct OutgoingDraft {
+    envelope: Envelope,
+    raw: Vec<u8>,
+    subject: String,
+    from: String,
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+}
+
+/// Prepare a stored draft for sending: the envelope comes from its From/To/Cc/Bcc headers,
+/// the Bcc header is removed and Date is set to the time of sending
+fn prepare_draft(raw: &[u8], date: DateTime<Utc>) -> Result<OutgoingDraft> {
+    let parsed = MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| anyhow!("Failed to parse the draft"))?;
+    let addresses = |header: Option<&mail_parser::Address>| -> Vec<String> {
+        header
+            .map(|addrs| {
+                addrs
+                    .iter()
+                    .filter_map(|a| a.address())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let from = addresses(parsed.from())
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Draft has no From address"))?;
+    let (to, cc, bcc) = (
+        addresses(parsed.to()),
+        addresses(parsed.cc()),
+        addresses(parsed.bcc()),
+    );
+
+    let recipients = to
+        .iter()
+        .chain(&cc)
+        .chain(&bcc)
+        .map(|addr| {
+            addr.parse::<Address>()
+                .context(format!("Invalid recipient address in draft: {}", addr))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if recipients.is_empty() {
+        return Err(anyhow!(
+            "Draft has no recipients; add To, Cc or Bcc before sending"
+        ));
+    }
+    let sender = from
+        .parse::<Address>()
+        .context(format!("Invalid From address in draft: {}", from))?;
+
+    Ok(OutgoingDraft {
+        envelope: Envelope::new(Some(sender), recipients)?,
+        raw: rewrite_draft_headers(raw, date),
+        subject: parsed.subject().unwrap_or("(No subject)").to_string(),
+        from,
+        to,
+        cc,
+        bcc,
+    })
+}
+
+/// Drop Bcc and replace Date in the header block, leaving everything else byte for byte
+fn rewrite_draft_headers(raw: &[u8], date: DateTime<Utc>) -> Vec<u8> {
+    let (header_len, newline) = match raw.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(pos) => (pos + 2, "\r\n"),
+        None => match raw.windows(2).position(|w| w == b"\n\n") {
+            Some(pos) => (pos + 1, "\n"),
+            None => (raw.len(), "\r\n"),
+        },
+    };
+    let date_header = format!("Date: {}{}", date.to_rfc2822(), newline);
+
+    let mut out = Vec::with_capacity(raw.len() + date_header.len());
+    let mut has_date = false;
+    let mut skipping = false;
+    for line in raw[..header_len].split_inclusive(|&b| b == b'\n') {
+        // Folded continuation lines belong to the previous header
+        if !line.starts_with(b" ") && !line.starts_with(b"\t") {
+            let name = line.split(|&b| b == b':').next().unwrap_or_default();
+            let is_date = name.eq_ignore_ascii_case(b"Date");
+            skipping = is_date || name.eq_ignore_ascii_case(b"Bcc");
+            if is_date {
+                out.extend_from_slice(date_header.as_bytes());
+                has_date = true;
+            }
+        }
+        if !skipping {
+            out.extend_from_slice(line);
+        }
+    }
+    if !has_date {
+        out.splice(0..0, date_header.bytes());
+    }
+    out.extend_from_slice(&raw[header_len..]);
+    out
+}
+
+/// Send a draft stored in the Drafts folder (`compose --save-draft`), then remove it from
+/// Drafts and save the sent copy as usual
+pub async fn send_draft(
+    account_email: Option<&str>,
+    draft_id: i64,
+    output_format: Option<&str>,
+) -> Result<()> {
+    validate_shadow_uids(&[draft_id])?;
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?;
+    let drafts_folder = config.preferences.drafts_folder.as_str();
+
+    let state = StateManager::new().await?;
+    let draft = state
+        .resolve_shadow_uids(&account.email, &[draft_id])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Message {} not found", draft_id))?;
+    if draft.folder != drafts_folder {
+        return Err(anyhow!(
+            "Message {} is in '{}', not in '{}'",
+            draft_id,
+            draft.folder,
+            drafts_folder
+        ));
+    }
+
+    let mut client = ImapClient::connect(account).await?;
+    let message = client
+        .fetch_message_by_uid(draft.imap_uid, drafts_folder, true)
+        .await?;
+    let raw = message
+        .raw_message
+        .ok_or_else(|| anyhow!("Failed to fetch draft {}", draft_id))?;
+    let outgoing = prepare_draft(&raw, Utc::now())?;
+
+    let mut smtp_client = SmtpClient::connect(account)?;
+    smtp_client.send_raw(&outgoing.envelope, &outgoing.raw)?;
+
+    // The message is out; from here on problems only warn
+    if let Err(e) = remove_draft(
+        &mut client,
+        &state,
+        &account.email,
+        drafts_folder,
+        draft.imap_uid,
+    )
+    .await
+    {
+        eprintln!(
+            "Warning: draft sent but not removed from '{}': {:#}",
+            drafts_folder, e
+        );
+    }
+    let sent_id = save_to_sent(&mut client, &config, &account.email, &outgoing.raw).await;
+
+    let output = SendOutput {
+        account: account.email.clone(),
+        from: outgoing.from,
+        subject: outgoing.subject,
+        to: outgoing.to,
+        cc: outgoing.cc,
+        bcc: outgoing.bcc,
+        draft_id: Some(draft_id),
+        sent_id,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            let recipients: Vec<&str> = output
+                .to
+                .iter()
+                .chain(&output.cc)
+                .chain(&output.bcc)
+                .map(String::as_str)
+                .collect();
+            println!("✓ Draft {} sent to {}", draft_id, recipients.join(", "));
+            println!("  Subject: {}", output.subject);
+            if let Some(id) = sent_id {
+                println!("  Saved to {} (id {})", config.preferences.sent_folder, id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete a sent draft from the Drafts folder and the local cache
+async fn remove_draft(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    folder: &str,
+    uid: u32,
+) -> Result<()> {
+    let status = client.select_folder(folder).await?;
+    client.mark_messages_deleted(&[uid]).await?;
+    client.expunge_uids(&[uid]).await?;
+    if let Some(uid_validity) = status.uid_validity {
+        state
+            .remove_cached_uids(account, folder, uid_validity, &[uid])
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(builder: EmailBuilder) -> String {
+        let message = builder
+            .from("me@example.com")
+            .to("you@example.com")
+            .unwrap()
+            .subject("Report".to_string())
+            .build()
+            .unwrap();
+        String::from_utf8(message.formatted()).unwrap()
+    }
+
+    #[test]
+    fn test_attachment_spec_type_override() {
+        assert_eq!(
+            split_attachment_spec("report.bin:application/pdf"),
+            ("report.bin", Some("application/pdf"))
+        );
+        assert_eq!(split_attachment_spec("report.pdf"), ("report.pdf", None));
+        assert_eq!(
+            split_attachment_spec("C:\\Reports\\q3.pdf"),
+            ("C:\\Reports\\q3.pdf", None)
+        );
+        assert_eq!(split_attachment_spec("notes:draft"), ("notes:draft", None));
+    }
+
+    #[test]
+    fn test_attachment_content_types_and_names() {
+        let dir = std::env::temp_dir().join(format!("protoncli-attach-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pdf = dir.join("Résumé.pdf");
+        fs::write(&pdf, b"%PDF-1.7\n").unwrap();
+        let blob = dir.join("data.bin");
+        fs::write(&blob, b"\0\x01\x02").unwrap();
+
+        let raw = formatted(
+            EmailBuilder::new()
+                .body("see attached".to_string())
+                .attach(pdf.to_string_lossy().into_owned())
+                .attach(format!("{}:image/png", blob.display())),
+        );
+        let bad = EmailBuilder::new()
+            .from("me@example.com")
+            .to("you@example.com")
+            .unwrap()
+            .attach(format!("{}:image/png garbage", blob.display()))
+            .build();
+        fs::remove_dir_all(&dir).ok();
+
+        assert!(raw.contains("Content-Type: application/pdf; name*=utf-8''R%C3%A9sum%C3%A9.pdf"));
+        assert!(raw.contains("filename*0*=utf-8''R%C3%A9sum%C3%A9.pdf"));
+        assert!(raw.contains("Content-Type: image/png; name=\"data.bin\""));
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn test_inline_image_spec() {
+        let image = InlineImage::parse("chart=out/chart.png").unwrap();
+        assert_eq!(image.content_id, "chart");
+        assert_eq!(image.path, "out/chart.png");
+
+        let image = InlineImage::parse("out/logo.png").unwrap();
+        assert_eq!(image.content_id, "logo.png");
+        assert_eq!(image.path, "out/logo.png");
+    }
+
+    #[test]
+    fn test_html_body_is_multipart_alternative() {
+        let raw = formatted(EmailBuilder::new().html("<p>Fish &amp; chips</p>".to_string()));
+        assert!(raw.contains("Content-Type: multipart/alternative"));
+        assert!(raw.contains("Content-Type: text/plain"));
+        assert!(raw.contains("Content-Type: text/html"));
+        assert!(raw.contains("Fish & chips"));
+
+        let raw = formatted(EmailBuilder::new().body("plain".to_string()));
+        assert!(!raw.contains("multipart"));
+    }
+
+    #[test]
+    fn test_draft_keeps_bcc_and_allows_no_recipients() {
+        let draft = EmailBuilder::new()
+            .from("me@example.com")
+            .bcc("hidden@example.com")
+            .unwrap()
+            .body("later".to_string())
+            .draft()
+            .build()
+            .unwrap();
+        let raw = String::from_utf8(draft.formatted()).unwrap();
+        assert!(raw.contains("Bcc: hidden@example.com"));
+
+        let empty = EmailBuilder::new()
+            .from("me@example.com")
+            .body("no one yet".to_string());
+        assert!(empty.build().is_err());
+        let empty = EmailBuilder::new()
+            .from("me@example.com")
+            .body("no one yet".to_string())
+            .draft();
+        assert!(empty.build().is_ok());
+    }
+
+    #[test]
+    fn test_prepare_draft_for_sending() {
+        let raw = b"From: Me <me@example.com>\r\n\
+To: you@example.com\r\n\
+Bcc: hidden@example.com,\r\n \
+ other@example.com\r\n\
+Subject: Later\r\n\
+Date: Mon, 1 Jan 2024 09:00:00 +0000\r\n\
+\r\n\
+Bcc: this line is body text\r\n";
+        let date = DateTime::parse_from_rfc3339("2026-10-17T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let draft = prepare_draft(raw, date).unwrap();
+        let sent = String::from_utf8(draft.raw).unwrap();
+
+        assert_eq!(
+            sent,
+            "From: Me <me@example.com>\r\n\
+To: you@example.com\r\n\
+Subject: Later\r\n\
+Date: Sat, 17 Oct 2026 12:00:00 +0000\r\n\
+\r\n\
+Bcc: this line is body text\r\n"
+        );
+        assert_eq!(draft.from, "me@example.com");
+        assert_eq!(draft.bcc, vec!["hidden@example.com", "other@example.com"]);
+        assert_eq!(draft.envelope.to().len(), 3);
+
+        let no_recipients = b"From: me@example.com\r\nSubject: x\r\n\r\nbody";
+        assert!(prepare_draft(no_recipients, date).is_err());
+    }
+
+    #[test]
+    fn test_message_id_uses_sender_domain() {
+        let first = formatted(EmailBuilder::new().body("one".to_string()));
+        let second = formatted(EmailBuilder::new().body("two".to_string()));
+        let message_id = |raw: &str| {
+            raw.lines()
+                .find_map(|line| line.strip_prefix("Message-ID: "))
+                .map(String::from)
+        };
+        let id = message_id(&first).unwrap();
+        assert!(id.starts_with('<') && id.ends_with("@example.com>"));
+        assert_ne!(message_id(&second), Some(id));
+    }
+
+    #[test]
+    fn test_inline_images_are_related_to_the_html_part() {
+        let path =
+            std::env::temp_dir().join(format!("protoncli-inline-{}.png", std::process::id()));
+        fs::write(&path, b"\x89PNG\r\n\x1a\n").unwrap();
+
+        let raw = formatted(
+            EmailBuilder::new()
+                .html("<img src=\"cid:chart\">".to_string())
+                .inline_image(InlineImage {
+                    content_id: "chart".to_string(),
+                    path: path.to_string_lossy().into_owned(),
+                })
+                .attach(path.to_string_lossy().into_owned()),
+        );
+        fs::remove_file(&path).ok();
+
+        let alternative = raw.find("multipart/alternative").unwrap();
+        let related = raw.find("multipart/related").unwrap();
+        assert!(raw.find("multipart/mixed").unwrap() < alternative);
+        assert!(alternative < related);
+        assert!(raw.contains("Content-ID: <chart>"));
+        assert!(raw.contains("Content-Type: image/png"));
+        assert!(raw.contains("Content-Disposition: inline"));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::core::cache::{self, FolderSyncResult};
+use crate::core::imap::ImapClient;
+use crate::core::state::StateManager;
+use crate::models::config::Config;
+use crate::output::json;
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct SyncOutput {
+    account: String,
+    folders: Vec<FolderSyncResult>,
+}
+
+/// Incrementally refresh the local message cache for one or more folders
+pub async fn sync(
+    account_email: Option<&str>,
+    folders: Vec<String>,
+    headers_only: bool,
+    output_format: Option<&str>,
+) -> Result<()> {
+    let config = Config::load()?;
+    cache::ensure_enabled(&config)?;
+    let account = config.resolve_account(account_email)?;
+
+    let folders = if folders.is_empty() {
+        vec!["INBOX".to_string()]
+    } else {
+        folders
+    };
+
+    let state = StateManager::new().await?;
+    let mut client = ImapClient::connect(account).await?;
+
+    let mut results = Vec::new();
+    for folder in &folders {
+        results.push(
+            cache::sync_folder(&mut client, &state, &account.email, folder, !headers_only).await?,
+        );
+    }
+
+    let output = SyncOutput {
+        account: account.email.clone(),
+        folders: results,
+    };
+
+    match output_format.unwrap_or("text") {
+        "json" => json::print_json(&output)?,
+        _ => {
+            for result in &output.folders {
+                println!(
+                    "✓ {}: {} new, {} bodies fetched, {} flag change(s), {} removed ({} cached)",
+                    result.folder,
+                    result.added,
+                    result.bodies_fetched,
+                    result.flags_updated,
+                    result.removed,
+                    result.total.saturating_sub(result.skipped)
+                );
+                if result.invalidated > 0 || result.remapped > 0 {
+                    println!(
+                        "  UIDVALIDITY changed; dropped {} stale cache entries, re-mapped {} message ID(s)",
+                        result.invalidated, result.remapped
+                    );
+                }
+                if result.flags_skipped > 0 {
+                    println!(
+                        "  Skipped {} unreadable flag row(s); they are checked again next sync",
+                        result.flags_skipped
+                    );
+                }
+                if result.skipped > 0 {
+                    println!(
+                        "  Skipped {} message(s) the server failed to return",
+                        result.skipped
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use crate::core::cache;
+use crate::core::imap::ImapClient;
+use crate::core::state::{validate_shadow_uids, StateManager};
+use crate::core::thread;
+use crate::models::config::Config;
+use crate::models::message::Message;
+use crate::output::{json, markdown};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Rounds of "search for messages mentioning the IDs found so far"
+const MAX_SEARCH_ROUNDS: usize = 4;
+
+#[derive(Serialize)]
+struct ThreadMessage {
+    depth: usize,
+    #[serde(flatten)]
+    message: Message,
+}
+
+#[derive(Serialize)]
+struct ThreadOutput {
+    account: String,
+    id: i64,
+    subject: Option<String>,
+    folders: Vec<String>,
+    count: usize,
+    messages: Vec<ThreadMessage>,
+}
+
+/// Message-IDs a message contributes to a thread search
+fn thread_ids(message: &Message) -> impl Iterator<Item = &String> {
+    message
+        .message_id
+        .iter()
+        .chain(message.in_reply_to.iter())
+        .chain(message.references.iter())
+}
+
+/// Show the whole conversation a message belongs to, across INBOX, Sent and `extra_folders`
+pub async fn show_thread(
+    account_email: Option<&str>,
+    shadow_uid: i64,
+    extra_folders: Vec<String>,
+    output_format: Option<&str>,
+) -> Result<()> {
+    validate_shadow_uids(&[shadow_uid])?;
+
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?;
+    let state = StateManager::new().await?;
+
+    let resolved = state
+        .resolve_shadow_uids(&account.email, &[shadow_uid])
+        .await?;
+    let seed = resolved
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Message {} not found", shadow_uid))?;
+
+    let mut folders = vec![seed.folder.clone()];
+    for folder in ["INBOX".to_string(), "Sent".to_string()]
+        .into_iter()
+        .chain(extra_folders)
+    {
+        if !folders.contains(&folder) {
+            folders.push(folder);
+        }
+    }
+
+    let mut client = ImapClient::connect(account).await?;
+
+    // Skip folders this account doesn't have (e.g. no "Sent" on some servers)
+    let mut existing = Vec::new();
+    for folder in folders {
+        if client.folder_exists(&folder).await? {
+            existing.push(folder);
+        }
+    }
+    let folders = existing;
+
+    // Start from the requested message's own headers
+    let (_, remap) =
+        cache::select_tracked(&mut client, &state, &account.email, &seed.folder).await?;
+    let seed_uid = if remap.is_some() {
+        state
+            .resolve_shadow_uids(&account.email, &[shadow_uid])
+            .await?
+            .first()
+            .map_or(0, |m| m.imap_uid)
+    } else {
+        seed.imap_uid
+    };
+    let (seed_messages, _) = client.fetch_messages_by_uids(&[seed_uid], false).await?;
+    let seed_message = seed_messages
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Message {} not found in {}", shadow_uid, seed.folder))?;
+
+    let mut known_ids: HashSet<String> = thread_ids(&seed_message).cloned().collect();
+    let mut pending: Vec<String> = known_ids.iter().cloned().collect();
+    let mut seen_uids: HashSet<(String, u32)> = HashSet::new();
+    let mut messages: Vec<Message> = Vec::new();
+
+    // Follow the conversation: each round searches for messages mentioning newly found IDs
+    for _ in 0..MAX_SEARCH_ROUNDS {
+        if pending.is_empty() {
+            break;
+        }
+        let mut found = Vec::new();
+
+        for folder in &folders {
+            cache::select_tracked(&mut client, &state, &account.email, folder).await?;
+            let uids: Vec<u32> = client
+                .search_related(&pending)
+                .await?
+                .into_iter()
+                .filter(|&uid| seen_uids.insert((folder.clone(), uid)))
+                .collect();
+            if uids.is_empty() {
+                continue;
+            }
+
+            let (fetched, _) = client.fetch_for_cache(&uids, true).await?;
+            for (mut message, _) in fetched {
+                message.folder = Some(folder.clone());
+                found.push(message);
+            }
+        }
+
+        pending.clear();
+        for message in &found {
+            for id in thread_ids(message) {
+                if known_ids.insert(id.clone()) {
+                    pending.push(id.clone());
+                }
+            }
+        }
+        messages.extend(found);
+    }
+
+    // Same message in several folders (e.g. INBOX and All Mail): keep the first
+    let mut seen_message_ids = HashSet::new();
+    messages.retain(|m| match m.message_id {
+        Some(ref id) => seen_message_ids.insert(id.clone()),
+        None => true,
+    });
+
+    for message in &mut messages {
+        if let (Some(msg_id), Some(folder)) = (message.message_id.clone(), message.folder.clone()) {
+            let shadow_uid = state
+                .get_or_create_shadow_uid(
+                    &account.email,
+                    &folder,
+                    message.uid,
+                    Some(&msg_id),
+                    message.subject.as_deref(),
+                    message.from.as_ref().map(|f| f.address.as_str()),
+                    message.date,
+                )
+                .await?;
+            message.shadow_uid = Some(shadow_uid);
+        }
+    }
+
+    let seed_index = messages
+        .iter()
+        .position(|m| m.message_id.is_some() && m.message_id == seed_message.message_id)
+        .ok_or_else(|| {
+            anyhow!(
+                "Message {} could not be found again by Message-ID",
+                shadow_uid
+            )
+        })?;
+    let conversation = thread::build_threads(&messages)
+        .into_iter()
+        .find(|c| c.contains(seed_index))
+        .ok_or_else(|| anyhow!("Message {} is not part of any conversation", shadow_uid))?;
+
+    let ordered: Vec<(usize, &Message)> = conversation
+        .entries
+        .iter()
+        .map(|e| (e.depth, &messages[e.index]))
+        .collect();
+    let subject = ordered.first().and_then(|(_, m)| m.subject.clone());
+
+    match output_format.unwrap_or("markdown") {
+        "json" => {
+            let output = ThreadOutput {
+                account: account.email.clone(),
+                id: shadow_uid,
+                subject,
+                folders,
+                count: ordered.len(),
+                messages: ordered
+                    .into_iter()
+                    .map(|(depth, message)| ThreadMessage {
+                        depth,
+                        message: message.clone(),
+                    })
+                    .collect(),
+            };
+            json::print_json(&output)?;
+        }
+        "markdown" => markdown::print_thread(subject.as_deref(), &ordered),
+        _ => return Err(anyhow!("Invalid output format")),
+    }
+
+    Ok(())
+}
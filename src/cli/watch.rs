@@ -0,0 +1,576 @@
+use crate::core::imap::{uid_set, ImapClient, MailboxChanges};
+use crate::core::state::StateManager;
+use crate::models::account::Account;
+use crate::models::config::Config;
+use crate::models::message::{Message, MessageFlags};
+use crate::output::json;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Re-issue IDLE well before the 29 minute limit from RFC 2177
+const IDLE_TIMEOUT: Duration = Duration::from_secs(25 * 60);
+
+/// Initial delay before reconnecting; doubles up to MAX_RECONNECT_DELAY
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// A change detected in a watched folder
+#[derive(Debug)]
+enum WatchEvent {
+    New {
+        folder: String,
+        message: Box<Message>,
+    },
+    Expunged {
+        folder: String,
+        uid: u32,
+    },
+    FlagsChanged {
+        folder: String,
+        uid: u32,
+        previous: MessageFlags,
+        current: MessageFlags,
+    },
+}
+
+/// One NDJSON line on stdout
+#[derive(Serialize)]
+struct EventRecord {
+    event: &'static str,
+    account: String,
+    folder: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i64>,
+    uid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subject: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flags: Option<Vec<&'static str>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    added: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    removed: Vec<&'static str>,
+    timestamp: String,
+}
+
+/// Flags snapshot of a folder, tied to the UIDVALIDITY it was taken under
+struct Snapshot {
+    uid_validity: Option<u32>,
+    flags: HashMap<u32, MessageFlags>,
+}
+
+/// Differences between two flag snapshots
+#[derive(Debug, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub new: Vec<u32>,
+    pub expunged: Vec<u32>,
+    pub changed: Vec<(u32, MessageFlags, MessageFlags)>,
+}
+
+/// Compare two snapshots; results are sorted by UID
+pub fn diff_snapshots(
+    previous: &HashMap<u32, MessageFlags>,
+    current: &HashMap<u32, MessageFlags>,
+) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+
+    for (&uid, &flags) in current {
+        match previous.get(&uid) {
+            None => diff.new.push(uid),
+            Some(&old) if old != flags => diff.changed.push((uid, old, flags)),
+            Some(_) => {}
+        }
+    }
+    for &uid in previous.keys() {
+        if !current.contains_key(&uid) {
+            diff.expunged.push(uid);
+        }
+    }
+
+    diff.new.sort_unstable();
+    diff.expunged.sort_unstable();
+    diff.changed.sort_unstable_by_key(|(uid, _, _)| *uid);
+    diff
+}
+
+/// Watch folders for changes and print one NDJSON `event` record per change
+pub async fn watch(
+    account_email: Option<&str>,
+    folders: Vec<String>,
+    poll_interval: u64,
+    output_format: Option<&str>,
+) -> Result<()> {
+    if let Some(other) = output_format.filter(|f| *f != "ndjson") {
+        return Err(anyhow!(
+            "Unsupported output format '{}' for watch (only ndjson)",
+            other
+        ));
+    }
+    let config = Config::load()?;
+    let account = config.resolve_account(account_email)?.clone();
+
+    let folders = if folders.is_empty() {
+        vec!["INBOX".to_string()]
+    } else {
+        folders
+    };
+    let poll_interval = Duration::from_secs(poll_interval.max(1));
+
+    let state = StateManager::new().await?;
+
+    // One connection per folder: IDLE only reports changes for the selected mailbox
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    for folder in &folders {
+        tokio::spawn(watch_folder(
+            account.clone(),
+            folder.clone(),
+            poll_interval,
+            tx.clone(),
+        ));
+    }
+    drop(tx);
+
+    eprintln!(
+        "Watching {} for {}. Press Ctrl-C to stop.",
+        folders.join(", "),
+        account.email
+    );
+
+    while let Some(event) = rx.recv().await {
+        let record = match build_record(&state, &account.email, event).await {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!(
+                    "Warning: skipping an event that could not be recorded: {:#}",
+                    e
+                );
+                continue;
+            }
+        };
+        json::print_ndjson_record("event", &record)?;
+    }
+
+    Err(anyhow!("All folder watchers stopped"))
+}
+
+/// Turn an event into an output record, assigning or looking up shadow UIDs
+async fn build_record(
+    state: &StateManager,
+    account: &str,
+    event: WatchEvent,
+) -> Result<EventRecord> {
+    let timestamp = Utc::now().to_rfc3339();
+
+    let record = match event {
+        WatchEvent::New { folder, message } => {
+            let id = match message.message_id {
+                Some(ref msg_id) => Some(
+                    state
+                        .get_or_create_shadow_uid(
+                            account,
+                            &folder,
+                            message.uid,
+                            Some(msg_id),
+                            message.subject.as_deref(),
+                            message.from.as_ref().map(|f| f.address.as_str()),
+                            message.date,
+                        )
+                        .await?,
+                ),
+                None => None,
+            };
+            EventRecord {
+                event: "new",
+                account: account.to_string(),
+                id,
+                uid: message.uid,
+                message_id: message.message_id.clone(),
+                subject: message.subject.clone(),
+                from: message.from.as_ref().map(|f| f.format()),
+                date: message.date.map(|d| d.to_rfc3339()),
+                flags: Some(message.flags.names()),
+                added: vec![],
+                removed: vec![],
+                folder,
+                timestamp,
+            }
+        }
+        WatchEvent::Expunged { folder, uid } => EventRecord {
+            event: "expunged",
+            account: account.to_string(),
+            id: state
+                .find_shadow_uid_by_location(account, &folder, uid)
+                .await?,
+            uid,
+            message_id: None,
+            subject: None,
+            from: None,
+            date: None,
+            flags: None,
+            added: vec![],
+            removed: vec![],
+            folder,
+            timestamp,
+        },
+        WatchEvent::FlagsChanged {
+            folder,
+            uid,
+            previous,
+            current,
+        } => {
+            let before = previous.names();
+            let after = current.names();
+            EventRecord {
+                event: "flags",
+                account: account.to_string(),
+                id: state
+                    .find_shadow_uid_by_location(account, &folder, uid)
+                    .await?,
+                uid,
+                message_id: None,
+                subject: None,
+                from: None,
+                date: None,
+                added: after
+                    .iter()
+                    .filter(|f| !before.contains(f))
+                    .copied()
+                    .collect(),
+                removed: before
+                    .iter()
+                    .filter(|f| !after.contains(f))
+                    .copied()
+                    .collect(),
+                flags: Some(after),
+                folder,
+                timestamp,
+            }
+        }
+    };
+
+    Ok(record)
+}
+
+/// Watch one folder forever, reconnecting with backoff (e.g. after a Bridge restart).
+/// Changes that happen while disconnected are reported after reconnecting.
+async fn watch_folder(
+    account: Account,
+    folder: String,
+    poll_interval: Duration,
+    tx: mpsc::UnboundedSender<WatchEvent>,
+) {
+    let mut snapshot: Option<Snapshot> = None;
+    let mut delay = INITIAL_RECONNECT_DELAY;
+
+    loop {
+        match run_folder_session(
+            &account,
+            &folder,
+            poll_interval,
+            &mut snapshot,
+            &mut delay,
+            &tx,
+        )
+        .await
+        {
+            // Receiver gone: the command is shutting down
+            Ok(()) => return,
+            Err(e) => {
+                eprintln!(
+                    "Watch of {} interrupted: {:#}. Reconnecting in {}s...",
+                    folder,
+                    e,
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+/// One connection's worth of watching. Returns Ok only when the event receiver is closed.
+async fn run_folder_session(
+    account: &Account,
+    folder: &str,
+    poll_interval: Duration,
+    snapshot: &mut Option<Snapshot>,
+    delay: &mut Duration,
+    tx: &mpsc::UnboundedSender<WatchEvent>,
+) -> Result<()> {
+    let mut client = ImapClient::connect(account).await?;
+    let use_idle = client.has_capability("IDLE");
+    let status = client.select_folder(folder).await?;
+
+    // Connected: reset backoff for the next failure
+    *delay = INITIAL_RECONNECT_DELAY;
+
+    if snapshot
+        .as_ref()
+        .is_some_and(|s| s.uid_validity != status.uid_validity)
+    {
+        eprintln!(
+            "UIDVALIDITY of {} changed; resetting watch state without emitting events",
+            folder
+        );
+        *snapshot = None;
+    }
+
+    if use_idle {
+        eprintln!(
+            "{}: {} message(s), waiting with IDLE",
+            folder, status.exists
+        );
+    } else {
+        eprintln!(
+            "{}: {} message(s), IDLE not supported, polling every {}s",
+            folder,
+            status.exists,
+            poll_interval.as_secs()
+        );
+    }
+
+    // Read every flag once per connection; this also catches up on changes made while
+    // disconnected. From then on only what the server reports as changed is fetched.
+    let mut current = if status.exists == 0 {
+        HashMap::new()
+    } else {
+        let fetched = client.fetch_flags("1:*", None).await?;
+        warn_skipped(folder, fetched.skipped);
+        fetched.flags
+    };
+    let mut modseq = status
+        .highest_modseq
+        .filter(|_| client.supports_condstore());
+    client.take_changes();
+
+    loop {
+        match snapshot {
+            // First look at the folder: remember its state, existing mail is not "new"
+            None => {}
+            Some(previous) => {
+                let diff = diff_snapshots(&previous.flags, &current);
+                if !emit_events(&mut client, folder, diff, tx).await? {
+                    return Ok(());
+                }
+            }
+        }
+        *snapshot = Some(Snapshot {
+            uid_validity: status.uid_validity,
+            flags: current.clone(),
+        });
+
+        let pending = client.take_changes();
+        let changes = if has_changes(&pending, current.len()) {
+            pending
+        } else if use_idle {
+            let (idle_client, changes) = client.idle_wait(IDLE_TIMEOUT).await?;
+            client = idle_client;
+            changes
+        } else {
+            tokio::time::sleep(poll_interval).await;
+            client.noop().await?;
+            client.take_changes()
+        };
+
+        refresh_flags(&mut client, folder, &mut current, &mut modseq, changes).await?;
+    }
+}
+
+/// Whether reported changes need a refresh of a folder holding `known` messages
+fn has_changes(changes: &MailboxChanges, known: usize) -> bool {
+    changes.expunged > 0
+        || !changes.fetched.is_empty()
+        || changes.exists.is_some_and(|count| count as usize != known)
+}
+
+/// Bring `flags` up to date from the changes the server reported, fetching only what
+/// changed: messages above the highest known UID, the UID list after an EXPUNGE, and
+/// flags changed since `modseq` (CONDSTORE) or at the reported sequence numbers.
+async fn refresh_flags(
+    client: &mut ImapClient,
+    folder: &str,
+    flags: &mut HashMap<u32, MessageFlags>,
+    modseq: &mut Option<u64>,
+    changes: MailboxChanges,
+) -> Result<()> {
+    let count = changes
+        .exists
+        .unwrap_or_else(|| (flags.len().saturating_sub(changes.expunged)) as u32);
+    if count == 0 {
+        flags.clear();
+        return Ok(());
+    }
+
+    // New mail. "N:*" always matches the last message, even when its UID is below N.
+    let next_uid = flags.keys().max().map_or(1, |uid| uid + 1);
+    let fetched = client.fetch_flags(&format!("{}:*", next_uid), None).await?;
+    warn_skipped(folder, fetched.skipped);
+    flags.extend(
+        fetched
+            .flags
+            .into_iter()
+            .filter(|(uid, _)| *uid >= next_uid),
+    );
+
+    // Expunged mail (or notifications we missed): EXPUNGE only names sequence numbers
+    if changes.expunged > 0 || flags.len() != count as usize {
+        let uids = client.fetch_all_uids().await?;
+        flags.retain(|uid, _| uids.contains(uid));
+        let mut missing: Vec<u32> = uids
+            .into_iter()
+            .filter(|uid| !flags.contains_key(uid))
+            .collect();
+        if !missing.is_empty() {
+            missing.sort_unstable();
+            let fetched = client.fetch_flags(&uid_set(&missing), None).await?;
+            warn_skipped(folder, fetched.skipped);
+            flags.extend(fetched.flags);
+        }
+    }
+
+    // Flag changes
+    let changed = match *modseq {
+        Some(since) => {
+            let fetched = client.fetch_flags("1:*", Some(since)).await?;
+            *modseq = fetched.highest_modseq.map(|m| m.max(since)).or(*modseq);
+            fetched
+        }
+        None if changes.fetched.is_empty() => return Ok(()),
+        // Sequence numbers shift with every EXPUNGE, so they no longer say which messages
+        None if changes.expunged > 0 => client.fetch_flags("1:*", None).await?,
+        None => client.fetch_flags_by_seq(&changes.fetched).await?,
+    };
+    warn_skipped(folder, changed.skipped);
+    for (uid, current) in changed.flags {
+        if let Some(known) = flags.get_mut(&uid) {
+            *known = current;
+        }
+    }
+
+    Ok(())
+}
+
+fn warn_skipped(folder: &str, skipped: usize) {
+    if skipped > 0 {
+        eprintln!(
+            "Warning: skipped {} message(s) in {} that could not be parsed",
+            skipped, folder
+        );
+    }
+}
+
+/// Send events for a snapshot diff. Returns false if the receiver is closed.
+async fn emit_events(
+    client: &mut ImapClient,
+    folder: &str,
+    diff: SnapshotDiff,
+    tx: &mpsc::UnboundedSender<WatchEvent>,
+) -> Result<bool> {
+    let mut events = Vec::new();
+
+    if !diff.new.is_empty() {
+        let (mut messages, skipped) = client.fetch_messages_by_uids(&diff.new, false).await?;
+        warn_skipped(folder, skipped);
+        messages.sort_by_key(|m| m.uid);
+        for mut message in messages {
+            message.folder = Some(folder.to_string());
+            events.push(WatchEvent::New {
+                folder: folder.to_string(),
+                message: Box::new(message),
+            });
+        }
+    }
+
+    for uid in diff.expunged {
+        events.push(WatchEvent::Expunged {
+            folder: folder.to_string(),
+            uid,
+        });
+    }
+
+    for (uid, previous, current) in diff.changed {
+        events.push(WatchEvent::FlagsChanged {
+            folder: folder.to_string(),
+            uid,
+            previous,
+            current,
+        });
+    }
+
+    for event in events {
+        if tx.send(event).is_err() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(seen: bool, flagged: bool) -> MessageFlags {
+        MessageFlags {
+            seen,
+            answered: false,
+            flagged,
+            deleted: false,
+            draft: false,
+        }
+    }
+
+    #[test]
+    fn test_diff_detects_new_expunged_and_changed() {
+        let previous = HashMap::from([(1, flags(true, false)), (2, flags(false, false))]);
+        let current = HashMap::from([
+            (2, flags(true, true)),
+            (4, flags(false, false)),
+            (3, flags(false, false)),
+        ]);
+
+        let diff = diff_snapshots(&previous, &current);
+        assert_eq!(diff.new, vec![3, 4]);
+        assert_eq!(diff.expunged, vec![1]);
+        assert_eq!(
+            diff.changed,
+            vec![(2, flags(false, false), flags(true, true))]
+        );
+    }
+
+    #[test]
+    fn test_has_changes() {
+        let quiet = MailboxChanges {
+            exists: Some(3),
+            ..MailboxChanges::default()
+        };
+        assert!(!has_changes(&MailboxChanges::default(), 3));
+        assert!(!has_changes(&quiet, 3));
+        assert!(has_changes(&quiet, 2));
+        let flagged = MailboxChanges {
+            fetched: vec![1],
+            ..MailboxChanges::default()
+        };
+        assert!(has_changes(&flagged, 3));
+    }
+
+    #[test]
+    fn test_diff_unchanged_is_empty() {
+        let snapshot = HashMap::from([(1, flags(true, false))]);
+        assert_eq!(
+            diff_snapshots(&snapshot, &snapshot),
+            SnapshotDiff::default()
+        );
+    }
+}
@@ -0,0 +1,626 @@
+use crate::core::imap::{FlagsFetch, FolderStatus, ImapClient};
+use crate::core::state::{IndexedText, StateManager};
+use crate::models::config::Config;
+use crate::models::filter::MessageFilter;
+use crate::models::message::{Message, MessageFlags};
+use crate::output::html;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Length of the body preview shown in message lists
+const PREVIEW_CHARS: usize = 200;
+
+/// Result of syncing one folder into the cache
+#[derive(Debug, Default, Serialize)]
+pub struct FolderSyncResult {
+    pub folder: String,
+    pub uid_validity: u32,
+    /// Messages cached for the first time
+    pub added: usize,
+    /// Already cached headers whose body was fetched
+    pub bodies_fetched: usize,
+    pub flags_updated: usize,
+    /// Messages expunged on the server and dropped from the cache
+    pub removed: usize,
+    /// Stale entries dropped because the folder's UIDVALIDITY changed
+    pub invalidated: usize,
+    /// Shadow UIDs re-pointed by Message-ID because UIDVALIDITY changed
+    pub remapped: usize,
+    /// Whether flags were refreshed with CONDSTORE CHANGEDSINCE instead of a full fetch
+    pub incremental: bool,
+    /// Messages the server failed to return or that could not be parsed
+    pub skipped: usize,
+    /// Flag rows that could not be parsed; the next sync checks those messages again
+    pub flags_skipped: usize,
+    /// Messages in the folder after the sync
+    pub total: usize,
+}
+
+/// What a sync has to do to bring the cache in line with the server
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncPlan {
+    pub to_fetch: Vec<u32>,
+    pub flag_updates: Vec<(u32, MessageFlags)>,
+    pub removed: Vec<u32>,
+}
+
+/// Shadow UIDs re-pointed after a folder's UIDVALIDITY changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UidRemap {
+    pub previous_uid_validity: u32,
+    /// Messages found again by Message-ID
+    pub remapped: usize,
+    /// Messages no longer in the folder (UID set to 0 until seen again)
+    pub orphaned: usize,
+    /// Cache entries dropped with the old UIDVALIDITY
+    pub invalidated: usize,
+}
+
+/// Select a folder and compare its UIDVALIDITY with the one recorded in the state DB.
+/// When it changed, UIDs stored for the folder name other messages now: shadow UIDs are
+/// re-mapped by Message-ID and cached entries from the old UIDVALIDITY are dropped.
+pub async fn select_tracked(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    folder: &str,
+) -> Result<(FolderStatus, Option<UidRemap>)> {
+    let status = client.select_folder(folder).await?;
+    let previous = state
+        .folder_state(account, folder)
+        .await?
+        .and_then(|f| f.uid_validity);
+
+    let remap = match (previous, status.uid_validity) {
+        (Some(previous_uid_validity), Some(current)) if previous_uid_validity != current => {
+            let uids_by_message_id = if status.exists == 0 {
+                HashMap::new()
+            } else {
+                client.fetch_message_id_map().await?
+            };
+            let (remapped, orphaned) = state
+                .remap_folder_uids(account, folder, &uids_by_message_id)
+                .await?;
+            let invalidated = state
+                .invalidate_cache_folder(account, folder, current)
+                .await?;
+            eprintln!(
+                "Note: UIDVALIDITY of '{}' changed ({} -> {}); re-mapped {} message(s), {} no longer found",
+                folder, previous_uid_validity, current, remapped, orphaned
+            );
+            Some(UidRemap {
+                previous_uid_validity,
+                remapped,
+                orphaned,
+                invalidated,
+            })
+        }
+        _ => None,
+    };
+
+    state.record_folder_status(account, folder, &status).await?;
+    Ok((status, remap))
+}
+
+/// Plan a CONDSTORE sync: `server_uids` is every UID in the folder and `changed` the
+/// flags of messages modified since the last sync. Unchanged cached entries are kept
+/// without comparing their flags. Results are sorted by UID.
+pub fn plan_incremental_sync(
+    cached: &HashMap<u32, (MessageFlags, bool)>,
+    server_uids: &HashSet<u32>,
+    changed: &HashMap<u32, MessageFlags>,
+    bodies: bool,
+) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+
+    for &uid in server_uids {
+        match cached.get(&uid) {
+            None => plan.to_fetch.push(uid),
+            Some(&(_, has_body)) if bodies && !has_body => plan.to_fetch.push(uid),
+            Some(&(cached_flags, _)) => match changed.get(&uid) {
+                Some(&flags) if flags != cached_flags => plan.flag_updates.push((uid, flags)),
+                _ => {}
+            },
+        }
+    }
+    for &uid in cached.keys() {
+        if !server_uids.contains(&uid) {
+            plan.removed.push(uid);
+        }
+    }
+
+    plan.to_fetch.sort_unstable();
+    plan.flag_updates.sort_unstable_by_key(|(uid, _)| *uid);
+    plan.removed.sort_unstable();
+    plan
+}
+
+/// Compare cached entries (flags, body cached?) with the server's UIDs and flags.
+/// Results are sorted by UID.
+pub fn plan_sync(
+    cached: &HashMap<u32, (MessageFlags, bool)>,
+    server: &HashMap<u32, MessageFlags>,
+    bodies: bool,
+) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+
+    for (&uid, &flags) in server {
+        match cached.get(&uid) {
+            None => plan.to_fetch.push(uid),
+            Some(&(_, has_body)) if bodies && !has_body => plan.to_fetch.push(uid),
+            Some(&(cached_flags, _)) if cached_flags != flags => {
+                plan.flag_updates.push((uid, flags))
+            }
+            Some(_) => {}
+        }
+    }
+    for &uid in cached.keys() {
+        if !server.contains_key(&uid) {
+            plan.removed.push(uid);
+        }
+    }
+
+    plan.to_fetch.sort_unstable();
+    plan.flag_updates.sort_unstable_by_key(|(uid, _)| *uid);
+    plan.removed.sort_unstable();
+    plan
+}
+
+/// Add a message to the full-text index: subject, addresses and the decoded text body
+/// (HTML-only bodies are reduced to text). Header-only messages keep any indexed body.
+pub async fn index_message(state: &StateManager, shadow_uid: i64, message: &Message) -> Result<()> {
+    index_messages(state, &[(shadow_uid, message)]).await
+}
+
+/// Index a batch of messages (shadow UID, message) in one transaction, as `index_message`
+pub async fn index_messages(state: &StateManager, messages: &[(i64, &Message)]) -> Result<()> {
+    let texts: Vec<IndexedText> = messages
+        .iter()
+        .map(|&(shadow_uid, message)| IndexedText {
+            shadow_uid,
+            subject: message.subject.clone().unwrap_or_default(),
+            addresses: message
+                .from
+                .iter()
+                .chain(&message.to)
+                .chain(&message.cc)
+                .map(|addr| addr.format())
+                .collect::<Vec<_>>()
+                .join(", "),
+            body: match (&message.body_text, &message.body_html) {
+                (Some(text), _) => Some(text.clone()),
+                (None, Some(html)) => Some(html::to_text(html)),
+                (None, None) => None,
+            },
+        })
+        .collect();
+
+    state.index_message_texts(&texts).await
+}
+
+/// Turn user search terms into a safe FTS5 match expression.
+/// Words and "quoted phrases" are matched literally (implicitly ANDed), `word*` is a
+/// prefix search, and upper-case AND/OR/NOT are kept as operators.
+pub fn fts_match_expression(terms: &str) -> Result<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut chars = terms.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let (word, quoted) = if c == '"' {
+            chars.next();
+            let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+            (phrase, true)
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            (word, false)
+        };
+
+        if !quoted && matches!(word.as_str(), "AND" | "OR" | "NOT") {
+            let previous_is_operator = match parts.last() {
+                None => true,
+                Some(p) => matches!(p.as_str(), "AND" | "OR" | "NOT"),
+            };
+            if previous_is_operator {
+                return Err(anyhow!(
+                    "Invalid search: '{}' must follow a search term",
+                    word
+                ));
+            }
+            parts.push(word);
+            continue;
+        }
+
+        let (text, prefix) = match word.strip_suffix('*') {
+            Some(stem) if !quoted && !stem.is_empty() => (stem, true),
+            _ => (word.as_str(), false),
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        parts.push(format!(
+            "\"{}\"{}",
+            text.replace('"', "\"\""),
+            if prefix { "*" } else { "" }
+        ));
+    }
+
+    match parts.last().map(String::as_str) {
+        None => Err(anyhow!("Empty search terms")),
+        Some(op @ ("AND" | "OR" | "NOT")) => Err(anyhow!(
+            "Invalid search: '{}' must be followed by a search term",
+            op
+        )),
+        Some(_) => Ok(parts.join(" ")),
+    }
+}
+
+/// Look up the `text:` terms of a query in the full-text index for one folder,
+/// so the filter can evaluate them. No-op for queries without `text:`.
+pub async fn resolve_text_terms(
+    config: &Config,
+    state: &StateManager,
+    account: &str,
+    folder: &str,
+    filter: &mut MessageFilter,
+) -> Result<()> {
+    let terms = filter.text_terms()?;
+    if terms.is_empty() {
+        return Ok(());
+    }
+    ensure_enabled(config)?;
+
+    filter.text_matches.clear();
+    for terms in terms {
+        let uids = state
+            .search_text_uids(account, folder, &fts_match_expression(&terms)?)
+            .await?;
+        filter.text_matches.insert(terms, uids);
+    }
+    Ok(())
+}
+
+/// Fail unless `preferences.cache_enabled` is set
+pub fn ensure_enabled(config: &Config) -> Result<()> {
+    if config.preferences.cache_enabled {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "The local message cache is disabled. Set 'cache_enabled = true' under [preferences] in config.toml"
+        ))
+    }
+}
+
+/// Incrementally sync one folder: drop entries from an old UIDVALIDITY and expunged
+/// messages, refresh changed flags, and fetch what is missing (bodies unless `bodies` is false).
+/// With CONDSTORE only flags changed since the last sync's HIGHESTMODSEQ are fetched.
+/// Fetched messages are also registered for shadow UIDs so `read --offline` can find them.
+pub async fn sync_folder(
+    client: &mut ImapClient,
+    state: &StateManager,
+    account: &str,
+    folder: &str,
+    bodies: bool,
+) -> Result<FolderSyncResult> {
+    // HIGHESTMODSEQ of the last completed sync (read before select_tracked may reset it)
+    let last_modseq = state
+        .folder_state(account, folder)
+        .await?
+        .and_then(|f| f.highest_modseq);
+
+    let (status, remap) = select_tracked(client, state, account, folder).await?;
+    let uid_validity = status
+        .uid_validity
+        .ok_or_else(|| anyhow!("Server did not report UIDVALIDITY for folder '{}'", folder))?;
+
+    let mut result = FolderSyncResult {
+        folder: folder.to_string(),
+        uid_validity,
+        remapped: remap.map_or(0, |r| r.remapped),
+        ..Default::default()
+    };
+
+    result.invalidated = remap.map_or(0, |r| r.invalidated)
+        + state
+            .invalidate_cache_folder(account, folder, uid_validity)
+            .await?;
+
+    let cached = state.cached_uids(account, folder, uid_validity).await?;
+    // What the next sync resumes from; it only moves past changes that were all read
+    let mut synced = status.clone();
+    let (plan, server_count) = match (last_modseq, status.highest_modseq) {
+        (Some(since), Some(_)) if remap.is_none() && client.supports_condstore() => {
+            result.incremental = true;
+            let server_uids = client.fetch_all_uids().await?;
+            // UID FETCH 1:* on an empty mailbox is an error on some servers
+            let mut changed = FlagsFetch::default();
+            if !server_uids.is_empty() {
+                changed = client.fetch_flags("1:*", Some(since)).await?;
+            }
+            result.flags_skipped = changed.skipped;
+            if changed.skipped > 0 {
+                synced.highest_modseq = Some(since);
+            } else {
+                synced.highest_modseq = synced.highest_modseq.max(changed.highest_modseq);
+            }
+            (
+                plan_incremental_sync(&cached, &server_uids, &changed.flags, bodies),
+                server_uids.len(),
+            )
+        }
+        _ if status.exists == 0 => (plan_sync(&cached, &HashMap::new(), bodies), 0),
+        _ => {
+            let server = client.fetch_flags("1:*", None).await?;
+            result.flags_skipped = server.skipped;
+            if server.skipped > 0 {
+                // Unreadable rows must not count as expunged: compare against every UID,
+                // and sync the flags in full again next time
+                synced.highest_modseq = None;
+                let server_uids = client.fetch_all_uids().await?;
+                (
+                    plan_incremental_sync(&cached, &server_uids, &server.flags, bodies),
+                    server_uids.len(),
+                )
+            } else {
+                (
+                    plan_sync(&cached, &server.flags, bodies),
+                    server.flags.len(),
+                )
+            }
+        }
+    };
+
+    result.removed = state
+        .remove_cached_uids(account, folder, uid_validity, &plan.removed)
+        .await?;
+
+    for (uid, flags) in &plan.flag_updates {
+        state
+            .update_cached_flags(account, folder, uid_validity, *uid, flags)
+            .await?;
+    }
+    result.flags_updated = plan.flag_updates.len();
+
+    let (fetched, _) = client.fetch_for_cache(&plan.to_fetch, bodies).await?;
+    result.skipped = plan.to_fetch.len().saturating_sub(fetched.len());
+
+    let mut indexed = Vec::new();
+    for (message, raw) in &fetched {
+        state
+            .store_cached_message(account, folder, uid_validity, message, raw.as_deref())
+            .await?;
+
+        if let Some(ref msg_id) = message.message_id {
+            let shadow_uid = state
+                .get_or_create_shadow_uid(
+                    account,
+                    folder,
+                    message.uid,
+                    Some(msg_id),
+                    message.subject.as_deref(),
+                    message.from.as_ref().map(|f| f.address.as_str()),
+                    message.date,
+                )
+                .await?;
+            indexed.push((shadow_uid, message));
+        }
+
+        if cached.contains_key(&message.uid) {
+            result.bodies_fetched += 1;
+        } else {
+            result.added += 1;
+        }
+    }
+    index_messages(state, &indexed).await?;
+
+    state.mark_folder_synced(account, folder, &synced).await?;
+    result.total = server_count;
+
+    Ok(result)
+}
+
+/// Answer a message listing from the cache: messages of `folder` matching `filter`,
+/// highest UID first (or in the filter's sort order), with the filter's limit applied
+/// after sorting. Bodies are stripped like an
+/// online header fetch; previews are filled in when the filter asks for them.
+pub async fn query_cached(
+    state: &StateManager,
+    account: &str,
+    folder: &str,
+    filter: &MessageFilter,
+) -> Result<Vec<Message>> {
+    ensure_synced(state, account, folder).await?;
+
+    let mut messages = Vec::new();
+    for message in state.load_cached_messages(account, folder).await? {
+        if !filter.matches_message(&message)? {
+            continue;
+        }
+        messages.push(message);
+
+        // Without a sort the cache order (highest UID first) is the display order
+        if filter.sort.is_none() && filter.limit.is_some_and(|l| messages.len() >= l) {
+            break;
+        }
+    }
+
+    if let Some(order) = filter.sort {
+        order.sort(&mut messages);
+        if let Some(limit) = filter.limit {
+            messages.truncate(limit);
+        }
+    }
+
+    for message in &mut messages {
+        message.preview = if filter.preview {
+            preview_text(message)
+        } else {
+            None
+        };
+        message.body_text = None;
+        message.body_html = None;
+        message.headers.clear();
+    }
+
+    Ok(messages)
+}
+
+/// Load a full message (including raw source) from the cache
+pub async fn read_cached(
+    state: &StateManager,
+    account: &str,
+    folder: &str,
+    uid: u32,
+) -> Result<Message> {
+    let (mut message, raw) = state
+        .load_cached_message(account, folder, uid)
+        .await?
+        .ok_or_else(|| {
+            anyhow!(
+                "Message is not in the offline cache. Run 'protoncli sync -F {}' first.",
+                folder
+            )
+        })?;
+    let raw = raw.ok_or_else(|| {
+        anyhow!(
+            "Only the headers of this message are cached. Run 'protoncli sync -F {}' without --headers-only.",
+            folder
+        )
+    })?;
+
+    message.raw_message = Some(raw);
+    Ok(message)
+}
+
+async fn ensure_synced(state: &StateManager, account: &str, folder: &str) -> Result<()> {
+    if state.folder_last_synced(account, folder).await?.is_none() {
+        return Err(anyhow!(
+            "Folder '{}' has not been synced for offline use. Run 'protoncli sync -F {}' first.",
+            folder,
+            folder
+        ));
+    }
+    Ok(())
+}
+
+/// Short plain-text preview of a cached message body
+fn preview_text(message: &Message) -> Option<String> {
+    let text = match (&message.body_text, &message.body_html) {
+        (Some(text), _) => text.clone(),
+        (None, Some(html)) => html::to_text(html),
+        (None, None) => return None,
+    };
+    let preview: String = text.trim().chars().take(PREVIEW_CHARS).collect();
+    let preview = preview.trim();
+    (!preview.is_empty()).then(|| preview.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(seen: bool) -> MessageFlags {
+        MessageFlags {
+            seen,
+            answered: false,
+            flagged: false,
+            deleted: false,
+            draft: false,
+        }
+    }
+
+    #[test]
+    fn test_plan_sync_fetches_new_updates_flags_and_removes() {
+        let cached = HashMap::from([
+            (1, (flags(true), true)),
+            (2, (flags(false), true)),
+            (3, (flags(false), false)),
+        ]);
+        let server = HashMap::from([
+            (2, flags(true)),
+            (3, flags(false)),
+            (5, flags(false)),
+            (4, flags(false)),
+        ]);
+
+        let plan = plan_sync(&cached, &server, false);
+        assert_eq!(plan.to_fetch, vec![4, 5]);
+        assert_eq!(plan.flag_updates, vec![(2, flags(true))]);
+        assert_eq!(plan.removed, vec![1]);
+    }
+
+    #[test]
+    fn test_plan_sync_fetches_missing_bodies() {
+        let cached = HashMap::from([(1, (flags(true), true)), (2, (flags(true), false))]);
+        let server = HashMap::from([(1, flags(true)), (2, flags(true))]);
+
+        assert_eq!(plan_sync(&cached, &server, true).to_fetch, vec![2]);
+        assert_eq!(plan_sync(&cached, &server, false), SyncPlan::default());
+    }
+
+    #[test]
+    fn test_plan_incremental_sync_uses_changed_flags_only() {
+        let cached = HashMap::from([
+            (1, (flags(true), true)),
+            (2, (flags(false), true)),
+            (3, (flags(false), true)),
+        ]);
+        let server_uids = HashSet::from([2, 3, 6]);
+        // UID 3 changed to the value already cached, UID 6 is new
+        let changed = HashMap::from([(2, flags(true)), (3, flags(false)), (6, flags(false))]);
+
+        let plan = plan_incremental_sync(&cached, &server_uids, &changed, false);
+        assert_eq!(plan.to_fetch, vec![6]);
+        assert_eq!(plan.flag_updates, vec![(2, flags(true))]);
+        assert_eq!(plan.removed, vec![1]);
+    }
+
+    #[test]
+    fn test_fts_match_expression_quotes_terms() {
+        assert_eq!(
+            fts_match_expression("invoice alice@example.com").unwrap(),
+            "\"invoice\" \"alice@example.com\""
+        );
+        assert_eq!(
+            fts_match_expression("\"quarterly report\" OR budg*").unwrap(),
+            "\"quarterly report\" OR \"budg\"*"
+        );
+        // Lower-case keywords and quoted keywords are plain words
+        assert_eq!(
+            fts_match_expression("cats or \"NOT\"").unwrap(),
+            "\"cats\" \"or\" \"NOT\""
+        );
+    }
+
+    #[test]
+    fn test_fts_match_expression_rejects_bad_input() {
+        assert!(fts_match_expression("   ").is_err());
+        assert!(fts_match_expression("OR invoice").is_err());
+        assert!(fts_match_expression("invoice AND").is_err());
+        assert!(fts_match_expression("a OR NOT b").is_err());
+    }
+
+    #[test]
+    fn test_preview_text_prefers_plain_text() {
+        let mut message = Message::new(1);
+        assert_eq!(preview_text(&message), None);
+
+        message.body_html = Some("<p>Hello <b>there</b></p>".to_string());
+        assert_eq!(preview_text(&message).as_deref(), Some("Hello there"));
+
+        message.body_text = Some("  plain body  ".to_string());
+        assert_eq!(preview_text(&message).as_deref(), Some("plain body"));
+    }
+}
@@ -1,15 +1,27 @@
 use crate::core::auth::KeychainManager;
 use crate::models::account::{Account, SecurityType};
-use crate::models::filter::MessageFilter;
+use crate::models::filter::{ClientFilter, MessageFilter};
 use crate::models::folder::Folder;
-use crate::models::message::{EmailAddress, Message, MessageFlags};
+use crate::models::message::{
+    extract_attachments, labels_from_imap_flags, Attachment, EmailAddress, Message, MessageFlags,
+};
+use crate::models::sort::SortOrder;
+use crate::output::html;
 use crate::utils::batch::{chunk_uids, FETCH_BATCH_SIZE};
 use anyhow::{anyhow, Context, Result};
+use async_imap::extensions::idle::IdleResponse;
+use async_imap::imap_proto::types::{
+    BodyParams, BodyStructure, MailboxDatum, Response, ResponseCode, Status, UidSetMember,
+};
+use async_imap::types::{Capability, Flag, UnsolicitedResponse};
 use async_imap::Session;
 use async_native_tls::{TlsConnector, TlsStream};
 use chrono::{DateTime, Utc};
+use futures::io::AsyncWriteExt;
 use futures::stream::StreamExt;
 use secrecy::ExposeSecret;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
 
@@ -26,8 +38,127 @@ pub struct FetchStats {
     pub skipped_count: usize,
 }
 
+/// Planned listing fetch (see `ImapClient::plan_fetch`)
+#[derive(Debug)]
+pub struct FetchBatches {
+    /// UIDs to fetch, in display order
+    uids: Vec<u32>,
+    position: usize,
+    preview: bool,
+    limit: Option<usize>,
+    /// Messages returned so far, when the limit is applied while fetching
+    returned: usize,
+    /// Set when the server can't sort: every match is ranked on its sort key, then only
+    /// the ones kept after the limit are fetched in full
+    client_sort: Option<SortOrder>,
+    before_date: Option<DateTime<Utc>>,
+    stats: FetchStats,
+}
+
+impl FetchBatches {
+    fn record(&mut self, messages: &mut Vec<Message>, skipped: usize) {
+        self.stats.skipped_count += skipped;
+        self.stats.parsed_count += messages.len();
+        // Undated messages can't be placed relative to the cursor, so they're kept
+        if let Some(before) = self.before_date {
+            messages.retain(|m| m.date.is_none_or(|d| d < before));
+        }
+    }
+
+    /// Report parse problems on stderr and return the fetch statistics
+    pub fn finish(self) -> FetchStats {
+        let stats = self.stats;
+
+        if stats.skipped_count > 0 {
+            eprintln!(
+                "\nNote: Skipped {} message(s) due to parsing errors.",
+                stats.skipped_count
+            );
+        }
+
+        // Warn if Bridge returned significantly fewer messages than requested
+        let expected = stats.fetch_count.saturating_sub(stats.skipped_count);
+        if stats.parsed_count < expected && expected > 0 {
+            let missing = expected - stats.parsed_count;
+            eprintln!(
+                "\nWarning: ProtonMail Bridge returned {} fewer message(s) than expected.",
+                missing
+            );
+            eprintln!("This is a known Bridge issue with large folders. Try using --days to filter by date.");
+        }
+
+        stats
+    }
+}
+
+/// State of a folder reported by SELECT
+#[derive(Debug, Clone, Default)]
+pub struct FolderStatus {
+    pub uid_validity: Option<u32>,
+    pub uid_next: Option<u32>,
+    /// Only reported when the server supports CONDSTORE (RFC 7162)
+    pub highest_modseq: Option<u64>,
+    pub exists: u32,
+}
+
+/// Changes the server reported on its own for the selected folder, during IDLE, NOOP
+/// or other commands
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MailboxChanges {
+    /// Latest message count (EXISTS, minus any EXPUNGE that followed it)
+    pub exists: Option<u32>,
+    /// Number of EXPUNGE responses; they only carry sequence numbers
+    pub expunged: usize,
+    /// Sequence numbers of messages whose flags changed (unsolicited FETCH)
+    pub fetched: Vec<u32>,
+}
+
+impl MailboxChanges {
+    fn record(&mut self, response: &Response) {
+        match response {
+            Response::MailboxData(MailboxDatum::Exists(count)) => self.exists = Some(*count),
+            Response::Expunge(_) => self.expunge(),
+            Response::Fetch(seq, _) => self.fetched.push(*seq),
+            _ => {}
+        }
+    }
+
+    fn record_unsolicited(&mut self, response: UnsolicitedResponse) {
+        match response {
+            UnsolicitedResponse::Exists(count) => self.exists = Some(count),
+            UnsolicitedResponse::Expunge(_) => self.expunge(),
+            UnsolicitedResponse::Other(data) => self.record(data.parsed()),
+            _ => {}
+        }
+    }
+
+    fn expunge(&mut self) {
+        self.expunged += 1;
+        self.exists = self.exists.map(|count| count.saturating_sub(1));
+    }
+}
+
+/// Flags fetched for a set of messages
+#[derive(Debug, Default)]
+pub struct FlagsFetch {
+    pub flags: HashMap<u32, MessageFlags>,
+    /// Highest MODSEQ among the returned messages (CHANGEDSINCE fetches only)
+    pub highest_modseq: Option<u64>,
+    /// Rows that failed to parse
+    pub skipped: usize,
+}
+
+/// Location of a message stored by APPEND, from the APPENDUID response code (RFC 4315)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppendedUid {
+    pub uid_validity: u32,
+    pub uid: u32,
+}
+
 pub struct ImapClient {
     session: Session<TlsStream<Compat<TcpStream>>>,
+    /// Capabilities advertised after login (uppercased, e.g. "MOVE", "UIDPLUS", "AUTH=PLAIN")
+    capabilities: HashSet<String>,
 }
 
 impl ImapClient {
@@ -99,12 +230,28 @@ impl ImapClient {
         };
 
         // Step 8: Authenticate
-        let session = client
+        let mut session = client
             .login(&account.email, password.expose_secret())
             .await
             .map_err(|e| anyhow!("Authentication failed: {}", e.0))?;
 
-        Ok(Self { session })
+        // Step 9: Detect capabilities (they can change after login, so ask now)
+        let capabilities = session
+            .capabilities()
+            .await
+            .context("Failed to query server capabilities")?
+            .iter()
+            .map(|capability| match capability {
+                Capability::Imap4rev1 => "IMAP4REV1".to_string(),
+                Capability::Auth(mechanism) => format!("AUTH={}", mechanism.to_uppercase()),
+                Capability::Atom(atom) => atom.to_uppercase(),
+            })
+            .collect();
+
+        Ok(Self {
+            session,
+            capabilities,
+        })
     }
 
     pub async fn test_connection(account: &Account) -> Result<String> {
@@ -146,12 +293,196 @@ impl ImapClient {
         Ok(folders)
     }
 
-    pub async fn select_folder(&mut self, folder: &str) -> Result<()> {
+    pub async fn select_folder(&mut self, folder: &str) -> Result<FolderStatus> {
+        // SELECT (CONDSTORE) makes the server report HIGHESTMODSEQ
+        let mailbox = if self.supports_condstore() {
+            self.session.select_condstore(folder).await
+        } else {
+            self.session.select(folder).await
+        }
+        .context(format!("Failed to select folder: {}", folder))?;
+
+        Ok(FolderStatus {
+            uid_validity: mailbox.uid_validity,
+            uid_next: mailbox.uid_next,
+            highest_modseq: mailbox.highest_modseq,
+            exists: mailbox.exists,
+        })
+    }
+
+    /// Check whether the server advertises a capability (e.g. "IDLE"), case-insensitive
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.contains(&capability.to_uppercase())
+    }
+
+    /// UID MOVE (RFC 6851)
+    pub fn supports_move(&self) -> bool {
+        self.has_capability("MOVE")
+    }
+
+    /// UID EXPUNGE and COPYUID/APPENDUID response codes (RFC 4315)
+    pub fn supports_uidplus(&self) -> bool {
+        self.has_capability("UIDPLUS")
+    }
+
+    /// Per-message mod-sequences and CHANGEDSINCE fetches (RFC 7162)
+    pub fn supports_condstore(&self) -> bool {
+        self.has_capability("CONDSTORE") || self.has_capability("QRESYNC")
+    }
+
+    /// UIDs of every message in the selected folder
+    pub async fn fetch_all_uids(&mut self) -> Result<HashSet<u32>> {
         self.session
-            .select(folder)
+            .uid_search("ALL")
+            .await
+            .context("Failed to search messages")
+    }
+
+    /// Map the Message-ID of every message in the selected folder to its UID
+    /// (newest UID wins for duplicates). Fetches only the Message-ID header and parses it
+    /// with mail-parser (ENVELOPE parsing fails on Bridge's Unicode headers); messages that
+    /// fail to parse are skipped. The folder must not be empty.
+    pub async fn fetch_message_id_map(&mut self) -> Result<HashMap<String, u32>> {
+        self.fetch_message_ids("1:*").await
+    }
+
+    /// Map the Message-IDs of the messages in `uid_set` to their UIDs, as in
+    /// `fetch_message_id_map`
+    async fn fetch_message_ids(&mut self, uid_set: &str) -> Result<HashMap<String, u32>> {
+        let mut uids_by_id = HashMap::new();
+
+        let mut stream = self
+            .session
+            .uid_fetch(uid_set, "(UID BODY.PEEK[HEADER.FIELDS (MESSAGE-ID)])")
+            .await
+            .context("Failed to fetch Message-IDs")?;
+
+        while let Some(fetch_result) = stream.next().await {
+            let Ok(fetch) = fetch_result else {
+                continue;
+            };
+            let (Some(uid), Some(header)) = (fetch.uid, fetch.header()) else {
+                continue;
+            };
+            let Some(message_id) = mail_parser::MessageParser::default()
+                .parse_headers(header)
+                .and_then(|parsed| parsed.message_id().map(str::to_string))
+            else {
+                continue;
+            };
+            let entry = uids_by_id.entry(message_id).or_insert(uid);
+            *entry = (*entry).max(uid);
+        }
+
+        Ok(uids_by_id)
+    }
+
+    /// Flags of the messages in `uid_set` (e.g. `"120:*"`), keyed by UID. With
+    /// `changed_since` only messages whose mod-sequence is higher are returned (CONDSTORE).
+    /// Rows that fail to parse are skipped and counted instead of failing the fetch.
+    pub async fn fetch_flags(
+        &mut self,
+        uid_set: &str,
+        changed_since: Option<u64>,
+    ) -> Result<FlagsFetch> {
+        let query = match changed_since {
+            Some(modseq) => format!("(UID FLAGS) (CHANGEDSINCE {})", modseq),
+            None => "(UID FLAGS)".to_string(),
+        };
+        let stream = self
+            .session
+            .uid_fetch(uid_set, query)
+            .await
+            .context("Failed to fetch message flags")?;
+        Ok(collect_flags(stream).await)
+    }
+
+    /// Flags of the messages at the given sequence numbers, keyed by UID
+    pub async fn fetch_flags_by_seq(&mut self, seqs: &[u32]) -> Result<FlagsFetch> {
+        if seqs.is_empty() {
+            return Ok(FlagsFetch::default());
+        }
+        let stream = self
+            .session
+            .fetch(uid_set(seqs), "(UID FLAGS)")
+            .await
+            .context("Failed to fetch message flags")?;
+        Ok(collect_flags(stream).await)
+    }
+
+    /// Take the changes the server has reported on its own since the last call
+    pub fn take_changes(&mut self) -> MailboxChanges {
+        let mut changes = MailboxChanges::default();
+        while let Ok(response) = self.session.unsolicited_responses.try_recv() {
+            changes.record_unsolicited(response);
+        }
+        changes
+    }
+
+    /// Raw flags of the given messages in the selected folder, keyed by UID.
+    /// System flags are named as on the wire (`\Seen`), keywords as-is.
+    pub async fn fetch_raw_flags(&mut self, uids: &[u32]) -> Result<HashMap<u32, Vec<String>>> {
+        let mut flags_by_uid = HashMap::new();
+        if uids.is_empty() {
+            return Ok(flags_by_uid);
+        }
+
+        let mut flags_stream = self
+            .session
+            .uid_fetch(uid_set(uids), "(UID FLAGS)")
             .await
-            .context(format!("Failed to select folder: {}", folder))?;
+            .context("Failed to fetch message flags")?;
 
+        while let Some(fetch_result) = flags_stream.next().await {
+            let fetch = fetch_result.context("Failed to read message flags")?;
+            if let Some(uid) = fetch.uid {
+                let flags = fetch.flags().filter_map(|f| flag_name(&f)).collect();
+                flags_by_uid.insert(uid, flags);
+            }
+        }
+
+        Ok(flags_by_uid)
+    }
+
+    /// Block in IMAP IDLE until the server reports a change or `timeout` elapses.
+    /// Consumes the client because the session is unusable while idling; it is
+    /// handed back once IDLE is terminated, with the changes reported meanwhile
+    /// (including any still pending from earlier commands).
+    pub async fn idle_wait(mut self, timeout: Duration) -> Result<(Self, MailboxChanges)> {
+        let mut changes = self.take_changes();
+        let capabilities = self.capabilities;
+        let mut handle = self.session.idle();
+        handle.init().await.context("Failed to start IDLE")?;
+
+        {
+            let (wait, _interrupt) = handle.wait_with_timeout(timeout);
+            if let IdleResponse::NewData(data) = wait.await.context("IDLE connection failed")? {
+                changes.record(data.parsed());
+            }
+        }
+
+        let session = handle.done().await.context("Failed to end IDLE")?;
+        let mut client = Self {
+            session,
+            capabilities,
+        };
+        // Responses that arrived before DONE completed
+        let later = client.take_changes();
+        if later.exists.is_some() {
+            changes.exists = later.exists;
+        } else {
+            changes.exists = changes
+                .exists
+                .map(|count| count.saturating_sub(later.expunged as u32));
+        }
+        changes.expunged += later.expunged;
+        changes.fetched.extend(later.fetched);
+        Ok((client, changes))
+    }
+
+    /// Send NOOP, keeping the connection alive and letting the server report changes
+    pub async fn noop(&mut self) -> Result<()> {
+        self.session.noop().await.context("NOOP failed")?;
         Ok(())
     }
 
@@ -159,6 +490,17 @@ impl ImapClient {
         &mut self,
         filter: &MessageFilter,
     ) -> Result<(Vec<Message>, FetchStats)> {
+        let mut batches = self.plan_fetch(filter).await?;
+        let mut messages = Vec::new();
+        while let Some(batch) = self.next_batch(&mut batches).await? {
+            messages.extend(batch);
+        }
+        Ok((messages, batches.finish()))
+    }
+
+    /// Search for `filter` and decide which UIDs to fetch, in display order.
+    /// Consume the plan with `next_batch` to get messages as each batch arrives.
+    pub async fn plan_fetch(&mut self, filter: &MessageFilter) -> Result<FetchBatches> {
         let mut stats = FetchStats::default();
 
         // Build and execute search query
@@ -171,33 +513,175 @@ impl ImapClient {
 
         let mut uids: Vec<u32> = uids_set.into_iter().collect();
         uids.sort(); // Ensure deterministic ordering
+        if let Some(before) = filter.before_uid {
+            uids.retain(|&uid| uid < before);
+        }
         stats.search_count = uids.len();
 
+        let mut batches = FetchBatches {
+            uids: vec![],
+            position: 0,
+            preview: filter.preview,
+            limit: filter.limit,
+            returned: 0,
+            client_sort: None,
+            before_date: filter.before_date,
+            stats,
+        };
         if uids.is_empty() {
-            return Ok((vec![], stats));
+            return Ok(batches);
         }
 
-        // Apply limit if specified
-        let uids_to_fetch: Vec<_> = if let Some(limit) = filter.limit {
-            uids.into_iter().rev().take(limit).collect()
+        // Candidates in display order: server-sorted when SORT is available, else newest first
+        let server_sorted = match filter.sort {
+            Some(order) if order.order_uids(&uids).is_some() => order.order_uids(&uids),
+            Some(order) if self.has_capability("SORT") => {
+                match self.uid_sort(&order.imap_criteria(), &search_query).await {
+                    Ok(sorted) => Some(sorted),
+                    Err(e) => {
+                        eprintln!(
+                            "Warning: server-side sort failed ({:#}); sorting {} message(s) locally",
+                            e,
+                            uids.len()
+                        );
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        batches.client_sort = filter.sort.filter(|_| server_sorted.is_none());
+        let candidates: Vec<u32> = match server_sorted {
+            Some(sorted) => sorted,
+            None => uids.into_iter().rev().collect(),
+        };
+
+        // Sorting client-side needs every match before the limit can apply, and so does
+        // the exact date bound (SEARCH only compares days); the latter stops once it's full
+        let fetch_limit = if batches.client_sort.is_some() || batches.before_date.is_some() {
+            None
+        } else {
+            filter.limit
+        };
+
+        // Apply client-side filters (attachments) before the limit, otherwise apply limit directly
+        batches.uids = if let Some(client_filter) = filter.client_filter()? {
+            self.apply_client_filter(
+                &client_filter,
+                &filter.text_matches,
+                &candidates,
+                fetch_limit,
+            )
+            .await?
+        } else if let Some(limit) = fetch_limit {
+            candidates.into_iter().take(limit).collect()
         } else {
-            uids.into_iter().rev().collect()
+            candidates
         };
-        stats.fetch_count = uids_to_fetch.len();
 
+        Ok(batches)
+    }
+
+    /// Fetch the next `FETCH_BATCH_SIZE` messages of a plan, in display order.
+    /// With a client-side sort everything is fetched and ordered in one go.
+    pub async fn next_batch(&mut self, batches: &mut FetchBatches) -> Result<Option<Vec<Message>>> {
+        if batches.position >= batches.uids.len() {
+            return Ok(None);
+        }
+
+        if let Some(order) = batches.client_sort {
+            batches.position = batches.uids.len();
+
+            // Rank on the sort keys alone, then fetch only the messages kept
+            let mut ranked = self.fetch_sort_keys(&batches.uids).await?;
+            order.sort(&mut ranked);
+            let mut shown: Vec<u32> = ranked.iter().map(|m| m.uid).collect();
+            if let Some(limit) = batches.limit {
+                shown.truncate(limit);
+            }
+            batches.stats.fetch_count = shown.len();
+
+            let (mut messages, skipped) =
+                self.fetch_messages_by_uids(&shown, batches.preview).await?;
+            batches.record(&mut messages, skipped);
+            order_by_uids(&mut messages, &shown);
+            return Ok(Some(messages));
+        }
+
+        let end = (batches.position + FETCH_BATCH_SIZE).min(batches.uids.len());
+        let batch = batches.uids[batches.position..end].to_vec();
+        batches.position = end;
+        batches.stats.fetch_count += batch.len();
+
+        let (mut messages, skipped) = self.fetch_messages_by_uids(&batch, batches.preview).await?;
+        batches.record(&mut messages, skipped);
+        order_by_uids(&mut messages, &batch);
+
+        if let Some(limit) = batches.limit {
+            messages.truncate(limit - batches.returned);
+            batches.returned += messages.len();
+            if batches.returned == limit {
+                batches.position = batches.uids.len();
+            }
+        }
+        Ok(Some(messages))
+    }
+
+    /// Just what a client-side sort needs (Date, From, Subject and size) for the given UIDs;
+    /// messages that fail to parse rank as having no date, sender or subject
+    async fn fetch_sort_keys(&mut self, uids: &[u32]) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+
+        for batch in chunk_uids(uids, FETCH_BATCH_SIZE) {
+            let mut stream = self
+                .session
+                .uid_fetch(
+                    uid_set(&batch),
+                    "(UID RFC822.SIZE BODY.PEEK[HEADER.FIELDS (DATE FROM SUBJECT)])",
+                )
+                .await
+                .context("Failed to fetch sort keys")?;
+
+            while let Some(fetch_result) = stream.next().await {
+                let Ok(fetch) = fetch_result else {
+                    continue;
+                };
+                let Some(uid) = fetch.uid else {
+                    continue;
+                };
+                let mut message = fetch
+                    .header()
+                    .and_then(|header| parse_message(uid, header))
+                    .unwrap_or_else(|| Message::new(uid));
+                message.size = fetch.size.map(|s| s as usize);
+                messages.push(message);
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Fetch and parse headers (or full messages when `preview` is set) for the given UIDs.
+    /// Returns the parsed messages and the number of messages skipped due to parse errors.
+    pub async fn fetch_messages_by_uids(
+        &mut self,
+        uids: &[u32],
+        preview: bool,
+    ) -> Result<(Vec<Message>, usize)> {
         // Fetch headers using BODY.PEEK[HEADER] and parse with mail-parser
         // This avoids async-imap's ENVELOPE parsing which has Unicode issues
         // For preview, fetch full RFC822 to properly parse MIME content
-        let fetch_query = if filter.preview {
-            "(UID FLAGS RFC822)"
+        let fetch_query = if preview {
+            "(UID FLAGS RFC822.SIZE RFC822)"
         } else {
-            "(UID FLAGS BODY.PEEK[HEADER])"
+            "(UID FLAGS RFC822.SIZE BODY.PEEK[HEADER])"
         };
 
         let mut messages = Vec::new();
+        let mut skipped = 0;
 
         // Fetch in batches to work around ProtonMail Bridge issues with large requests
-        let batches = chunk_uids(&uids_to_fetch, FETCH_BATCH_SIZE);
+        let batches = chunk_uids(uids, FETCH_BATCH_SIZE);
 
         for batch in batches.iter() {
             let uid_set = batch
@@ -217,7 +701,7 @@ impl ImapClient {
                     Ok(f) => f,
                     Err(_) => {
                         // Skip messages that fail to parse (often due to Unicode issues in headers)
-                        stats.skipped_count += 1;
+                        skipped += 1;
                         continue;
                     }
                 };
@@ -228,6 +712,8 @@ impl ImapClient {
                     // Parse flags
                     let flags: Vec<_> = fetch.flags().collect();
                     message.flags = MessageFlags::from_imap_flags(&flags);
+                    message.labels = labels_from_imap_flags(&flags);
+                    message.size = fetch.size.map(|s| s as usize);
 
                     // Get the raw bytes - either from body() for RFC822 or header() for BODY.PEEK[HEADER]
                     let mail_bytes = fetch.body().or_else(|| fetch.header());
@@ -239,6 +725,13 @@ impl ImapClient {
                         {
                             message.subject = parsed_mail.subject().map(String::from);
                             message.message_id = parsed_mail.message_id().map(String::from);
+                            message.in_reply_to =
+                                parsed_mail.in_reply_to().as_text().map(String::from);
+                            message.references = parsed_mail
+                                .references()
+                                .as_text_list()
+                                .map(|ids| ids.into_iter().map(String::from).collect())
+                                .unwrap_or_default();
 
                             // Parse from address
                             if let Some(from_addr) =
@@ -263,6 +756,16 @@ impl ImapClient {
                                     }
                                 }
                             }
+                            if let Some(cc_addrs) = parsed_mail.cc() {
+                                for addr in cc_addrs.iter() {
+                                    if let Some(email) = addr.address() {
+                                        message.cc.push(EmailAddress::new(
+                                            email.to_string(),
+                                            addr.name().map(String::from),
+                                        ));
+                                    }
+                                }
+                            }
 
                             // Parse date
                             if let Some(date) = parsed_mail.date() {
@@ -273,7 +776,7 @@ impl ImapClient {
                             }
 
                             // Extract preview from body (only when we have full RFC822)
-                            if filter.preview {
+                            if preview {
                                 // Try to get plain text body first
                                 if let Some(body_text) = parsed_mail.body_text(0) {
                                     let preview: String = body_text.chars().take(200).collect();
@@ -281,20 +784,10 @@ impl ImapClient {
                                         message.preview = Some(preview.trim().to_string());
                                     }
                                 }
-                                // If no text part, try HTML and strip tags
+                                // If no text part, render the HTML part as text
                                 else if let Some(body_html) = parsed_mail.body_html(0) {
-                                    // Basic HTML stripping - just remove tags for preview
-                                    let text =
-                                        body_html.replace("<br>", "\n").replace("</p>", "\n");
-                                    let preview: String = text
-                                        .split('<')
-                                        .map(|s| {
-                                            s.split_once('>').map(|(_, rest)| rest).unwrap_or(s)
-                                        })
-                                        .collect::<String>()
-                                        .chars()
-                                        .take(200)
-                                        .collect();
+                                    let preview: String =
+                                        html::to_text(&body_html).chars().take(200).collect();
                                     if !preview.trim().is_empty() {
                                         message.preview = Some(preview.trim().to_string());
                                     }
@@ -308,30 +801,137 @@ impl ImapClient {
             }
         }
 
-        stats.parsed_count = messages.len();
+        Ok((messages, skipped))
+    }
 
-        if stats.skipped_count > 0 {
-            eprintln!(
-                "\nNote: Skipped {} message(s) due to parsing errors.",
-                stats.skipped_count
-            );
+    /// Fetch messages for the local cache, without marking them as seen.
+    /// With `bodies` the full source is fetched and returned alongside the parsed message;
+    /// otherwise only headers, size and attachment metadata (from BODYSTRUCTURE).
+    /// Returns the messages and the number skipped due to parse errors.
+    pub async fn fetch_for_cache(
+        &mut self,
+        uids: &[u32],
+        bodies: bool,
+    ) -> Result<(Vec<(Message, Option<Vec<u8>>)>, usize)> {
+        let fetch_query = if bodies {
+            "(UID FLAGS RFC822.SIZE BODY.PEEK[])"
+        } else {
+            "(UID FLAGS RFC822.SIZE BODYSTRUCTURE BODY.PEEK[HEADER])"
+        };
+
+        let mut messages = Vec::new();
+        let mut skipped = 0;
+
+        for batch in chunk_uids(uids, FETCH_BATCH_SIZE) {
+            let mut messages_stream = self
+                .session
+                .uid_fetch(uid_set(&batch), fetch_query)
+                .await
+                .context("Failed to fetch messages")?;
+
+            while let Some(fetch_result) = messages_stream.next().await {
+                let Ok(fetch) = fetch_result else {
+                    skipped += 1;
+                    continue;
+                };
+                let Some(uid) = fetch.uid else {
+                    continue;
+                };
+
+                let bytes = if bodies { fetch.body() } else { fetch.header() };
+                let Some(mut message) = bytes.and_then(|b| parse_message(uid, b)) else {
+                    skipped += 1;
+                    continue;
+                };
+
+                let flags: Vec<_> = fetch.flags().collect();
+                message.flags = MessageFlags::from_imap_flags(&flags);
+                message.labels = labels_from_imap_flags(&flags);
+                message.size = fetch.size.map(|s| s as usize);
+                if !bodies {
+                    message.attachments = fetch
+                        .bodystructure()
+                        .map(attachments_from_bodystructure)
+                        .unwrap_or_default();
+                }
+
+                let raw = if bodies {
+                    bytes.map(<[u8]>::to_vec)
+                } else {
+                    None
+                };
+                messages.push((message, raw));
+            }
         }
 
-        // Warn if Bridge returned significantly fewer messages than requested
-        let expected = stats.fetch_count.saturating_sub(stats.skipped_count);
-        if stats.parsed_count < expected && expected > 0 {
-            let missing = expected - stats.parsed_count;
-            eprintln!(
-                "\nWarning: ProtonMail Bridge returned {} fewer message(s) than expected.",
-                missing
-            );
-            eprintln!("This is a known Bridge issue with large folders. Try using --days to filter by date.");
+        Ok((messages, skipped))
+    }
+
+    /// Narrow search results with the client-side filter stage.
+    /// Only BODYSTRUCTURE is fetched (never message bodies). Returns matching UIDs
+    /// newest first, stopping once `limit` matches are found.
+    async fn apply_client_filter(
+        &mut self,
+        client_filter: &ClientFilter,
+        text_matches: &HashMap<String, HashSet<u32>>,
+        candidates: &[u32],
+        limit: Option<usize>,
+    ) -> Result<Vec<u32>> {
+        // Resolve server-side parts of the expression to UID sets
+        let mut server_matches = HashMap::new();
+        for query in client_filter.server_queries() {
+            let matched = self
+                .session
+                .uid_search(&query)
+                .await
+                .context("Failed to search messages")?;
+            server_matches.insert(query, matched);
+        }
+        for (terms, matched) in text_matches {
+            server_matches.insert(ClientFilter::text_key(terms), matched.clone());
         }
 
-        // Sort by UID descending (newest first) for deterministic ordering
-        messages.sort_by(|a, b| b.uid.cmp(&a.uid));
+        let mut matches = Vec::new();
+
+        for batch in chunk_uids(candidates, FETCH_BATCH_SIZE) {
+            let mut attachments_by_uid: HashMap<u32, Vec<Attachment>> = HashMap::new();
+            {
+                let mut structure_stream = self
+                    .session
+                    .uid_fetch(uid_set(&batch), "(UID BODYSTRUCTURE)")
+                    .await
+                    .context("Failed to fetch message structure")?;
+
+                while let Some(fetch_result) = structure_stream.next().await {
+                    // Skip messages the server fails to describe, like the header fetch does
+                    let Ok(fetch) = fetch_result else {
+                        continue;
+                    };
+                    if let Some(uid) = fetch.uid {
+                        let attachments = fetch
+                            .bodystructure()
+                            .map(attachments_from_bodystructure)
+                            .unwrap_or_default();
+                        attachments_by_uid.insert(uid, attachments);
+                    }
+                }
+            }
 
-        Ok((messages, stats))
+            // Keep the candidates' display order
+            for uid in batch {
+                let Some(attachments) = attachments_by_uid.get(&uid) else {
+                    continue;
+                };
+                if client_filter.matches(uid, attachments, &server_matches) {
+                    matches.push(uid);
+                    if limit.is_some_and(|l| matches.len() >= l) {
+                        return Ok(matches);
+                    }
+                }
+            }
+        }
+
+        Ok(matches)
     }
 
     pub async fn fetch_message_by_uid(
@@ -356,100 +956,8 @@ impl ImapClient {
             let fetch = fetch_result.context("Failed to read message")?;
 
             if let Some(body_bytes) = fetch.body() {
-                // Parse with mail-parser
-                if let Some(parsed_mail) = mail_parser::MessageParser::default().parse(body_bytes) {
-                    let mut message = Message::new(uid);
-
-                    // Extract subject
-                    message.subject = parsed_mail.subject().map(String::from);
-                    message.message_id = parsed_mail.message_id().map(String::from);
-
-                    // Extract from address
-                    if let Some(from_addr) = parsed_mail.from().and_then(|addrs| addrs.first()) {
-                        if let Some(email) = from_addr.address() {
-                            message.from = Some(EmailAddress::new(
-                                email.to_string(),
-                                from_addr.name().map(String::from),
-                            ));
-                        }
-                    }
-
-                    // Extract to addresses
-                    if let Some(to_addrs) = parsed_mail.to() {
-                        if let Some(addrs) = to_addrs.as_list() {
-                            for addr in addrs {
-                                if let Some(email) = addr.address() {
-                                    message.to.push(EmailAddress::new(
-                                        email.to_string(),
-                                        addr.name().map(String::from),
-                                    ));
-                                }
-                            }
-                        }
-                    }
-
-                    // Extract cc addresses
-                    if let Some(cc_addrs) = parsed_mail.cc() {
-                        if let Some(addrs) = cc_addrs.as_list() {
-                            for addr in addrs {
-                                if let Some(email) = addr.address() {
-                                    message.cc.push(EmailAddress::new(
-                                        email.to_string(),
-                                        addr.name().map(String::from),
-                                    ));
-                                }
-                            }
-                        }
-                    }
-
-                    // Extract bcc addresses
-                    if let Some(bcc_addrs) = parsed_mail.bcc() {
-                        if let Some(addrs) = bcc_addrs.as_list() {
-                            for addr in addrs {
-                                if let Some(email) = addr.address() {
-                                    message.bcc.push(EmailAddress::new(
-                                        email.to_string(),
-                                        addr.name().map(String::from),
-                                    ));
-                                }
-                            }
-                        }
-                    }
-
-                    // Extract reply-to
-                    if let Some(reply_to_addrs) = parsed_mail.reply_to() {
-                        if let Some(addrs) = reply_to_addrs.as_list() {
-                            if let Some(addr) = addrs.first() {
-                                if let Some(email) = addr.address() {
-                                    message.reply_to = Some(EmailAddress::new(
-                                        email.to_string(),
-                                        addr.name().map(String::from),
-                                    ));
-                                }
-                            }
-                        }
-                    }
-
-                    // Extract date
-                    if let Some(date) = parsed_mail.date() {
-                        // Convert mail_parser::DateTime to chrono::DateTime<Utc>
-                        message.date = Some(
-                            DateTime::from_timestamp(date.to_timestamp(), 0)
-                                .unwrap_or_else(Utc::now),
-                        );
-                    }
-
-                    // Extract body text and HTML
-                    message.body_text = parsed_mail.body_text(0).map(String::from);
-                    message.body_html = parsed_mail.body_html(0).map(String::from);
-
-                    // Extract headers
-                    for header in parsed_mail.headers() {
-                        let name = header.name().to_string();
-                        if let Some(value) = header.value().as_text() {
-                            message.headers.insert(name, value.to_string());
-                        }
-                    }
+                if let Some(mut message) = parse_message(uid, body_bytes) {
+                    message.size = Some(body_bytes.len());
 
                     // Store raw if requested
                     if include_raw {
@@ -487,45 +995,318 @@ impl ImapClient {
         Ok(())
     }
 
-    /// Copy messages to a destination folder
-    pub async fn copy_messages(&mut self, uids: &[u32], dest_folder: &str) -> Result<()> {
-        if uids.is_empty() {
-            return Ok(());
+    /// APPEND a raw RFC822 message to `folder` with `flags` (e.g. "\\Seen \\Draft").
+    /// Returns where it was stored when the server reports APPENDUID (UIDPLUS).
+    pub async fn append(
+        &mut self,
+        folder: &str,
+        content: &[u8],
+        flags: &str,
+    ) -> Result<Option<AppendedUid>> {
+        let command = format!(
+            "APPEND {} ({}) {{{}}}",
+            quote_mailbox(folder),
+            flags,
+            content.len()
+        );
+        let tag = self.session.run_command(&command).await?;
+
+        // The message goes out as a literal once the server asks for it
+        loop {
+            let Some(response) = self.session.read_response().await? else {
+                return Err(anyhow!("Connection lost while waiting for server response"));
+            };
+            match response.parsed() {
+                Response::Continue { .. } => break,
+                Response::Done {
+                    tag: done_tag,
+                    information,
+                    ..
+                } if *done_tag == tag => {
+                    return Err(anyhow!(
+                        "Server rejected APPEND to {}: {}",
+                        folder,
+                        information.as_deref().unwrap_or("no details")
+                    ));
+                }
+                _ => {}
+            }
         }
 
-        let uid_set = uids
-            .iter()
-            .map(|u| u.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
+        let stream = self.session.get_mut();
+        stream.write_all(content).await?;
+        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+
+        while let Some(response) = self.session.read_response().await? {
+            if let Response::Done {
+                tag: done_tag,
+                status,
+                code,
+                information,
+            } = response.parsed()
+            {
+                if *done_tag != tag {
+                    continue;
+                }
+                return match status {
+                    Status::Ok => Ok(match code {
+                        Some(ResponseCode::AppendUid(uid_validity, uids)) => {
+                            expand_uid_set(uids).first().map(|&uid| AppendedUid {
+                                uid_validity: *uid_validity,
+                                uid,
+                            })
+                        }
+                        _ => None,
+                    }),
+                    _ => Err(anyhow!(
+                        "Server rejected APPEND to {}: {}",
+                        folder,
+                        information.as_deref().unwrap_or("no details")
+                    )),
+                };
+            }
+        }
 
-        self.session
-            .uid_copy(&uid_set, dest_folder)
-            .await
-            .context(format!(
-                "Failed to copy messages to folder: {}",
-                dest_folder
-            ))?;
+        Err(anyhow!("Connection lost while waiting for server response"))
+    }
 
-        Ok(())
+    /// Copy messages to a destination folder.
+    /// Returns a map of source UID -> new UID when the server reports COPYUID (UIDPLUS).
+    pub async fn copy_messages(
+        &mut self,
+        uids: &[u32],
+        dest_folder: &str,
+    ) -> Result<HashMap<u32, u32>> {
+        if uids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        self.run_copyuid_command(
+            &format!("UID COPY {} {}", uid_set(uids), quote_mailbox(dest_folder)),
+            dest_folder,
+        )
+        .await
+        .context(format!(
+            "Failed to copy messages to folder: {}",
+            dest_folder
+        ))
     }
 
-    /// Move messages to a destination folder (COPY + DELETE + EXPUNGE)
-    pub async fn move_messages(&mut self, uids: &[u32], dest_folder: &str) -> Result<()> {
+    /// Move messages to a destination folder.
+    /// Uses UID MOVE when supported; otherwise COPY + \Deleted + an expunge scoped to
+    /// the moved UIDs (UID EXPUNGE) so other clients' deleted messages are left alone.
+    /// Returns a map of source UID -> new UID when the server reports COPYUID.
+    pub async fn move_messages(
+        &mut self,
+        uids: &[u32],
+        dest_folder: &str,
+    ) -> Result<HashMap<u32, u32>> {
         if uids.is_empty() {
-            return Ok(());
+            return Ok(HashMap::new());
+        }
+
+        if self.supports_move() {
+            return self
+                .run_copyuid_command(
+                    &format!("UID MOVE {} {}", uid_set(uids), quote_mailbox(dest_folder)),
+                    dest_folder,
+                )
+                .await
+                .context(format!(
+                    "Failed to move messages to folder: {}",
+                    dest_folder
+                ));
         }
 
         // Copy to destination
-        self.copy_messages(uids, dest_folder).await?;
+        let uid_map = self.copy_messages(uids, dest_folder).await?;
 
         // Mark as deleted in source
         self.mark_messages_deleted(uids).await?;
 
-        // Expunge deleted messages
-        self.expunge().await?;
+        // Expunge only what we moved
+        self.expunge_uids(uids).await?;
 
-        Ok(())
+        Ok(uid_map)
+    }
+
+    /// Run a UID COPY/MOVE command and collect the COPYUID response code.
+    /// COPYUID arrives in the tagged OK for COPY and in an untagged OK for MOVE.
+    async fn run_copyuid_command(
+        &mut self,
+        command: &str,
+        dest_folder: &str,
+    ) -> Result<HashMap<u32, u32>> {
+        let tag = self.session.run_command(command).await?;
+        let mut uid_map = HashMap::new();
+
+        while let Some(response) = self.session.read_response().await? {
+            match response.parsed() {
+                Response::Done {
+                    tag: done_tag,
+                    status,
+                    code,
+                    information,
+                } if *done_tag == tag => {
+                    if let Some(ResponseCode::CopyUid(_, source, dest)) = code {
+                        uid_map.extend(map_copyuid(source, dest));
+                    }
+                    return match status {
+                        Status::Ok => Ok(uid_map),
+                        _ => Err(anyhow!(
+                            "Server rejected {} for {}: {}",
+                            command
+                                .split_whitespace()
+                                .take(2)
+                                .collect::<Vec<_>>()
+                                .join(" "),
+                            dest_folder,
+                            information.as_deref().unwrap_or("no details")
+                        )),
+                    };
+                }
+                Response::Data {
+                    status: Status::Ok,
+                    code: Some(ResponseCode::CopyUid(_, source, dest)),
+                    ..
+                } => {
+                    uid_map.extend(map_copyuid(source, dest));
+                }
+                // Untagged EXPUNGE/EXISTS etc. from the move itself
+                _ => {}
+            }
+        }
+
+        Err(anyhow!("Connection lost while waiting for server response"))
+    }
+
+    /// UID SORT (RFC 5256): UIDs matching `search` in the order given by `criteria`
+    async fn uid_sort(&mut self, criteria: &str, search: &str) -> Result<Vec<u32>> {
+        let command = format!("UID SORT ({}) UTF-8 {}", criteria, search);
+        let tag = self.session.run_command(&command).await?;
+        let mut uids = Vec::new();
+
+        while let Some(response) = self.session.read_response().await? {
+            match response.parsed() {
+                Response::Done {
+                    tag: done_tag,
+                    status,
+                    information,
+                    ..
+                } if *done_tag == tag => {
+                    return match status {
+                        Status::Ok => Ok(uids),
+                        _ => Err(anyhow!(
+                            "Server rejected SORT: {}",
+                            information.as_deref().unwrap_or("no details")
+                        )),
+                    };
+                }
+                Response::MailboxData(MailboxDatum::Sort(sorted)) => {
+                    uids.extend(sorted.iter().copied());
+                }
+                _ => {}
+            }
+        }
+
+        Err(anyhow!("Connection lost while waiting for server response"))
+    }
+
+    /// Find a message's UID in a folder by its Message-ID header
+    pub async fn find_uid_by_message_id(
+        &mut self,
+        folder: &str,
+        message_id: &str,
+    ) -> Result<Option<u32>> {
+        self.select_folder(folder).await?;
+
+        let query = format!(
+            "HEADER Message-ID \"{}\"",
+            message_id.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        let uids = self
+            .session
+            .uid_search(&query)
+            .await
+            .context("Failed to search by Message-ID")?;
+
+        // Newest wins if the same Message-ID appears more than once
+        Ok(uids.into_iter().max())
+    }
+
+    /// UIDs in the selected folder of the messages with the given Message-IDs, keyed by
+    /// Message-ID without angle brackets (newest UID wins for duplicates). IDs that aren't
+    /// found are left out. Searches a few IDs per command instead of one each.
+    pub async fn find_uids_by_message_ids(
+        &mut self,
+        message_ids: &[String],
+    ) -> Result<HashMap<String, u32>> {
+        let mut found = HashSet::new();
+        for batch in message_ids.chunks(8) {
+            let query = batch
+                .iter()
+                .map(|id| {
+                    format!(
+                        "HEADER Message-ID \"{}\"",
+                        id.replace('\\', "\\\\").replace('"', "\\\"")
+                    )
+                })
+                .reduce(|acc, term| format!("OR {} {}", acc, term))
+                .unwrap_or_default();
+            found.extend(
+                self.session
+                    .uid_search(&query)
+                    .await
+                    .context("Failed to search by Message-ID")?,
+            );
+        }
+
+        // SEARCH HEADER matches substrings, so read back which message is which
+        let wanted: HashSet<&str> = message_ids.iter().map(|id| bare_message_id(id)).collect();
+        let mut uids: Vec<u32> = found.into_iter().collect();
+        uids.sort_unstable();
+        let mut uids_by_id = HashMap::new();
+        for batch in chunk_uids(&uids, FETCH_BATCH_SIZE) {
+            for (message_id, uid) in self.fetch_message_ids(&uid_set(&batch)).await? {
+                if wanted.contains(message_id.as_str()) {
+                    let entry = uids_by_id.entry(message_id).or_insert(uid);
+                    *entry = (*entry).max(uid);
+                }
+            }
+        }
+
+        Ok(uids_by_id)
+    }
+
+    /// UIDs in the selected folder of messages that have one of `message_ids` as their
+    /// Message-ID or mention it in In-Reply-To/References (i.e. belong to the same thread)
+    pub async fn search_related(&mut self, message_ids: &[String]) -> Result<HashSet<u32>> {
+        let mut uids = HashSet::new();
+
+        // A few IDs per SEARCH keeps the command line short
+        for batch in message_ids.chunks(8) {
+            let query = batch
+                .iter()
+                .map(|id| {
+                    let quoted = format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+                    format!(
+                        "OR OR HEADER Message-ID {q} HEADER In-Reply-To {q} HEADER References {q}",
+                        q = quoted
+                    )
+                })
+                .reduce(|acc, term| format!("OR ({}) ({})", acc, term))
+                .unwrap_or_default();
+
+            uids.extend(
+                self.session
+                    .uid_search(&query)
+                    .await
+                    .context("Failed to search for related messages")?,
+            );
+        }
+
+        Ok(uids)
     }
 
     /// Mark messages with \Deleted flag
@@ -566,6 +1347,46 @@ impl ImapClient {
         Ok(())
     }
 
+    /// Expunge only the given UIDs (UID EXPUNGE). Without UIDPLUS a plain EXPUNGE is only
+    /// issued when no other message in the folder is flagged \Deleted; otherwise the
+    /// messages are left flagged and a warning is printed.
+    pub async fn expunge_uids(&mut self, uids: &[u32]) -> Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        if !self.supports_uidplus() {
+            let targets: HashSet<u32> = uids.iter().copied().collect();
+            let deleted = self
+                .session
+                .uid_search("DELETED")
+                .await
+                .context("Failed to search deleted messages")?;
+            let others = deleted.iter().filter(|uid| !targets.contains(uid)).count();
+            if others == 0 {
+                return self.expunge().await;
+            }
+            eprintln!(
+                "Warning: the server lacks UID EXPUNGE and {} other message(s) in this folder \
+                 are flagged \\Deleted; left {} message(s) flagged \\Deleted instead of \
+                 expunging the whole folder",
+                others,
+                uids.len()
+            );
+            return Ok(());
+        }
+
+        let expunge_stream = self
+            .session
+            .uid_expunge(uid_set(uids))
+            .await
+            .context("Failed to expunge messages")?;
+
+        // Collect the stream to consume it and complete the operation
+        let _: Vec<_> = expunge_stream.collect().await;
+
+        Ok(())
+    }
+
     /// Modify flags on messages (add or remove)
     pub async fn modify_flags(&mut self, uids: &[u32], flags: &str, add: bool) -> Result<()> {
         if uids.is_empty() {
@@ -666,3 +1487,355 @@ impl ImapClient {
         Ok(())
     }
 }
+
+/// Wire name of a storable flag; `\Recent` and `\*` can't be set so are skipped
+fn flag_name(flag: &Flag) -> Option<String> {
+    match flag {
+        Flag::Seen => Some("\\Seen".to_string()),
+        Flag::Answered => Some("\\Answered".to_string()),
+        Flag::Flagged => Some("\\Flagged".to_string()),
+        Flag::Deleted => Some("\\Deleted".to_string()),
+        Flag::Draft => Some("\\Draft".to_string()),
+        Flag::Custom(name) => Some(name.to_string()),
+        Flag::Recent | Flag::MayCreate => None,
+    }
+}
+
+/// Parse a full RFC822 message (or just its header block) into a Message.
+/// Flags and raw bytes are left for the caller to fill in.
+pub fn parse_message(uid: u32, bytes: &[u8]) -> Option<Message> {
+    let parsed_mail = mail_parser::MessageParser::default().parse(bytes)?;
+    let mut message = Message::new(uid);
+
+    // Extract subject
+    message.subject = parsed_mail.subject().map(String::from);
+    message.message_id = parsed_mail.message_id().map(String::from);
+    message.in_reply_to = parsed_mail.in_reply_to().as_text().map(String::from);
+    message.references = parsed_mail
+        .references()
+        .as_text_list()
+        .map(|ids| ids.into_iter().map(String::from).collect())
+        .unwrap_or_default();
+
+    // Extract from address
+    if let Some(from_addr) = parsed_mail.from().and_then(|addrs| addrs.first()) {
+        if let Some(email) = from_addr.address() {
+            message.from = Some(EmailAddress::new(
+                email.to_string(),
+                from_addr.name().map(String::from),
+            ));
+        }
+    }
+
+    // Extract to addresses
+    if let Some(to_addrs) = parsed_mail.to() {
+        if let Some(addrs) = to_addrs.as_list() {
+            for addr in addrs {
+                if let Some(email) = addr.address() {
+                    message.to.push(EmailAddress::new(
+                        email.to_string(),
+                        addr.name().map(String::from),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Extract cc addresses
+    if let Some(cc_addrs) = parsed_mail.cc() {
+        if let Some(addrs) = cc_addrs.as_list() {
+            for addr in addrs {
+                if let Some(email) = addr.address() {
+                    message.cc.push(EmailAddress::new(
+                        email.to_string(),
+                        addr.name().map(String::from),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Extract bcc addresses
+    if let Some(bcc_addrs) = parsed_mail.bcc() {
+        if let Some(addrs) = bcc_addrs.as_list() {
+            for addr in addrs {
+                if let Some(email) = addr.address() {
+                    message.bcc.push(EmailAddress::new(
+                        email.to_string(),
+                        addr.name().map(String::from),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Extract reply-to
+    if let Some(reply_to_addrs) = parsed_mail.reply_to() {
+        if let Some(addrs) = reply_to_addrs.as_list() {
+            if let Some(addr) = addrs.first() {
+                if let Some(email) = addr.address() {
+                    message.reply_to = Some(EmailAddress::new(
+                        email.to_string(),
+                        addr.name().map(String::from),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Extract date
+    if let Some(date) = parsed_mail.date() {
+        // Convert mail_parser::DateTime to chrono::DateTime<Utc>
+        message.date =
+            Some(DateTime::from_timestamp(date.to_timestamp(), 0).unwrap_or_else(Utc::now));
+    }
+
+    // Extract body text and HTML
+    message.body_text = parsed_mail.body_text(0).map(String::from);
+    message.body_html = parsed_mail.body_html(0).map(String::from);
+
+    // Extract attachment metadata
+    message.attachments = extract_attachments(&parsed_mail);
+
+    // Extract headers
+    for header in parsed_mail.headers() {
+        let name = header.name().to_string();
+        if let Some(value) = header.value().as_text() {
+            message.headers.insert(name, value.to_string());
+        }
+    }
+
+    Some(message)
+}
+
+/// Collect `(UID FLAGS)` fetch rows, skipping the ones that fail to parse
+async fn collect_flags(
+    mut stream: impl futures::Stream<Item = async_imap::error::Result<async_imap::types::Fetch>> + Unpin,
+) -> FlagsFetch {
+    let mut fetched = FlagsFetch::default();
+    while let Some(fetch_result) = stream.next().await {
+        let Ok(fetch) = fetch_result else {
+            fetched.skipped += 1;
+            continue;
+        };
+        if let Some(uid) = fetch.uid {
+            let flags: Vec<_> = fetch.flags().collect();
+            fetched
+                .flags
+                .insert(uid, MessageFlags::from_imap_flags(&flags));
+        }
+        if let Some(modseq) = fetch.modseq {
+            fetched.highest_modseq = Some(fetched.highest_modseq.map_or(modseq, |m| m.max(modseq)));
+        }
+    }
+    fetched
+}
+
+/// A Message-ID without surrounding whitespace and angle brackets, as mail-parser gives it
+pub fn bare_message_id(message_id: &str) -> &str {
+    message_id
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+}
+
+/// Format UIDs as an IMAP sequence set ("1,2,5")
+pub(crate) fn uid_set(uids: &[u32]) -> String {
+    uids.iter()
+        .map(|u| u.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Reorder fetched messages to follow `uids` (FETCH responses come back in server order)
+fn order_by_uids(messages: &mut [Message], uids: &[u32]) {
+    let position: HashMap<u32, usize> = uids.iter().enumerate().map(|(i, &u)| (u, i)).collect();
+    messages.sort_by_key(|m| position.get(&m.uid).copied().unwrap_or(usize::MAX));
+}
+
+/// Quote a mailbox name for use in a raw IMAP command
+fn quote_mailbox(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Expand a UidSetMember list into individual UIDs
+fn expand_uid_set(members: &[UidSetMember]) -> Vec<u32> {
+    members
+        .iter()
+        .flat_map(|member| match member {
+            UidSetMember::Uid(uid) => *uid..=*uid,
+            UidSetMember::UidRange(range) => range.clone(),
+        })
+        .collect()
+}
+
+/// Pair source and destination UIDs from a COPYUID response code (same order per RFC 4315)
+fn map_copyuid(source: &[UidSetMember], dest: &[UidSetMember]) -> Vec<(u32, u32)> {
+    expand_uid_set(source)
+        .into_iter()
+        .zip(expand_uid_set(dest))
+        .collect()
+}
+
+/// Collect attachment metadata from an IMAP BODYSTRUCTURE, mirroring what
+/// mail-parser reports as attachments when the full message is parsed
+pub fn attachments_from_bodystructure(structure: &BodyStructure) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    collect_bodystructure_attachments(structure, &mut attachments);
+    attachments
+}
+
+fn collect_bodystructure_attachments(structure: &BodyStructure, attachments: &mut Vec<Attachment>) {
+    let (common, other, is_text) = match structure {
+        BodyStructure::Multipart { bodies, .. } => {
+            for body in bodies {
+                collect_bodystructure_attachments(body, attachments);
+            }
+            return;
+        }
+        BodyStructure::Basic { common, other, .. } => (common, other, false),
+        BodyStructure::Message { common, other, .. } => (common, other, false),
+        BodyStructure::Text { common, other, .. } => (common, other, true),
+    };
+
+    let disposition = common.disposition.as_ref();
+    let filename = disposition
+        .and_then(|d| find_param(&d.params, "filename"))
+        .or_else(|| find_param(&common.ty.params, "name"));
+    let is_attachment_disposition =
+        disposition.is_some_and(|d| d.ty.eq_ignore_ascii_case("attachment"));
+
+    // Text parts without a filename are message bodies, everything else is an attachment
+    if is_text && !is_attachment_disposition && filename.is_none() {
+        return;
+    }
+
+    attachments.push(Attachment {
+        part: attachments.len() + 1,
+        filename,
+        content_type: format!("{}/{}", common.ty.ty, common.ty.subtype).to_lowercase(),
+        size: other.octets as usize,
+        content_id: other
+            .id
+            .as_ref()
+            .map(|id| id.trim_matches(|c| c == '<' || c == '>').to_string()),
+    });
+}
+
+/// Look up a MIME parameter, handling RFC 2231 (`name*=utf-8''...`) and encoded-word values
+fn find_param(params: &BodyParams, name: &str) -> Option<String> {
+    let params = params.as_ref()?;
+    let extended = format!("{}*", name);
+
+    for (key, value) in params {
+        if key.eq_ignore_ascii_case(&extended) {
+            return Some(decode_rfc2231_value(value));
+        }
+    }
+    params
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| decode_encoded_words(value))
+}
+
+/// Decode an RFC 2231 extended value: charset'language'percent-encoded
+fn decode_rfc2231_value(value: &str) -> String {
+    let encoded = value.splitn(3, '\'').nth(2).unwrap_or(value);
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut chars = encoded.bytes();
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = chars.by_ref().take(2).collect();
+            match std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                Some(decoded) => bytes.push(decoded),
+                None => {
+                    bytes.push(b'%');
+                    bytes.extend(hex);
+                }
+            }
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Decode RFC 2047 encoded words (=?utf-8?B?...?=) by letting mail-parser parse a header
+fn decode_encoded_words(value: &str) -> String {
+    if !value.contains("=?") {
+        return value.to_string();
+    }
+    let header = format!("Subject: {}\r\n\r\n", value);
+    mail_parser::MessageParser::default()
+        .parse(header.as_bytes())
+        .and_then(|m| m.subject().map(String::from))
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bare_message_id() {
+        assert_eq!(bare_message_id(" <abc@example.com> "), "abc@example.com");
+        assert_eq!(bare_message_id("abc@example.com"), "abc@example.com");
+    }
+
+    #[test]
+    fn test_mailbox_changes_track_exists_and_expunge() {
+        let mut changes = MailboxChanges::default();
+        changes.record(&Response::Expunge(4));
+        assert_eq!(changes.exists, None);
+        changes.record(&Response::MailboxData(MailboxDatum::Exists(10)));
+        changes.record(&Response::Expunge(2));
+        changes.record(&Response::Fetch(7, vec![]));
+        assert_eq!(
+            changes,
+            MailboxChanges {
+                exists: Some(9),
+                expunged: 2,
+                fetched: vec![7],
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_rfc2231_value() {
+        assert_eq!(
+            decode_rfc2231_value("utf-8''r%C3%A9sum%C3%A9.pdf"),
+            "résumé.pdf"
+        );
+        assert_eq!(decode_rfc2231_value("plain.txt"), "plain.txt");
+        assert_eq!(decode_rfc2231_value("utf-8''100%zz"), "100%zz");
+    }
+
+    #[test]
+    fn test_decode_encoded_words() {
+        assert_eq!(decode_encoded_words("report.pdf"), "report.pdf");
+        assert_eq!(
+            decode_encoded_words("=?UTF-8?B?csOpc3Vtw6kucGRm?="),
+            "résumé.pdf"
+        );
+    }
+
+    #[test]
+    fn test_map_copyuid_expands_ranges() {
+        let source = vec![UidSetMember::Uid(4), UidSetMember::UidRange(7..=9)];
+        let dest = vec![UidSetMember::UidRange(101..=104)];
+        assert_eq!(
+            map_copyuid(&source, &dest),
+            vec![(4, 101), (7, 102), (8, 103), (9, 104)]
+        );
+    }
+
+    #[test]
+    fn test_uid_set_and_quote_mailbox() {
+        assert_eq!(uid_set(&[1, 2, 5]), "1,2,5");
+        assert_eq!(quote_mailbox("Folders/Work"), "\"Folders/Work\"");
+        assert_eq!(quote_mailbox("a\"b"), "\"a\\\"b\"");
+    }
+}
@@ -1,4 +1,6 @@
 pub mod auth;
+pub mod cache;
 pub mod imap;
 pub mod smtp;
 pub mod state;
+pub mod thread;
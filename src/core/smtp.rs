@@ -2,6 +2,7 @@ use crate::core::auth::KeychainManager;
 use crate::models::account::{Account, SecurityType};
 use anyhow::{Context, Result};
 use lettre::{
+    address::Envelope,
     message::Message,
     transport::smtp::{authentication::Credentials, response::Response},
     SmtpTransport, Transport,
@@ -42,9 +43,17 @@ impl SmtpClient {
         Ok(Self { transport })
     }
 
-    pub fn send_message(&mut self, message: Message) -> Result<Response> {
+    /// Send a message; returns the server response and the exact RFC822 bytes sent
+    pub fn send_message(&mut self, message: Message) -> Result<(Response, Vec<u8>)> {
+        let raw = message.formatted();
+        let response = self.send_raw(message.envelope(), &raw)?;
+        Ok((response, raw))
+    }
+
+    /// Send an already formatted RFC822 message to the envelope's recipients
+    pub fn send_raw(&mut self, envelope: &Envelope, raw: &[u8]) -> Result<Response> {
         self.transport
-            .send(&message)
+            .send_raw(envelope, raw)
             .context("Failed to send email via SMTP")
     }
 }
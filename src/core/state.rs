@@ -1,10 +1,13 @@
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
+use crate::core::imap::FolderStatus;
+use crate::models::message::{Message, MessageFlags};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{migrate::MigrateDatabase, sqlite::SqlitePool, FromRow, Sqlite};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 /// Type alias for selection/query entry tuple: (uid, folder, message_id, subject, shadow_uid)
@@ -48,6 +51,42 @@ pub struct MessageRecord {
     pub agent_read: bool,
 }
 
+/// What the full-text index holds for one message (see `index_message_texts`)
+#[derive(Debug, Clone)]
+pub struct IndexedText {
+    pub shadow_uid: i64,
+    pub subject: String,
+    pub addresses: String,
+    /// None keeps the body already indexed
+    pub body: Option<String>,
+}
+
+/// A full-text search result, best match first
+#[derive(Debug, Clone, FromRow)]
+pub struct TextSearchHit {
+    pub shadow_uid: i64,
+    pub folder: String,
+    pub uid: i64,
+    pub message_id: String,
+    pub subject: Option<String>,
+    pub from_address: Option<String>,
+    pub date_sent: Option<String>,
+    /// bm25 score (lower is a better match)
+    pub score: f64,
+    /// Matching excerpt with terms wrapped in `**`
+    pub snippet: String,
+}
+
+/// What the state DB last recorded about a folder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FolderState {
+    pub uid_validity: Option<u32>,
+    pub uid_next: Option<u32>,
+    /// HIGHESTMODSEQ at the last completed sync
+    pub highest_modseq: Option<u64>,
+    pub last_synced: Option<String>,
+}
+
 /// Resolved message location for operations
 #[derive(Debug, Clone)]
 #[allow(dead_code)] // shadow_uid included for debugging/logging purposes
@@ -146,6 +185,143 @@ pub struct Draft {
     pub permanent: bool,
 }
 
+impl Draft {
+    /// What the draft does, without its folder and UIDs
+    pub fn operation(&self) -> Operation {
+        Operation {
+            account: self.account.clone(),
+            action_type: self.action_type.clone(),
+            flag_params: self.flag_params.clone(),
+            dest_folder: self.dest_folder.clone(),
+            permanent: self.permanent,
+        }
+    }
+}
+
+/// What an action does, independent of the messages it applies to
+#[derive(Debug, Clone, Serialize)]
+pub struct Operation {
+    pub account: String,
+    pub action_type: ActionType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flag_params: Option<FlagParams>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dest_folder: Option<String>,
+    pub permanent: bool,
+}
+
+/// A draft operation waiting in the queue
+#[derive(Debug, Clone, Serialize)]
+pub struct StagedDraft {
+    pub id: i64,
+    pub created_at: String,
+    #[serde(flatten)]
+    pub draft: Draft,
+}
+
+/// A message touched by a logged action, with what's needed to reverse it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedMessage {
+    pub shadow_uid: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    pub source_folder: String,
+    /// Flags before a flag action ran (system flags as `\Seen`, keywords as-is)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_flags: Vec<String>,
+}
+
+/// An executed operation from the action log
+#[derive(Debug, Clone, Serialize)]
+pub struct LoggedAction {
+    pub id: i64,
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undone_at: Option<String>,
+    #[serde(flatten)]
+    pub operation: Operation,
+    pub messages: Vec<LoggedMessage>,
+}
+
+#[derive(FromRow)]
+struct ActionLogRow {
+    id: i64,
+    account: String,
+    action_type: String,
+    messages_json: String,
+    flag_params_json: Option<String>,
+    dest_folder: Option<String>,
+    permanent: bool,
+    created_at: String,
+    undone_at: Option<String>,
+}
+
+impl ActionLogRow {
+    fn into_logged(self) -> Result<LoggedAction> {
+        let action_type = ActionType::from_str(&self.action_type).ok_or_else(|| {
+            anyhow::anyhow!("Invalid action type in action log: {}", self.action_type)
+        })?;
+        let messages: Vec<LoggedMessage> = serde_json::from_str(&self.messages_json)?;
+        let flag_params: Option<FlagParams> = self
+            .flag_params_json
+            .map(|s| serde_json::from_str(&s))
+            .transpose()?;
+
+        Ok(LoggedAction {
+            id: self.id,
+            created_at: self.created_at,
+            undone_at: self.undone_at,
+            operation: Operation {
+                account: self.account,
+                action_type,
+                flag_params,
+                dest_folder: self.dest_folder,
+                permanent: self.permanent,
+            },
+            messages,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct DraftRow {
+    id: i64,
+    account: String,
+    action_type: String,
+    folder: String,
+    uids_json: String,
+    flag_params_json: Option<String>,
+    dest_folder: Option<String>,
+    permanent: bool,
+    created_at: String,
+}
+
+impl DraftRow {
+    fn into_staged(self) -> Result<StagedDraft> {
+        let action_type = ActionType::from_str(&self.action_type)
+            .ok_or_else(|| anyhow::anyhow!("Invalid action type in draft: {}", self.action_type))?;
+        let uids: Vec<u32> = serde_json::from_str(&self.uids_json)?;
+        let flag_params: Option<FlagParams> = self
+            .flag_params_json
+            .map(|s| serde_json::from_str(&s))
+            .transpose()?;
+
+        Ok(StagedDraft {
+            id: self.id,
+            created_at: self.created_at,
+            draft: Draft {
+                account: self.account,
+                action_type,
+                folder: self.folder,
+                uids,
+                flag_params,
+                dest_folder: self.dest_folder,
+                permanent: self.permanent,
+            },
+        })
+    }
+}
+
 pub struct StateManager {
     pool: SqlitePool,
 }
@@ -260,6 +436,70 @@ impl StateManager {
                 .context("Failed to mark migration 004 as applied")?;
         }
 
+        let migration_005 = include_str!("../../migrations/005_message_cache.sql");
+        sqlx::query(migration_005)
+            .execute(&pool)
+            .await
+            .context("Failed to run migration 005")?;
+
+        let migration_006 = include_str!("../../migrations/006_fulltext_search.sql");
+        sqlx::query(migration_006)
+            .execute(&pool)
+            .await
+            .context("Failed to run migration 006")?;
+
+        // Run migration 007 - folder sync state (uses separate statements for ALTER TABLE)
+        let migration_007_applied: Option<(i32,)> =
+            sqlx::query_as("SELECT version FROM schema_migrations WHERE version = 7")
+                .fetch_optional(&pool)
+                .await
+                .context("Failed to check migration 007 status")?;
+
+        if migration_007_applied.is_none() {
+            for column in ["uid_validity", "uid_next", "highest_modseq"] {
+                let _ = sqlx::query(&format!(
+                    "ALTER TABLE folders ADD COLUMN {} INTEGER",
+                    column
+                ))
+                .execute(&pool)
+                .await;
+            }
+
+            sqlx::query("INSERT OR IGNORE INTO schema_migrations (version) VALUES (7)")
+                .execute(&pool)
+                .await
+                .context("Failed to mark migration 007 as applied")?;
+        }
+
+        // Run migration 008 - draft queue (table rebuild, so only once)
+        let migration_008_applied: Option<(i32,)> =
+            sqlx::query_as("SELECT version FROM schema_migrations WHERE version = 8")
+                .fetch_optional(&pool)
+                .await
+                .context("Failed to check migration 008 status")?;
+
+        if migration_008_applied.is_none() {
+            // An interrupted rebuild would leave `drafts` dropped, so all or nothing
+            let migration_008 = include_str!("../../migrations/008_draft_queue.sql");
+            let mut tx = pool
+                .begin()
+                .await
+                .context("Failed to start migration 008")?;
+            sqlx::query(migration_008)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to run migration 008")?;
+            tx.commit()
+                .await
+                .context("Failed to commit migration 008")?;
+        }
+
+        let migration_009 = include_str!("../../migrations/009_action_log.sql");
+        sqlx::query(migration_009)
+            .execute(&pool)
+            .await
+            .context("Failed to run migration 009")?;
+
         Ok(Self { pool })
     }
 
@@ -526,8 +766,8 @@ impl StateManager {
     // Draft methods
     // ============================================================
 
-    /// Save a draft operation (replaces any existing draft for the account)
-    pub async fn save_draft(&self, draft: &Draft) -> Result<()> {
+    /// Append an operation to the account's draft queue, returning its draft ID
+    pub async fn save_draft(&self, draft: &Draft) -> Result<i64> {
         let uids_json = serde_json::to_string(&draft.uids)?;
         let flag_params_json = draft
             .flag_params
@@ -535,18 +775,10 @@ impl StateManager {
             .map(serde_json::to_string)
             .transpose()?;
 
-        sqlx::query(
+        let result = sqlx::query(
             r#"
             INSERT INTO drafts (account, action_type, folder, uids_json, flag_params_json, dest_folder, permanent)
             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            ON CONFLICT(account) DO UPDATE SET
-                action_type = ?2,
-                folder = ?3,
-                uids_json = ?4,
-                flag_params_json = ?5,
-                dest_folder = ?6,
-                permanent = ?7,
-                created_at = CURRENT_TIMESTAMP
             "#,
         )
         .bind(&draft.account)
@@ -560,75 +792,157 @@ impl StateManager {
         .await
         .context("Failed to save draft")?;
 
-        Ok(())
+        Ok(result.last_insert_rowid())
     }
 
-    /// Get the current draft for an account
-    #[allow(clippy::type_complexity)]
-    pub async fn get_draft(&self, account: &str) -> Result<Option<Draft>> {
-        let row: Option<(
-            String,
-            String,
-            String,
-            String,
-            Option<String>,
-            Option<String>,
-            bool,
-        )> = sqlx::query_as(
+    /// All staged operations for an account, in the order they were staged
+    pub async fn list_drafts(&self, account: &str) -> Result<Vec<StagedDraft>> {
+        let rows: Vec<DraftRow> = sqlx::query_as(
             r#"
-            SELECT account, action_type, folder, uids_json, flag_params_json, dest_folder, permanent
+            SELECT id, account, action_type, folder, uids_json, flag_params_json, dest_folder,
+                   permanent, CAST(created_at AS TEXT) AS created_at
             FROM drafts
             WHERE account = ?1
+            ORDER BY id ASC
             "#,
         )
         .bind(account)
-        .fetch_optional(&self.pool)
+        .fetch_all(&self.pool)
         .await
-        .context("Failed to get draft")?;
+        .context("Failed to list drafts")?;
 
-        match row {
-            Some((
-                account,
-                action_type,
-                folder,
-                uids_json,
-                flag_params_json,
-                dest_folder,
-                permanent,
-            )) => {
-                let action_type = ActionType::from_str(&action_type).ok_or_else(|| {
-                    anyhow::anyhow!("Invalid action type in draft: {}", action_type)
-                })?;
-                let uids: Vec<u32> = serde_json::from_str(&uids_json)?;
-                let flag_params: Option<FlagParams> = flag_params_json
-                    .map(|s| serde_json::from_str(&s))
-                    .transpose()?;
-
-                Ok(Some(Draft {
-                    account,
-                    action_type,
-                    folder,
-                    uids,
-                    flag_params,
-                    dest_folder,
-                    permanent,
-                }))
-            }
-            None => Ok(None),
-        }
+        rows.into_iter().map(DraftRow::into_staged).collect()
     }
 
-    /// Clear the draft for an account
-    pub async fn clear_draft(&self, account: &str) -> Result<bool> {
-        let result = sqlx::query("DELETE FROM drafts WHERE account = ?1")
+    /// Remove one staged operation. Returns false if it didn't exist.
+    pub async fn drop_draft(&self, account: &str, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM drafts WHERE account = ?1 AND id = ?2")
             .bind(account)
+            .bind(id)
             .execute(&self.pool)
             .await
-            .context("Failed to clear draft")?;
+            .context("Failed to drop draft")?;
 
         Ok(result.rows_affected() > 0)
     }
 
+    /// Clear every staged operation for an account, returning how many were removed
+    pub async fn clear_drafts(&self, account: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM drafts WHERE account = ?1")
+            .bind(account)
+            .execute(&self.pool)
+            .await
+            .context("Failed to clear drafts")?;
+
+        Ok(result.rows_affected())
+    }
+
+    // ============================================================
+    // Action log methods
+    // ============================================================
+
+    /// Record an executed operation and the messages it touched, returning the log ID
+    pub async fn log_action(&self, op: &Draft, messages: &[LoggedMessage]) -> Result<i64> {
+        let messages_json = serde_json::to_string(messages)?;
+        let flag_params_json = op
+            .flag_params
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO action_log (account, action_type, messages_json, flag_params_json, dest_folder, permanent)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+        )
+        .bind(&op.account)
+        .bind(op.action_type.as_str())
+        .bind(&messages_json)
+        .bind(&flag_params_json)
+        .bind(&op.dest_folder)
+        .bind(op.permanent)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record action")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Most recent logged actions for an account, newest first
+    pub async fn list_actions(&self, account: &str, limit: usize) -> Result<Vec<LoggedAction>> {
+        let rows: Vec<ActionLogRow> = sqlx::query_as(
+            r#"
+            SELECT id, account, action_type, messages_json, flag_params_json, dest_folder, permanent,
+                   CAST(created_at AS TEXT) AS created_at, CAST(undone_at AS TEXT) AS undone_at
+            FROM action_log
+            WHERE account = ?1
+            ORDER BY id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(account)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list actions")?;
+
+        rows.into_iter().map(ActionLogRow::into_logged).collect()
+    }
+
+    /// A logged action by ID, or the most recent one not yet undone
+    pub async fn get_action(&self, account: &str, id: Option<i64>) -> Result<Option<LoggedAction>> {
+        let row: Option<ActionLogRow> = sqlx::query_as(
+            r#"
+            SELECT id, account, action_type, messages_json, flag_params_json, dest_folder, permanent,
+                   CAST(created_at AS TEXT) AS created_at, CAST(undone_at AS TEXT) AS undone_at
+            FROM action_log
+            WHERE account = ?1 AND (id = ?2 OR (?2 IS NULL AND undone_at IS NULL))
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(account)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to get action")?;
+
+        row.map(ActionLogRow::into_logged).transpose()
+    }
+
+    /// Replace the messages of a logged action, e.g. with the ones a partial undo left
+    pub async fn set_action_messages(
+        &self,
+        account: &str,
+        id: i64,
+        messages: &[LoggedMessage],
+    ) -> Result<()> {
+        sqlx::query("UPDATE action_log SET messages_json = ?1 WHERE account = ?2 AND id = ?3")
+            .bind(serde_json::to_string(messages)?)
+            .bind(account)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update action")?;
+
+        Ok(())
+    }
+
+    /// Mark a logged action as reversed
+    pub async fn mark_action_undone(&self, account: &str, id: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE action_log SET undone_at = CURRENT_TIMESTAMP WHERE account = ?1 AND id = ?2",
+        )
+        .bind(account)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .context("Failed to mark action as undone")?;
+
+        Ok(())
+    }
+
     // ============================================================
     // Shadow UID methods
     // ============================================================
@@ -714,6 +1028,26 @@ impl StateManager {
         Ok(record)
     }
 
+    /// Find the shadow UID of the message currently at folder/uid, if known
+    pub async fn find_shadow_uid_by_location(
+        &self,
+        account: &str,
+        folder: &str,
+        uid: u32,
+    ) -> Result<Option<i64>> {
+        let result: Option<(i64,)> = sqlx::query_as(
+            "SELECT id FROM messages WHERE account = ?1 AND folder = ?2 AND uid = ?3",
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(uid as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to look up message by location")?;
+
+        Ok(result.map(|r| r.0))
+    }
+
     /// Resolve shadow UIDs to their current IMAP locations
     /// Returns a list of resolved messages with their current folder and IMAP UID
     pub async fn resolve_shadow_uids(
@@ -776,4 +1110,482 @@ impl StateManager {
 
         Ok(())
     }
+
+    // ============================================================
+    // Message cache methods
+    // ============================================================
+
+    /// Drop cached entries of a folder that belong to a different UIDVALIDITY.
+    /// Returns the number of removed entries.
+    pub async fn invalidate_cache_folder(
+        &self,
+        account: &str,
+        folder: &str,
+        uid_validity: u32,
+    ) -> Result<usize> {
+        let result = sqlx::query(
+            "DELETE FROM message_cache WHERE account = ?1 AND folder = ?2 AND uid_validity != ?3",
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(uid_validity as i64)
+        .execute(&self.pool)
+        .await
+        .context("Failed to invalidate message cache")?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    /// Cached UIDs of a folder with their flags and whether the body is cached
+    pub async fn cached_uids(
+        &self,
+        account: &str,
+        folder: &str,
+        uid_validity: u32,
+    ) -> Result<HashMap<u32, (MessageFlags, bool)>> {
+        let rows: Vec<(i64, String, bool)> = sqlx::query_as(
+            r#"
+            SELECT uid, flags_json, raw IS NOT NULL
+            FROM message_cache
+            WHERE account = ?1 AND folder = ?2 AND uid_validity = ?3
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(uid_validity as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to read message cache")?;
+
+        rows.into_iter()
+            .map(|(uid, flags_json, has_body)| {
+                let flags: MessageFlags = serde_json::from_str(&flags_json)?;
+                Ok((uid as u32, (flags, has_body)))
+            })
+            .collect()
+    }
+
+    /// Insert or refresh a cached message. A header-only update never discards a cached body.
+    pub async fn store_cached_message(
+        &self,
+        account: &str,
+        folder: &str,
+        uid_validity: u32,
+        message: &Message,
+        raw: Option<&[u8]>,
+    ) -> Result<()> {
+        let mut entry = message.clone();
+        entry.raw_message = None;
+        entry.shadow_uid = None;
+        entry.agent_read = None;
+        let message_json = serde_json::to_string(&entry)?;
+        let flags_json = serde_json::to_string(&message.flags)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO message_cache (account, folder, uid_validity, uid, message_id, flags_json, message_json, raw)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT(account, folder, uid_validity, uid) DO UPDATE SET
+                message_id = excluded.message_id,
+                flags_json = excluded.flags_json,
+                message_json = CASE
+                    WHEN excluded.raw IS NOT NULL OR message_cache.raw IS NULL THEN excluded.message_json
+                    ELSE message_cache.message_json
+                END,
+                raw = COALESCE(excluded.raw, message_cache.raw),
+                cached_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(uid_validity as i64)
+        .bind(message.uid as i64)
+        .bind(&message.message_id)
+        .bind(&flags_json)
+        .bind(&message_json)
+        .bind(raw)
+        .execute(&self.pool)
+        .await
+        .context("Failed to store cached message")?;
+
+        Ok(())
+    }
+
+    /// Update the cached flags of a message
+    pub async fn update_cached_flags(
+        &self,
+        account: &str,
+        folder: &str,
+        uid_validity: u32,
+        uid: u32,
+        flags: &MessageFlags,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE message_cache
+            SET flags_json = ?5
+            WHERE account = ?1 AND folder = ?2 AND uid_validity = ?3 AND uid = ?4
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(uid_validity as i64)
+        .bind(uid as i64)
+        .bind(serde_json::to_string(flags)?)
+        .execute(&self.pool)
+        .await
+        .context("Failed to update cached flags")?;
+
+        Ok(())
+    }
+
+    /// Remove cached messages that no longer exist on the server
+    pub async fn remove_cached_uids(
+        &self,
+        account: &str,
+        folder: &str,
+        uid_validity: u32,
+        uids: &[u32],
+    ) -> Result<usize> {
+        let mut removed = 0;
+        for uid in uids {
+            let result = sqlx::query(
+                "DELETE FROM message_cache WHERE account = ?1 AND folder = ?2 AND uid_validity = ?3 AND uid = ?4",
+            )
+            .bind(account)
+            .bind(folder)
+            .bind(uid_validity as i64)
+            .bind(*uid as i64)
+            .execute(&self.pool)
+            .await
+            .context("Failed to remove cached message")?;
+            removed += result.rows_affected() as usize;
+        }
+        Ok(removed)
+    }
+
+    /// Load all cached messages of a folder (without raw bytes)
+    pub async fn load_cached_messages(&self, account: &str, folder: &str) -> Result<Vec<Message>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT message_json, flags_json
+            FROM message_cache
+            WHERE account = ?1 AND folder = ?2
+            ORDER BY uid DESC
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to load cached messages")?;
+
+        rows.into_iter()
+            .map(|(message_json, flags_json)| {
+                let mut message: Message = serde_json::from_str(&message_json)?;
+                message.flags = serde_json::from_str(&flags_json)?;
+                message.folder = Some(folder.to_string());
+                Ok(message)
+            })
+            .collect()
+    }
+
+    /// Load one cached message with its raw source, if the body has been cached
+    pub async fn load_cached_message(
+        &self,
+        account: &str,
+        folder: &str,
+        uid: u32,
+    ) -> Result<Option<(Message, Option<Vec<u8>>)>> {
+        let row: Option<(String, String, Option<Vec<u8>>)> = sqlx::query_as(
+            r#"
+            SELECT message_json, flags_json, raw
+            FROM message_cache
+            WHERE account = ?1 AND folder = ?2 AND uid = ?3
+            ORDER BY cached_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(uid as i64)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to load cached message")?;
+
+        row.map(|(message_json, flags_json, raw)| {
+            let mut message: Message = serde_json::from_str(&message_json)?;
+            message.flags = serde_json::from_str(&flags_json)?;
+            message.folder = Some(folder.to_string());
+            Ok((message, raw))
+        })
+        .transpose()
+    }
+
+    /// Record that a folder was synced now
+    pub async fn mark_folder_synced(
+        &self,
+        account: &str,
+        folder: &str,
+        status: &FolderStatus,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO folders (account, folder_path, last_synced, uid_validity, uid_next, highest_modseq)
+            VALUES (?1, ?2, CURRENT_TIMESTAMP, ?3, ?4, ?5)
+            ON CONFLICT(account, folder_path) DO UPDATE SET
+                last_synced = CURRENT_TIMESTAMP,
+                uid_validity = ?3,
+                uid_next = ?4,
+                highest_modseq = ?5
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(status.uid_validity)
+        .bind(status.uid_next)
+        .bind(status.highest_modseq.map(|m| m as i64))
+        .execute(&self.pool)
+        .await
+        .context("Failed to record folder sync time")?;
+
+        Ok(())
+    }
+
+    /// Record UIDVALIDITY and UIDNEXT from a SELECT. The stored HIGHESTMODSEQ is kept
+    /// (it belongs to the last sync) unless UIDVALIDITY changed, which voids it.
+    pub async fn record_folder_status(
+        &self,
+        account: &str,
+        folder: &str,
+        status: &FolderStatus,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO folders (account, folder_path, uid_validity, uid_next)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(account, folder_path) DO UPDATE SET
+                highest_modseq = CASE WHEN uid_validity IS ?3 THEN highest_modseq ELSE NULL END,
+                uid_validity = ?3,
+                uid_next = ?4
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .bind(status.uid_validity)
+        .bind(status.uid_next)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record folder status")?;
+
+        Ok(())
+    }
+
+    /// Sync state recorded for a folder, if it has been seen before
+    pub async fn folder_state(&self, account: &str, folder: &str) -> Result<Option<FolderState>> {
+        #[derive(FromRow)]
+        struct FolderRow {
+            uid_validity: Option<i64>,
+            uid_next: Option<i64>,
+            highest_modseq: Option<i64>,
+            last_synced: Option<String>,
+        }
+
+        let row: Option<FolderRow> = sqlx::query_as(
+            r#"
+            SELECT uid_validity, uid_next, highest_modseq, last_synced
+            FROM folders WHERE account = ?1 AND folder_path = ?2
+            "#,
+        )
+        .bind(account)
+        .bind(folder)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to read folder state")?;
+
+        Ok(row.map(|row| FolderState {
+            uid_validity: row.uid_validity.map(|v| v as u32),
+            uid_next: row.uid_next.map(|v| v as u32),
+            highest_modseq: row.highest_modseq.map(|v| v as u64),
+            last_synced: row.last_synced,
+        }))
+    }
+
+    /// Re-point the shadow UIDs of a folder after a UIDVALIDITY change, using the
+    /// server's Message-ID -> UID map. Messages no longer in the folder get UID 0
+    /// (unknown) rather than keeping a UID that now names another message.
+    /// Returns (remapped, orphaned).
+    pub async fn remap_folder_uids(
+        &self,
+        account: &str,
+        folder: &str,
+        uids_by_message_id: &HashMap<String, u32>,
+    ) -> Result<(usize, usize)> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, message_id FROM messages WHERE account = ?1 AND folder = ?2",
+        )
+        .bind(account)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to load messages for UID remap")?;
+
+        let mut tx = self.pool.begin().await?;
+        let (mut remapped, mut orphaned) = (0, 0);
+        for (id, message_id) in rows {
+            let uid = match uids_by_message_id.get(&message_id) {
+                Some(&uid) => {
+                    remapped += 1;
+                    uid
+                }
+                None => {
+                    orphaned += 1;
+                    0
+                }
+            };
+            sqlx::query("UPDATE messages SET uid = ?1 WHERE id = ?2")
+                .bind(uid)
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to remap message UID")?;
+        }
+        tx.commit().await?;
+
+        Ok((remapped, orphaned))
+    }
+
+    /// When a folder was last synced (UTC, "YYYY-MM-DD HH:MM:SS"), if ever
+    pub async fn folder_last_synced(&self, account: &str, folder: &str) -> Result<Option<String>> {
+        let result: Option<(Option<String>,)> = sqlx::query_as(
+            "SELECT last_synced FROM folders WHERE account = ?1 AND folder_path = ?2",
+        )
+        .bind(account)
+        .bind(folder)
+        .fetch_optional(&self.pool)
+        .await
+        .context("Failed to read folder sync time")?;
+
+        Ok(result.and_then(|r| r.0))
+    }
+
+    // ============================================================
+    // Full-text search methods
+    // ============================================================
+
+    /// Add or refresh messages in the full-text index (keyed by shadow UID), in one
+    /// transaction. When a body is None an already indexed body is kept, so header-only
+    /// fetches never erase text indexed by `read` or `sync`.
+    pub async fn index_message_texts(&self, texts: &[IndexedText]) -> Result<()> {
+        if texts.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for text in texts {
+            let existing: Option<(String,)> =
+                sqlx::query_as("SELECT body FROM message_fts WHERE rowid = ?1")
+                    .bind(text.shadow_uid)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .context("Failed to read full-text index")?;
+            let body = text
+                .body
+                .as_deref()
+                .map(String::from)
+                .or(existing.map(|(b,)| b))
+                .unwrap_or_default();
+
+            // FTS5 has no upsert; replace the row
+            sqlx::query("DELETE FROM message_fts WHERE rowid = ?1")
+                .bind(text.shadow_uid)
+                .execute(&mut *tx)
+                .await
+                .context("Failed to update full-text index")?;
+            sqlx::query(
+                "INSERT INTO message_fts (rowid, subject, addresses, body) VALUES (?1, ?2, ?3, ?4)",
+            )
+            .bind(text.shadow_uid)
+            .bind(&text.subject)
+            .bind(&text.addresses)
+            .bind(body)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update full-text index")?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Rank indexed messages of an account against an FTS5 match expression with bm25
+    /// (subject weighs most, then addresses, then body)
+    pub async fn search_text(
+        &self,
+        account: &str,
+        match_expr: &str,
+        folder: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<TextSearchHit>> {
+        let hits: Vec<TextSearchHit> = sqlx::query_as(
+            r#"
+            SELECT m.id AS shadow_uid, m.folder, m.uid, m.message_id, m.subject,
+                   m.from_address, m.date_sent,
+                   bm25(message_fts, 10.0, 5.0, 1.0) AS score,
+                   snippet(message_fts, -1, '**', '**', '…', 16) AS snippet
+            FROM message_fts
+            JOIN messages m ON m.id = message_fts.rowid
+            WHERE message_fts MATCH ?1 AND m.account = ?2 AND (?3 IS NULL OR m.folder = ?3)
+            ORDER BY score
+            LIMIT ?4
+            "#,
+        )
+        .bind(match_expr)
+        .bind(account)
+        .bind(folder)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Full-text search failed")?;
+
+        Ok(hits)
+    }
+
+    /// IMAP UIDs of indexed messages in a folder matching an FTS5 match expression
+    pub async fn search_text_uids(
+        &self,
+        account: &str,
+        folder: &str,
+        match_expr: &str,
+    ) -> Result<HashSet<u32>> {
+        let rows: Vec<(i64,)> = sqlx::query_as(
+            r#"
+            SELECT m.uid
+            FROM message_fts
+            JOIN messages m ON m.id = message_fts.rowid
+            WHERE message_fts MATCH ?1 AND m.account = ?2 AND m.folder = ?3
+            "#,
+        )
+        .bind(match_expr)
+        .bind(account)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("Full-text search failed")?;
+
+        Ok(rows.into_iter().map(|(uid,)| uid as u32).collect())
+    }
+
+    /// Number of messages of an account in the full-text index
+    pub async fn indexed_message_count(&self, account: &str) -> Result<usize> {
+        let count: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM message_fts JOIN messages m ON m.id = message_fts.rowid WHERE m.account = ?1",
+        )
+        .bind(account)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to count indexed messages")?;
+
+        Ok(count.0 as usize)
+    }
 }
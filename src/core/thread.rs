@@ -0,0 +1,427 @@
+//! Conversation threading (JWZ algorithm) over Message-ID, In-Reply-To and References.
+//!
+//! Server-side IMAP THREAD (RFC 5256) is not used: the IMAP response parser has no
+//! support for THREAD data, and an unparsed untagged response breaks the session.
+
+use crate::models::message::Message;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A message's place in a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadEntry {
+    /// Index into the messages passed to `build_threads`
+    pub index: usize,
+    /// Reply depth (0 = start of the conversation, or a reply to a message not present)
+    pub depth: usize,
+}
+
+/// One conversation in reading order: depth-first, replies sorted by date
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversation {
+    pub entries: Vec<ThreadEntry>,
+}
+
+impl Conversation {
+    /// Whether the conversation contains the message at `index`
+    pub fn contains(&self, index: usize) -> bool {
+        self.entries.iter().any(|e| e.index == index)
+    }
+}
+
+/// One line of a `--threads` listing
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSummary {
+    /// Shadow UID of the newest message (use with `thread`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub subject: Option<String>,
+    pub count: usize,
+    pub unread: usize,
+    /// Senders in order of first appearance
+    pub participants: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_date: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_date: Option<DateTime<Utc>>,
+    /// Shadow UIDs of all messages, in reading order
+    pub ids: Vec<i64>,
+}
+
+#[derive(Default)]
+struct Container {
+    message: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+fn normalize_id(id: &str) -> Option<String> {
+    let id = id
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim();
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// Strip reply/forward prefixes ("Re:", "Fwd:", "AW:", "Re[2]:") from a subject.
+/// Returns the base subject (lowercased) and whether a prefix was removed.
+pub fn normalize_subject(subject: &str) -> (String, bool) {
+    let mut rest = subject.trim();
+    let mut stripped = false;
+
+    while let Some(colon) = rest.find(':') {
+        let prefix = rest[..colon].trim().to_lowercase();
+        let base = prefix.split('[').next().unwrap_or("").trim();
+        if matches!(base, "re" | "fw" | "fwd" | "aw" | "wg" | "sv" | "vs") {
+            rest = rest[colon + 1..].trim_start();
+            stripped = true;
+        } else {
+            break;
+        }
+    }
+
+    (rest.trim().to_lowercase(), stripped)
+}
+
+struct Threader {
+    containers: Vec<Container>,
+    by_id: HashMap<String, usize>,
+}
+
+impl Threader {
+    fn new_container(&mut self) -> usize {
+        self.containers.push(Container::default());
+        self.containers.len() - 1
+    }
+
+    fn lookup(&mut self, id: &str) -> usize {
+        if let Some(&c) = self.by_id.get(id) {
+            return c;
+        }
+        let c = self.new_container();
+        self.by_id.insert(id.to_string(), c);
+        c
+    }
+
+    /// Whether `ancestor` is `node` or one of its parents
+    fn is_ancestor(&self, ancestor: usize, node: usize) -> bool {
+        let mut current = Some(node);
+        while let Some(c) = current {
+            if c == ancestor {
+                return true;
+            }
+            current = self.containers[c].parent;
+        }
+        false
+    }
+
+    fn set_parent(&mut self, child: usize, parent: usize) {
+        if let Some(old) = self.containers[child].parent.take() {
+            self.containers[old].children.retain(|&c| c != child);
+        }
+        self.containers[child].parent = Some(parent);
+        self.containers[parent].children.push(child);
+    }
+
+    /// Replace empty containers below `id` by their children
+    fn prune_children(&mut self, id: usize) {
+        let children = std::mem::take(&mut self.containers[id].children);
+        let mut kept = Vec::new();
+        for child in children {
+            self.prune_children(child);
+            if self.containers[child].message.is_some() {
+                kept.push(child);
+            } else {
+                kept.append(&mut self.containers[child].children);
+            }
+        }
+        self.containers[id].children = kept;
+    }
+
+    /// Earliest date in a subtree, used to order replies
+    fn sort_date(&self, id: usize, messages: &[Message]) -> Option<DateTime<Utc>> {
+        let container = &self.containers[id];
+        match container.message {
+            Some(index) => messages[index].date,
+            None => container
+                .children
+                .iter()
+                .filter_map(|&c| self.sort_date(c, messages))
+                .min(),
+        }
+    }
+
+    fn emit(&self, id: usize, depth: usize, messages: &[Message], out: &mut Vec<ThreadEntry>) {
+        let container = &self.containers[id];
+        let child_depth = match container.message {
+            Some(index) => {
+                out.push(ThreadEntry { index, depth });
+                depth + 1
+            }
+            None => depth,
+        };
+
+        let mut children = container.children.clone();
+        children.sort_by_key(|&c| (self.sort_date(c, messages), c));
+        for child in children {
+            self.emit(child, child_depth, messages, out);
+        }
+    }
+
+    /// Base subject of a root and whether it carries a reply prefix
+    fn root_subject(&self, id: usize, messages: &[Message]) -> Option<(String, bool)> {
+        let container = &self.containers[id];
+        let index = match container.message {
+            Some(index) => index,
+            None => container
+                .children
+                .first()
+                .and_then(|&c| self.containers[c].message)?,
+        };
+        let (subject, is_reply) = normalize_subject(messages[index].subject.as_deref()?);
+        (!subject.is_empty()).then_some((subject, is_reply))
+    }
+}
+
+/// Group messages into conversations, newest conversation first.
+/// Follows JWZ: link References chains, use In-Reply-To as the direct parent, drop
+/// placeholder containers for missing messages, then gather "Re:" roots by subject.
+pub fn build_threads(messages: &[Message]) -> Vec<Conversation> {
+    let mut threader = Threader {
+        containers: Vec::new(),
+        by_id: HashMap::new(),
+    };
+
+    for (index, message) in messages.iter().enumerate() {
+        let own = match message.message_id.as_deref().and_then(normalize_id) {
+            Some(id) => {
+                let c = threader.lookup(&id);
+                if threader.containers[c].message.is_some() {
+                    // Duplicate Message-ID: thread it separately
+                    threader.new_container()
+                } else {
+                    c
+                }
+            }
+            None => threader.new_container(),
+        };
+        threader.containers[own].message = Some(index);
+
+        let mut chain: Vec<String> = message
+            .references
+            .iter()
+            .filter_map(|id| normalize_id(id))
+            .collect();
+        if let Some(parent) = message.in_reply_to.as_deref().and_then(normalize_id) {
+            if chain.last() != Some(&parent) {
+                chain.push(parent);
+            }
+        }
+
+        // Link each reference to the next, without overriding earlier links or making loops
+        let mut previous: Option<usize> = None;
+        for id in &chain {
+            let c = threader.lookup(id);
+            if let Some(p) = previous {
+                if threader.containers[c].parent.is_none() && !threader.is_ancestor(c, p) {
+                    threader.set_parent(c, p);
+                }
+            }
+            previous = Some(c);
+        }
+
+        // The last reference is this message's parent, replacing any earlier guess
+        if let Some(p) = previous {
+            if !threader.is_ancestor(own, p) {
+                threader.set_parent(own, p);
+            }
+        }
+    }
+
+    let mut roots = Vec::new();
+    for id in 0..threader.containers.len() {
+        if threader.containers[id].parent.is_some() {
+            continue;
+        }
+        threader.prune_children(id);
+        let container = &threader.containers[id];
+        match (container.message, container.children.len()) {
+            (Some(_), _) => roots.push(id),
+            (None, 0) => {}
+            (None, 1) => roots.push(container.children[0]),
+            // Missing start of a conversation with several replies: keep them together
+            (None, _) => roots.push(id),
+        }
+    }
+
+    // Gather roots whose subject is a reply to another root's subject
+    let mut by_subject: HashMap<String, (usize, bool)> = HashMap::new();
+    for &root in &roots {
+        let Some((subject, is_reply)) = threader.root_subject(root, messages) else {
+            continue;
+        };
+        // Prefer a root without a reply prefix as the conversation start
+        let replace = match by_subject.get(&subject) {
+            None => true,
+            Some(&(_, existing_is_reply)) => existing_is_reply && !is_reply,
+        };
+        if replace {
+            by_subject.insert(subject, (root, is_reply));
+        }
+    }
+
+    let mut top_level = Vec::new();
+    for root in roots {
+        let target = threader
+            .root_subject(root, messages)
+            .filter(|(_, is_reply)| *is_reply)
+            .and_then(|(subject, _)| by_subject.get(&subject).map(|&(r, _)| r))
+            .filter(|&target| target != root);
+        match target {
+            Some(target) => {
+                threader.containers[root].parent = Some(target);
+                threader.containers[target].children.push(root);
+            }
+            None => top_level.push(root),
+        }
+    }
+
+    let mut conversations: Vec<Conversation> = top_level
+        .into_iter()
+        .map(|root| {
+            let mut entries = Vec::new();
+            threader.emit(root, 0, messages, &mut entries);
+            Conversation { entries }
+        })
+        .filter(|c| !c.entries.is_empty())
+        .collect();
+
+    conversations.sort_by_key(|c| {
+        std::cmp::Reverse(
+            c.entries
+                .iter()
+                .filter_map(|e| messages[e.index].date)
+                .max(),
+        )
+    });
+    conversations
+}
+
+/// Counts, participants and dates of a conversation
+pub fn summarize(conversation: &Conversation, messages: &[Message]) -> ConversationSummary {
+    let thread: Vec<&Message> = conversation
+        .entries
+        .iter()
+        .map(|e| &messages[e.index])
+        .collect();
+
+    let mut participants: Vec<String> = Vec::new();
+    for message in &thread {
+        if let Some(ref from) = message.from {
+            let name = from.name.clone().unwrap_or_else(|| from.address.clone());
+            if !participants.contains(&name) {
+                participants.push(name);
+            }
+        }
+    }
+
+    let newest = thread.iter().max_by_key(|m| (m.date, m.uid));
+
+    ConversationSummary {
+        id: newest.and_then(|m| m.shadow_uid),
+        subject: thread.first().and_then(|m| m.subject.clone()),
+        count: thread.len(),
+        unread: thread.iter().filter(|m| !m.flags.seen).count(),
+        participants,
+        first_date: thread.iter().filter_map(|m| m.date).min(),
+        last_date: thread.iter().filter_map(|m| m.date).max(),
+        ids: thread.iter().filter_map(|m| m.shadow_uid).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn message(uid: u32, id: &str, subject: &str, refs: &[&str], day: u32) -> Message {
+        let mut message = Message::new(uid);
+        message.message_id = Some(id.to_string());
+        message.subject = Some(subject.to_string());
+        message.references = refs.iter().map(|r| r.to_string()).collect();
+        message.in_reply_to = refs.last().map(|r| r.to_string());
+        message.date = Some(Utc.with_ymd_and_hms(2024, 5, day, 9, 0, 0).unwrap());
+        message.shadow_uid = Some(uid as i64);
+        message
+    }
+
+    fn indices(conversation: &Conversation) -> Vec<(usize, usize)> {
+        conversation
+            .entries
+            .iter()
+            .map(|e| (e.index, e.depth))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_threads_links_replies() {
+        let messages = vec![
+            message(1, "a@x", "Plan", &[], 1),
+            message(2, "b@x", "Re: Plan", &["a@x"], 2),
+            message(3, "c@x", "Re: Plan", &["a@x", "b@x"], 4),
+            message(4, "d@x", "Re: Plan", &["a@x"], 3),
+            message(5, "e@x", "Lunch?", &[], 5),
+        ];
+
+        let threads = build_threads(&messages);
+        assert_eq!(threads.len(), 2);
+        // Newest conversation first
+        assert_eq!(indices(&threads[0]), vec![(4, 0)]);
+        assert_eq!(indices(&threads[1]), vec![(0, 0), (1, 1), (2, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn test_build_threads_missing_root_and_subject_grouping() {
+        let messages = vec![
+            // Replies to a message we don't have
+            message(1, "b@x", "Re: Budget", &["a@x"], 2),
+            message(2, "c@x", "Re: Budget", &["a@x"], 3),
+            // A reply without headers, grouped by subject
+            message(3, "d@x", "RE: Re: budget", &[], 4),
+        ];
+
+        let threads = build_threads(&messages);
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].entries.len(), 3);
+
+        let summary = summarize(&threads[0], &messages);
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.id, Some(3));
+    }
+
+    #[test]
+    fn test_build_threads_ignores_reference_loops() {
+        let messages = vec![
+            message(1, "a@x", "Loop", &["b@x"], 1),
+            message(2, "b@x", "Loop", &["a@x"], 2),
+        ];
+        let threads = build_threads(&messages);
+        let total: usize = threads.iter().map(|t| t.entries.len()).sum();
+        assert_eq!(total, 2);
+    }
+
+    #[test]
+    fn test_normalize_subject() {
+        assert_eq!(
+            normalize_subject("Re: Fwd: AW: Report"),
+            ("report".to_string(), true)
+        );
+        assert_eq!(normalize_subject("Re[2]: x"), ("x".to_string(), true));
+        assert_eq!(
+            normalize_subject("Meeting: Monday"),
+            ("meeting: monday".to_string(), false)
+        );
+    }
+}
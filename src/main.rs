@@ -11,6 +11,9 @@ use clap::{Parser, Subcommand};
 #[command(version = "0.4.2")]
 #[command(about = "A production-ready CLI email client for ProtonMail Bridge", long_about = None)]
 struct Cli {
+    /// Account to use (defaults to the configured default account)
+    #[arg(long, global = true, env = "PROTONCLI_ACCOUNT")]
+    account: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -39,10 +42,10 @@ enum Commands {
         /// Folder(s) to search (can be specified multiple times)
         #[arg(long, short = 'F')]
         folder: Vec<String>,
-        /// Comma-separated fields to display (uid,subject,from,date,flags,message_id)
+        /// Comma-separated fields to display (uid,folder,message_id,subject,from,to,cc,date,size,flags,labels,preview)
         #[arg(long, short = 'f')]
         fields: Option<String>,
-        /// Output format (json, markdown, text)
+        /// Output format (json, ndjson, csv, tsv, markdown, text)
         #[arg(long, short)]
         output: Option<String>,
         /// Add results to selection
@@ -54,6 +57,27 @@ enum Commands {
         /// Include body preview
         #[arg(long)]
         preview: bool,
+        /// Answer from the local cache without connecting (see `sync`)
+        #[arg(long)]
+        offline: bool,
+        /// Collapse results into conversations with message counts
+        #[arg(long)]
+        threads: bool,
+        /// Sort by date, from, subject, size or arrival (applied before --limit)
+        #[arg(long, value_name = "KEY")]
+        sort: Option<String>,
+        /// Reverse the sort order (oldest/smallest first, or Z to A)
+        #[arg(long, requires = "sort")]
+        reverse: bool,
+        /// Return one page of N messages, newest UID first (JSON includes next_cursor)
+        #[arg(long, value_name = "N", conflicts_with_all = ["limit", "sort"])]
+        page_size: Option<usize>,
+        /// Continue after the page that returned this next_cursor
+        #[arg(long, requires = "page_size")]
+        cursor: Option<String>,
+        /// Render each message with a template or a named template from config.toml
+        #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["output", "threads"])]
+        format_template: Option<String>,
     },
     /// Manage message selection
     Select {
@@ -65,6 +89,23 @@ enum Commands {
         #[command(subcommand)]
         action: DraftAction,
     },
+    /// Show executed move/copy/archive/delete/flag actions, newest first
+    History {
+        /// Maximum number of actions to show
+        #[arg(long, default_value = "20")]
+        limit: usize,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Reverse an action from 'history' (default: the most recent one)
+    Undo {
+        /// Action number (from 'history')
+        id: Option<i64>,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
     /// List inbox messages
     Inbox {
         /// Filter messages from the last N days
@@ -79,15 +120,39 @@ enum Commands {
         /// Limit number of messages
         #[arg(long)]
         limit: Option<usize>,
-        /// Output format (json, markdown, or table)
+        /// Output format (json, ndjson, csv, tsv, markdown, or table)
         #[arg(long, short)]
         output: Option<String>,
+        /// Comma-separated columns for csv/tsv output (same names as query --fields)
+        #[arg(long, short = 'f')]
+        fields: Option<String>,
         /// Query expression (Gmail-style syntax)
         #[arg(long, short = 'q', value_name = "QUERY")]
         query: Option<String>,
         /// Fetch message body previews (slower but shows content)
         #[arg(long)]
         preview: bool,
+        /// Answer from the local cache without connecting (see `sync`)
+        #[arg(long)]
+        offline: bool,
+        /// Collapse messages into conversations with message counts
+        #[arg(long)]
+        threads: bool,
+        /// Sort by date, from, subject, size or arrival (applied before --limit)
+        #[arg(long, value_name = "KEY")]
+        sort: Option<String>,
+        /// Reverse the sort order (oldest/smallest first, or Z to A)
+        #[arg(long, requires = "sort")]
+        reverse: bool,
+        /// Return one page of N messages, newest UID first (JSON includes next_cursor)
+        #[arg(long, value_name = "N", conflicts_with_all = ["limit", "sort"])]
+        page_size: Option<usize>,
+        /// Continue after the page that returned this next_cursor
+        #[arg(long, requires = "page_size")]
+        cursor: Option<String>,
+        /// Render each message with a template or a named template from config.toml
+        #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["output", "threads"])]
+        format_template: Option<String>,
     },
     /// Read a message
     Read {
@@ -96,7 +161,7 @@ enum Commands {
         /// Folder to read from (optional, resolved from shadow UID)
         #[arg(long)]
         folder: Option<String>,
-        /// Output format (json, markdown, raw)
+        /// Output format (json, markdown, text, raw)
         #[arg(long, short, default_value = "markdown")]
         output: String,
         /// Mark message as read in IMAP
@@ -105,12 +170,93 @@ enum Commands {
         /// Show raw RFC822 message
         #[arg(long)]
         raw: bool,
+        /// Read from the local cache without connecting (see `sync`)
+        #[arg(long)]
+        offline: bool,
+        /// Render the message with a template or a named template from config.toml
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "output")]
+        format_template: Option<String>,
+    },
+    /// Show the conversation a message belongs to (searches INBOX and Sent)
+    Thread {
+        /// Message ID (shadow UID from inbox/query output)
+        id: i64,
+        /// Additional folder(s) to search (can be specified multiple times)
+        #[arg(long, short = 'F')]
+        folder: Vec<String>,
+        /// Output format (json or markdown)
+        #[arg(long, short, default_value = "markdown")]
+        output: String,
     },
     /// Send an email
     Send {
-        /// Sender email address (defaults to default account if not specified)
+        #[command(flatten)]
+        message: MessageArgs,
+        /// Send a draft saved with `compose --save-draft` and remove it from Drafts
+        #[arg(long, value_name = "ID", conflicts_with_all = [
+            "from", "to", "cc", "bcc", "subject", "body", "body_file",
+            "html", "html_file", "markdown", "inline", "attach",
+        ])]
+        draft_id: Option<i64>,
+        /// Output format (json or text)
         #[arg(long, short)]
-        from: Option<String>,
+        output: Option<String>,
+    },
+    /// Write an email in $EDITOR, then send it, save it as a draft or drop it
+    Compose {
+        #[command(flatten)]
+        message: MessageArgs,
+        /// Save the message straight to the Drafts folder without opening an editor
+        /// (send it later with `send --draft-id`)
+        #[arg(long)]
+        save_draft: bool,
+        /// Reply to this message (shadow UID), starting from the quoted original
+        #[arg(long, value_name = "ID", conflicts_with_all = ["forward", "save_draft"])]
+        reply: Option<i64>,
+        /// With --reply, reply to all recipients
+        #[arg(long, requires = "reply")]
+        all: bool,
+        /// Forward this message (shadow UID), including its attachments
+        #[arg(long, value_name = "ID", conflicts_with = "save_draft")]
+        forward: Option<i64>,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Email drafts in the Drafts folder (queued operations are under `draft`)
+    DraftsMail {
+        #[command(subcommand)]
+        action: DraftsMailAction,
+    },
+    /// List and save message attachments
+    Attachments {
+        #[command(subcommand)]
+        action: AttachmentAction,
+    },
+    /// Reply to a message, quoting the original
+    Reply {
+        /// Message ID (shadow UID from inbox/query output)
+        id: i64,
+        /// Reply to all recipients (To and Cc of the original)
+        #[arg(long)]
+        all: bool,
+        /// Reply text (placed above the quoted original)
+        #[arg(long, short)]
+        body: Option<String>,
+        /// Read reply text from file
+        #[arg(long)]
+        body_file: Option<String>,
+        /// Attach files (PATH or PATH:TYPE to set the content type)
+        #[arg(long, value_name = "PATH[:TYPE]")]
+        attach: Vec<String>,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Forward a message, including its attachments
+    Forward {
+        /// Message ID (shadow UID from inbox/query output)
+        id: i64,
         /// Recipient email address(es)
         #[arg(long, required = true)]
         to: Vec<String>,
@@ -120,18 +266,56 @@ enum Commands {
         /// BCC recipient(s)
         #[arg(long)]
         bcc: Vec<String>,
-        /// Email subject
-        #[arg(long, short = 's')]
-        subject: Option<String>,
-        /// Email body text
+        /// Note to include above the forwarded message
         #[arg(long, short)]
         body: Option<String>,
-        /// Read body from file
+        /// Read note from file
         #[arg(long)]
         body_file: Option<String>,
-        /// Attach files
-        #[arg(long)]
+        /// Attach additional files (PATH or PATH:TYPE to set the content type)
+        #[arg(long, value_name = "PATH[:TYPE]")]
         attach: Vec<String>,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Watch folders for new, expunged and flag-changed messages (NDJSON on stdout)
+    Watch {
+        /// Folder(s) to watch (can be specified multiple times, default: INBOX)
+        #[arg(long, short = 'F')]
+        folder: Vec<String>,
+        /// Seconds between NOOP polls when the server doesn't support IDLE
+        #[arg(long, default_value_t = 30)]
+        poll_interval: u64,
+        /// Output format (ndjson, the default)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Refresh the local message cache used by --offline
+    Sync {
+        /// Folder(s) to sync (can be specified multiple times, default: INBOX)
+        #[arg(long, short = 'F')]
+        folder: Vec<String>,
+        /// Cache headers only (bodies are fetched later by `read` or a full sync)
+        #[arg(long)]
+        headers_only: bool,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Full-text search over locally indexed mail (requires cache_enabled)
+    Search {
+        /// Search terms: words, "quoted phrases", prefix*, AND/OR/NOT
+        terms: String,
+        /// Restrict results to one folder
+        #[arg(long, short = 'F')]
+        folder: Option<String>,
+        /// Maximum number of results
+        #[arg(long, default_value = "20")]
+        limit: usize,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
     },
     /// Show query language documentation
     QueryHelp,
@@ -145,6 +329,11 @@ enum Commands {
         /// Use selection instead of IDs
         #[arg(long, conflicts_with = "ids")]
         selection: bool,
+        #[command(flatten)]
+        query: QueryTargetArgs,
+        /// Skip confirmation when --query matches many messages
+        #[arg(long, short)]
+        yes: bool,
         /// Stage action as draft without executing
         #[arg(long)]
         draft: bool,
@@ -165,6 +354,11 @@ enum Commands {
         /// Use selection instead of IDs
         #[arg(long, conflicts_with = "ids")]
         selection: bool,
+        #[command(flatten)]
+        query: QueryTargetArgs,
+        /// Skip confirmation when --query matches many messages
+        #[arg(long, short)]
+        yes: bool,
         /// Stage action as draft without executing
         #[arg(long)]
         draft: bool,
@@ -182,12 +376,14 @@ enum Commands {
         /// Permanently delete (bypasses Trash)
         #[arg(long)]
         permanent: bool,
-        /// Skip confirmation for permanent delete
+        /// Skip confirmation (permanent delete, or many --query matches)
         #[arg(long, short)]
         yes: bool,
         /// Use selection instead of IDs
         #[arg(long, conflicts_with = "ids")]
         selection: bool,
+        #[command(flatten)]
+        query: QueryTargetArgs,
         /// Stage action as draft without executing
         #[arg(long)]
         draft: bool,
@@ -205,6 +401,11 @@ enum Commands {
         /// Use selection instead of IDs
         #[arg(long, conflicts_with = "ids")]
         selection: bool,
+        #[command(flatten)]
+        query: QueryTargetArgs,
+        /// Skip confirmation when --query matches many messages
+        #[arg(long, short)]
+        yes: bool,
         /// Stage action as draft without executing
         #[arg(long)]
         draft: bool,
@@ -243,6 +444,11 @@ enum Commands {
         /// Use selection instead of IDs
         #[arg(long, conflicts_with = "ids")]
         selection: bool,
+        #[command(flatten)]
+        query: QueryTargetArgs,
+        /// Skip confirmation when --query matches many messages
+        #[arg(long, short)]
+        yes: bool,
         /// Stage action as draft without executing
         #[arg(long)]
         draft: bool,
@@ -255,6 +461,95 @@ enum Commands {
     },
 }
 
+/// Message content shared by `send` and `compose`
+#[derive(clap::Args)]
+struct MessageArgs {
+    /// Sender email address (defaults to default account if not specified)
+    #[arg(long, short)]
+    from: Option<String>,
+    /// Recipient email address(es)
+    #[arg(long)]
+    to: Vec<String>,
+    /// CC recipient(s)
+    #[arg(long)]
+    cc: Vec<String>,
+    /// BCC recipient(s)
+    #[arg(long)]
+    bcc: Vec<String>,
+    /// Email subject
+    #[arg(long, short = 's')]
+    subject: Option<String>,
+    /// Email body text
+    #[arg(long, short)]
+    body: Option<String>,
+    /// Read body from file
+    #[arg(long)]
+    body_file: Option<String>,
+    /// HTML body (sent as multipart/alternative with a plain-text part)
+    #[arg(long, conflicts_with = "html_file")]
+    html: Option<String>,
+    /// Read the HTML body from a file
+    #[arg(long)]
+    html_file: Option<String>,
+    /// Render the body (--body / --body-file) from markdown into an HTML part
+    #[arg(long, conflicts_with_all = ["html", "html_file"])]
+    markdown: bool,
+    /// Inline image for the HTML body as CID=PATH, referenced as <img src="cid:CID">
+    #[arg(long, value_name = "CID=PATH")]
+    inline: Vec<String>,
+    /// Attach files (PATH or PATH:TYPE to set the content type)
+    #[arg(long, value_name = "PATH[:TYPE]")]
+    attach: Vec<String>,
+}
+
+impl MessageArgs {
+    fn into_content(self) -> cli::send::MessageContent {
+        cli::send::MessageContent {
+            from: self.from,
+            to: self.to,
+            cc: self.cc,
+            bcc: self.bcc,
+            subject: self.subject,
+            body: self.body,
+            body_file: self.body_file,
+            html: self.html,
+            html_file: self.html_file,
+            markdown: self.markdown,
+            inline: self.inline,
+            attach: self.attach,
+        }
+    }
+}
+
+/// Act on the messages a query matches instead of IDs or the selection
+#[derive(clap::Args)]
+struct QueryTargetArgs {
+    /// Act on messages matching a query (same syntax as `protoncli query`)
+    #[arg(long, conflicts_with_all = ["ids", "selection"])]
+    query: Option<String>,
+    /// Folder(s) to search with --query (can be specified multiple times)
+    #[arg(long, short = 'F', requires = "query")]
+    folder: Vec<String>,
+    /// Act on at most N matches (newest first)
+    #[arg(long, requires = "query")]
+    limit: Option<usize>,
+    /// List the messages --query would affect without changing anything
+    #[arg(long, requires = "query")]
+    dry_run: bool,
+}
+
+impl QueryTargetArgs {
+    fn into_target(self, yes: bool) -> Option<cli::actions::QueryTarget> {
+        Some(cli::actions::QueryTarget {
+            query: self.query?,
+            folders: self.folder,
+            limit: self.limit,
+            yes,
+            dry_run: self.dry_run,
+        })
+    }
+}
+
 #[derive(Subcommand)]
 enum AccountAction {
     /// Add a new account
@@ -310,7 +605,7 @@ enum SelectAction {
     },
     /// Show current selection
     Show {
-        /// Output format (json or text)
+        /// Output format (json, ndjson, table or text)
         #[arg(long, short)]
         output: Option<String>,
     },
@@ -328,15 +623,52 @@ enum SelectAction {
     },
 }
 
+#[derive(Subcommand)]
+enum DraftsMailAction {
+    /// List drafts, newest first (IDs work with `send --draft-id`)
+    List {
+        /// Limit number of drafts
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Output format (json, ndjson, csv, tsv, markdown, or table)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum DraftAction {
-    /// Show the current draft
-    Show {
+    /// List queued operations with the messages they will touch
+    #[command(visible_alias = "show")]
+    List {
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Execute queued operations in order (default: the oldest one)
+    Apply {
+        /// Draft number to apply (from 'draft list')
+        #[arg(conflicts_with = "all")]
+        id: Option<i64>,
+        /// Apply every queued draft in order
+        #[arg(long)]
+        all: bool,
+        /// Skip confirmation for permanent deletes
+        #[arg(long, short)]
+        yes: bool,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Discard one queued operation
+    Drop {
+        /// Draft number (from 'draft list')
+        id: i64,
         /// Output format (json or text)
         #[arg(long, short)]
         output: Option<String>,
     },
-    /// Clear the current draft
+    /// Discard all queued operations
     Clear {
         /// Output format (json or text)
         #[arg(long, short)]
@@ -344,6 +676,32 @@ enum DraftAction {
     },
 }
 
+#[derive(Subcommand)]
+enum AttachmentAction {
+    /// List the attachments of a message
+    List {
+        /// Message ID (shadow UID from inbox/query output)
+        id: i64,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Save attachments of a message to disk
+    Save {
+        /// Message ID (shadow UID from inbox/query output)
+        id: i64,
+        /// Attachment number to save (default: all)
+        #[arg(long)]
+        part: Option<usize>,
+        /// Directory to save into (default: current directory)
+        #[arg(long)]
+        dir: Option<String>,
+        /// Output format (json or text)
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum FolderAction {
     /// List all folders
@@ -419,6 +777,7 @@ enum LabelAction {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    let account = cli.account.as_deref();
 
     match cli.command {
         Commands::Account { action } => match action {
@@ -430,36 +789,36 @@ async fn main() -> anyhow::Result<()> {
         },
         Commands::Folders { action } => match action {
             None | Some(FolderAction::List { output: None }) => {
-                cli::folder::list_folders(None).await?
+                cli::folder::list_folders(account, None).await?
             }
             Some(FolderAction::List { output }) => {
-                cli::folder::list_folders(output.as_deref()).await?
+                cli::folder::list_folders(account, output.as_deref()).await?
             }
             Some(FolderAction::Create { name, output }) => {
-                cli::folder::create_folder(&name, output.as_deref()).await?
+                cli::folder::create_folder(account, &name, output.as_deref()).await?
             }
             Some(FolderAction::Delete { name, output }) => {
-                cli::folder::delete_folder(&name, output.as_deref()).await?
+                cli::folder::delete_folder(account, &name, output.as_deref()).await?
             }
             Some(FolderAction::Rename { from, to, output }) => {
-                cli::folder::rename_folder(&from, &to, output.as_deref()).await?
+                cli::folder::rename_folder(account, &from, &to, output.as_deref()).await?
             }
         },
         Commands::Labels { action } => match action {
             None | Some(LabelAction::List { output: None }) => {
-                cli::label::list_labels(None).await?
+                cli::label::list_labels(account, None).await?
             }
             Some(LabelAction::List { output }) => {
-                cli::label::list_labels(output.as_deref()).await?
+                cli::label::list_labels(account, output.as_deref()).await?
             }
             Some(LabelAction::Create { name, output }) => {
-                cli::label::create_label(&name, output.as_deref()).await?
+                cli::label::create_label(account, &name, output.as_deref()).await?
             }
             Some(LabelAction::Delete { name, output }) => {
-                cli::label::delete_label(&name, output.as_deref()).await?
+                cli::label::delete_label(account, &name, output.as_deref()).await?
             }
             Some(LabelAction::Rename { from, to, output }) => {
-                cli::label::rename_label(&from, &to, output.as_deref()).await?
+                cli::label::rename_label(account, &from, &to, output.as_deref()).await?
             }
         },
         Commands::Query {
@@ -470,8 +829,16 @@ async fn main() -> anyhow::Result<()> {
             select,
             limit,
             preview,
+            offline,
+            threads,
+            sort,
+            reverse,
+            page_size,
+            cursor,
+            format_template,
         } => {
             cli::query::execute_query(
+                account,
                 &query,
                 &folder,
                 fields.as_deref(),
@@ -479,31 +846,58 @@ async fn main() -> anyhow::Result<()> {
                 preview,
                 select,
                 output.as_deref(),
+                offline,
+                threads,
+                sort.as_deref(),
+                reverse,
+                page_size,
+                cursor.as_deref(),
+                format_template.as_deref(),
             )
             .await?
         }
         Commands::Select { action } => match action {
             SelectAction::Add { ids, output } => {
-                cli::select::add_to_selection(ids, output.as_deref()).await?
+                cli::select::add_to_selection(account, ids, output.as_deref()).await?
             }
             SelectAction::Last { folder, output } => {
-                cli::select::add_last_query_to_selection(&folder, output.as_deref()).await?
+                cli::select::add_last_query_to_selection(account, &folder, output.as_deref())
+                    .await?
             }
             SelectAction::Remove { ids, output } => {
-                cli::select::remove_from_selection(ids, output.as_deref()).await?
+                cli::select::remove_from_selection(account, ids, output.as_deref()).await?
+            }
+            SelectAction::Show { output } => {
+                cli::select::show_selection(account, output.as_deref()).await?
             }
-            SelectAction::Show { output } => cli::select::show_selection(output.as_deref()).await?,
             SelectAction::Clear { output } => {
-                cli::select::clear_selection(output.as_deref()).await?
+                cli::select::clear_selection(account, output.as_deref()).await?
             }
             SelectAction::Count { output } => {
-                cli::select::count_selection(output.as_deref()).await?
+                cli::select::count_selection(account, output.as_deref()).await?
             }
         },
         Commands::Draft { action } => match action {
-            DraftAction::Show { output } => cli::draft::show_draft(output.as_deref()).await?,
-            DraftAction::Clear { output } => cli::draft::clear_draft(output.as_deref()).await?,
+            DraftAction::List { output } => {
+                cli::draft::list_drafts(account, output.as_deref()).await?
+            }
+            DraftAction::Apply {
+                id,
+                all,
+                yes,
+                output,
+            } => cli::draft::apply_drafts(account, id, all, yes, output.as_deref()).await?,
+            DraftAction::Drop { id, output } => {
+                cli::draft::drop_draft(account, id, output.as_deref()).await?
+            }
+            DraftAction::Clear { output } => {
+                cli::draft::clear_draft(account, output.as_deref()).await?
+            }
         },
+        Commands::History { limit, output } => {
+            cli::history::show_history(account, limit, output.as_deref()).await?
+        }
+        Commands::Undo { id, output } => cli::history::undo(account, id, output.as_deref()).await?,
         Commands::Inbox {
             days,
             unread_only,
@@ -512,8 +906,17 @@ async fn main() -> anyhow::Result<()> {
             output,
             query,
             preview,
+            offline,
+            threads,
+            sort,
+            reverse,
+            page_size,
+            cursor,
+            fields,
+            format_template,
         } => {
             cli::message::list_inbox(
+                account,
                 days,
                 unread_only,
                 agent_unread,
@@ -521,6 +924,14 @@ async fn main() -> anyhow::Result<()> {
                 output.as_deref(),
                 query,
                 preview,
+                offline,
+                threads,
+                sort.as_deref(),
+                reverse,
+                page_size,
+                cursor.as_deref(),
+                fields.as_deref(),
+                format_template.as_deref(),
             )
             .await?
         }
@@ -530,54 +941,194 @@ async fn main() -> anyhow::Result<()> {
             output,
             mark_read,
             raw,
+            offline,
+            format_template,
         } => {
-            cli::message::read_message(id, folder.as_deref(), Some(&output), mark_read, raw).await?
+            cli::message::read_message(
+                account,
+                id,
+                folder.as_deref(),
+                Some(&output),
+                mark_read,
+                raw,
+                offline,
+                format_template.as_deref(),
+            )
+            .await?
+        }
+        Commands::Thread { id, folder, output } => {
+            cli::thread::show_thread(account, id, folder, Some(&output)).await?
         }
         Commands::Send {
-            from,
+            message,
+            draft_id,
+            output,
+        } => match draft_id {
+            Some(draft_id) => cli::send::send_draft(account, draft_id, output.as_deref()).await?,
+            None => {
+                cli::send::send_email(account, message.into_content(), output.as_deref()).await?
+            }
+        },
+        Commands::Compose {
+            message,
+            save_draft,
+            reply,
+            all,
+            forward,
+            output,
+        } => {
+            let content = message.into_content();
+            if save_draft {
+                cli::compose::save_draft(account, content, output.as_deref()).await?
+            } else {
+                let source = match (reply, forward) {
+                    (Some(id), _) => cli::compose::ComposeSource::Reply { id, all },
+                    (None, Some(id)) => cli::compose::ComposeSource::Forward { id },
+                    (None, None) => cli::compose::ComposeSource::New,
+                };
+                cli::compose::compose_in_editor(account, content, source, output.as_deref()).await?
+            }
+        }
+        Commands::DraftsMail { action } => match action {
+            DraftsMailAction::List { limit, output } => {
+                cli::compose::list_draft_mail(account, limit, output.as_deref()).await?
+            }
+        },
+        Commands::Attachments { action } => match action {
+            AttachmentAction::List { id, output } => {
+                cli::attachments::list_attachments(account, id, output.as_deref()).await?
+            }
+            AttachmentAction::Save {
+                id,
+                part,
+                dir,
+                output,
+            } => {
+                cli::attachments::save_attachments(
+                    account,
+                    id,
+                    part,
+                    dir.as_deref(),
+                    output.as_deref(),
+                )
+                .await?
+            }
+        },
+        Commands::Reply {
+            id,
+            all,
+            body,
+            body_file,
+            attach,
+            output,
+        } => {
+            cli::reply::reply_message(account, id, all, body, body_file, attach, output.as_deref())
+                .await?
+        }
+        Commands::Forward {
+            id,
             to,
             cc,
             bcc,
-            subject,
             body,
             body_file,
             attach,
-        } => cli::send::send_email(from, to, cc, bcc, subject, body, body_file, attach).await?,
+            output,
+        } => {
+            cli::reply::forward_message(
+                account,
+                id,
+                to,
+                cc,
+                bcc,
+                body,
+                body_file,
+                attach,
+                output.as_deref(),
+            )
+            .await?
+        }
+        Commands::Watch {
+            folder,
+            poll_interval,
+            output,
+        } => cli::watch::watch(account, folder, poll_interval, output.as_deref()).await?,
+        Commands::Sync {
+            folder,
+            headers_only,
+            output,
+        } => cli::sync::sync(account, folder, headers_only, output.as_deref()).await?,
+        Commands::Search {
+            terms,
+            folder,
+            limit,
+            output,
+        } => {
+            cli::search::search(account, &terms, folder.as_deref(), limit, output.as_deref())
+                .await?
+        }
         Commands::QueryHelp => show_query_help(),
         Commands::Move {
             ids,
             to,
             selection,
+            query,
+            yes,
             draft,
             keep,
             output,
         } => {
-            cli::actions::move_messages(ids, &to, selection, draft, keep, output.as_deref()).await?
+            cli::actions::move_messages(
+                account,
+                ids,
+                &to,
+                selection,
+                query.into_target(yes),
+                draft,
+                keep,
+                output.as_deref(),
+            )
+            .await?
         }
         Commands::Copy {
             ids,
             to,
             selection,
+            query,
+            yes,
             draft,
             keep,
             output,
         } => {
-            cli::actions::copy_messages(ids, &to, selection, draft, keep, output.as_deref()).await?
+            cli::actions::copy_messages(
+                account,
+                ids,
+                &to,
+                selection,
+                query.into_target(yes),
+                draft,
+                keep,
+                output.as_deref(),
+            )
+            .await?
         }
         Commands::Delete {
             ids,
             permanent,
             yes,
             selection,
+            query,
             draft,
             keep,
             output,
         } => {
             cli::actions::delete_messages(
+                account,
                 ids,
                 permanent,
                 yes,
                 selection,
+                query.into_target(yes),
                 draft,
                 keep,
                 output.as_deref(),
@@ -587,10 +1138,23 @@ async fn main() -> anyhow::Result<()> {
         Commands::Archive {
             ids,
             selection,
+            query,
+            yes,
             draft,
             keep,
             output,
-        } => cli::actions::archive_messages(ids, selection, draft, keep, output.as_deref()).await?,
+        } => {
+            cli::actions::archive_messages(
+                account,
+                ids,
+                selection,
+                query.into_target(yes),
+                draft,
+                keep,
+                output.as_deref(),
+            )
+            .await?
+        }
         Commands::Flag {
             ids,
             read,
@@ -601,11 +1165,14 @@ async fn main() -> anyhow::Result<()> {
             unlabels,
             move_to,
             selection,
+            query,
+            yes,
             draft,
             keep,
             output,
         } => {
             cli::actions::modify_flags(
+                account,
                 ids,
                 read,
                 unread,
@@ -615,6 +1182,7 @@ async fn main() -> anyhow::Result<()> {
                 unlabels,
                 move_to,
                 selection,
+                query.into_target(yes),
                 draft,
                 keep,
                 output.as_deref(),
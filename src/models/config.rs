@@ -1,6 +1,7 @@
 use crate::models::account::Account;
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
@@ -18,6 +19,20 @@ pub struct Preferences {
     pub cache_enabled: bool,
     #[serde(default = "default_log_level")]
     pub log_level: String,
+    /// Ask before acting on more `--query` matches than this
+    #[serde(default = "default_bulk_confirm_threshold")]
+    pub bulk_confirm_threshold: usize,
+    /// Warn before sending when attachments add up to more than this many MB (0 = never)
+    #[serde(default = "default_attachment_warning_mb")]
+    pub attachment_warning_mb: u64,
+    /// APPEND sent messages to `sent_folder` (turn off if the server files them itself)
+    #[serde(default = "default_save_sent")]
+    pub save_sent: bool,
+    #[serde(default = "default_sent_folder")]
+    pub sent_folder: String,
+    /// Where `compose --save-draft` stores drafts
+    #[serde(default = "default_drafts_folder")]
+    pub drafts_folder: String,
 }
 
 fn default_output_format() -> String {
@@ -36,6 +51,26 @@ fn default_log_level() -> String {
     "info".to_string()
 }
 
+fn default_bulk_confirm_threshold() -> usize {
+    25
+}
+
+fn default_attachment_warning_mb() -> u64 {
+    25
+}
+
+fn default_save_sent() -> bool {
+    true
+}
+
+fn default_sent_folder() -> String {
+    "Sent".to_string()
+}
+
+fn default_drafts_folder() -> String {
+    "Drafts".to_string()
+}
+
 impl Default for Preferences {
     fn default() -> Self {
         Self {
@@ -43,6 +78,11 @@ impl Default for Preferences {
             date_filter_days: default_date_filter_days(),
             cache_enabled: default_cache_enabled(),
             log_level: default_log_level(),
+            bulk_confirm_threshold: default_bulk_confirm_threshold(),
+            attachment_warning_mb: default_attachment_warning_mb(),
+            save_sent: default_save_sent(),
+            sent_folder: default_sent_folder(),
+            drafts_folder: default_drafts_folder(),
         }
     }
 }
@@ -53,6 +93,9 @@ pub struct Config {
     pub accounts: Vec<Account>,
     #[serde(default)]
     pub preferences: Preferences,
+    /// Named `--format-template` templates (`[templates]` table)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, String>,
 }
 
 impl Config {
@@ -73,6 +116,7 @@ impl Config {
             return Ok(Self {
                 accounts: vec![],
                 preferences: Preferences::default(),
+                templates: BTreeMap::new(),
             });
         }
 
@@ -120,6 +164,34 @@ impl Config {
             .or_else(|| self.accounts.first())
     }
 
+    /// Resolve the account to operate on.
+    /// Uses the explicitly requested account (--account / PROTONCLI_ACCOUNT) if given,
+    /// otherwise falls back to the default account.
+    pub fn resolve_account(&self, email: Option<&str>) -> Result<&Account> {
+        match email {
+            Some(email) => self.get_account(email).ok_or_else(|| {
+                let configured: Vec<&str> =
+                    self.accounts.iter().map(|a| a.email.as_str()).collect();
+                if configured.is_empty() {
+                    anyhow!(
+                        "Account '{}' not found. No accounts configured; add one with 'protoncli account add {}'",
+                        email,
+                        email
+                    )
+                } else {
+                    anyhow!(
+                        "Account '{}' not found in config. Configured accounts: {}",
+                        email,
+                        configured.join(", ")
+                    )
+                }
+            }),
+            None => self.get_default_account().ok_or_else(|| {
+                anyhow!("No default account configured. Please add an account first.")
+            }),
+        }
+    }
+
     pub fn add_account(&mut self, account: Account) {
         // If this is the first account or marked as default, make it default
         if self.accounts.is_empty() || account.default {
@@ -163,3 +235,74 @@ impl Config {
         found
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_config(emails: &[&str]) -> Config {
+        let mut config = Config {
+            accounts: vec![],
+            preferences: Preferences::default(),
+            templates: BTreeMap::new(),
+        };
+        for email in emails {
+            config.add_account(Account::new_protonmail_bridge(email.to_string()));
+        }
+        config
+    }
+
+    #[test]
+    fn test_resolve_account_default() {
+        let config = make_config(&["a@example.com", "b@example.com"]);
+        let account = config.resolve_account(None).unwrap();
+        assert_eq!(account.email, "a@example.com");
+    }
+
+    #[test]
+    fn test_resolve_account_explicit() {
+        let config = make_config(&["a@example.com", "b@example.com"]);
+        let account = config.resolve_account(Some("b@example.com")).unwrap();
+        assert_eq!(account.email, "b@example.com");
+    }
+
+    #[test]
+    fn test_resolve_account_unknown_lists_configured() {
+        let config = make_config(&["a@example.com"]);
+        let err = config
+            .resolve_account(Some("x@example.com"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("x@example.com"));
+        assert!(err.contains("a@example.com"));
+    }
+
+    #[test]
+    fn test_resolve_account_none_configured() {
+        let config = make_config(&[]);
+        assert!(config.resolve_account(None).is_err());
+        assert!(config.resolve_account(Some("a@example.com")).is_err());
+    }
+
+    #[test]
+    fn test_preferences_defaults_when_missing() {
+        let config: Config = toml::from_str("[preferences]\ncache_enabled = false\n").unwrap();
+        assert!(!config.preferences.cache_enabled);
+        assert_eq!(config.preferences.bulk_confirm_threshold, 25);
+        assert_eq!(config.preferences.attachment_warning_mb, 25);
+        assert!(config.preferences.save_sent);
+        assert_eq!(config.preferences.sent_folder, "Sent");
+        assert_eq!(config.preferences.drafts_folder, "Drafts");
+        assert!(config.templates.is_empty());
+    }
+
+    #[test]
+    fn test_named_templates() {
+        let config: Config = toml::from_str(
+            "[templates]\noneline = \"{id} {subject}\"\nslack = '{{\"text\": \"{subject|json}\"}}'\n",
+        )
+        .unwrap();
+        assert_eq!(config.templates["oneline"], "{id} {subject}");
+        assert!(config.templates["slack"].starts_with("{{"));
+    }
+}
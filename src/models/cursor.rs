@@ -0,0 +1,106 @@
+use crate::models::message::Message;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+/// Resume point of a paged listing (`--page-size` / `--cursor`).
+/// Pages walk each folder newest UID first; the cursor names the last message returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageCursor {
+    pub folder: String,
+    /// UIDVALIDITY of `folder` when the page was fetched
+    pub uid_validity: u32,
+    pub uid: u32,
+    /// Date of the last message, used to resume when UIDVALIDITY changed
+    pub date: Option<DateTime<Utc>>,
+}
+
+impl PageCursor {
+    /// Cursor pointing just past `message` in `folder`
+    pub fn after(message: &Message, folder: &str, uid_validity: u32) -> Self {
+        Self {
+            folder: folder.to_string(),
+            uid_validity,
+            uid: message.uid,
+            date: message.date,
+        }
+    }
+
+    /// Opaque form printed as `next_cursor`: hex folder name, UIDVALIDITY, UID, timestamp
+    pub fn encode(&self) -> String {
+        let folder: String = self.folder.bytes().map(|b| format!("{:02x}", b)).collect();
+        let date = self
+            .date
+            .map(|d| d.timestamp().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        format!("{}.{}.{}.{}", folder, self.uid_validity, self.uid, date)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid cursor '{}'. Pass the next_cursor value of a previous page.",
+                cursor
+            )
+        };
+
+        let parts: Vec<&str> = cursor.trim().split('.').collect();
+        let [folder, uid_validity, uid, date] = parts.as_slice() else {
+            return Err(invalid());
+        };
+
+        if folder.is_empty() || folder.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        let bytes = (0..folder.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&folder[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let folder = String::from_utf8(bytes).map_err(|_| invalid())?;
+
+        let date = match *date {
+            "-" => None,
+            ts => Some(
+                ts.parse::<i64>()
+                    .ok()
+                    .and_then(|ts| DateTime::from_timestamp(ts, 0))
+                    .ok_or_else(invalid)?,
+            ),
+        };
+
+        Ok(Self {
+            folder,
+            uid_validity: uid_validity.parse().map_err(|_| invalid())?,
+            uid: uid.parse().map_err(|_| invalid())?,
+            date,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let mut message = Message::new(4821);
+        message.date = DateTime::from_timestamp(1_700_000_000, 0);
+        let cursor = PageCursor::after(&message, "Folders/Café.2024", 1_234_567);
+
+        let encoded = cursor.encode();
+        assert!(!encoded.contains('/'));
+        assert_eq!(PageCursor::decode(&encoded).unwrap(), cursor);
+
+        let undated = PageCursor::after(&Message::new(7), "INBOX", 1);
+        assert_eq!(undated.encode(), "494e424f58.1.7.-");
+        assert_eq!(PageCursor::decode("494e424f58.1.7.-").unwrap(), undated);
+    }
+
+    #[test]
+    fn test_cursor_rejects_garbage() {
+        assert!(PageCursor::decode("").is_err());
+        assert!(PageCursor::decode("INBOX.1.7.-").is_err());
+        assert!(PageCursor::decode("494e424f58.1.x.-").is_err());
+        assert!(PageCursor::decode("494e424f58.1.7").is_err());
+    }
+}
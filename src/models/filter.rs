@@ -1,6 +1,156 @@
+use crate::models::message::{Attachment, EmailAddress, Message};
 use crate::models::query::{Operator, QueryExpr, QueryParser};
+use crate::models::sort::SortOrder;
 use anyhow::{anyhow, Context, Result};
-use chrono::{Duration, NaiveDate, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// Attachment condition that IMAP SEARCH cannot express; evaluated client-side from BODYSTRUCTURE
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttachmentPredicate {
+    /// has:attachment
+    Any,
+    /// filename:PATTERN (glob with * and ?, otherwise substring; case-insensitive)
+    Filename(String),
+    /// attachment-type:TYPE (image, pdf, image/png, image/*)
+    ContentType(String),
+}
+
+impl AttachmentPredicate {
+    pub fn matches(&self, attachments: &[Attachment]) -> bool {
+        match self {
+            AttachmentPredicate::Any => !attachments.is_empty(),
+            AttachmentPredicate::Filename(pattern) => attachments.iter().any(|a| {
+                a.filename
+                    .as_deref()
+                    .is_some_and(|name| filename_matches(pattern, name))
+            }),
+            AttachmentPredicate::ContentType(pattern) => attachments
+                .iter()
+                .any(|a| content_type_matches(pattern, &a.content_type)),
+        }
+    }
+}
+
+/// A query split into server-side IMAP searches and client-side checks.
+/// Server-only subtrees are kept as a single IMAP query whose matching UIDs are looked up,
+/// so AND/OR/NOT combine correctly across both kinds of conditions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientFilter {
+    Server(String),
+    Attachment(AttachmentPredicate),
+    /// text:TERMS, answered by the local full-text index (UIDs stored under `text_key`)
+    Text(String),
+    And(Box<ClientFilter>, Box<ClientFilter>),
+    Or(Box<ClientFilter>, Box<ClientFilter>),
+    Not(Box<ClientFilter>),
+}
+
+impl ClientFilter {
+    /// Key of a text: term's UID set in the map passed to `matches`
+    pub fn text_key(terms: &str) -> String {
+        format!("text:{}", terms)
+    }
+
+    /// IMAP search queries whose results are needed to evaluate this filter (deduplicated)
+    pub fn server_queries(&self) -> Vec<String> {
+        let mut queries = Vec::new();
+        self.collect_server_queries(&mut queries);
+        queries
+    }
+
+    fn collect_server_queries(&self, queries: &mut Vec<String>) {
+        match self {
+            ClientFilter::Server(query) => {
+                if !queries.contains(query) {
+                    queries.push(query.clone());
+                }
+            }
+            ClientFilter::Attachment(_) | ClientFilter::Text(_) => {}
+            ClientFilter::And(left, right) | ClientFilter::Or(left, right) => {
+                left.collect_server_queries(queries);
+                right.collect_server_queries(queries);
+            }
+            ClientFilter::Not(inner) => inner.collect_server_queries(queries),
+        }
+    }
+
+    /// Evaluate the filter for one message.
+    /// `server_matches` maps each server query (and each `text_key`) to the set of UIDs it matched.
+    pub fn matches(
+        &self,
+        uid: u32,
+        attachments: &[Attachment],
+        server_matches: &HashMap<String, HashSet<u32>>,
+    ) -> bool {
+        match self {
+            ClientFilter::Server(query) => server_matches
+                .get(query)
+                .is_some_and(|uids| uids.contains(&uid)),
+            ClientFilter::Attachment(predicate) => predicate.matches(attachments),
+            ClientFilter::Text(terms) => server_matches
+                .get(&Self::text_key(terms))
+                .is_some_and(|uids| uids.contains(&uid)),
+            ClientFilter::And(left, right) => {
+                left.matches(uid, attachments, server_matches)
+                    && right.matches(uid, attachments, server_matches)
+            }
+            ClientFilter::Or(left, right) => {
+                left.matches(uid, attachments, server_matches)
+                    || right.matches(uid, attachments, server_matches)
+            }
+            ClientFilter::Not(inner) => !inner.matches(uid, attachments, server_matches),
+        }
+    }
+}
+
+/// Case-insensitive glob match supporting `*` and `?`
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last * absorb one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn filename_matches(pattern: &str, filename: &str) -> bool {
+    if pattern.contains('*') || pattern.contains('?') {
+        glob_match(pattern, filename)
+    } else {
+        filename.to_lowercase().contains(&pattern.to_lowercase())
+    }
+}
+
+fn content_type_matches(pattern: &str, content_type: &str) -> bool {
+    let content_type = content_type.to_lowercase();
+    let pattern = pattern.to_lowercase();
+    if pattern.contains('/') {
+        glob_match(&pattern, &content_type)
+    } else {
+        // Bare word matches either half: "image" -> image/png, "pdf" -> application/pdf
+        let (ty, subtype) = content_type
+            .split_once('/')
+            .unwrap_or((content_type.as_str(), ""));
+        ty == pattern || subtype == pattern
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct MessageFilter {
@@ -10,6 +160,14 @@ pub struct MessageFilter {
     pub limit: Option<usize>,
     pub query: Option<String>,
     pub preview: bool,
+    /// UIDs matching each `text:` term in the current folder (see `cache::resolve_text_terms`)
+    pub text_matches: HashMap<String, HashSet<u32>>,
+    /// Result order; `None` keeps each command's default
+    pub sort: Option<SortOrder>,
+    /// Only UIDs below this (resuming a paged listing)
+    pub before_uid: Option<u32>,
+    /// Only messages dated before this (resuming a page after UIDVALIDITY changed)
+    pub before_date: Option<DateTime<Utc>>,
 }
 
 impl MessageFilter {
@@ -21,6 +179,10 @@ impl MessageFilter {
             limit: None,
             query: None,
             preview: false,
+            text_matches: HashMap::new(),
+            sort: None,
+            before_uid: None,
+            before_date: None,
         }
     }
 
@@ -54,6 +216,21 @@ impl MessageFilter {
         self
     }
 
+    pub fn with_sort(mut self, sort: Option<SortOrder>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn with_before_uid(mut self, uid: u32) -> Self {
+        self.before_uid = Some(uid);
+        self
+    }
+
+    pub fn with_before_date(mut self, date: DateTime<Utc>) -> Self {
+        self.before_date = Some(date);
+        self
+    }
+
     pub fn build_imap_search_query(&self) -> Result<String> {
         let mut parts = Vec::new();
 
@@ -81,6 +258,13 @@ impl MessageFilter {
             parts.push(format!("SINCE {}", date_str));
         }
 
+        // SENTBEFORE compares the Date header by day; the exact time is checked after
+        // fetching. Messages without a Date header are kept, like `matches_message` does
+        if let Some(before) = self.before_date {
+            let date_str = (before + Duration::days(1)).format("%d-%b-%Y").to_string();
+            parts.push(format!("OR SENTBEFORE {} NOT HEADER DATE \"\"", date_str));
+        }
+
         if parts.is_empty() {
             Ok("ALL".to_string())
         } else {
@@ -88,6 +272,36 @@ impl MessageFilter {
         }
     }
 
+    /// Values of all `text:` fields in the query, deduplicated
+    pub fn text_terms(&self) -> Result<Vec<String>> {
+        let Some(query_str) = self.query.as_deref().filter(|q| !q.trim().is_empty()) else {
+            return Ok(vec![]);
+        };
+        let expr = QueryParser::parse(query_str).context(format!(
+            "Invalid query syntax: '{}'\n\nRun 'protoncli query-help' to see syntax examples.",
+            query_str
+        ))?;
+
+        fn collect(expr: &QueryExpr, terms: &mut Vec<String>) {
+            match expr {
+                QueryExpr::Field { name, value, .. } => {
+                    if name.eq_ignore_ascii_case("text") && !terms.contains(value) {
+                        terms.push(value.clone());
+                    }
+                }
+                QueryExpr::And(left, right) | QueryExpr::Or(left, right) => {
+                    collect(left, terms);
+                    collect(right, terms);
+                }
+                QueryExpr::Not(inner) => collect(inner, terms),
+            }
+        }
+
+        let mut terms = Vec::new();
+        collect(&expr, &mut terms);
+        Ok(terms)
+    }
+
     fn translate_to_imap(&self, expr: &QueryExpr) -> Result<String> {
         match expr {
             QueryExpr::Field {
@@ -106,12 +320,219 @@ impl MessageFilter {
                 Ok(format!("OR ({}) ({})", left_imap, right_imap))
             }
             QueryExpr::Not(inner) => {
+                // Client-side conditions can't be negated on the server; search a superset
+                if Self::has_client_side_fields(inner) {
+                    return Ok("ALL".to_string());
+                }
                 let inner_imap = self.translate_to_imap(inner)?;
-                Ok(format!("NOT {}", inner_imap))
+                // Group multi-key searches so NOT applies to all of them
+                if matches!(inner.as_ref(), QueryExpr::And(_, _)) {
+                    Ok(format!("NOT ({})", inner_imap))
+                } else {
+                    Ok(format!("NOT {}", inner_imap))
+                }
+            }
+        }
+    }
+
+    /// Fields that IMAP SEARCH cannot express and are filtered client-side
+    fn is_client_side_field(name: &str) -> bool {
+        matches!(
+            name.to_lowercase().as_str(),
+            "has" | "filename" | "attachment-type" | "text"
+        )
+    }
+
+    fn has_client_side_fields(expr: &QueryExpr) -> bool {
+        match expr {
+            QueryExpr::Field { name, .. } => Self::is_client_side_field(name),
+            QueryExpr::And(left, right) | QueryExpr::Or(left, right) => {
+                Self::has_client_side_fields(left) || Self::has_client_side_fields(right)
+            }
+            QueryExpr::Not(inner) => Self::has_client_side_fields(inner),
+        }
+    }
+
+    /// Build the client-side filter stage for the query, if it uses any client-side fields.
+    /// The IMAP search from `build_imap_search_query` returns a superset of the matches;
+    /// this filter narrows it down to the exact result.
+    pub fn client_filter(&self) -> Result<Option<ClientFilter>> {
+        let Some(query_str) = self.query.as_deref().filter(|q| !q.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let expr = QueryParser::parse(query_str).context(format!(
+            "Invalid query syntax: '{}'\n\nRun 'protoncli query-help' to see syntax examples.",
+            query_str
+        ))?;
+
+        if !Self::has_client_side_fields(&expr) {
+            return Ok(None);
+        }
+        self.build_client_filter(&expr).map(Some)
+    }
+
+    fn build_client_filter(&self, expr: &QueryExpr) -> Result<ClientFilter> {
+        if !Self::has_client_side_fields(expr) {
+            return Ok(ClientFilter::Server(self.translate_to_imap(expr)?));
+        }
+
+        match expr {
+            QueryExpr::Field {
+                name,
+                operator,
+                value,
+            } => {
+                // Validates the field (e.g. rejects has:foo)
+                self.translate_field(name, operator, value)?;
+                if name.eq_ignore_ascii_case("text") {
+                    return Ok(ClientFilter::Text(value.clone()));
+                }
+                Ok(ClientFilter::Attachment(Self::attachment_predicate(
+                    name, value,
+                )))
+            }
+            QueryExpr::And(left, right) => Ok(ClientFilter::And(
+                Box::new(self.build_client_filter(left)?),
+                Box::new(self.build_client_filter(right)?),
+            )),
+            QueryExpr::Or(left, right) => Ok(ClientFilter::Or(
+                Box::new(self.build_client_filter(left)?),
+                Box::new(self.build_client_filter(right)?),
+            )),
+            QueryExpr::Not(inner) => Ok(ClientFilter::Not(Box::new(
+                self.build_client_filter(inner)?,
+            ))),
+        }
+    }
+
+    /// Attachment predicate for a client-side field (has, filename, attachment-type)
+    fn attachment_predicate(name: &str, value: &str) -> AttachmentPredicate {
+        match name.to_lowercase().as_str() {
+            "has" => AttachmentPredicate::Any,
+            "filename" => AttachmentPredicate::Filename(value.to_string()),
+            _ => AttachmentPredicate::ContentType(value.to_string()),
+        }
+    }
+
+    /// Evaluate the whole filter against a message locally (used by --offline on the cache).
+    /// Follows IMAP SEARCH semantics: case-insensitive substrings, dates compared by day.
+    /// `body:` only matches messages whose body has been cached.
+    pub fn matches_message(&self, message: &Message) -> Result<bool> {
+        if self.unread_only && message.flags.seen {
+            return Ok(false);
+        }
+        if self.before_uid.is_some_and(|before| message.uid >= before) {
+            return Ok(false);
+        }
+        if let Some(before) = self.before_date {
+            if message.date.is_some_and(|d| d >= before) {
+                return Ok(false);
+            }
+        }
+        if let Some(days) = self.days {
+            let since = (Utc::now() - Duration::days(days as i64)).date_naive();
+            let recent = message.date.is_some_and(|d| d.date_naive() >= since);
+            if !recent {
+                return Ok(false);
+            }
+        }
+
+        let Some(query_str) = self.query.as_deref().filter(|q| !q.trim().is_empty()) else {
+            return Ok(true);
+        };
+        let expr = QueryParser::parse(query_str).context(format!(
+            "Invalid query syntax: '{}'\n\nRun 'protoncli query-help' to see syntax examples.",
+            query_str
+        ))?;
+        self.matches_expr(&expr, message)
+    }
+
+    fn matches_expr(&self, expr: &QueryExpr, message: &Message) -> Result<bool> {
+        match expr {
+            QueryExpr::Field {
+                name,
+                operator,
+                value,
+            } => self.matches_field(name, operator, value, message),
+            QueryExpr::And(left, right) => {
+                Ok(self.matches_expr(left, message)? && self.matches_expr(right, message)?)
+            }
+            QueryExpr::Or(left, right) => {
+                Ok(self.matches_expr(left, message)? || self.matches_expr(right, message)?)
             }
+            QueryExpr::Not(inner) => Ok(!self.matches_expr(inner, message)?),
         }
     }
 
+    fn matches_field(
+        &self,
+        field: &str,
+        operator: &Operator,
+        value: &str,
+        message: &Message,
+    ) -> Result<bool> {
+        // Same validation (and errors) as the online search
+        self.translate_field(field, operator, value)?;
+
+        let needle = value.to_lowercase();
+        let contains = |text: &str| text.to_lowercase().contains(&needle);
+        let address_matches = |addr: &EmailAddress| contains(&addr.format());
+        let day = message.date.map(|d| d.date_naive());
+        let parse_size = || -> Result<usize> {
+            value
+                .parse()
+                .context(format!("Invalid size '{}'. Use a number of bytes", value))
+        };
+
+        let matched = match (field.to_lowercase().as_str(), operator) {
+            ("from", _) => message.from.as_ref().is_some_and(address_matches),
+            ("to", _) => message.to.iter().any(address_matches),
+            ("subject", _) => message.subject.as_deref().is_some_and(contains),
+            ("body", _) => {
+                message.body_text.as_deref().is_some_and(contains)
+                    || message.body_html.as_deref().is_some_and(contains)
+            }
+            ("unread" | "is", _) => !message.flags.seen,
+            ("date", Operator::GreaterThan) | ("since", _) => {
+                let since = Self::parse_naive_date(value)?;
+                day.is_some_and(|d| d >= since)
+            }
+            ("date", _) | ("before", _) => {
+                let before = Self::parse_naive_date(value)?;
+                day.is_some_and(|d| d < before)
+            }
+            ("newer", _) => {
+                let since =
+                    (Utc::now() - Duration::days(Self::parse_relative_days(value)?)).date_naive();
+                day.is_some_and(|d| d >= since)
+            }
+            ("older", _) => {
+                let before =
+                    (Utc::now() - Duration::days(Self::parse_relative_days(value)?)).date_naive();
+                day.is_some_and(|d| d < before)
+            }
+            ("size", Operator::GreaterThan) => {
+                let size = parse_size()?;
+                message.size.is_some_and(|s| s > size)
+            }
+            ("size", _) => {
+                let size = parse_size()?;
+                message.size.is_some_and(|s| s < size)
+            }
+            ("text", _) => self
+                .text_matches
+                .get(value)
+                .is_some_and(|uids| uids.contains(&message.uid)),
+            (name, _) if Self::is_client_side_field(name) => {
+                Self::attachment_predicate(name, value).matches(&message.attachments)
+            }
+            // in:/folder: choose folders, not messages
+            _ => true,
+        };
+
+        Ok(matched)
+    }
+
     /// Escape a string for safe use in IMAP commands.
     /// Filters out control characters and null bytes, then escapes backslashes and quotes.
     fn escape_imap_string(s: &str) -> String {
@@ -150,10 +571,13 @@ impl MessageFilter {
             }
             ("size", Operator::GreaterThan) => Ok(format!("LARGER {}", value)),
             ("size", Operator::LessThan) => Ok(format!("SMALLER {}", value)),
-            ("has", Operator::Equals) if value == "attachment" => {
-                // Mark for client-side filtering
-                Ok("ALL".to_string()) // Will filter client-side
+            // Attachment fields: matched client-side (see client_filter), ALL keeps the search a superset
+            ("has", Operator::Equals) if value.eq_ignore_ascii_case("attachment") => {
+                Ok("ALL".to_string())
             }
+            ("filename" | "attachment-type", Operator::Equals) => Ok("ALL".to_string()),
+            // Full-text search: matched against the local index, ALL keeps the search a superset
+            ("text", Operator::Equals) => Ok("ALL".to_string()),
             // Relative date shortcuts: newer:30d, older:7d
             ("newer", Operator::Equals) => {
                 let days = Self::parse_relative_days(value)?;
@@ -173,8 +597,24 @@ impl MessageFilter {
             }
             _ => {
                 let supported_fields = vec![
-                    "from", "to", "subject", "body", "unread", "is", "date", "since", "before",
-                    "size", "has", "newer", "older", "in", "folder",
+                    "from",
+                    "to",
+                    "subject",
+                    "body",
+                    "unread",
+                    "is",
+                    "date",
+                    "since",
+                    "before",
+                    "size",
+                    "has",
+                    "filename",
+                    "attachment-type",
+                    "text",
+                    "newer",
+                    "older",
+                    "in",
+                    "folder",
                 ];
                 Err(anyhow!(
                     "Unsupported query: '{}:{}'\n\nSupported fields: {}\n\nRun 'protoncli query-help' for more information.",
@@ -185,15 +625,20 @@ impl MessageFilter {
     }
 
     fn parse_date(&self, value: &str) -> Result<String> {
+        Ok(Self::parse_naive_date(value)?
+            .format("%d-%b-%Y")
+            .to_string())
+    }
+
+    /// Parse an absolute (YYYY-MM-DD) or relative (30d, 2w, 1m) date into a day
+    fn parse_naive_date(value: &str) -> Result<NaiveDate> {
         // Try relative date first (e.g., 30d, 2w, 1m)
         if let Ok(days) = Self::parse_relative_days(value) {
-            let date = Utc::now() - Duration::days(days);
-            return Ok(date.format("%d-%b-%Y").to_string());
+            return Ok((Utc::now() - Duration::days(days)).date_naive());
         }
         // Fall back to absolute date
-        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
-            .context("Invalid date format. Use YYYY-MM-DD or relative like 30d, 2w, 1m")?;
-        Ok(date.format("%d-%b-%Y").to_string())
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .context("Invalid date format. Use YYYY-MM-DD or relative like 30d, 2w, 1m")
     }
 
     /// Parse relative date strings like "30d", "2w", "1m" into days
@@ -225,6 +670,8 @@ impl MessageFilter {
     pub fn extract_folders_from_query(query: &str) -> Vec<String> {
         let mut folders = Vec::new();
         for token in query.split_whitespace() {
+            // Allow folders inside groups: (in:Sent OR in:Archive)
+            let token = token.trim_start_matches('(').trim_end_matches(')');
             if let Some(folder) = token.strip_prefix("in:") {
                 if !folder.is_empty() {
                     folders.push(folder.to_string());
@@ -376,6 +823,24 @@ mod tests {
         assert!(imap_query.starts_with("NOT"));
     }
 
+    #[test]
+    fn test_not_group_translation() {
+        let filter = MessageFilter::new().with_query("-(from:a@b.com subject:test)".to_string());
+        let imap_query = filter.build_imap_search_query().unwrap();
+        assert_eq!(imap_query, "NOT (FROM \"a@b.com\" SUBJECT \"test\")");
+    }
+
+    #[test]
+    fn test_grouped_or_translation() {
+        let filter = MessageFilter::new()
+            .with_query("(from:alice OR from:bob) AND subject:invoice".to_string());
+        let imap_query = filter.build_imap_search_query().unwrap();
+        assert_eq!(
+            imap_query,
+            "OR (FROM \"alice\") (FROM \"bob\") SUBJECT \"invoice\""
+        );
+    }
+
     #[test]
     fn test_size_greater_than_translation() {
         let filter = MessageFilter::new().with_query("size:>1000000".to_string());
@@ -476,6 +941,12 @@ mod tests {
         assert!(folders.is_empty());
     }
 
+    #[test]
+    fn test_extract_folders_in_group() {
+        let folders = MessageFilter::extract_folders_from_query("(in:Sent OR in:Archive) from:a");
+        assert_eq!(folders, vec!["Sent", "Archive"]);
+    }
+
     #[test]
     fn test_extract_folders_no_folders() {
         let folders =
@@ -488,4 +959,191 @@ mod tests {
         let folders = MessageFilter::extract_folders_from_query("in: in:Sent");
         assert_eq!(folders, vec!["Sent"]);
     }
+
+    fn attachment(filename: &str, content_type: &str) -> Attachment {
+        Attachment {
+            part: 1,
+            filename: Some(filename.to_string()),
+            content_type: content_type.to_string(),
+            size: 100,
+            content_id: None,
+        }
+    }
+
+    #[test]
+    fn test_no_client_filter_for_server_query() {
+        let filter = MessageFilter::new().with_query("from:alice subject:report".to_string());
+        assert_eq!(filter.client_filter().unwrap(), None);
+    }
+
+    #[test]
+    fn test_client_filter_splits_server_and_client_parts() {
+        let filter =
+            MessageFilter::new().with_query("from:alice@example.com has:attachment".to_string());
+        let client = filter.client_filter().unwrap().unwrap();
+        assert_eq!(
+            client,
+            ClientFilter::And(
+                Box::new(ClientFilter::Server(
+                    "FROM \"alice@example.com\"".to_string()
+                )),
+                Box::new(ClientFilter::Attachment(AttachmentPredicate::Any)),
+            )
+        );
+        assert_eq!(
+            client.server_queries(),
+            vec!["FROM \"alice@example.com\"".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_negated_client_field_searches_superset() {
+        let filter = MessageFilter::new().with_query("NOT has:attachment".to_string());
+        assert_eq!(filter.build_imap_search_query().unwrap(), "ALL");
+        let client = filter.client_filter().unwrap().unwrap();
+        let server = HashMap::new();
+        assert!(client.matches(1, &[], &server));
+        assert!(!client.matches(1, &[attachment("a.pdf", "application/pdf")], &server));
+    }
+
+    #[test]
+    fn test_client_filter_or_uses_server_matches() {
+        let filter = MessageFilter::new().with_query("from:alice OR filename:*.pdf".to_string());
+        let client = filter.client_filter().unwrap().unwrap();
+        let mut server = HashMap::new();
+        server.insert("FROM \"alice\"".to_string(), HashSet::from([7]));
+
+        assert!(client.matches(7, &[], &server));
+        assert!(client.matches(8, &[attachment("Invoice.PDF", "application/pdf")], &server));
+        assert!(!client.matches(8, &[attachment("photo.jpg", "image/jpeg")], &server));
+    }
+
+    #[test]
+    fn test_invalid_has_value_rejected() {
+        let filter = MessageFilter::new().with_query("has:nothing".to_string());
+        assert!(filter.client_filter().is_err());
+    }
+
+    #[test]
+    fn test_filename_predicate() {
+        let attachments = [attachment("Q3 report.pdf", "application/pdf")];
+        assert!(AttachmentPredicate::Filename("*.pdf".to_string()).matches(&attachments));
+        assert!(AttachmentPredicate::Filename("report".to_string()).matches(&attachments));
+        assert!(AttachmentPredicate::Filename("q?*.PDF".to_string()).matches(&attachments));
+        assert!(!AttachmentPredicate::Filename("*.doc".to_string()).matches(&attachments));
+    }
+
+    #[test]
+    fn test_attachment_type_predicate() {
+        let attachments = [attachment("photo.png", "image/png")];
+        assert!(AttachmentPredicate::ContentType("image".to_string()).matches(&attachments));
+        assert!(AttachmentPredicate::ContentType("png".to_string()).matches(&attachments));
+        assert!(AttachmentPredicate::ContentType("image/*".to_string()).matches(&attachments));
+        assert!(!AttachmentPredicate::ContentType("pdf".to_string()).matches(&attachments));
+        assert!(!AttachmentPredicate::Any.matches(&[]));
+    }
+
+    fn cached_message() -> Message {
+        let mut message = Message::new(3);
+        message.from = Some(EmailAddress::new(
+            "alice@example.com".to_string(),
+            Some("Alice".to_string()),
+        ));
+        message.subject = Some("Quarterly Invoice".to_string());
+        message.date = Some(
+            NaiveDate::from_ymd_opt(2024, 3, 15)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+                .and_utc(),
+        );
+        message.size = Some(2048);
+        message.attachments = vec![attachment("q1.pdf", "application/pdf")];
+        message
+    }
+
+    fn matches(query: &str, message: &Message) -> bool {
+        MessageFilter::new()
+            .with_query(query.to_string())
+            .matches_message(message)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_matches_message_fields() {
+        let message = cached_message();
+        assert!(matches("from:ALICE subject:invoice", &message));
+        assert!(matches("date:>2024-03-15 before:2024-03-16", &message));
+        assert!(!matches("date:<2024-03-15", &message));
+        assert!(matches(
+            "size:>1000 has:attachment filename:*.pdf",
+            &message
+        ));
+        assert!(matches("unread:true", &message));
+        assert!(matches("in:Archive", &message));
+    }
+
+    #[test]
+    fn test_matches_message_boolean_logic() {
+        let message = cached_message();
+        assert!(matches("from:bob OR subject:quarterly", &message));
+        assert!(!matches("-from:alice", &message));
+        assert!(matches("NOT (from:bob AND subject:invoice)", &message));
+        // Body not cached: body searches never match
+        assert!(!matches("body:invoice", &message));
+    }
+
+    #[test]
+    fn test_text_field_uses_index_matches() {
+        let mut filter = MessageFilter::new()
+            .with_query("text:\"quarterly report\" OR text:invoice".to_string());
+        assert_eq!(
+            filter.text_terms().unwrap(),
+            vec!["quarterly report".to_string(), "invoice".to_string()]
+        );
+        assert_eq!(filter.build_imap_search_query().unwrap(), "OR (ALL) (ALL)");
+
+        filter
+            .text_matches
+            .insert("invoice".to_string(), HashSet::from([3]));
+        let message = cached_message();
+        assert!(filter.matches_message(&message).unwrap());
+
+        let client = filter.client_filter().unwrap().unwrap();
+        let mut sets = HashMap::new();
+        sets.insert(ClientFilter::text_key("invoice"), HashSet::from([3]));
+        assert!(client.matches(3, &[], &sets));
+        assert!(!client.matches(4, &[], &sets));
+    }
+
+    #[test]
+    fn test_before_date_keeps_undated_messages() {
+        let mut message = cached_message();
+        let before = message.date.unwrap();
+        let filter = MessageFilter::new().with_before_date(before);
+        assert_eq!(
+            filter.build_imap_search_query().unwrap(),
+            "OR SENTBEFORE 16-Mar-2024 NOT HEADER DATE \"\""
+        );
+        assert!(!filter.matches_message(&message).unwrap());
+
+        message.date = Some(before - Duration::minutes(1));
+        assert!(filter.matches_message(&message).unwrap());
+        message.date = None;
+        assert!(filter.matches_message(&message).unwrap());
+    }
+
+    #[test]
+    fn test_matches_message_legacy_flags_and_errors() {
+        let mut message = cached_message();
+        message.flags.seen = true;
+        assert!(!MessageFilter::new()
+            .with_unread_only(true)
+            .matches_message(&message)
+            .unwrap());
+        assert!(MessageFilter::new()
+            .with_query("bogus:value".to_string())
+            .matches_message(&message)
+            .is_err());
+    }
 }
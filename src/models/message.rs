@@ -22,7 +22,7 @@ impl EmailAddress {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MessageFlags {
     pub seen: bool,
     pub answered: bool,
@@ -43,6 +43,94 @@ impl MessageFlags {
             draft: flags.iter().any(|f| matches!(f, Flag::Draft)),
         }
     }
+
+    /// Names of the flags that are set, in a stable order
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.seen {
+            names.push("seen");
+        }
+        if self.answered {
+            names.push("answered");
+        }
+        if self.flagged {
+            names.push("flagged");
+        }
+        if self.deleted {
+            names.push("deleted");
+        }
+        if self.draft {
+            names.push("draft");
+        }
+        names
+    }
+}
+
+/// Custom IMAP keywords set on a message (labels applied with `flag --label`).
+/// `$`-prefixed keywords such as `$Forwarded` or `$NotJunk` are server bookkeeping and skipped.
+pub fn labels_from_imap_flags(flags: &[async_imap::types::Flag]) -> Vec<String> {
+    use async_imap::types::Flag;
+
+    flags
+        .iter()
+        .filter_map(|f| match f {
+            Flag::Custom(name) if !name.starts_with('$') => Some(name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Metadata for a single attachment of a message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    /// 1-based attachment index (used with `attachments save --part`)
+    pub part: usize,
+    pub filename: Option<String>,
+    pub content_type: String,
+    /// Decoded size in bytes
+    pub size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>,
+}
+
+impl Attachment {
+    /// Build attachment metadata from a parsed MIME part
+    pub fn from_part(part_index: usize, part: &mail_parser::MessagePart) -> Self {
+        use mail_parser::MimeHeaders;
+
+        let content_type = part
+            .content_type()
+            .map(|ct| match ct.subtype() {
+                Some(sub) => format!("{}/{}", ct.ctype(), sub),
+                None => ct.ctype().to_string(),
+            })
+            .unwrap_or_else(|| "application/octet-stream".to_string())
+            .to_lowercase();
+
+        Self {
+            part: part_index,
+            filename: part.attachment_name().map(String::from),
+            content_type,
+            size: part.contents().len(),
+            content_id: part.content_id().map(String::from),
+        }
+    }
+
+    /// Filename to display or save as, falling back to a generated name
+    pub fn display_name(&self) -> String {
+        self.filename
+            .clone()
+            .unwrap_or_else(|| format!("attachment-{}", self.part))
+    }
+}
+
+/// Extract attachment metadata from a parsed message
+pub fn extract_attachments(parsed: &mail_parser::Message) -> Vec<Attachment> {
+    parsed
+        .attachments()
+        .enumerate()
+        .map(|(i, part)| Attachment::from_part(i + 1, part))
+        .collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,12 +141,24 @@ pub struct Message {
     /// IMAP UID - used internally for IMAP operations (can change when message moves)
     pub uid: u32,
     pub message_id: Option<String>,
+    /// Message-ID this message is a reply to (In-Reply-To header)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+    /// Message-IDs of the conversation ancestors (References header), oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
     pub subject: Option<String>,
     pub from: Option<EmailAddress>,
     pub to: Vec<EmailAddress>,
     pub cc: Vec<EmailAddress>,
     pub date: Option<DateTime<Utc>>,
     pub flags: MessageFlags,
+    /// Custom IMAP keywords (labels), when fetched
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// Size of the full message in bytes (RFC822.SIZE), when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
     pub preview: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub agent_read: Option<bool>,
@@ -71,6 +171,8 @@ pub struct Message {
     pub reply_to: Option<EmailAddress>,
     pub body_text: Option<String>,
     pub body_html: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
     pub headers: HashMap<String, String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw_message: Option<Vec<u8>>,
@@ -82,6 +184,8 @@ impl Message {
             shadow_uid: None,
             uid,
             message_id: None,
+            in_reply_to: None,
+            references: vec![],
             subject: None,
             from: None,
             to: vec![],
@@ -94,6 +198,8 @@ impl Message {
                 deleted: false,
                 draft: false,
             },
+            labels: vec![],
+            size: None,
             preview: None,
             agent_read: None,
             folder: None,
@@ -101,6 +207,7 @@ impl Message {
             reply_to: None,
             body_text: None,
             body_html: None,
+            attachments: vec![],
             headers: HashMap::new(),
             raw_message: None,
         }
@@ -1,6 +1,8 @@
 pub mod account;
 pub mod config;
+pub mod cursor;
 pub mod filter;
 pub mod folder;
 pub mod message;
 pub mod query;
+pub mod sort;
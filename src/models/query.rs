@@ -19,122 +19,348 @@ pub enum Operator {
     LessThan,    // field:<value
 }
 
-pub struct QueryParser;
+/// Lexical token with its 1-based column in the query string
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    /// A search term; `colon` is the byte offset of the first unquoted ':' in `text`
+    Term {
+        text: String,
+        colon: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    column: usize,
+}
+
+/// Recursive-descent parser for Gmail-style queries.
+///
+/// Grammar (lowest to highest precedence):
+///   or    := and (OR and)*
+///   and   := unary (AND? unary)*        adjacent terms are an implicit AND
+///   unary := (NOT | ! | -) unary | primary
+///   primary := field:value | '(' or ')'
+pub struct QueryParser {
+    query: String,
+    tokens: Vec<Spanned>,
+    pos: usize,
+}
 
 impl QueryParser {
     pub fn parse(query: &str) -> Result<QueryExpr> {
         let tokens = Self::tokenize(query)?;
-        Self::parse_tokens(&tokens)
+        if tokens.is_empty() {
+            return Err(anyhow!("Empty query"));
+        }
+
+        let mut parser = Self {
+            query: query.to_string(),
+            tokens,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+
+        if let Some(spanned) = parser.peek() {
+            let column = spanned.column;
+            let message = match spanned.token {
+                Token::RParen => "Unmatched ')'".to_string(),
+                _ => "Unexpected token".to_string(),
+            };
+            return Err(parser.error_at(column, &message));
+        }
+
+        Ok(expr)
     }
 
-    fn tokenize(query: &str) -> Result<Vec<String>> {
-        // Split on whitespace, preserve quoted strings
+    fn tokenize(query: &str) -> Result<Vec<Spanned>> {
+        let chars: Vec<char> = query.chars().collect();
         let mut tokens = Vec::new();
-        let mut current = String::new();
-        let mut in_quotes = false;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let ch = chars[i];
+            let column = i + 1;
+
+            if ch.is_whitespace() {
+                i += 1;
+                continue;
+            }
 
-        for ch in query.chars() {
             match ch {
-                '"' => in_quotes = !in_quotes,
-                ' ' if !in_quotes => {
-                    if !current.is_empty() {
-                        tokens.push(current.clone());
-                        current.clear();
+                '(' => {
+                    tokens.push(Spanned {
+                        token: Token::LParen,
+                        column,
+                    });
+                    i += 1;
+                    continue;
+                }
+                ')' => {
+                    tokens.push(Spanned {
+                        token: Token::RParen,
+                        column,
+                    });
+                    i += 1;
+                    continue;
+                }
+                '!' => {
+                    tokens.push(Spanned {
+                        token: Token::Not,
+                        column,
+                    });
+                    i += 1;
+                    continue;
+                }
+                // Leading '-' negates the following term or group: -from:spam, -(a OR b)
+                '-' if chars.get(i + 1).is_some_and(|c| !c.is_whitespace()) => {
+                    tokens.push(Spanned {
+                        token: Token::Not,
+                        column,
+                    });
+                    i += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // Read a term up to whitespace or a parenthesis, honoring quotes and escapes
+            let mut text = String::new();
+            let mut colon = None;
+            let mut quoted = false;
+            let mut any_quoted = false;
+            let mut quote_start = 0;
+            while i < chars.len() {
+                let c = chars[i];
+                if quoted {
+                    match c {
+                        '\\' if i + 1 < chars.len() => {
+                            text.push(chars[i + 1]);
+                            i += 2;
+                            continue;
+                        }
+                        '"' => quoted = false,
+                        _ => text.push(c),
+                    }
+                } else {
+                    match c {
+                        '"' => {
+                            quoted = true;
+                            any_quoted = true;
+                            quote_start = i + 1;
+                        }
+                        c if c.is_whitespace() || c == '(' || c == ')' => break,
+                        ':' if colon.is_none() => {
+                            colon = Some(text.len());
+                            text.push(c);
+                        }
+                        _ => text.push(c),
                     }
                 }
-                _ => current.push(ch),
+                i += 1;
             }
-        }
 
-        if !current.is_empty() {
-            tokens.push(current);
+            if quoted {
+                return Err(Self::format_error(
+                    query,
+                    quote_start,
+                    "Unterminated quoted string",
+                ));
+            }
+
+            // Quoted "AND"/"OR"/"NOT" are literal text, not operators
+            let keyword = if any_quoted {
+                String::new()
+            } else {
+                text.to_uppercase()
+            };
+            let token = match keyword.as_str() {
+                "AND" => Token::And,
+                "OR" => Token::Or,
+                "NOT" => Token::Not,
+                _ => Token::Term { text, colon },
+            };
+            tokens.push(Spanned { token, column });
         }
 
         Ok(tokens)
     }
 
-    fn parse_tokens(tokens: &[String]) -> Result<QueryExpr> {
-        // Recursive descent parser
-        // Handle OR (lowest precedence)
-        // Handle AND (medium precedence)
-        // Handle NOT (highest precedence)
-        // Handle field:value expressions
+    fn peek(&self) -> Option<&Spanned> {
+        self.tokens.get(self.pos)
+    }
 
-        // Simplified implementation for phase 1
-        if tokens.is_empty() {
-            return Err(anyhow!("Empty query"));
-        }
+    fn next(&mut self) -> Option<Spanned> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
 
-        // Find OR operators first
-        for (i, token) in tokens.iter().enumerate() {
-            if token.to_uppercase() == "OR" {
-                let left = Self::parse_tokens(&tokens[..i])?;
-                let right = Self::parse_tokens(&tokens[i + 1..])?;
-                return Ok(QueryExpr::Or(Box::new(left), Box::new(right)));
-            }
+    /// Column just past the end of the query, for "expected ... at end" errors
+    fn end_column(&self) -> usize {
+        self.query.chars().count() + 1
+    }
+
+    fn parse_or(&mut self) -> Result<QueryExpr> {
+        let mut left = self.parse_and()?;
+        while matches!(self.peek().map(|s| &s.token), Some(Token::Or)) {
+            self.next();
+            let right = self.parse_and()?;
+            left = QueryExpr::Or(Box::new(left), Box::new(right));
         }
+        Ok(left)
+    }
 
-        // Find AND operators
-        for (i, token) in tokens.iter().enumerate() {
-            if token.to_uppercase() == "AND" {
-                let left = Self::parse_tokens(&tokens[..i])?;
-                let right = Self::parse_tokens(&tokens[i + 1..])?;
-                return Ok(QueryExpr::And(Box::new(left), Box::new(right)));
+    fn parse_and(&mut self) -> Result<QueryExpr> {
+        let mut left = self.parse_unary()?;
+        loop {
+            match self.peek().map(|s| &s.token) {
+                Some(Token::And) => {
+                    self.next();
+                }
+                // Implicit AND: another term, group or negation follows directly
+                Some(Token::Term { .. } | Token::LParen | Token::Not) => {}
+                _ => break,
             }
+            let right = self.parse_unary()?;
+            left = QueryExpr::And(Box::new(left), Box::new(right));
         }
+        Ok(left)
+    }
 
-        // Handle NOT
-        if tokens[0].to_uppercase() == "NOT" || tokens[0] == "!" {
-            let inner = Self::parse_tokens(&tokens[1..])?;
+    fn parse_unary(&mut self) -> Result<QueryExpr> {
+        if matches!(self.peek().map(|s| &s.token), Some(Token::Not)) {
+            self.next();
+            let inner = self.parse_unary()?;
             return Ok(QueryExpr::Not(Box::new(inner)));
         }
+        self.parse_primary()
+    }
 
-        // Parse field:value
-        if tokens.len() == 1 {
-            return Self::parse_field_expr(&tokens[0]);
+    fn parse_primary(&mut self) -> Result<QueryExpr> {
+        let Some(spanned) = self.next() else {
+            return Err(self.error_at(self.end_column(), "Expected a search term at end of query"));
+        };
+
+        match spanned.token {
+            Token::Term { text, colon } => self.parse_field_expr(&text, colon, spanned.column),
+            Token::LParen => {
+                if matches!(self.peek().map(|s| &s.token), Some(Token::RParen)) {
+                    let column = self.peek().map(|s| s.column).unwrap_or(spanned.column);
+                    return Err(self.error_at(column, "Empty parentheses"));
+                }
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Spanned {
+                        token: Token::RParen,
+                        ..
+                    }) => Ok(inner),
+                    _ => Err(self.error_at(spanned.column, "Missing closing ')' for '('")),
+                }
+            }
+            Token::RParen => Err(self.error_at(spanned.column, "Unmatched ')'")),
+            Token::And | Token::Or => Err(self.error_at(
+                spanned.column,
+                "Expected a search term before boolean operator",
+            )),
+            Token::Not => Err(self.error_at(spanned.column, "Unexpected NOT")),
         }
-
-        // Implicit AND for multiple tokens
-        let left = Self::parse_field_expr(&tokens[0])?;
-        let right = Self::parse_tokens(&tokens[1..])?;
-        Ok(QueryExpr::And(Box::new(left), Box::new(right)))
     }
 
-    fn parse_field_expr(token: &str) -> Result<QueryExpr> {
-        if let Some((field, value)) = token.split_once(':') {
-            if value.is_empty() {
-                return Err(anyhow!(
+    fn parse_field_expr(
+        &self,
+        token: &str,
+        colon: Option<usize>,
+        column: usize,
+    ) -> Result<QueryExpr> {
+        let Some(colon) = colon else {
+            return Err(self.error_at(
+                column,
+                &format!(
+                    "Invalid query syntax: '{}'\n\nExpected format: field:value (e.g., from:user@example.com)\nOr use boolean operators: AND, OR, NOT",
+                    token
+                ),
+            ));
+        };
+
+        let field = &token[..colon];
+        let value = &token[colon + 1..];
+
+        if field.is_empty() {
+            return Err(self.error_at(
+                column,
+                &format!(
+                    "Missing field name in '{}'. Expected format: field:value",
+                    token
+                ),
+            ));
+        }
+
+        if value.is_empty() {
+            return Err(self.error_at(
+                column,
+                &format!(
                     "Empty value for field '{}'. Expected format: field:value\n\nExample: from:user@example.com",
                     field
-                ));
-            }
+                ),
+            ));
+        }
 
-            let (operator, clean_value) = if let Some(stripped) = value.strip_prefix('>') {
-                (Operator::GreaterThan, stripped)
-            } else if let Some(stripped) = value.strip_prefix('<') {
-                (Operator::LessThan, stripped)
-            } else {
-                (Operator::Equals, value)
-            };
+        let (operator, clean_value) = if let Some(stripped) = value.strip_prefix('>') {
+            (Operator::GreaterThan, stripped)
+        } else if let Some(stripped) = value.strip_prefix('<') {
+            (Operator::LessThan, stripped)
+        } else {
+            (Operator::Equals, value)
+        };
 
-            if clean_value.is_empty() {
-                return Err(anyhow!(
+        if clean_value.is_empty() {
+            return Err(self.error_at(
+                column,
+                &format!(
                     "Empty value after operator in '{}'. Expected format: field:>value or field:<value",
                     token
-                ));
-            }
-
-            Ok(QueryExpr::Field {
-                name: field.to_string(),
-                operator,
-                value: clean_value.to_string(),
-            })
-        } else {
-            Err(anyhow!(
-                "Invalid query syntax: '{}'\n\nExpected format: field:value (e.g., from:user@example.com)\nOr use boolean operators: AND, OR, NOT",
-                token
-            ))
+                ),
+            ));
         }
+
+        Ok(QueryExpr::Field {
+            name: field.to_string(),
+            operator,
+            value: clean_value.to_string(),
+        })
+    }
+
+    fn error_at(&self, column: usize, message: &str) -> anyhow::Error {
+        Self::format_error(&self.query, column, message)
+    }
+
+    /// Error message pointing at a column of the query:
+    ///
+    /// ```text
+    /// Missing closing ')' for '(' at column 1
+    ///
+    ///   (from:a OR from:b
+    ///   ^
+    /// ```
+    fn format_error(query: &str, column: usize, message: &str) -> anyhow::Error {
+        // Keep multi-line messages readable: position goes on the first line
+        let (first, rest) = message.split_once('\n').unwrap_or((message, ""));
+        anyhow!(
+            "{} at column {}\n\n  {}\n  {}^{}{}",
+            first,
+            column,
+            query,
+            " ".repeat(column.saturating_sub(1)),
+            if rest.is_empty() { "" } else { "\n" },
+            rest
+        )
     }
 }
 
@@ -255,4 +481,141 @@ mod tests {
         assert!(matches!(result1, QueryExpr::And(_, _)));
         assert!(matches!(result2, QueryExpr::And(_, _)));
     }
+
+    fn field(name: &str, value: &str) -> QueryExpr {
+        QueryExpr::Field {
+            name: name.to_string(),
+            operator: Operator::Equals,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parenthesized_grouping() {
+        let result = QueryParser::parse("(from:alice OR from:bob) AND subject:invoice").unwrap();
+        assert_eq!(
+            result,
+            QueryExpr::And(
+                Box::new(QueryExpr::Or(
+                    Box::new(field("from", "alice")),
+                    Box::new(field("from", "bob")),
+                )),
+                Box::new(field("subject", "invoice")),
+            )
+        );
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        let result = QueryParser::parse("from:a subject:b OR from:c").unwrap();
+        assert_eq!(
+            result,
+            QueryExpr::Or(
+                Box::new(QueryExpr::And(
+                    Box::new(field("from", "a")),
+                    Box::new(field("subject", "b")),
+                )),
+                Box::new(field("from", "c")),
+            )
+        );
+    }
+
+    #[test]
+    fn test_not_binds_to_single_term() {
+        let result = QueryParser::parse("NOT from:spam subject:hello").unwrap();
+        assert_eq!(
+            result,
+            QueryExpr::And(
+                Box::new(QueryExpr::Not(Box::new(field("from", "spam")))),
+                Box::new(field("subject", "hello")),
+            )
+        );
+    }
+
+    #[test]
+    fn test_dash_negation() {
+        let result = QueryParser::parse("subject:report -from:bot").unwrap();
+        assert_eq!(
+            result,
+            QueryExpr::And(
+                Box::new(field("subject", "report")),
+                Box::new(QueryExpr::Not(Box::new(field("from", "bot")))),
+            )
+        );
+
+        let group = QueryParser::parse("-(from:a OR from:b)").unwrap();
+        assert!(matches!(group, QueryExpr::Not(inner) if matches!(*inner, QueryExpr::Or(_, _))));
+    }
+
+    #[test]
+    fn test_dash_inside_value_is_literal() {
+        let result = QueryParser::parse("from:no-reply@example.com").unwrap();
+        assert_eq!(result, field("from", "no-reply@example.com"));
+    }
+
+    #[test]
+    fn test_quoted_phrase_with_escaped_quotes() {
+        let result = QueryParser::parse(r#"subject:"say \"hi\" (now)" from:a"#).unwrap();
+        assert_eq!(
+            result,
+            QueryExpr::And(
+                Box::new(field("subject", "say \"hi\" (now)")),
+                Box::new(field("from", "a")),
+            )
+        );
+    }
+
+    #[test]
+    fn test_quoted_keyword_is_literal() {
+        let result = QueryParser::parse(r#"subject:"OR""#).unwrap();
+        assert_eq!(result, field("subject", "OR"));
+    }
+
+    #[test]
+    fn test_unmatched_close_paren_reports_column() {
+        let err = QueryParser::parse("from:a)").unwrap_err().to_string();
+        assert!(err.contains("Unmatched ')' at column 7"), "{}", err);
+    }
+
+    #[test]
+    fn test_missing_close_paren_reports_column() {
+        let err = QueryParser::parse("from:x (from:a OR from:b")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Missing closing ')'"), "{}", err);
+        assert!(err.contains("at column 8"), "{}", err);
+    }
+
+    #[test]
+    fn test_unterminated_quote_reports_column() {
+        let err = QueryParser::parse(r#"subject:"hello"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Unterminated quoted string at column 9"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_dangling_operator_reports_column() {
+        let err = QueryParser::parse("from:a AND").unwrap_err().to_string();
+        assert!(err.contains("at column 11"), "{}", err);
+
+        let err = QueryParser::parse("OR from:a").unwrap_err().to_string();
+        assert!(err.contains("at column 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_term_reports_column() {
+        let err = QueryParser::parse("from:a justtext")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Invalid query syntax: 'justtext' at column 8"),
+            "{}",
+            err
+        );
+    }
 }
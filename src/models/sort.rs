@@ -0,0 +1,208 @@
+use crate::core::thread::normalize_subject;
+use crate::models::message::Message;
+use anyhow::{anyhow, Result};
+use std::cmp::Ordering;
+
+/// What to order messages by (`--sort`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Date header (newest first)
+    Date,
+    /// Sender address (A to Z)
+    From,
+    /// Subject without Re:/Fwd: prefixes (A to Z)
+    Subject,
+    /// Message size (largest first)
+    Size,
+    /// Arrival in the folder, i.e. UID order (newest first)
+    Arrival,
+}
+
+impl SortKey {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "date" => Some(SortKey::Date),
+            "from" => Some(SortKey::From),
+            "subject" => Some(SortKey::Subject),
+            "size" => Some(SortKey::Size),
+            "arrival" => Some(SortKey::Arrival),
+            _ => None,
+        }
+    }
+
+    /// RFC 5256 sort criterion
+    fn imap_criterion(self) -> &'static str {
+        match self {
+            SortKey::Date => "DATE",
+            SortKey::From => "FROM",
+            SortKey::Subject => "SUBJECT",
+            SortKey::Size => "SIZE",
+            SortKey::Arrival => "ARRIVAL",
+        }
+    }
+
+    /// Dates and sizes read best largest first; text keys alphabetically
+    fn descending_by_default(self) -> bool {
+        matches!(self, SortKey::Date | SortKey::Size | SortKey::Arrival)
+    }
+}
+
+/// A sort key and direction. `reverse` flips the key's natural order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortOrder {
+    pub key: SortKey,
+    pub reverse: bool,
+}
+
+impl SortOrder {
+    /// Parse `--sort` / `--reverse`
+    pub fn parse(key: &str, reverse: bool) -> Result<Self> {
+        let key = SortKey::from_str(key).ok_or_else(|| {
+            anyhow!(
+                "Invalid sort key '{}'. Use date, from, subject, size or arrival",
+                key
+            )
+        })?;
+        Ok(Self { key, reverse })
+    }
+
+    fn descending(&self) -> bool {
+        self.key.descending_by_default() != self.reverse
+    }
+
+    /// Criteria for `UID SORT`, e.g. "REVERSE DATE"
+    pub fn imap_criteria(&self) -> String {
+        if self.descending() {
+            format!("REVERSE {}", self.key.imap_criterion())
+        } else {
+            self.key.imap_criterion().to_string()
+        }
+    }
+
+    /// Compare two messages in display order (ties broken by UID)
+    pub fn compare(&self, a: &Message, b: &Message) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Date => a.date.cmp(&b.date),
+            SortKey::From => sender_key(a).cmp(&sender_key(b)),
+            SortKey::Subject => subject_key(a).cmp(&subject_key(b)),
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Arrival => Ordering::Equal,
+        }
+        .then(a.uid.cmp(&b.uid));
+
+        if self.descending() {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Order UIDs without any message data when the key allows it (arrival is UID order)
+    pub fn order_uids(&self, uids: &[u32]) -> Option<Vec<u32>> {
+        if self.key != SortKey::Arrival {
+            return None;
+        }
+        let mut ordered = uids.to_vec();
+        ordered.sort_unstable();
+        if self.descending() {
+            ordered.reverse();
+        }
+        Some(ordered)
+    }
+
+    /// Sort messages client-side, for servers without SORT and for multi-folder results
+    pub fn sort(&self, messages: &mut [Message]) {
+        messages.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+fn sender_key(message: &Message) -> String {
+    message
+        .from
+        .as_ref()
+        .map(|f| f.address.to_lowercase())
+        .unwrap_or_default()
+}
+
+fn subject_key(message: &Message) -> String {
+    normalize_subject(message.subject.as_deref().unwrap_or("")).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::EmailAddress;
+
+    fn message(uid: u32, subject: &str, from: &str, size: usize) -> Message {
+        let mut m = Message::new(uid);
+        m.subject = Some(subject.to_string());
+        m.from = Some(EmailAddress::new(from.to_string(), None));
+        m.size = Some(size);
+        m
+    }
+
+    fn uids(order: SortOrder, messages: &[Message]) -> Vec<u32> {
+        let mut sorted = messages.to_vec();
+        order.sort(&mut sorted);
+        sorted.iter().map(|m| m.uid).collect()
+    }
+
+    #[test]
+    fn test_order_uids_only_for_arrival() {
+        let arrival = SortOrder::parse("arrival", false).unwrap();
+        assert_eq!(arrival.order_uids(&[3, 9, 1]), Some(vec![9, 3, 1]));
+        let oldest_first = SortOrder::parse("arrival", true).unwrap();
+        assert_eq!(oldest_first.order_uids(&[3, 9, 1]), Some(vec![1, 3, 9]));
+        assert_eq!(
+            SortOrder::parse("date", false).unwrap().order_uids(&[1]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_imap_criteria_direction() {
+        assert_eq!(
+            SortOrder::parse("date", false).unwrap().imap_criteria(),
+            "REVERSE DATE"
+        );
+        assert_eq!(
+            SortOrder::parse("date", true).unwrap().imap_criteria(),
+            "DATE"
+        );
+        assert_eq!(
+            SortOrder::parse("subject", false).unwrap().imap_criteria(),
+            "SUBJECT"
+        );
+        assert_eq!(
+            SortOrder::parse("SIZE", false).unwrap().imap_criteria(),
+            "REVERSE SIZE"
+        );
+        assert!(SortOrder::parse("priority", false).is_err());
+    }
+
+    #[test]
+    fn test_client_sort_keys() {
+        let messages = vec![
+            message(1, "Re: banana", "carol@example.com", 300),
+            message(2, "apple", "Alice@example.com", 100),
+            message(3, "cherry", "bob@example.com", 200),
+        ];
+
+        assert_eq!(
+            uids(SortOrder::parse("subject", false).unwrap(), &messages),
+            vec![2, 1, 3]
+        );
+        assert_eq!(
+            uids(SortOrder::parse("from", false).unwrap(), &messages),
+            vec![2, 3, 1]
+        );
+        assert_eq!(
+            uids(SortOrder::parse("size", false).unwrap(), &messages),
+            vec![1, 3, 2]
+        );
+        assert_eq!(
+            uids(SortOrder::parse("arrival", true).unwrap(), &messages),
+            vec![1, 2, 3]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::{anyhow, Result};
+use std::io::Write;
+
+/// Delimited text output: `csv` (comma, CRLF line endings as in RFC 4180) or `tsv`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimited {
+    Csv,
+    Tsv,
+}
+
+impl Delimited {
+    /// The delimited format for an `--output` value, if it is one
+    pub fn from_format(format: &str) -> Option<Self> {
+        match format {
+            "csv" => Some(Delimited::Csv),
+            "tsv" => Some(Delimited::Tsv),
+            _ => None,
+        }
+    }
+
+    fn delimiter(self) -> char {
+        match self {
+            Delimited::Csv => ',',
+            Delimited::Tsv => '\t',
+        }
+    }
+
+    fn line_ending(self) -> &'static str {
+        match self {
+            Delimited::Csv => "\r\n",
+            Delimited::Tsv => "\n",
+        }
+    }
+
+    /// Quote a field when it contains the delimiter, a double quote or a line break;
+    /// quotes inside are doubled (RFC 4180)
+    pub fn escape(self, value: &str) -> String {
+        let needs_quotes = value
+            .chars()
+            .any(|c| c == self.delimiter() || c == '"' || c == '\r' || c == '\n');
+        if needs_quotes {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// One record, including its line ending
+    pub fn format_row<S: AsRef<str>>(self, values: &[S]) -> String {
+        let fields: Vec<String> = values.iter().map(|v| self.escape(v.as_ref())).collect();
+        let mut row = fields.join(&self.delimiter().to_string());
+        row.push_str(self.line_ending());
+        row
+    }
+}
+
+/// Print a header row followed by the records
+pub fn print_rows<S: AsRef<str>>(
+    format: Delimited,
+    header: &[&str],
+    rows: impl IntoIterator<Item = Vec<S>>,
+) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(format.format_row(header).as_bytes())?;
+    for row in rows {
+        if row.len() != header.len() {
+            return Err(anyhow!(
+                "Row has {} columns, header has {}",
+                row.len(),
+                header.len()
+            ));
+        }
+        stdout.write_all(format.format_row(&row).as_bytes())?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_quoting() {
+        let csv = Delimited::Csv;
+        assert_eq!(csv.escape("plain"), "plain");
+        assert_eq!(csv.escape("a, b"), "\"a, b\"");
+        assert_eq!(csv.escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv.escape("two\nlines"), "\"two\nlines\"");
+        assert_eq!(
+            csv.format_row(&["1", "Alice <a@x.com>", ""]),
+            "1,Alice <a@x.com>,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_tsv_quoting() {
+        let tsv = Delimited::Tsv;
+        assert_eq!(tsv.escape("a, b"), "a, b");
+        assert_eq!(tsv.escape("a\tb"), "\"a\tb\"");
+        assert_eq!(tsv.format_row(&["uid", "subject"]), "uid\tsubject\n");
+    }
+}
@@ -0,0 +1,1135 @@
+/// Render an HTML body as plain text: paragraphs, lists, aligned tables, and links
+/// as `[n]` footnotes listed at the end
+pub fn to_text(html: &str) -> String {
+    render(html, Mode::Text)
+}
+
+/// Render an HTML body as markdown, with reference-style links (`[text][n]`)
+pub fn to_markdown(html: &str) -> String {
+    render(html, Mode::Markdown)
+}
+
+/// Render markdown (with tables and strikethrough) as a small HTML document for sending
+pub fn from_markdown(markdown: &str) -> String {
+    use pulldown_cmark::{html, Options, Parser};
+
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    );
+    let mut body = String::new();
+    html::push_html(&mut body, parser);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"></head>\n<body>\n{}</body>\n</html>\n",
+        body
+    )
+}
+
+fn render(html: &str, mode: Mode) -> String {
+    let nodes = parse(html);
+    let mut renderer = Renderer::new(mode);
+    renderer.render_nodes(&nodes);
+    renderer.finish()
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Element(Element),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+/// Elements that never have content
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose content is skipped entirely
+const SKIPPED_ELEMENTS: [&str; 4] = ["script", "style", "title", "template"];
+
+/// Elements that start a block (and close an open `<p>`)
+const BLOCK_ELEMENTS: [&str; 28] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "center",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+];
+
+/// Content that makes a table a layout table rather than a grid of values
+const TABLE_BREAKING: [&str; 8] = ["table", "p", "div", "ul", "ol", "br", "blockquote", "pre"];
+
+impl Element {
+    fn new(name: &str, attrs: Vec<(String, String)>) -> Self {
+        Self {
+            name: name.to_string(),
+            attrs,
+            children: Vec::new(),
+        }
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// `hidden`, `display: none` and Outlook's `mso-hide: all` (used for preheader text)
+    fn is_hidden(&self) -> bool {
+        if self.attr("hidden").is_some() {
+            return true;
+        }
+        self.attr("style").is_some_and(|style| {
+            let style: String = style
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_ascii_lowercase();
+            style.contains("display:none") || style.contains("mso-hide:all")
+        })
+    }
+
+    fn child_elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// `td` / `th` children of a table row
+    fn cells(&self) -> Vec<&Element> {
+        self.child_elements()
+            .filter(|cell| (cell.name == "td" || cell.name == "th") && !cell.is_hidden())
+            .collect()
+    }
+
+    fn contains_any(&self, names: &[&str]) -> bool {
+        self.child_elements()
+            .any(|child| names.contains(&child.name.as_str()) || child.contains_any(names))
+    }
+}
+
+enum Tag {
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    End(String),
+    /// Comment, doctype or processing instruction
+    Ignored,
+}
+
+fn parse(html: &str) -> Vec<Node> {
+    let mut stack = vec![Element::new("#root", Vec::new())];
+    let mut text = String::new();
+    let mut pos = 0;
+
+    while pos < html.len() {
+        let rest = &html[pos..];
+        let Some(lt) = rest.find('<') else {
+            text.push_str(rest);
+            break;
+        };
+        text.push_str(&rest[..lt]);
+        pos += lt;
+
+        let Some((tag, len)) = parse_tag(&html[pos..]) else {
+            text.push('<');
+            pos += 1;
+            continue;
+        };
+        pos += len;
+
+        if !text.is_empty() {
+            let decoded = decode_entities(&std::mem::take(&mut text));
+            push_node(&mut stack, Node::Text(decoded));
+        }
+
+        match tag {
+            Tag::Ignored => {}
+            Tag::End(name) => {
+                if let Some(index) = (1..stack.len()).rev().find(|&i| stack[i].name == name) {
+                    close_to(&mut stack, index);
+                }
+            }
+            Tag::Start {
+                name,
+                attrs,
+                self_closing,
+            } => {
+                // Skip script/style/... content (and <head> when it is closed)
+                let skip = SKIPPED_ELEMENTS.contains(&name.as_str()) || name == "head";
+                if skip && !self_closing {
+                    let closing = format!("</{}", name);
+                    match html[pos..].to_ascii_lowercase().find(&closing) {
+                        Some(end) => {
+                            let after = pos + end;
+                            pos = html[after..]
+                                .find('>')
+                                .map(|gt| after + gt + 1)
+                                .unwrap_or(html.len());
+                            continue;
+                        }
+                        None if name != "head" => break,
+                        None => {}
+                    }
+                }
+                open_element(&mut stack, Element::new(&name, attrs), self_closing);
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        push_node(&mut stack, Node::Text(decode_entities(&text)));
+    }
+    close_to(&mut stack, 1);
+    stack.pop().map(|root| root.children).unwrap_or_default()
+}
+
+fn push_node(stack: &mut [Element], node: Node) {
+    if let Some(parent) = stack.last_mut() {
+        parent.children.push(node);
+    }
+}
+
+/// Close open elements down to (and including) `stack[index]`
+fn close_to(stack: &mut Vec<Element>, index: usize) {
+    while stack.len() > index.max(1) {
+        if let Some(element) = stack.pop() {
+            push_node(stack, Node::Element(element));
+        }
+    }
+}
+
+/// Close the innermost open `name` unless one of `boundaries` is opened after it
+fn close_implied(stack: &mut Vec<Element>, names: &[&str], boundaries: &[&str]) {
+    for index in (1..stack.len()).rev() {
+        let open = stack[index].name.as_str();
+        if names.contains(&open) {
+            close_to(stack, index);
+            return;
+        }
+        if boundaries.contains(&open) {
+            return;
+        }
+    }
+}
+
+fn open_element(stack: &mut Vec<Element>, element: Element, self_closing: bool) {
+    match element.name.as_str() {
+        "li" => close_implied(stack, &["li"], &["ul", "ol"]),
+        "td" | "th" => {
+            close_implied(stack, &["td", "th"], &["tr", "table"]);
+            // Cells outside a row get one, as browsers do
+            let in_row = stack
+                .iter()
+                .rev()
+                .find(|open| open.name == "tr" || open.name == "table")
+                .is_some_and(|open| open.name == "tr");
+            if !in_row {
+                stack.push(Element::new("tr", Vec::new()));
+            }
+        }
+        "tr" => {
+            close_implied(stack, &["td", "th"], &["table"]);
+            close_implied(stack, &["tr"], &["table", "thead", "tbody", "tfoot"]);
+        }
+        "dt" | "dd" => close_implied(stack, &["dt", "dd"], &["dl"]),
+        _ => {}
+    }
+    let in_paragraph = stack.last().is_some_and(|open| open.name == "p");
+    if in_paragraph && BLOCK_ELEMENTS.contains(&element.name.as_str()) {
+        let index = stack.len() - 1;
+        close_to(stack, index);
+    }
+
+    if self_closing || VOID_ELEMENTS.contains(&element.name.as_str()) {
+        push_node(stack, Node::Element(element));
+    } else {
+        stack.push(element);
+    }
+}
+
+/// Parse the tag at the start of `input` (which begins with `<`), returning it with its length.
+/// `None` means the `<` is literal text.
+fn parse_tag(input: &str) -> Option<(Tag, usize)> {
+    let until = |pattern: &str, from: usize| {
+        input[from..]
+            .find(pattern)
+            .map(|i| from + i + pattern.len())
+            .unwrap_or(input.len())
+    };
+
+    if input.starts_with("<!--") {
+        return Some((Tag::Ignored, until("-->", 4)));
+    }
+    if input.starts_with("<!") || input.starts_with("<?") {
+        return Some((Tag::Ignored, until(">", 2)));
+    }
+
+    let (closing, name_start) = match input.as_bytes().get(1) {
+        Some(b'/') => (true, 2),
+        Some(c) if c.is_ascii_alphabetic() => (false, 1),
+        _ => return None,
+    };
+    let name_len = input[name_start..]
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == ':'))
+        .unwrap_or(input.len() - name_start);
+    if name_len == 0 {
+        return None;
+    }
+    let name = input[name_start..name_start + name_len].to_ascii_lowercase();
+    let mut pos = name_start + name_len;
+
+    if closing {
+        return Some((Tag::End(name), until(">", pos)));
+    }
+
+    let bytes = input.as_bytes();
+    let mut attrs = Vec::new();
+    let mut self_closing = false;
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        match bytes.get(pos) {
+            None => break,
+            Some(b'>') => {
+                pos += 1;
+                break;
+            }
+            Some(b'/') => {
+                self_closing = bytes.get(pos + 1) == Some(&b'>');
+                pos += 1;
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        let key_len = input[pos..]
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(input.len() - pos);
+        let key = input[pos..pos + key_len].to_ascii_lowercase();
+        pos += key_len.max(1);
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let mut value = String::new();
+        if bytes.get(pos) == Some(&b'=') {
+            pos += 1;
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            match bytes.get(pos) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let end = input[pos + 1..]
+                        .find(quote as char)
+                        .map(|i| pos + 1 + i)
+                        .unwrap_or(input.len());
+                    value = decode_entities(&input[pos + 1..end]);
+                    pos = (end + 1).min(input.len());
+                }
+                _ => {
+                    let len = input[pos..]
+                        .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                        .unwrap_or(input.len() - pos);
+                    value = decode_entities(&input[pos..pos + len]);
+                    pos += len;
+                }
+            }
+        }
+        if !key.is_empty() {
+            attrs.push((key, value));
+        }
+    }
+
+    Some((
+        Tag::Start {
+            name,
+            attrs,
+            self_closing,
+        },
+        pos,
+    ))
+}
+
+/// Decode named (`&amp;`) and numeric (`&#39;`, `&#x27;`) character references
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        match decode_entity(rest) {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(input: &str) -> Option<(char, usize)> {
+    let semicolon = input[1..].find(';').filter(|&i| i > 0 && i <= 32)? + 1;
+    let name = &input[1..semicolon];
+    let c = match name.strip_prefix('#') {
+        Some(number) => {
+            let code = match number
+                .strip_prefix('x')
+                .or_else(|| number.strip_prefix('X'))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code).unwrap_or('\u{fffd}')
+        }
+        None => named_entity(name)?,
+    };
+    Some((c, semicolon + 1))
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ensp" | "emsp" | "thinsp" => ' ',
+        "shy" => '\u{ad}',
+        "zwnj" => '\u{200c}',
+        "zwj" => '\u{200d}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "sbquo" => '‚',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "bdquo" => '„',
+        "laquo" => '«',
+        "raquo" => '»',
+        "bull" => '•',
+        "middot" => '·',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "deg" => '°',
+        "times" => '×',
+        "divide" => '÷',
+        "plusmn" => '±',
+        "para" => '¶',
+        "sect" => '§',
+        "iexcl" => '¡',
+        "iquest" => '¿',
+        "larr" => '←',
+        "rarr" => '→',
+        "uarr" => '↑',
+        "darr" => '↓',
+        "agrave" => 'à',
+        "aacute" => 'á',
+        "acirc" => 'â',
+        "auml" => 'ä',
+        "aring" => 'å',
+        "ccedil" => 'ç',
+        "egrave" => 'è',
+        "eacute" => 'é',
+        "ecirc" => 'ê',
+        "euml" => 'ë',
+        "iacute" => 'í',
+        "iuml" => 'ï',
+        "ntilde" => 'ñ',
+        "oacute" => 'ó',
+        "ocirc" => 'ô',
+        "ouml" => 'ö',
+        "oslash" => 'ø',
+        "uacute" => 'ú',
+        "ugrave" => 'ù',
+        "uuml" => 'ü',
+        "szlig" => 'ß',
+        "Agrave" => 'À',
+        "Aacute" => 'Á',
+        "Auml" => 'Ä',
+        "Aring" => 'Å',
+        "Ccedil" => 'Ç',
+        "Eacute" => 'É',
+        "Egrave" => 'È',
+        "Ntilde" => 'Ñ',
+        "Ouml" => 'Ö',
+        "Oslash" => 'Ø',
+        "Uuml" => 'Ü',
+        _ => return None,
+    };
+    Some(c)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Text,
+    Markdown,
+}
+
+/// Output of rendering a fragment on its own (link labels, emphasis, table cells)
+struct Fragment {
+    text: String,
+    leading_space: bool,
+    trailing_space: bool,
+}
+
+struct Renderer {
+    mode: Mode,
+    out: String,
+    /// Line prefixes (quote markers, list indentation), outermost first
+    prefixes: Vec<String>,
+    /// List or heading marker that replaces the innermost prefix on the next line
+    marker: Option<String>,
+    /// Line breaks owed before the next content (1 = new line, 2 = blank line)
+    pending_breaks: usize,
+    /// Line breaks at the end of `out`
+    newlines: usize,
+    at_line_start: bool,
+    pending_space: bool,
+    /// Whitespace seen before any output
+    leading_space: bool,
+    /// Open lists; `Some(n)` is an ordered list whose next item is `n`
+    lists: Vec<Option<usize>>,
+    pre_depth: usize,
+    /// Set at `<pre>` so that a line break right after it is dropped, as browsers do
+    pre_start: bool,
+    /// Footnote URLs, `[1]` first
+    links: Vec<String>,
+}
+
+impl Renderer {
+    fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            out: String::new(),
+            prefixes: Vec::new(),
+            marker: None,
+            pending_breaks: 0,
+            newlines: 0,
+            at_line_start: true,
+            pending_space: false,
+            leading_space: false,
+            lists: Vec::new(),
+            pre_depth: 0,
+            pre_start: false,
+            links: Vec::new(),
+        }
+    }
+
+    fn finish(self) -> String {
+        let body = self
+            .out
+            .replace('\u{a0}', " ")
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut out = body.trim_matches('\n').to_string();
+
+        if !self.links.is_empty() {
+            out.push_str("\n\n");
+            let notes: Vec<String> = self
+                .links
+                .iter()
+                .enumerate()
+                .map(|(i, url)| match self.mode {
+                    Mode::Text => format!("[{}] {}", i + 1, url),
+                    Mode::Markdown => format!("[{}]: {}", i + 1, url),
+                })
+                .collect();
+            out.push_str(&notes.join("\n"));
+        }
+        out
+    }
+
+    fn newline(&mut self) {
+        if self.newlines > 0 {
+            let prefix = self.prefixes.concat();
+            self.out.push_str(prefix.trim_end());
+        }
+        self.out.push('\n');
+        self.newlines += 1;
+        self.at_line_start = true;
+        self.pending_space = false;
+    }
+
+    /// Ask for at least `breaks` line breaks before the next content
+    fn block(&mut self, breaks: usize) {
+        self.pending_breaks = self.pending_breaks.max(breaks);
+    }
+
+    fn flush_breaks(&mut self) {
+        if !self.out.is_empty() {
+            while self.newlines < self.pending_breaks {
+                self.newline();
+            }
+        }
+        self.pending_breaks = 0;
+    }
+
+    /// Write text that contains no line breaks
+    fn push_content(&mut self, content: &str) {
+        self.flush_breaks();
+        if self.at_line_start {
+            let prefix = match self.marker.take() {
+                Some(marker) => {
+                    let outer = self.prefixes.len().saturating_sub(1);
+                    self.prefixes[..outer].concat() + &marker
+                }
+                None => self.prefixes.concat(),
+            };
+            self.out.push_str(&prefix);
+            self.at_line_start = false;
+        } else if self.pending_space {
+            self.out.push(' ');
+        }
+        self.pending_space = false;
+        self.newlines = 0;
+        self.out.push_str(content);
+    }
+
+    /// Write rendered text, turning its line breaks into prefixed lines
+    fn push_lines(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            if !line.is_empty() {
+                self.push_content(line);
+            }
+        }
+    }
+
+    fn space(&mut self) {
+        if self.out.is_empty() {
+            self.leading_space = true;
+        } else {
+            self.pending_space = true;
+        }
+    }
+
+    fn line_break(&mut self) {
+        self.flush_breaks();
+        if !self.out.is_empty() && (self.newlines < 2 || self.pre_depth > 0) {
+            self.newline();
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        let text: String = text
+            .chars()
+            .filter(|c| {
+                !matches!(
+                    c,
+                    '\u{200b}'..='\u{200d}' | '\u{feff}' | '\u{ad}' | '\u{34f}'
+                )
+            })
+            .collect();
+
+        if self.pre_depth > 0 {
+            let text = text.replace("\r\n", "\n");
+            let text = match std::mem::take(&mut self.pre_start) {
+                true => text.strip_prefix('\n').unwrap_or(&text),
+                false => &text,
+            };
+            self.push_lines(text);
+            return;
+        }
+        for (i, word) in text.split(|c: char| c.is_ascii_whitespace()).enumerate() {
+            if i > 0 {
+                self.space();
+            }
+            if !word.is_empty() {
+                self.push_content(word);
+            }
+        }
+    }
+
+    /// Render `nodes` on their own, sharing the footnote list
+    fn fragment(&mut self, nodes: &[Node]) -> Fragment {
+        let mut sub = Renderer::new(self.mode);
+        sub.links = std::mem::take(&mut self.links);
+        sub.pre_depth = self.pre_depth;
+        sub.render_nodes(nodes);
+        self.links = std::mem::take(&mut sub.links);
+
+        Fragment {
+            text: sub.out.trim_matches('\n').to_string(),
+            leading_space: sub.leading_space,
+            trailing_space: sub.pending_space || sub.newlines > 0,
+        }
+    }
+
+    fn push_fragment(&mut self, fragment: &Fragment, text: &str) {
+        if fragment.leading_space {
+            self.space();
+        }
+        self.push_lines(text);
+        if fragment.trailing_space {
+            self.space();
+        }
+    }
+
+    fn footnote(&mut self, url: &str) -> usize {
+        match self.links.iter().position(|link| link == url) {
+            Some(index) => index + 1,
+            None => {
+                self.links.push(url.to_string());
+                self.links.len()
+            }
+        }
+    }
+
+    fn paragraph_gap(&self) -> usize {
+        if self.lists.is_empty() {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn render_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.text(text),
+                Node::Element(element) => self.element(element),
+            }
+        }
+    }
+
+    fn element(&mut self, element: &Element) {
+        if element.is_hidden() {
+            return;
+        }
+
+        match element.name.as_str() {
+            "br" => self.line_break(),
+            "hr" => {
+                self.block(2);
+                match self.mode {
+                    Mode::Text => self.push_content(&"-".repeat(40)),
+                    Mode::Markdown => self.push_content("---"),
+                }
+                self.block(2);
+            }
+            "p" => {
+                let gap = self.paragraph_gap();
+                self.block(gap);
+                self.render_nodes(&element.children);
+                self.block(gap);
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.heading(element),
+            "ul" | "ol" => self.list(element),
+            "li" => self.list_item(element),
+            "blockquote" => {
+                let gap = self.paragraph_gap();
+                self.block(gap);
+                // The gap before the quote is not part of it
+                self.flush_breaks();
+                self.prefixes.push("> ".to_string());
+                self.render_nodes(&element.children);
+                self.prefixes.pop();
+                self.block(gap);
+            }
+            "pre" => self.preformatted(element),
+            "table" => self.table(element),
+            "a" => self.link(element),
+            "img" => self.image(element),
+            "b" | "strong" => self.emphasis(element, "**"),
+            "i" | "em" => self.emphasis(element, "_"),
+            "s" | "strike" | "del" => self.emphasis(element, "~~"),
+            "code" | "kbd" | "tt" => self.emphasis(element, "`"),
+            "head" | "script" | "style" | "title" | "template" => {}
+            name if BLOCK_ELEMENTS.contains(&name) || matches!(name, "tr" | "td" | "th") => {
+                self.block(1);
+                self.render_nodes(&element.children);
+                self.block(1);
+            }
+            _ => self.render_nodes(&element.children),
+        }
+    }
+
+    fn heading(&mut self, element: &Element) {
+        let level = element.name[1..].parse::<usize>().unwrap_or(1);
+        self.block(2);
+        match self.mode {
+            Mode::Markdown => {
+                self.prefixes.push(String::new());
+                self.marker = Some(format!("{} ", "#".repeat(level)));
+                self.render_nodes(&element.children);
+                self.marker = None;
+                self.prefixes.pop();
+            }
+            Mode::Text => {
+                self.render_nodes(&element.children);
+                // Underline the top two levels, setext style
+                if level <= 2 && self.newlines == 0 && !self.at_line_start {
+                    let line = self.out.rsplit('\n').next().unwrap_or_default();
+                    let width = line
+                        .chars()
+                        .count()
+                        .saturating_sub(self.prefixes.concat().chars().count());
+                    self.newline();
+                    self.push_content(&(if level == 1 { "=" } else { "-" }).repeat(width));
+                }
+            }
+        }
+        self.block(2);
+    }
+
+    fn list(&mut self, element: &Element) {
+        let gap = self.paragraph_gap();
+        self.block(gap);
+        let start = element
+            .attr("start")
+            .and_then(|start| start.trim().parse().ok())
+            .unwrap_or(1);
+        self.lists.push((element.name == "ol").then_some(start));
+        self.render_nodes(&element.children);
+        self.lists.pop();
+        self.block(gap);
+    }
+
+    fn list_item(&mut self, element: &Element) {
+        self.block(1);
+        let marker = match self.lists.last_mut() {
+            Some(Some(next)) => {
+                let marker = format!("{}. ", next);
+                *next += 1;
+                marker
+            }
+            _ => "- ".to_string(),
+        };
+        self.prefixes.push(" ".repeat(marker.len()));
+        self.marker = Some(marker);
+        self.render_nodes(&element.children);
+        self.marker = None;
+        self.prefixes.pop();
+        self.block(1);
+    }
+
+    fn preformatted(&mut self, element: &Element) {
+        let gap = self.paragraph_gap();
+        self.block(gap);
+        if self.mode == Mode::Markdown {
+            self.push_content("```");
+            self.newline();
+        }
+        self.pre_depth += 1;
+        self.pre_start = true;
+        self.render_nodes(&element.children);
+        self.pre_start = false;
+        self.pre_depth -= 1;
+        if self.mode == Mode::Markdown {
+            self.block(1);
+            self.push_content("```");
+        }
+        self.block(gap);
+    }
+
+    fn emphasis(&mut self, element: &Element, marker: &str) {
+        if self.mode == Mode::Text || self.pre_depth > 0 {
+            self.render_nodes(&element.children);
+            return;
+        }
+        let fragment = self.fragment(&element.children);
+        if fragment.text.is_empty() {
+            self.push_fragment(&fragment, "");
+        } else {
+            let wrapped = format!("{}{}{}", marker, fragment.text, marker);
+            self.push_fragment(&fragment, &wrapped);
+        }
+    }
+
+    fn link(&mut self, element: &Element) {
+        let fragment = self.fragment(&element.children);
+        let href = element.attr("href").map(str::trim).filter(|href| {
+            let href = href.to_ascii_lowercase();
+            ["http://", "https://", "mailto:", "ftp://"]
+                .iter()
+                .any(|scheme| href.starts_with(scheme))
+        });
+        let Some(href) = href else {
+            self.push_fragment(&fragment, &fragment.text);
+            return;
+        };
+
+        // Links that spell out their target need no footnote
+        let label = fragment.text.as_str();
+        if label == href || Some(label) == href.strip_prefix("mailto:") {
+            self.push_fragment(&fragment, label);
+            return;
+        }
+
+        let n = self.footnote(href);
+        let rendered = if label.is_empty() {
+            format!("[{}]", n)
+        } else if self.mode == Mode::Markdown && !label.contains('\n') {
+            format!("[{}][{}]", label, n)
+        } else {
+            format!("{} [{}]", label, n)
+        };
+        self.push_fragment(&fragment, &rendered);
+    }
+
+    fn image(&mut self, element: &Element) {
+        let alt = element
+            .attr("alt")
+            .map(|alt| alt.split_whitespace().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        if alt.is_empty() {
+            return;
+        }
+        let src = element
+            .attr("src")
+            .filter(|src| src.starts_with("http://") || src.starts_with("https://"));
+        match (self.mode, src) {
+            (Mode::Markdown, Some(src)) => self.push_content(&format!("![{}]({})", alt, src)),
+            _ => self.push_content(&alt),
+        }
+    }
+
+    fn table(&mut self, table: &Element) {
+        let mut rows: Vec<&Element> = Vec::new();
+        let mut captions: Vec<&Element> = Vec::new();
+        for child in table.child_elements() {
+            match child.name.as_str() {
+                "tr" => rows.push(child),
+                "thead" | "tbody" | "tfoot" => {
+                    rows.extend(child.child_elements().filter(|row| row.name == "tr"))
+                }
+                "caption" => captions.push(child),
+                _ => {}
+            }
+        }
+
+        // Tables used for page layout (nested tables, paragraphs, a single column)
+        // are rendered as blocks; only grids of short values keep their columns
+        let columns = rows.iter().map(|row| row.cells().len()).max().unwrap_or(0);
+        let layout = columns < 2
+            || rows
+                .iter()
+                .flat_map(|row| row.cells())
+                .any(|cell| cell.contains_any(&TABLE_BREAKING));
+
+        let gap = self.paragraph_gap();
+        self.block(if layout { 1 } else { gap });
+        for caption in captions {
+            self.render_nodes(&caption.children);
+            self.block(1);
+        }
+
+        if layout {
+            for row in &rows {
+                for cell in row.cells() {
+                    self.block(1);
+                    self.render_nodes(&cell.children);
+                    self.block(1);
+                }
+            }
+            self.block(1);
+            return;
+        }
+
+        let grid: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| {
+                let mut values: Vec<String> = row
+                    .cells()
+                    .into_iter()
+                    .map(|cell| {
+                        let text = self.fragment(&cell.children).text.replace('\n', " ");
+                        match self.mode {
+                            Mode::Text => text,
+                            Mode::Markdown => text.replace('|', "\\|"),
+                        }
+                    })
+                    .collect();
+                values.resize(columns, String::new());
+                values
+            })
+            .collect();
+        let widths: Vec<usize> = (0..columns)
+            .map(|col| {
+                let widest = grid
+                    .iter()
+                    .map(|row| row[col].chars().count())
+                    .max()
+                    .unwrap_or(0);
+                match self.mode {
+                    Mode::Text => widest,
+                    Mode::Markdown => widest.max(3),
+                }
+            })
+            .collect();
+        let header = rows
+            .first()
+            .is_some_and(|row| row.cells().iter().any(|cell| cell.name == "th"));
+
+        let mut lines = Vec::new();
+        for (i, row) in grid.iter().enumerate() {
+            let padded: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(value, &width)| {
+                    let pad = width.saturating_sub(value.chars().count());
+                    format!("{}{}", value, " ".repeat(pad))
+                })
+                .collect();
+            match self.mode {
+                Mode::Text => lines.push(padded.join("  ")),
+                Mode::Markdown => lines.push(format!("| {} |", padded.join(" | "))),
+            }
+
+            if i == 0 {
+                let rules: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+                match self.mode {
+                    Mode::Text if header => lines.push(rules.join("  ")),
+                    Mode::Text => {}
+                    Mode::Markdown => lines.push(format!("| {} |", rules.join(" | "))),
+                }
+            }
+        }
+        self.push_lines(&lines.join("\n"));
+        self.block(gap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entities_and_skipped_content() {
+        let html = "<html><head><title>Newsletter</title><style>p { color: red; }</style></head>\
+            <body><span style=\"display: none\">preheader</span>\
+            <p>Fish &amp; chips &lt;3 &#8364;5 &#x2014; caf&eacute;&nbsp;bar &bogus; a&b</p>\
+            <script>alert('x')</script><!-- comment --></body></html>";
+        assert_eq!(to_text(html), "Fish & chips <3 €5 — café bar &bogus; a&b");
+    }
+
+    #[test]
+    fn test_blocks_and_lists() {
+        let html = "<div>Hi team,</div><div><br></div><div>Agenda:</div>\
+            <ol><li>Budget</li><li>Hiring<ul><li>Backend</li><li>Design</li></ul></li></ol>\
+            <p>Thanks,<br>Alice</p><blockquote><p>Earlier message</p></blockquote>";
+        assert_eq!(
+            to_text(html),
+            "Hi team,\n\nAgenda:\n\n1. Budget\n2. Hiring\n   - Backend\n   - Design\n\nThanks,\nAlice\n\n> Earlier message"
+        );
+    }
+
+    #[test]
+    fn test_links_become_footnotes() {
+        let html = "<p>See <a href=\"https://example.com/a\">the docs</a>, \
+            <a href=\"https://example.com/a\">again</a>, \
+            <a href=\"mailto:bob@example.com\">bob@example.com</a> and \
+            <a href=\"#top\">top</a>.</p>";
+        assert_eq!(
+            to_text(html),
+            "See the docs [1], again [1], bob@example.com and top.\n\n[1] https://example.com/a"
+        );
+        assert_eq!(
+            to_markdown(html),
+            "See [the docs][1], [again][1], bob@example.com and top.\n\n[1]: https://example.com/a"
+        );
+    }
+
+    #[test]
+    fn test_markdown_inline_and_headings() {
+        let html = "<h2>Release  notes</h2><p>This is <b> important</b> and <em>new</em>: \
+            <code>cargo test</code></p><pre>fn main() {\n    run();\n}</pre>";
+        assert_eq!(
+            to_markdown(html),
+            "## Release notes\n\nThis is **important** and _new_: `cargo test`\n\n```\nfn main() {\n    run();\n}\n```"
+        );
+        assert_eq!(to_text("<h1>Title</h1><p>Body</p>"), "Title\n=====\n\nBody");
+    }
+
+    #[test]
+    fn test_markdown_to_html_and_back() {
+        let html =
+            from_markdown("# Status\n\n- **done**: parser\n- see [docs](https://example.com)\n");
+        assert!(html.contains("<h1>Status</h1>"));
+        assert!(html.contains("<li><strong>done</strong>: parser</li>"));
+        assert_eq!(
+            to_text(&html),
+            "Status\n======\n\n- done: parser\n- see docs [1]\n\n[1] https://example.com"
+        );
+    }
+
+    #[test]
+    fn test_tables() {
+        let data = "<table><tr><th>Item</th><th>Qty</th></tr>\
+            <tr><td>Coffee</td><td>2</td></tr><tr><td>Tea | green</td><td>10</td></tr></table>";
+        assert_eq!(
+            to_text(data),
+            "Item         Qty\n-----------  ---\nCoffee       2\nTea | green  10"
+        );
+        assert_eq!(
+            to_markdown(data),
+            "| Item         | Qty |\n| ------------ | --- |\n| Coffee       | 2   |\n| Tea \\| green | 10  |"
+        );
+
+        // Layout tables (nested tables, block content) are flattened into blocks
+        let layout =
+            "<table><tr><td><table><tr><td>Logo</td><td>Oct 2024</td></tr></table></td></tr>\
+            <tr><td><p>Hello there</p><p>Second paragraph</p></td></tr></table>";
+        assert_eq!(
+            to_text(layout),
+            "Logo  Oct 2024\n\nHello there\n\nSecond paragraph"
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::Result;
 use serde::Serialize;
+use std::io::Write;
 
 pub fn format_json<T: Serialize>(data: &T) -> Result<String> {
     let json = serde_json::to_string_pretty(data)?;
@@ -11,3 +12,24 @@ pub fn print_json<T: Serialize>(data: &T) -> Result<()> {
     println!("{}", json);
     Ok(())
 }
+
+/// Print one compact JSON object as an NDJSON line, flushed so readers see it right away
+pub fn print_ndjson<T: Serialize>(data: &T) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", serde_json::to_string(data)?)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// NDJSON line tagged with its record type, e.g. `{"type":"message",...}`
+#[derive(Serialize)]
+struct NdjsonRecord<'a, T: Serialize> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(flatten)]
+    data: &'a T,
+}
+
+pub fn print_ndjson_record<T: Serialize>(kind: &'static str, data: &T) -> Result<()> {
+    print_ndjson(&NdjsonRecord { kind, data })
+}
@@ -1,4 +1,6 @@
-use crate::models::message::Message;
+use crate::core::thread::ConversationSummary;
+use crate::models::message::{EmailAddress, Message};
+use crate::output::html;
 
 pub fn format_message_list(account: &str, folder: &str, messages: &[Message]) -> String {
     let mut output = String::new();
@@ -63,50 +65,160 @@ pub fn print_message_list(account: &str, folder: &str, messages: &[Message]) {
     println!("{}", format_message_list(account, folder, messages));
 }
 
-fn basic_html_to_text(html: &str) -> String {
-    // Simple HTML tag removal for terminal display
-    html.replace("<br>", "\n")
-        .replace("<br/>", "\n")
-        .replace("<br />", "\n")
-        .replace("</p>", "\n\n")
-        .split('<')
-        .map(|s| s.split_once('>').map(|(_, rest)| rest).unwrap_or(s))
-        .collect::<String>()
+/// Conversation listing for `--threads`
+pub fn format_thread_list(account: &str, folder: &str, threads: &[ConversationSummary]) -> String {
+    let mut output = String::new();
+
+    output.push_str(&format!("# Conversations in {}/{}\n\n", account, folder));
+    output.push_str(&format!("Found {} conversation(s)\n\n", threads.len()));
+
+    if threads.is_empty() {
+        output.push_str("No messages found.\n");
+        return output;
+    }
+
+    for thread in threads {
+        output.push_str("---\n\n");
+
+        let id_display = thread
+            .id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        output.push_str(&format!("**ID:** {}\n\n", id_display));
+
+        if let Some(ref subject) = thread.subject {
+            output.push_str(&format!("**Subject:** {}\n\n", subject));
+        }
+
+        output.push_str(&format!(
+            "**Messages:** {} ({} unread)\n\n",
+            thread.count, thread.unread
+        ));
+
+        if !thread.participants.is_empty() {
+            output.push_str(&format!(
+                "**Participants:** {}\n\n",
+                thread.participants.join(", ")
+            ));
+        }
+
+        if let Some(ref date) = thread.last_date {
+            output.push_str(&format!(
+                "**Last message:** {}\n\n",
+                date.format("%Y-%m-%d %H:%M:%S UTC")
+            ));
+        }
+    }
+
+    output
 }
 
-pub fn print_message(message: &Message) {
-    println!("# Message");
-    println!();
+pub fn print_thread_list(account: &str, folder: &str, threads: &[ConversationSummary]) {
+    println!("{}", format_thread_list(account, folder, threads));
+}
 
-    if let Some(subject) = &message.subject {
-        println!("**Subject:** {}", subject);
+/// A whole conversation: one section per message, indented by reply depth
+pub fn format_thread(subject: Option<&str>, messages: &[(usize, &Message)]) -> String {
+    let mut output = String::new();
+
+    output.push_str(&format!(
+        "# Thread: {}\n\n",
+        subject.unwrap_or("(no subject)")
+    ));
+    output.push_str(&format!("{} message(s)\n\n", messages.len()));
+
+    for (depth, message) in messages {
+        output.push_str("---\n\n");
+
+        let from = message
+            .from
+            .as_ref()
+            .map(|f| f.format())
+            .unwrap_or_else(|| "(unknown sender)".to_string());
+        let date = message
+            .date
+            .map(|d| d.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default();
+        output.push_str(&format!(
+            "## {}{} — {}\n\n",
+            "↳ ".repeat(*depth),
+            from,
+            date
+        ));
+
+        let id_display = message
+            .shadow_uid
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| format!("~{}", message.uid));
+        output.push_str(&format!("**ID:** {}", id_display));
+        if let Some(ref folder) = message.folder {
+            output.push_str(&format!(" · **Folder:** {}", folder));
+        }
+        output.push_str("\n\n");
+
+        let body = match (&message.body_text, &message.body_html) {
+            (Some(text), _) => text.trim().to_string(),
+            (None, Some(html)) => html::to_markdown(html),
+            (None, None) => "(No body content)".to_string(),
+        };
+        output.push_str(&body);
+        output.push_str("\n\n");
     }
 
-    if let Some(from) = &message.from {
-        println!("**From:** {}", from.format());
+    output
+}
+
+pub fn print_thread(subject: Option<&str>, messages: &[(usize, &Message)]) {
+    print!("{}", format_thread(subject, messages));
+}
+
+/// Human-readable byte size (e.g. "12.3 KB")
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
+}
+
+/// Header lines shown above a message body by `read`
+fn message_headers(message: &Message) -> Vec<(&'static str, String)> {
+    let join = |addresses: &[EmailAddress]| {
+        addresses
+            .iter()
+            .map(|e| e.format())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut headers = Vec::new();
 
+    if let Some(subject) = &message.subject {
+        headers.push(("Subject", subject.clone()));
+    }
+    if let Some(from) = &message.from {
+        headers.push(("From", from.format()));
+    }
     if !message.to.is_empty() {
-        let to_list: Vec<_> = message.to.iter().map(|e| e.format()).collect();
-        println!("**To:** {}", to_list.join(", "));
+        headers.push(("To", join(&message.to)));
     }
-
     if !message.cc.is_empty() {
-        let cc_list: Vec<_> = message.cc.iter().map(|e| e.format()).collect();
-        println!("**CC:** {}", cc_list.join(", "));
+        headers.push(("CC", join(&message.cc)));
     }
-
     if !message.bcc.is_empty() {
-        let bcc_list: Vec<_> = message.bcc.iter().map(|e| e.format()).collect();
-        println!("**BCC:** {}", bcc_list.join(", "));
+        headers.push(("BCC", join(&message.bcc)));
     }
-
     if let Some(reply_to) = &message.reply_to {
-        println!("**Reply-To:** {}", reply_to.format());
+        headers.push(("Reply-To", reply_to.format()));
     }
-
     if let Some(date) = &message.date {
-        println!("**Date:** {}", date.format("%Y-%m-%d %H:%M:%S %Z"));
+        headers.push(("Date", date.format("%Y-%m-%d %H:%M:%S %Z").to_string()));
     }
 
     // Display shadow UID as ID (primary identifier), fall back to IMAP UID if not assigned
@@ -114,10 +226,10 @@ pub fn print_message(message: &Message) {
         .shadow_uid
         .map(|id| id.to_string())
         .unwrap_or_else(|| format!("~{}", message.uid));
-    println!("**ID:** {}", id_display);
+    headers.push(("ID", id_display));
 
     if let Some(msg_id) = &message.message_id {
-        println!("**Message-ID:** {}", msg_id);
+        headers.push(("Message-ID", msg_id.clone()));
     }
 
     // Flags
@@ -135,22 +247,76 @@ pub fn print_message(message: &Message) {
         flags.push("Draft");
     }
     if !flags.is_empty() {
-        println!("**Flags:** {}", flags.join(", "));
+        headers.push(("Flags", flags.join(", ")));
+    }
+
+    headers
+}
+
+fn attachment_lines(message: &Message) -> Vec<String> {
+    message
+        .attachments
+        .iter()
+        .map(|attachment| {
+            format!(
+                "- [{}] {} ({}, {})",
+                attachment.part,
+                attachment.display_name(),
+                attachment.content_type,
+                format_size(attachment.size)
+            )
+        })
+        .collect()
+}
+
+pub fn print_message(message: &Message) {
+    println!("# Message");
+    println!();
+
+    for (name, value) in message_headers(message) {
+        println!("**{}:** {}", name, value);
+    }
+
+    if !message.attachments.is_empty() {
+        println!();
+        println!("**Attachments:**");
+        for line in attachment_lines(message) {
+            println!("{}", line);
+        }
     }
 
     println!();
     println!("---");
     println!();
 
-    // Show body
+    // Show body; HTML-only messages are rendered as markdown
     if let Some(body_text) = &message.body_text {
         println!("{}", body_text);
     } else if let Some(body_html) = &message.body_html {
-        // Basic HTML stripping for display
-        println!("(HTML email - showing with tags stripped)");
-        println!();
-        let text = basic_html_to_text(body_html);
-        println!("{}", text);
+        println!("{}", html::to_markdown(body_html));
+    } else {
+        println!("(No body content)");
+    }
+}
+
+/// `read -o text`: plain headers and body, HTML rendered as text
+pub fn print_message_text(message: &Message) {
+    for (name, value) in message_headers(message) {
+        println!("{}: {}", name, value);
+    }
+
+    if !message.attachments.is_empty() {
+        println!("Attachments:");
+        for line in attachment_lines(message) {
+            println!("  {}", line);
+        }
+    }
+
+    println!();
+    if let Some(body_text) = &message.body_text {
+        println!("{}", body_text);
+    } else if let Some(body_html) = &message.body_html {
+        println!("{}", html::to_text(body_html));
     } else {
         println!("(No body content)");
     }
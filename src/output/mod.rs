@@ -1,3 +1,6 @@
+pub mod csv;
+pub mod html;
 pub mod json;
 pub mod markdown;
 pub mod table;
+pub mod template;
@@ -1,3 +1,4 @@
+use crate::core::thread::ConversationSummary;
 use crate::models::message::Message;
 
 /// Truncate a string to fit within max_width, adding "..." if truncated
@@ -133,6 +134,96 @@ pub fn print_message_table(account: &str, folder: &str, messages: &[Message]) {
     print!("{}", format_message_table(account, folder, messages));
 }
 
+/// Conversation table for `--threads`: newest message ID, count, participants, subject
+pub fn format_thread_table(account: &str, folder: &str, threads: &[ConversationSummary]) -> String {
+    let mut output = String::new();
+
+    output.push_str(&format!(
+        "{}/{} ({} conversations)\n",
+        account,
+        folder,
+        threads.len()
+    ));
+
+    if threads.is_empty() {
+        output.push_str("No messages found.\n");
+        return output;
+    }
+
+    let id_width = 8;
+    let count_width = 5;
+    let date_width = 11;
+    let from_width = 20;
+    let subject_width = 45;
+
+    output.push_str(&format!(
+        "{:>id_w$}  {:>count_w$}  {:date_w$}  {:from_w$}  {}\n",
+        "ID",
+        "MSGS",
+        "LAST",
+        "PARTICIPANTS",
+        "SUBJECT",
+        id_w = id_width,
+        count_w = count_width,
+        date_w = date_width,
+        from_w = from_width,
+    ));
+    output.push_str(&format!(
+        "{:->id_w$}  {:->count_w$}  {:->date_w$}  {:->from_w$}  {:->subj_w$}\n",
+        "",
+        "",
+        "",
+        "",
+        "",
+        id_w = id_width,
+        count_w = count_width,
+        date_w = date_width,
+        from_w = from_width,
+        subj_w = subject_width,
+    ));
+
+    for thread in threads {
+        let id_display = thread
+            .id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        // ● marks conversations with unread messages
+        let count = if thread.unread > 0 {
+            format!("●{}", thread.count)
+        } else {
+            thread.count.to_string()
+        };
+        let date = thread
+            .last_date
+            .map(|d| d.format("%m/%d %H:%M").to_string())
+            .unwrap_or_default();
+        let participants = truncate(&thread.participants.join(", "), from_width);
+        let subject = truncate(
+            thread.subject.as_deref().unwrap_or("(no subject)"),
+            subject_width,
+        );
+
+        output.push_str(&format!(
+            "{:>id_w$}  {:>count_w$}  {:date_w$}  {:from_w$}  {}\n",
+            id_display,
+            count,
+            date,
+            participants,
+            subject,
+            id_w = id_width,
+            count_w = count_width,
+            date_w = date_width,
+            from_w = from_width,
+        ));
+    }
+
+    output
+}
+
+pub fn print_thread_table(account: &str, folder: &str, threads: &[ConversationSummary]) {
+    print!("{}", format_thread_table(account, folder, threads));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,562 @@
+use crate::models::message::{EmailAddress, Message};
+use crate::output::{html, markdown};
+use anyhow::{anyhow, Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::DateTime;
+use std::collections::BTreeMap;
+
+/// A parsed `--format-template`: literal text with `{field|filter|filter:arg}` placeholders.
+/// `{{` and `}}` are literal braces; `\n`, `\t` and `\\` are escapes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field {
+        field: TemplateField,
+        filters: Vec<Filter>,
+    },
+}
+
+/// Message fields available to templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TemplateField {
+    Id,
+    Uid,
+    Folder,
+    MessageId,
+    InReplyTo,
+    References,
+    Subject,
+    From,
+    FromName,
+    FromAddress,
+    To,
+    Cc,
+    Bcc,
+    ReplyTo,
+    Date,
+    Size,
+    Flags,
+    Labels,
+    Seen,
+    Answered,
+    Flagged,
+    Deleted,
+    Draft,
+    AgentRead,
+    Preview,
+    Body,
+    BodyHtml,
+    Attachments,
+    AttachmentCount,
+}
+
+const FIELD_NAMES: [&str; 29] = [
+    "id",
+    "uid",
+    "folder",
+    "message_id",
+    "in_reply_to",
+    "references",
+    "subject",
+    "from",
+    "from_name",
+    "from_address",
+    "to",
+    "cc",
+    "bcc",
+    "reply_to",
+    "date",
+    "size",
+    "flags",
+    "labels",
+    "seen",
+    "answered",
+    "flagged",
+    "deleted",
+    "draft",
+    "agent_read",
+    "preview",
+    "body",
+    "body_html",
+    "attachments",
+    "attachment_count",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    Trunc(usize),
+    Pad(usize),
+    LPad(usize),
+    Date(String),
+    Size,
+    Oneline,
+    Json,
+    Default(String),
+    If(String),
+    Unless(String),
+}
+
+impl TemplateField {
+    fn from_str(s: &str) -> Option<Self> {
+        let field = match s {
+            "id" => TemplateField::Id,
+            "uid" => TemplateField::Uid,
+            "folder" => TemplateField::Folder,
+            "message_id" => TemplateField::MessageId,
+            "in_reply_to" => TemplateField::InReplyTo,
+            "references" => TemplateField::References,
+            "subject" => TemplateField::Subject,
+            "from" => TemplateField::From,
+            "from_name" => TemplateField::FromName,
+            "from_address" => TemplateField::FromAddress,
+            "to" => TemplateField::To,
+            "cc" => TemplateField::Cc,
+            "bcc" => TemplateField::Bcc,
+            "reply_to" => TemplateField::ReplyTo,
+            "date" => TemplateField::Date,
+            "size" => TemplateField::Size,
+            "flags" => TemplateField::Flags,
+            "labels" => TemplateField::Labels,
+            "seen" => TemplateField::Seen,
+            "answered" => TemplateField::Answered,
+            "flagged" => TemplateField::Flagged,
+            "deleted" => TemplateField::Deleted,
+            "draft" => TemplateField::Draft,
+            "agent_read" => TemplateField::AgentRead,
+            "preview" => TemplateField::Preview,
+            "body" => TemplateField::Body,
+            "body_html" => TemplateField::BodyHtml,
+            "attachments" => TemplateField::Attachments,
+            "attachment_count" => TemplateField::AttachmentCount,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    /// Text value; lists are joined like csv cells, booleans are `true` / `false`
+    fn value(self, msg: &Message) -> String {
+        match self {
+            TemplateField::Id => msg
+                .shadow_uid
+                .map(|id| id.to_string())
+                .unwrap_or_else(|| format!("~{}", msg.uid)),
+            TemplateField::Uid => msg.uid.to_string(),
+            TemplateField::Folder => msg.folder.clone().unwrap_or_default(),
+            TemplateField::MessageId => msg.message_id.clone().unwrap_or_default(),
+            TemplateField::InReplyTo => msg.in_reply_to.clone().unwrap_or_default(),
+            TemplateField::References => msg.references.join(" "),
+            TemplateField::Subject => msg.subject.clone().unwrap_or_default(),
+            TemplateField::From => msg.from.as_ref().map(|f| f.format()).unwrap_or_default(),
+            TemplateField::FromName => msg
+                .from
+                .as_ref()
+                .and_then(|f| f.name.clone())
+                .unwrap_or_default(),
+            TemplateField::FromAddress => msg
+                .from
+                .as_ref()
+                .map(|f| f.address.clone())
+                .unwrap_or_default(),
+            TemplateField::To => join_addresses(&msg.to),
+            TemplateField::Cc => join_addresses(&msg.cc),
+            TemplateField::Bcc => join_addresses(&msg.bcc),
+            TemplateField::ReplyTo => msg
+                .reply_to
+                .as_ref()
+                .map(|r| r.format())
+                .unwrap_or_default(),
+            TemplateField::Date => msg.date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            TemplateField::Size => msg.size.map(|s| s.to_string()).unwrap_or_default(),
+            TemplateField::Flags => msg.flags.names().join(" "),
+            TemplateField::Labels => msg.labels.join(" "),
+            TemplateField::Seen => msg.flags.seen.to_string(),
+            TemplateField::Answered => msg.flags.answered.to_string(),
+            TemplateField::Flagged => msg.flags.flagged.to_string(),
+            TemplateField::Deleted => msg.flags.deleted.to_string(),
+            TemplateField::Draft => msg.flags.draft.to_string(),
+            TemplateField::AgentRead => msg.agent_read.map(|r| r.to_string()).unwrap_or_default(),
+            TemplateField::Preview => msg.preview.clone().unwrap_or_default(),
+            TemplateField::Body => match (&msg.body_text, &msg.body_html) {
+                (Some(text), _) => text.clone(),
+                (None, Some(body)) => html::to_text(body),
+                (None, None) => String::new(),
+            },
+            TemplateField::BodyHtml => msg.body_html.clone().unwrap_or_default(),
+            TemplateField::Attachments => msg
+                .attachments
+                .iter()
+                .map(|a| a.filename.clone().unwrap_or_else(|| a.content_type.clone()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            TemplateField::AttachmentCount => msg.attachments.len().to_string(),
+        }
+    }
+}
+
+fn join_addresses(addresses: &[EmailAddress]) -> String {
+    addresses
+        .iter()
+        .map(|a| a.format())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Filter {
+    fn parse(spec: &str) -> Result<Self> {
+        let (name, arg) = match spec.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(unescape(arg))),
+            None => (spec.trim(), None),
+        };
+        let width = |arg: Option<String>| -> Result<usize> {
+            arg.as_deref()
+                .and_then(|n| n.trim().parse().ok())
+                .ok_or_else(|| anyhow!("Filter '{}' needs a width, e.g. {}:20", name, name))
+        };
+
+        let filter = match name {
+            "upper" => Filter::Upper,
+            "lower" => Filter::Lower,
+            "trunc" => Filter::Trunc(width(arg)?),
+            "pad" => Filter::Pad(width(arg)?),
+            "lpad" => Filter::LPad(width(arg)?),
+            "date" => {
+                let format = arg.unwrap_or_else(|| "%Y-%m-%d %H:%M".to_string());
+                if StrftimeItems::new(&format).any(|item| matches!(item, Item::Error)) {
+                    return Err(anyhow!("Invalid date format '{}'", format));
+                }
+                Filter::Date(format)
+            }
+            "size" => Filter::Size,
+            "oneline" => Filter::Oneline,
+            "json" => Filter::Json,
+            "default" => Filter::Default(arg.unwrap_or_default()),
+            "if" => Filter::If(arg.unwrap_or_default()),
+            "unless" => Filter::Unless(arg.unwrap_or_default()),
+            _ => {
+                return Err(anyhow!(
+                    "Unknown filter '{}'. Filters: upper, lower, trunc:N, pad:N, lpad:N, date:FORMAT, size, oneline, json, default:TEXT, if:TEXT, unless:TEXT",
+                    name
+                ))
+            }
+        };
+        Ok(filter)
+    }
+
+    fn apply(&self, value: String) -> String {
+        match self {
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Trunc(width) => {
+                if value.chars().count() <= *width {
+                    value
+                } else {
+                    let mut cut: String = value.chars().take(width.saturating_sub(1)).collect();
+                    cut.push('…');
+                    cut
+                }
+            }
+            Filter::Pad(width) => format!("{:<width$}", value, width = width),
+            Filter::LPad(width) => format!("{:>width$}", value, width = width),
+            // Values that are not dates (e.g. an empty date) pass through unchanged
+            Filter::Date(format) => match DateTime::parse_from_rfc3339(&value) {
+                Ok(date) => date.format(format).to_string(),
+                Err(_) => value,
+            },
+            Filter::Size => match value.parse::<usize>() {
+                Ok(bytes) => markdown::format_size(bytes),
+                Err(_) => value,
+            },
+            Filter::Oneline => value.split_whitespace().collect::<Vec<_>>().join(" "),
+            Filter::Json => {
+                let quoted = serde_json::Value::String(value).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            }
+            Filter::Default(text) if value.is_empty() => text.clone(),
+            Filter::Default(_) => value,
+            Filter::If(text) if is_truthy(&value) => text.clone(),
+            Filter::Unless(text) if !is_truthy(&value) => text.clone(),
+            Filter::If(_) | Filter::Unless(_) => String::new(),
+        }
+    }
+}
+
+fn is_truthy(value: &str) -> bool {
+    !value.is_empty() && value != "false" && value != "0"
+}
+
+/// Resolve `\n`, `\t` and `\\`; other backslashes are kept as written
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            _ => {
+                out.push('\\');
+                continue;
+            }
+        }
+        chars.next();
+    }
+    out
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => {
+                    return Err(anyhow!(
+                        "Unmatched '}}' in template (write '}}}}' for a brace)"
+                    ))
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(anyhow!("Unclosed '{{' in template")),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(unescape(&std::mem::take(&mut text))));
+                    }
+                    parts.push(Self::parse_placeholder(&placeholder)?);
+                }
+                _ => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(unescape(&text)));
+        }
+
+        Ok(Self { parts })
+    }
+
+    fn parse_placeholder(placeholder: &str) -> Result<Part> {
+        let mut specs = placeholder.split('|');
+        let name = specs.next().unwrap_or_default().trim();
+        let field = TemplateField::from_str(name).ok_or_else(|| {
+            anyhow!(
+                "Unknown template field '{}'. Fields: {}",
+                name,
+                FIELD_NAMES.join(", ")
+            )
+        })?;
+        let filters = specs.map(Filter::parse).collect::<Result<Vec<_>>>()?;
+        Ok(Part::Field { field, filters })
+    }
+
+    /// The named template from `[templates]` in config.toml, or `spec` itself as a template
+    pub fn resolve(spec: &str, named: &BTreeMap<String, String>) -> Result<Self> {
+        if let Some(source) = named.get(spec) {
+            return Self::parse(source)
+                .with_context(|| format!("Invalid template '{}' in config.toml", spec));
+        }
+
+        // A bare word is most likely a misspelled template name, not literal text
+        let looks_like_name = !spec.is_empty()
+            && spec
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if looks_like_name {
+            let known: Vec<&str> = named.keys().map(String::as_str).collect();
+            return Err(if known.is_empty() {
+                anyhow!(
+                    "No template named '{}': config.toml has no [templates] section",
+                    spec
+                )
+            } else {
+                anyhow!(
+                    "No template named '{}' in config.toml. Templates: {}",
+                    spec,
+                    known.join(", ")
+                )
+            });
+        }
+
+        Self::parse(spec).context("Invalid --format-template")
+    }
+
+    /// Whether `{preview}` appears, so listings know to fetch previews
+    pub fn uses_preview(&self) -> bool {
+        self.parts.iter().any(|part| {
+            matches!(
+                part,
+                Part::Field {
+                    field: TemplateField::Preview,
+                    ..
+                }
+            )
+        })
+    }
+
+    /// Fail if a field only `read` fills in appears: listings fetch neither bodies nor
+    /// the attachment structure, so those would render empty
+    pub fn check_listing_fields(&self) -> Result<()> {
+        let mut unavailable: Vec<&str> = Vec::new();
+        for part in &self.parts {
+            if let Part::Field {
+                field:
+                    field @ (TemplateField::Body
+                    | TemplateField::BodyHtml
+                    | TemplateField::Attachments
+                    | TemplateField::AttachmentCount),
+                ..
+            } = part
+            {
+                let name = FIELD_NAMES[*field as usize];
+                if !unavailable.contains(&name) {
+                    unavailable.push(name);
+                }
+            }
+        }
+
+        if unavailable.is_empty() {
+            return Ok(());
+        }
+        let fields: Vec<String> = unavailable
+            .iter()
+            .map(|name| format!("{{{}}}", name))
+            .collect();
+        let verb = if fields.len() == 1 { "is" } else { "are" };
+        Err(anyhow!(
+            "{} {} only available to `read`: listings don't fetch bodies or attachments",
+            fields.join(", "),
+            verb
+        ))
+    }
+
+    pub fn render(&self, msg: &Message) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Field { field, filters } => {
+                    let value = filters
+                        .iter()
+                        .fold(field.value(msg), |value, filter| filter.apply(value));
+                    out.push_str(&value);
+                }
+            }
+        }
+        out
+    }
+
+    /// Print one rendered template per message
+    pub fn print_messages(&self, messages: &[Message]) {
+        for message in messages {
+            println!("{}", self.render(message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::EmailAddress;
+
+    fn sample() -> Message {
+        let mut msg = Message::new(42);
+        msg.shadow_uid = Some(7);
+        msg.subject = Some("Quarterly \"numbers\"".to_string());
+        msg.from = Some(EmailAddress::new(
+            "alice@example.com".to_string(),
+            Some("Alice".to_string()),
+        ));
+        msg.date = DateTime::from_timestamp(1_700_000_000, 0);
+        msg.size = Some(2048);
+        msg.flags.flagged = true;
+        msg
+    }
+
+    #[test]
+    fn test_render_fields_and_filters() {
+        let msg = sample();
+        let template = Template::parse(
+            "{id}\\t{date|date:%Y-%m-%d} {from_name|upper} {flagged|if:★}{seen|unless:•} {size|size}",
+        )
+        .unwrap();
+        assert_eq!(template.render(&msg), "7\t2023-11-14 ALICE ★• 2.0 KB");
+
+        let template =
+            Template::parse("{subject|trunc:10}|{folder|default:-}|{uid|lpad:4}").unwrap();
+        assert_eq!(template.render(&msg), "Quarterly…|-|  42");
+    }
+
+    #[test]
+    fn test_render_json_block() {
+        let msg = sample();
+        let template =
+            Template::parse("{{\"text\": \"*{subject|json}* from {from|json}\"}}").unwrap();
+        assert_eq!(
+            template.render(&msg),
+            "{\"text\": \"*Quarterly \\\"numbers\\\"* from Alice <alice@example.com>\"}"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Template::parse("{subjct}").is_err());
+        assert!(Template::parse("{subject|shout}").is_err());
+        assert!(Template::parse("{subject|trunc}").is_err());
+        assert!(Template::parse("{subject").is_err());
+        assert!(Template::parse("a } b").is_err());
+        assert!(Template::parse("{date|date:%Q}").is_err());
+    }
+
+    #[test]
+    fn test_resolve_named_templates() {
+        let mut named = BTreeMap::new();
+        named.insert("oneline".to_string(), "{id} {subject}".to_string());
+
+        let template = Template::resolve("oneline", &named).unwrap();
+        assert_eq!(template.render(&sample()), "7 Quarterly \"numbers\"");
+
+        let err = Template::resolve("onelin", &named).unwrap_err().to_string();
+        assert!(err.contains("oneline"));
+        assert!(Template::resolve("{uid}", &named).is_ok());
+    }
+
+    #[test]
+    fn test_listing_rejects_body_and_attachment_fields() {
+        let listing = Template::parse("{id} {subject|trunc:40} {preview}").unwrap();
+        assert!(listing.check_listing_fields().is_ok());
+
+        let err = Template::parse("{body} {attachment_count} {body|oneline}")
+            .unwrap()
+            .check_listing_fields()
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "{body}, {attachment_count} are only available to `read`: listings don't fetch bodies or attachments"
+        );
+    }
+}
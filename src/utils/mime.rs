@@ -0,0 +1,201 @@
+//! Content type detection for outgoing attachments
+
+use std::path::Path;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+/// Container of legacy Office documents (doc, xls, ppt)
+const OLE_STORAGE: &str = "application/x-ole-storage";
+
+/// Content type of a file: its magic bytes when recognised, then its extension, then
+/// `text/plain` for UTF-8 text; `application/octet-stream` otherwise.
+/// Text types carry `charset=utf-8` when the content reads as UTF-8.
+pub fn detect(path: &Path, data: &[u8]) -> String {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .and_then(|e| from_extension(&e));
+
+    let mime = match (from_magic(data), extension) {
+        // Office documents, EPUB, JAR... are ZIP (or OLE) containers; the extension says which
+        (Some("application/zip" | OLE_STORAGE), Some(by_extension)) => by_extension,
+        (Some(OLE_STORAGE), None) => OCTET_STREAM,
+        (Some(by_magic), _) => by_magic,
+        (None, Some(by_extension)) => by_extension,
+        (None, None) if looks_like_text(data) => "text/plain",
+        (None, None) => OCTET_STREAM,
+    };
+
+    if mime.starts_with("text/") && looks_like_text(data) {
+        format!("{}; charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    }
+}
+
+fn from_magic(data: &[u8]) -> Option<&'static str> {
+    let starts = |magic: &[u8]| data.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+
+    let mime = if starts(b"%PDF-") {
+        "application/pdf"
+    } else if starts(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if starts(b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        "image/gif"
+    } else if starts(b"RIFF") && at(8, b"WEBP") {
+        "image/webp"
+    } else if starts(b"RIFF") && at(8, b"WAVE") {
+        "audio/wav"
+    } else if starts(b"II*\0") || starts(b"MM\0*") {
+        "image/tiff"
+    } else if at(4, b"ftypheic") || at(4, b"ftypheix") || at(4, b"ftypmif1") {
+        "image/heic"
+    } else if at(4, b"ftypqt") {
+        "video/quicktime"
+    } else if at(4, b"ftyp") {
+        "video/mp4"
+    } else if starts(b"PK\x03\x04") || starts(b"PK\x05\x06") {
+        "application/zip"
+    } else if starts(b"\x1f\x8b") {
+        "application/gzip"
+    } else if starts(b"7z\xbc\xaf\x27\x1c") {
+        "application/x-7z-compressed"
+    } else if starts(b"Rar!\x1a\x07") {
+        "application/vnd.rar"
+    } else if starts(b"ID3") || starts(b"\xff\xfb") {
+        "audio/mpeg"
+    } else if starts(b"OggS") {
+        "audio/ogg"
+    } else if starts(b"fLaC") {
+        "audio/flac"
+    } else if starts(b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1") {
+        OLE_STORAGE
+    } else if starts(b"{\\rtf") {
+        "application/rtf"
+    } else {
+        return None;
+    };
+    Some(mime)
+}
+
+fn from_extension(extension: &str) -> Option<&'static str> {
+    let mime = match extension {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "heic" => "image/heic",
+        "ico" => "image/vnd.microsoft.icon",
+        "txt" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "tsv" => "text/tab-separated-values",
+        "html" | "htm" => "text/html",
+        "ics" => "text/calendar",
+        "vcf" => "text/vcard",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "eml" => "message/rfc822",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "7z" => "application/x-7z-compressed",
+        "rar" => "application/vnd.rar",
+        "doc" => "application/msword",
+        "xls" => "application/vnd.ms-excel",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "odp" => "application/vnd.oasis.opendocument.presentation",
+        "epub" => "application/epub+zip",
+        "rtf" => "application/rtf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "mp4" | "m4v" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// UTF-8 without NUL bytes in the first 8 KB (allowing a character cut at the end)
+fn looks_like_text(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(8192)];
+    if sample.is_empty() || sample.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && sample.len() == 8192,
+    }
+}
+
+/// Whether `value` has the shape of a MIME type (`type/subtype`, optionally with parameters)
+pub fn is_mime_type(value: &str) -> bool {
+    let essence = value.split(';').next().unwrap_or_default().trim();
+    let token = |s: &str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    matches!(essence.split_once('/'), Some((kind, subtype)) if token(kind) && token(subtype))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_by_magic_and_extension() {
+        assert_eq!(
+            detect(Path::new("report.bin"), b"%PDF-1.7\n..."),
+            "application/pdf"
+        );
+        // Magic bytes win over a wrong extension
+        assert_eq!(
+            detect(Path::new("photo.jpg"), b"\x89PNG\r\n\x1a\n\0\0"),
+            "image/png"
+        );
+        // ZIP containers are named by their extension
+        assert_eq!(
+            detect(Path::new("Plan.DOCX"), b"PK\x03\x04\x14\0"),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        );
+        assert_eq!(detect(Path::new("a.zip"), b"PK\x03\x04"), "application/zip");
+        assert_eq!(
+            detect(Path::new("notes.csv"), b"a,b\n1,2\n"),
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(
+            detect(Path::new("README"), "Grüße\n".as_bytes()),
+            "text/plain; charset=utf-8"
+        );
+        // Latin-1 text keeps its type but doesn't claim UTF-8
+        assert_eq!(detect(Path::new("prices.csv"), b"caf\xe9,3\n"), "text/csv");
+        assert_eq!(detect(Path::new("blob"), b"\0\x01\x02"), OCTET_STREAM);
+    }
+
+    #[test]
+    fn test_is_mime_type() {
+        assert!(is_mime_type("application/pdf"));
+        assert!(is_mime_type("text/plain; charset=utf-8"));
+        assert!(is_mime_type("application/vnd.ms-excel"));
+        assert!(!is_mime_type("pdf"));
+        assert!(!is_mime_type("\\Users\\me\\report.pdf"));
+        assert!(!is_mime_type("/report.pdf"));
+    }
+}